@@ -47,6 +47,20 @@ function collectResourceTimings() {
     }
 }
 
+/**
+ * Reads a cookie value by name.
+ *
+ * @param {string} name - The cookie name.
+ * @returns {string|null} The cookie value, or null if it is not set.
+ */
+function readCookie(name) {
+    const match = document.cookie
+        .split(';')
+        .map(cookie => cookie.trim())
+        .find(cookie => cookie.startsWith(name + "="));
+    return match ? match.substring(name.length + 1) : null;
+}
+
 async function solveChallenge() {
     const startTime = performance.now();
     console.log(`[PERF] Challenge solving started at ${Math.round(startTime)}ms since page load`);
@@ -58,9 +72,11 @@ async function solveChallenge() {
     const difficultyMeta = document.querySelector('meta[name="x-ironshield-difficulty"]');
     const timestampMeta = document.querySelector('meta[name="x-ironshield-timestamp"]');
     const challengeMeta = document.querySelector('meta[name="x-ironshield-challenge"]');
+    // The worker can also deliver the challenge as a cookie.
+    const challengeCookie = readCookie("ironshield_challenge");
     
     // Error handling for missing meta tags
-    if (!difficultyMeta || !timestampMeta || !challengeMeta) {
+    if (!difficultyMeta || !timestampMeta || (!challengeMeta && !challengeCookie)) {
         uiManager.showError("Error: Security parameters missing. Please refresh the page.");
         return;
     }
@@ -75,7 +91,7 @@ async function solveChallenge() {
     }
     
     const timestamp = timestampMeta.getAttribute('content');
-    const challenge = challengeMeta.getAttribute('content');
+    const challenge = challengeMeta ? challengeMeta.getAttribute('content') : challengeCookie;
     
     if (!timestamp || !challenge) {
         uiManager.showError("Error: Missing security parameters. Please refresh the page.");
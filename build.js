@@ -27,7 +27,7 @@ if (noParallel) {
 
 try {
   // Determine feature flags based on command line arguments
-  const featureFlags = noParallel ? '--no-default-features' : '--features parallel';
+  const featureFlags = noParallel ? '--no-default-features --features bindings' : '--features parallel';
   
   if (!noParallel) {
     // Try to build with threading support (parallel mode)
@@ -47,7 +47,7 @@ try {
       console.log('📱 Falling back to standard build without threading...');
       
       // Build without threading support as fallback
-      execSync(`cd ironshield-wasm && wasm-pack build --target web --release --no-default-features`, { 
+      execSync(`cd ironshield-wasm && wasm-pack build --target web --release --no-default-features --features bindings`, { 
         stdio: 'inherit'
       });
       console.log('✅ Successfully built WASM without threading support');
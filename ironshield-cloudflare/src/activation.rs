@@ -0,0 +1,186 @@
+//! Adaptive activation: challenge visitors only while traffic is high.
+//!
+//! When `ACTIVATION_GLOBAL_REQUESTS` or `ACTIVATION_CLIENT_REQUESTS` is
+//! set, requests that would be issued a challenge are counted in fixed
+//! windows of `ACTIVATION_WINDOW_SECONDS`, globally and per client IP.
+//! Only once a count exceeds its threshold are challenges issued; until
+//! then such requests pass through to the origin. Submissions and bypass
+//! credentials are handled as usual either way.
+//!
+//! The thresholds are per isolate, not per deployment. Like
+//! `crate::throttle`, counts live in isolate memory, so each isolate counts
+//! only the requests it serves, activates independently, and starts over
+//! when it is evicted. Traffic spread over many isolates (or data centers)
+//! can therefore exceed a threshold several times over before any one
+//! isolate activates; set the thresholds for the traffic one isolate sees.
+//! A deployment-wide count would need a Durable Object on every request.
+//!
+//! Requests without a client IP cannot be told apart, so they share one
+//! per-client count instead of skipping the client threshold.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::config::Config;
+use crate::constant::CLIENT_IP_HEADER;
+
+/// Most clients counted separately in one window, bounding isolate
+/// memory. Clients beyond it share one overflow count with requests
+/// without a client IP, so neither a flood of new IPs nor of IP-less
+/// requests is passed through.
+const MAX_COUNTED_CLIENTS: usize = 10_000;
+
+thread_local! {
+    /// Request counts of this isolate.
+    static REQUESTS: RefCell<RequestCounter> = RefCell::new(RequestCounter::default());
+}
+
+/// Requests counted in the current window, globally and per client.
+#[derive(Debug, Default)]
+pub(crate) struct RequestCounter {
+    window_start_millis: i64,
+    global:              u64,
+    clients:             HashMap<String, u64>,
+    overflow:            u64,
+}
+
+impl RequestCounter {
+    /// Counts a request, starting a new window first if the current one
+    /// has ended.
+    ///
+    /// # Arguments
+    /// * `client`:     The client IP, if known.
+    /// * `now_millis`: The current time in Unix milliseconds.
+    /// * `window_ms`:  The counting window in milliseconds.
+    ///
+    /// # Returns
+    /// * `(u64, u64)`: The global and the client's requests in the
+    ///   window, including this one. The client count is the shared
+    ///   overflow count for unknown clients and for clients beyond
+    ///   `MAX_COUNTED_CLIENTS`.
+    pub(crate) fn record_request(&mut self, client: Option<&str>, now_millis: i64, window_ms: i64) -> (u64, u64) {
+        if now_millis.saturating_sub(self.window_start_millis) >= window_ms {
+            self.window_start_millis = now_millis;
+            self.global = 0;
+            self.clients.clear();
+            self.overflow = 0;
+        }
+
+        self.global = self.global.saturating_add(1);
+        let client_count: u64 = match client {
+            Some(client) if self.clients.len() < MAX_COUNTED_CLIENTS || self.clients.contains_key(client) => {
+                let count: &mut u64 = self.clients.entry(client.to_string()).or_insert(0);
+                *count = count.saturating_add(1);
+                *count
+            }
+            Some(_) | None => {
+                self.overflow = self.overflow.saturating_add(1);
+                self.overflow
+            }
+        };
+        (self.global, client_count)
+    }
+
+    /// Counts a request and decides whether it must solve a challenge.
+    ///
+    /// # Arguments
+    /// * `client`:     The client IP, if known.
+    /// * `config`:     The worker configuration.
+    /// * `now_millis`: The current time in Unix milliseconds.
+    ///
+    /// # Returns
+    /// * `bool`: Whether adaptive activation is off, or either configured
+    ///   threshold is exceeded in the current window.
+    pub(crate) fn challenge_required(&mut self, client: Option<&str>, config: &Config, now_millis: i64) -> bool {
+        if config.activation_global_requests.is_none() && config.activation_client_requests.is_none() {
+            return true;
+        }
+
+        let (global, client_count) = self.record_request(client, now_millis, config.activation_window_ms);
+        config.activation_global_requests.is_some_and(|threshold: u64| global > threshold)
+            || config.activation_client_requests.is_some_and(|threshold: u64| client_count > threshold)
+    }
+}
+
+/// Counts a request that would be issued a challenge against this
+/// isolate's counters, see `RequestCounter::challenge_required`.
+///
+/// # Arguments
+/// * `headers`:    The request headers, for the client IP.
+/// * `config`:     The worker configuration.
+/// * `now_millis`: The current time in Unix milliseconds.
+///
+/// # Returns
+/// * `bool`: Whether to issue the challenge rather than pass through.
+pub(crate) fn challenge_required(headers: &http::HeaderMap, config: &Config, now_millis: i64) -> bool {
+    let client_ip: Option<&str> = headers.get(CLIENT_IP_HEADER).and_then(|v| v.to_str().ok());
+    REQUESTS.with(|counter: &RefCell<RequestCounter>| {
+        counter.borrow_mut().challenge_required(client_ip, config, now_millis)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: &str = "203.0.113.5";
+
+    #[test]
+    fn test_always_challenges_when_disabled() {
+        let mut counter: RequestCounter = RequestCounter::default();
+        assert!(counter.challenge_required(Some(CLIENT), &Config::default(), 0));
+        assert_eq!(counter.global, 0, "nothing is counted");
+    }
+
+    #[test]
+    fn test_global_threshold() {
+        let config: Config = Config { activation_global_requests: Some(3), ..Config::default() };
+        let mut counter: RequestCounter = RequestCounter::default();
+
+        for client in ["198.51.100.1", "198.51.100.2", "198.51.100.3"] {
+            assert!(!counter.challenge_required(Some(client), &config, 1_000), "below threshold passes through");
+        }
+        assert!(counter.challenge_required(None, &config, 2_000), "above threshold is challenged");
+
+        // A new window starts calm again.
+        assert!(!counter.challenge_required(None, &config, 1_000 + config.activation_window_ms));
+    }
+
+    #[test]
+    fn test_client_threshold() {
+        let config: Config = Config { activation_client_requests: Some(2), ..Config::default() };
+        let mut counter: RequestCounter = RequestCounter::default();
+
+        assert!(!counter.challenge_required(Some(CLIENT), &config, 0));
+        assert!(!counter.challenge_required(Some(CLIENT), &config, 0));
+        assert!(counter.challenge_required(Some(CLIENT), &config, 0));
+
+        // Other clients are unaffected.
+        assert!(!counter.challenge_required(Some("198.51.100.7"), &config, 0));
+    }
+
+    #[test]
+    fn test_clients_without_an_ip_share_a_count() {
+        let config: Config = Config { activation_client_requests: Some(2), ..Config::default() };
+        let mut counter: RequestCounter = RequestCounter::default();
+
+        assert!(!counter.challenge_required(None, &config, 0));
+        assert!(!counter.challenge_required(None, &config, 0));
+        assert!(counter.challenge_required(None, &config, 0), "IP-less requests do not fail open");
+        assert!(!counter.challenge_required(Some(CLIENT), &config, 0), "clients with an IP are counted apart");
+    }
+
+    #[test]
+    fn test_clients_beyond_the_limit_share_a_count() {
+        let config: Config = Config { activation_client_requests: Some(2), ..Config::default() };
+        let mut counter: RequestCounter = RequestCounter::default();
+        for index in 0..MAX_COUNTED_CLIENTS {
+            counter.record_request(Some(&index.to_string()), 0, config.activation_window_ms);
+        }
+
+        assert!(!counter.challenge_required(Some("198.51.100.1"), &config, 0));
+        assert!(!counter.challenge_required(Some("198.51.100.2"), &config, 0));
+        assert!(counter.challenge_required(Some("198.51.100.3"), &config, 0), "new clients do not fail open");
+        assert_eq!(counter.record_request(Some("0"), 0, config.activation_window_ms), (MAX_COUNTED_CLIENTS as u64 + 4, 2));
+    }
+}
@@ -1,7 +1,14 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
 use axum::body;
 use http::{header, Response, StatusCode};
-use worker::{console_log, Error};
+use sha2::{Digest, Sha256};
+#[cfg(target_arch = "wasm32")]
+use worker::console_log;
+use worker::Error;
 use crate::challenge::CHALLENGE_CSS;
+use crate::config::{ChallengeDelivery, Config};
 
 /// Using placeholders during development to avoid linter errors,
 /// These will be correctly populated at runtime by wrangler
@@ -40,161 +47,287 @@ const WORKER_POOL_MANAGER_JS: &str = include_str!("../../assets/worker_pool_mana
 #[cfg(target_arch = "wasm32")]
 const API_CLIENT_JS: &str = include_str!("../../assets/api_client.js");
 
-/// Function to serve the WebAssembly binary
-async fn serve_wasm_file() -> worker::Result<Response<body::Body>> {
-    console_log!("Serving WebAssembly binary...");
-
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "application/wasm")
-        // Add cache control headers to help with caching
-        .header(header::CACHE_CONTROL, "public, max-age=3600")
-        // Add CORS headers to ensure it can be loaded from different origins if needed
-        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-        // Add streaming-friendly headers
-        .header(header::ACCEPT_RANGES, "bytes")
-        // Add a content-encoding header to indicate no compression
-        // This is important for streaming as compressed responses need to be fully downloaded first
-        .header(header::CONTENT_ENCODING, "identity")
-        .body(body::Body::from(WASM_BINARY.to_vec()))
-        .map_err(|e: http::Error| Error::RustError(format!("Failed to serve WebAssembly: {}", e)))
+/// A static asset served by the worker.
+///
+/// * `paths`:         The paths it is served at.
+/// * `name`:          Human-readable name for logs and errors.
+/// * `content_type`:  Its `Content-Type`.
+/// * `content`:       Its bytes.
+/// * `extra_headers`: Headers sent besides the common caching ones.
+struct Asset {
+    paths:         [&'static str; 2],
+    name:          &'static str,
+    content_type:  &'static str,
+    content:       &'static [u8],
+    extra_headers: &'static [(&'static str, &'static str)],
 }
 
-/// Function to serve the JavaScript bindings for the WebAssembly module
-async fn serve_wasm_js_file() -> worker::Result<Response<body::Body>> {
-    console_log!("Serving WebAssembly JavaScript bindings...");
+/// Headers letting the WASM module and its bindings load from other origins.
+const CROSS_ORIGIN_HEADERS: &[(&str, &str)] = &[("Access-Control-Allow-Origin", "*")];
 
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "application/javascript")
-        // Add cache control headers
-        .header(header::CACHE_CONTROL, "public, max-age=3600")
-        // Add CORS headers
-        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-        .body(body::Body::from(WASM_JS_BINDINGS.to_vec()))
-        .map_err(|e: http::Error| {
-            Error::RustError(format!("Failed to serve WebAssembly JS bindings: {}", e))
-        })
-}
+/// Every asset `handle_asset_request` serves; routing, headless mode and
+/// ETags all derive from this table.
+static ASSETS: &[Asset] = &[
+    Asset {
+        paths:         ["/ironshield_wasm_bg.wasm", "/assets/wasm/ironshield_wasm_bg.wasm"],
+        name:          "WebAssembly binary",
+        content_type:  "application/wasm",
+        content:       WASM_BINARY,
+        // Streaming-friendly: compressed responses need to be fully
+        // downloaded before they can be compiled.
+        extra_headers: &[
+            ("Access-Control-Allow-Origin", "*"),
+            ("Accept-Ranges", "bytes"),
+            ("Content-Encoding", "identity"),
+        ],
+    },
+    Asset {
+        paths:         ["/ironshield_wasm.js", "/assets/wasm/ironshield_wasm.js"],
+        name:          "WebAssembly JS bindings",
+        content_type:  "application/javascript",
+        content:       WASM_JS_BINDINGS,
+        extra_headers: CROSS_ORIGIN_HEADERS,
+    },
+    Asset {
+        paths:         ["/challenge.css", "/assets/challenge.css"],
+        name:          "challenge CSS",
+        content_type:  "text/css",
+        content:       CHALLENGE_CSS.as_bytes(),
+        extra_headers: &[],
+    },
+    Asset {
+        paths:         ["/pow_worker.js", "/assets/pow_worker.js"],
+        name:          "PoW worker JS",
+        content_type:  "application/javascript",
+        content:       POW_WORKER_JS.as_bytes(),
+        extra_headers: &[],
+    },
+    Asset {
+        paths:         ["/wasm_pow_worker.js", "/assets/wasm_pow_worker.js"],
+        name:          "WASM PoW worker JS",
+        content_type:  "application/javascript",
+        content:       WASM_POW_WORKER_JS.as_bytes(),
+        extra_headers: &[],
+    },
+    Asset {
+        paths:         ["/challenge_main.js", "/assets/challenge_main.js"],
+        name:          "main challenge JS",
+        content_type:  "application/javascript; charset=utf-8",
+        content:       CHALLENGE_MAIN_JS.as_bytes(),
+        extra_headers: &[],
+    },
+    Asset {
+        paths:         ["/ui_manager.js", "/assets/ui_manager.js"],
+        name:          "UI manager JS",
+        content_type:  "application/javascript; charset=utf-8",
+        content:       UI_MANAGER_JS.as_bytes(),
+        extra_headers: &[],
+    },
+    Asset {
+        paths:         ["/worker_pool_manager.js", "/assets/worker_pool_manager.js"],
+        name:          "Worker pool manager JS",
+        content_type:  "application/javascript; charset=utf-8",
+        content:       WORKER_POOL_MANAGER_JS.as_bytes(),
+        extra_headers: &[],
+    },
+    Asset {
+        paths:         ["/api_client.js", "/assets/api_client.js"],
+        name:          "API client JS",
+        content_type:  "application/javascript; charset=utf-8",
+        content:       API_CLIENT_JS.as_bytes(),
+        extra_headers: &[],
+    },
+];
 
-/// Function to serve the challenge CSS file
-async fn serve_challenge_css() -> worker::Result<Response<body::Body>> {
-    console_log!("Serving challenge CSS...");
+/// The `asset_etag` of every asset, keyed by each path it is served at.
+static ASSET_ETAGS: OnceLock<HashMap<&'static str, String>> = OnceLock::new();
 
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "text/css")
-        // Add cache control headers
-        .header(header::CACHE_CONTROL, "public, max-age=3600")
-        .body(body::Body::from(CHALLENGE_CSS))
-        .map_err(|e: http::Error| Error::RustError(format!("Failed to serve challenge CSS: {}", e)))
+/// Returns `ASSET_ETAGS`, hashing every asset on first use and keeping the
+/// table for the isolate's lifetime, since assets only change with a deploy.
+fn asset_etags() -> &'static HashMap<&'static str, String> {
+    ASSET_ETAGS.get_or_init(|| {
+        ASSETS
+            .iter()
+            .flat_map(|asset: &Asset| {
+                let etag: String = asset_etag(asset.content);
+                asset.paths.map(|path: &'static str| (path, etag.clone()))
+            })
+            .collect()
+    })
 }
 
-/// Function to serve the PoW worker JavaScript file
-async fn serve_pow_worker_js() -> worker::Result<Response<body::Body>> {
-    console_log!("Serving PoW worker JS...");
-
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "application/javascript")
-        // Add cache control headers
-        .header(header::CACHE_CONTROL, "public, max-age=3600")
-        .body(body::Body::from(POW_WORKER_JS))
-        .map_err(|e: http::Error| Error::RustError(format!("Failed to serve PoW worker: {}", e)))
+/// Returns the asset served at `path`.
+fn find_asset(path: &str) -> Option<&'static Asset> {
+    ASSETS.iter().find(|asset: &&Asset| asset.paths.contains(&path))
 }
 
-/// Function to serve the WASM PoW worker JavaScript file
-async fn serve_wasm_pow_worker_js() -> worker::Result<Response<body::Body>> {
-    console_log!("Serving WASM PoW worker JS...");
+/// Function to serve `asset`, requested at `path`.
+fn serve_asset(path: &str, asset: &'static Asset) -> worker::Result<Response<body::Body>> {
+    #[cfg(target_arch = "wasm32")]
+    console_log!("Serving {}...", asset.name);
 
-    Response::builder()
+    let builder: http::response::Builder = Response::builder()
         .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "application/javascript")
-        // Add cache control headers
+        .header(header::CONTENT_TYPE, asset.content_type)
         .header(header::CACHE_CONTROL, "public, max-age=3600")
-        .body(body::Body::from(WASM_POW_WORKER_JS))
-        .map_err(|e: http::Error| {
-            Error::RustError(format!("Failed to serve WASM PoW worker: {}", e))
-        })
+        .header(header::ETAG, asset_etags()[path].as_str());
+    asset.extra_headers
+        .iter()
+        .fold(builder, |builder, (name, value)| builder.header(*name, *value))
+        .body(body::Body::from(asset.content))
+        .map_err(|e: http::Error| Error::RustError(format!("Failed to serve {}: {}", asset.name, e)))
 }
 
-/// Function to serve the main challenge JavaScript file
-async fn serve_challenge_main_js() -> worker::Result<Response<body::Body>> {
-    serve_javascript_file("main challenge JS", CHALLENGE_MAIN_JS)
+/// Computes the strong ETag of an asset from its contents, so it changes
+/// exactly when a deploy changes the asset.
+fn asset_etag(content: &[u8]) -> String {
+    format!("\"{}\"", hex::encode(&Sha256::digest(content)[..16]))
 }
 
-/// Function to serve the UI Manager JavaScript file
-async fn serve_ui_manager_js() -> worker::Result<Response<body::Body>> {
-    serve_javascript_file("UI manager JS", UI_MANAGER_JS)
+/// Returns whether the request carries a `Cache-Control: no-cache`
+/// directive, i.e. the client insists on a full, fresh response.
+fn requests_no_cache(headers: &http::HeaderMap) -> bool {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|v: &http::HeaderValue| v.to_str().ok())
+        .flat_map(|directives: &str| directives.split(','))
+        .any(|directive: &str| directive.trim().eq_ignore_ascii_case("no-cache"))
 }
 
-/// Function to serve the Worker Pool Manager JavaScript file
-async fn serve_worker_pool_manager_js() -> worker::Result<Response<body::Body>> {
-    serve_javascript_file("Worker pool manager JS", WORKER_POOL_MANAGER_JS)
+/// Returns whether `If-None-Match` lists `etag`, ignoring weak prefixes.
+fn matches_if_none_match(headers: &http::HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v: &http::HeaderValue| v.to_str().ok())
+        .flat_map(|tags: &str| tags.split(','))
+        .map(|tag: &str| tag.trim())
+        .any(|tag: &str| tag == "*" || tag.trim_start_matches("W/") == etag)
 }
 
-/// Function to serve the API-Client JavaScript file
-async fn serve_api_client_js() -> worker::Result<Response<body::Body>> {
-    serve_javascript_file("API client JS", API_CLIENT_JS)
+/// Turns a served asset into `304 Not Modified` when the client already
+/// holds the current version.
+///
+/// A `no-cache` request always gets the full `200` with its ETag, so a
+/// client can force a fresh copy even while it holds a matching one.
+///
+/// # Arguments
+/// * `response`: The asset response, carrying its ETag.
+/// * `headers`:  The request headers.
+///
+/// # Returns
+/// * `Response<body::Body>`: `response`, or a bodiless `304` with its headers.
+fn conditional_response(response: Response<body::Body>, headers: &http::HeaderMap) -> Response<body::Body> {
+    let unchanged: bool = response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|v: &http::HeaderValue| v.to_str().ok())
+        .is_some_and(|etag: &str| matches_if_none_match(headers, etag));
+    if !unchanged || requests_no_cache(headers) {
+        return response;
+    }
+
+    let (mut parts, _) = response.into_parts();
+    parts.status = StatusCode::NOT_MODIFIED;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, body::Body::empty())
 }
 
-/// Generic function to serve JavaScript files
-fn serve_javascript_file(log_name: &str, content: &'static str) -> worker::Result<Response<body::Body>> {
-    console_log!("Serving {}...", log_name);
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(
-            header::CONTENT_TYPE,
-            "application/javascript; charset=utf-8",
-        )
-        .header(header::CACHE_CONTROL, "public, max-age=3600")
-        .body(body::Body::from(content))
-        .map_err(|e: http::Error| Error::RustError(format!("Failed to serve {}: {}", log_name, e)))
+/// Returns the 404 served for asset routes when assets are disabled by
+/// `ChallengeDelivery::Headless`, or `None` if the asset may be served.
+fn disabled_asset_response(path: &str, config: &Config) -> Option<worker::Result<Response<body::Body>>> {
+    if config.challenge_delivery != ChallengeDelivery::Headless || find_asset(path).is_none() {
+        return None;
+    }
+
+    Some(
+        Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(body::Body::from("Not Found"))
+            .map_err(|e: http::Error| Error::RustError(format!("Failed to build response: {}", e))),
+    )
 }
 
 /// Function to handle asset requests
-pub(crate) async fn handle_asset_request(path: &str) -> Option<worker::Result<Response<body::Body>>> {
-    match path {
-        // WASM files
-        "/ironshield_wasm_bg.wasm" | "/assets/wasm/ironshield_wasm_bg.wasm" => {
-            console_log!("Request for WebAssembly binary received.");
-            Some(serve_wasm_file().await)
-        }
-        "/ironshield_wasm.js" | "/assets/wasm/ironshield_wasm.js" => {
-            console_log!("Request for WebAssembly JS bindings received.");
-            Some(serve_wasm_js_file().await)
-        }
-        // CSS
-        "/challenge.css" | "/assets/challenge.css" => {
-            console_log!("Request for challenge CSS received.");
-            Some(serve_challenge_css().await)
-        }
-        // JavaScript files
-        "/pow_worker.js" | "/assets/pow_worker.js" => {
-            console_log!("Request for PoW worker JS received.");
-            Some(serve_pow_worker_js().await)
-        }
-        "/wasm_pow_worker.js" | "/assets/wasm_pow_worker.js" => {
-            console_log!("Request for WASM PoW worker JS received.");
-            Some(serve_wasm_pow_worker_js().await)
-        }
-        "/challenge_main.js" | "/assets/challenge_main.js" => {
-            console_log!("Request for main challenge JS received.");
-            Some(serve_challenge_main_js().await)
-        }
-        "/ui_manager.js" | "/assets/ui_manager.js" => {
-            console_log!("Request for UI manager JS received.");
-            Some(serve_ui_manager_js().await)
-        }
-        "/worker_pool_manager.js" | "/assets/worker_pool_manager.js" => {
-            console_log!("Request for Worker pool manager JS received.");
-            Some(serve_worker_pool_manager_js().await)
+///
+/// Assets carry an ETag; a request whose `If-None-Match` lists it gets a
+/// `304`, see `conditional_response`.
+pub(crate) async fn handle_asset_request(
+    path: &str,
+    headers: &http::HeaderMap,
+    config: &Config,
+) -> Option<worker::Result<Response<body::Body>>> {
+    if let Some(response) = disabled_asset_response(path, config) {
+        return Some(response);
+    }
+
+    let served: Option<worker::Result<Response<body::Body>>> = find_asset(path).map(|asset: &Asset| serve_asset(path, asset));
+    served.map(|response| response.map(|response| conditional_response(response, headers)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headless_mode_disables_assets() {
+        let headless: Config = Config { challenge_delivery: ChallengeDelivery::Headless, ..Config::default() };
+
+        for path in ["/challenge_main.js", "/assets/wasm/ironshield_wasm_bg.wasm", "/challenge.css"] {
+            let response = disabled_asset_response(path, &headless).unwrap().unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert!(disabled_asset_response(path, &Config::default()).is_none());
         }
-        "/api_client.js" | "/assets/api_client.js" => {
-            console_log!("Request for API client JS received.");
-            Some(serve_api_client_js().await)
+
+        // Non-asset paths still reach the challenge logic.
+        assert!(disabled_asset_response("/", &headless).is_none());
+    }
+
+    #[test]
+    fn test_every_asset_path_is_routed() {
+        for asset in ASSETS {
+            let etag: String = asset_etag(asset.content);
+            for path in asset.paths {
+                assert!(std::ptr::eq(find_asset(path).unwrap(), asset), "{} is not routed", path);
+                assert_eq!(asset_etags()[path], etag);
+            }
         }
-        // Return None if not an asset request
-        _ => None,
+        assert!(find_asset("/").is_none());
+        assert!(find_asset("/assets/missing.js").is_none());
+
+        let css: Response<body::Body> = serve_asset("/challenge.css", find_asset("/challenge.css").unwrap()).unwrap();
+        assert_eq!(css.headers()[header::CONTENT_TYPE], "text/css");
+        let wasm_path: &str = "/ironshield_wasm_bg.wasm";
+        let wasm: Response<body::Body> = serve_asset(wasm_path, find_asset(wasm_path).unwrap()).unwrap();
+        assert_eq!(wasm.headers()[header::CONTENT_TYPE], "application/wasm");
+        assert_eq!(wasm.headers()[header::CONTENT_ENCODING], "identity");
+        assert_eq!(wasm.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    }
+
+    #[test]
+    fn test_no_cache_request_gets_full_response() {
+        let path: &str = "/challenge_main.js";
+        let asset = || serve_asset(path, find_asset(path).unwrap()).unwrap();
+        let etag: String = asset_etag(CHALLENGE_MAIN_JS.as_bytes());
+        assert_eq!(asset().headers()[header::ETAG], etag.as_str());
+
+        let mut request: http::HeaderMap = http::HeaderMap::new();
+        request.insert(header::IF_NONE_MATCH, http::HeaderValue::from_str(&format!("W/{}", etag)).unwrap());
+
+        let revalidated: Response<body::Body> = conditional_response(asset(), &request);
+        assert_eq!(revalidated.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(revalidated.headers()[header::ETAG], etag.as_str());
+
+        request.insert(header::CACHE_CONTROL, http::HeaderValue::from_static("max-age=0, No-Cache"));
+        let fresh: Response<body::Body> = conditional_response(asset(), &request);
+        assert_eq!(fresh.status(), StatusCode::OK);
+        assert_eq!(fresh.headers()[header::ETAG], etag.as_str());
+
+        // A stale ETag gets the full asset too.
+        let stale: http::HeaderMap = http::HeaderMap::from_iter([(header::IF_NONE_MATCH, http::HeaderValue::from_static("\"0\""))]);
+        assert_eq!(conditional_response(asset(), &stale).status(), StatusCode::OK);
+        assert_ne!(asset_etag(b"a"), asset_etag(b"b"));
     }
-}
\ No newline at end of file
+}
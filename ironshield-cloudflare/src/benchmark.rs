@@ -0,0 +1,248 @@
+//! In-process verification throughput benchmark.
+//!
+//! `GET /benchmark` verifies a fixed, known-good solution repeatedly and
+//! reports how many verifications per second this isolate sustained, so
+//! operators can plan capacity per deployment region. Since each call
+//! burns CPU on request, the route answers `404` unless `BENCHMARK_ENABLED`
+//! is set and the request carries `IRONSHIELD_BENCHMARK_SECRET` in the
+//! `X-IronShield-Benchmark-Secret` header.
+//!
+//! Workers freeze the clock while code runs and only advance it across
+//! I/O, so in production a run usually measures `0` elapsed milliseconds
+//! however many `iterations` it does. The elapsed time and throughput are
+//! then reported as `null` rather than made up; the figures are only
+//! meaningful where the clock is live, such as `wrangler dev`. The
+//! verifications also show up in the `/metrics` counters like any other.
+
+use axum::body;
+use http::{header, Response, StatusCode};
+use ironshield_types::IronShieldChallenge;
+use worker::Error;
+
+use crate::config::Config;
+use crate::constant::BENCHMARK_SECRET_HEADER;
+use crate::cors::add_cors_headers;
+use crate::http_handler::presents_secret;
+
+/// Query parameter choosing how many verifications to run.
+const BENCHMARK_ITERATIONS_QUERY_PARAM: &str = "iterations";
+/// Verifications run when `iterations` is absent.
+const DEFAULT_BENCHMARK_ITERATIONS:     u64 = 10_000;
+/// Most verifications one request may run.
+const MAX_BENCHMARK_ITERATIONS:         u64 = 1_000_000;
+/// `random_nonce` of the fixed benchmark challenge (`"ironshield-benchmark"`).
+const BENCHMARK_RANDOM_NONCE:           &str = "69726f6e736869656c642d62656e63686d61726b";
+/// Difficulty of the fixed benchmark challenge.
+const BENCHMARK_DIFFICULTY:             u64 = 1024;
+/// A solution to the fixed benchmark challenge.
+const BENCHMARK_SOLUTION:               i64 = 285;
+
+/// Outcome of a benchmark run.
+///
+/// * `iterations`:          Verifications run.
+/// * `elapsed_ms`:          Wall-clock time they took, `None` if the clock did not advance.
+/// * `verifies_per_second`: `iterations` divided by the elapsed time, if known.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BenchmarkResult {
+    pub iterations:          u64,
+    pub elapsed_ms:          Option<i64>,
+    pub verifies_per_second: Option<f64>,
+}
+
+/// Checks a benchmark request's secret header against the configured
+/// secret, see `presents_secret`.
+///
+/// # Returns
+/// * `bool`: Whether a secret is configured and the request presented it.
+fn presents_benchmark_secret(headers: &http::HeaderMap, config: &Config) -> bool {
+    presents_secret(headers, BENCHMARK_SECRET_HEADER, config.benchmark_secret.as_deref())
+}
+
+/// Parses the `iterations` query parameter of a benchmark request.
+///
+/// # Returns
+/// * `Result<u64, String>`: The count clamped to
+///   `1..=MAX_BENCHMARK_ITERATIONS` (`DEFAULT_BENCHMARK_ITERATIONS` when
+///   absent), or an error if it is not a number.
+fn benchmark_iterations(query: Option<&str>) -> Result<u64, String> {
+    let raw: Option<&str> = query.and_then(|query: &str| {
+        query
+            .split('&')
+            .find_map(|pair: &str| pair.strip_prefix(BENCHMARK_ITERATIONS_QUERY_PARAM)?.strip_prefix('='))
+    });
+
+    match raw {
+        None => Ok(DEFAULT_BENCHMARK_ITERATIONS),
+        Some(raw) => raw
+            .parse::<u64>()
+            .map(|iterations: u64| iterations.clamp(1, MAX_BENCHMARK_ITERATIONS))
+            .map_err(|_| format!("Invalid iterations: {:?}", raw)),
+    }
+}
+
+/// Verifies the fixed benchmark solution `iterations` times.
+///
+/// # Arguments
+/// * `iterations`: How many verifications to run.
+/// * `now_millis`: The clock, in Unix milliseconds, read before and after
+///   the run.
+///
+/// # Returns
+/// * `Result<BenchmarkResult, String>`: The measured throughput, or an
+///   error if a verification unexpectedly failed.
+pub(crate) fn run_benchmark(iterations: u64, mut now_millis: impl FnMut() -> i64) -> Result<BenchmarkResult, String> {
+    let challenge: IronShieldChallenge = IronShieldChallenge::new(
+        BENCHMARK_RANDOM_NONCE.to_string(),
+        0,
+        "benchmark".to_string(),
+        IronShieldChallenge::difficulty_to_challenge_param(BENCHMARK_DIFFICULTY),
+        [0x00; 32],
+        [0x00; 64],
+    );
+
+    let started_millis: i64 = now_millis();
+    for _ in 0..iterations {
+        if !ironshield_core::verify_ironshield_solution(&challenge, std::hint::black_box(BENCHMARK_SOLUTION)) {
+            return Err("Benchmark solution failed to verify".to_string());
+        }
+    }
+    let elapsed_ms: Option<i64> = Some(now_millis() - started_millis)
+        .filter(|elapsed_ms: &i64| *elapsed_ms > 0);
+
+    Ok(BenchmarkResult {
+        iterations,
+        elapsed_ms,
+        verifies_per_second: elapsed_ms.map(|elapsed_ms: i64| iterations as f64 * 1000.0 / elapsed_ms as f64),
+    })
+}
+
+/// Function to run the benchmark and report its throughput as JSON.
+///
+/// # Arguments
+/// * `query`:      The request query, read for `iterations`.
+/// * `headers`:    The request headers.
+/// * `config`:     The worker configuration.
+/// * `now_millis`: The clock the run is timed with, see `run_benchmark`.
+///
+/// # Returns
+/// * `worker::Result<Response<body::Body>>`: `200` with the
+///   `BenchmarkResult` as JSON, `400` for a malformed `iterations`, or
+///   `404` when the benchmark is disabled or the request lacks the
+///   secret.
+pub(crate) fn handle_benchmark_request(
+    query: Option<&str>,
+    headers: &http::HeaderMap,
+    config: &Config,
+    now_millis: impl FnMut() -> i64,
+) -> worker::Result<Response<body::Body>> {
+    let (status, content_type, body) = match config.benchmark_enabled && presents_benchmark_secret(headers, config) {
+        false => (StatusCode::NOT_FOUND, "text/plain", "Not found".to_string()),
+        true => match benchmark_iterations(query).and_then(|iterations: u64| run_benchmark(iterations, now_millis)) {
+            Ok(result) => (
+                StatusCode::OK,
+                "application/json",
+                serde_json::json!({
+                    "iterations": result.iterations,
+                    "elapsed_ms": result.elapsed_ms,
+                    "verifies_per_second": result.verifies_per_second,
+                })
+                .to_string(),
+            ),
+            Err(message) => (StatusCode::BAD_REQUEST, "text/plain", message),
+        },
+    };
+
+    add_cors_headers(
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, content_type),
+        headers,
+    )
+        .body(body::Body::from(body))
+        .map_err(|e: http::Error| Error::RustError(format!("Failed to build benchmark response: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+
+    fn response_json(response: Response<body::Body>) -> serde_json::Value {
+        let mut reading = std::pin::pin!(body::to_bytes(response.into_body(), usize::MAX));
+        let Poll::Ready(bytes) = reading.as_mut().poll(&mut Context::from_waker(Waker::noop())) else {
+            panic!("an in-memory body is ready immediately");
+        };
+        serde_json::from_slice(&bytes.unwrap()).unwrap()
+    }
+
+    /// A clock that advances 10 ms every time it is read.
+    fn ticking_clock() -> impl FnMut() -> i64 {
+        let mut now_millis: i64 = 0;
+        move || {
+            now_millis += 10;
+            now_millis
+        }
+    }
+
+    #[test]
+    fn test_frozen_clock_reports_no_throughput() {
+        let result: BenchmarkResult = run_benchmark(5, || 1_700_000_000_000).unwrap();
+        assert_eq!(result, BenchmarkResult { iterations: 5, elapsed_ms: None, verifies_per_second: None });
+    }
+
+    #[test]
+    fn test_benchmark_route_enabled_and_disabled() {
+        let mut headers: http::HeaderMap = http::HeaderMap::new();
+        headers.insert(BENCHMARK_SECRET_HEADER, "swordfish".parse().unwrap());
+        let config: Config = Config {
+            benchmark_enabled: true,
+            benchmark_secret:  Some(b"swordfish".to_vec()),
+            ..Config::default()
+        };
+
+        let disabled: Config = Config { benchmark_enabled: false, ..config.clone() };
+        let response: Response<body::Body> = handle_benchmark_request(None, &headers, &disabled, ticking_clock()).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let enabled: Response<body::Body> =
+            handle_benchmark_request(Some("iterations=200"), &headers, &config, ticking_clock()).unwrap();
+        assert_eq!(enabled.status(), StatusCode::OK);
+        let result: serde_json::Value = response_json(enabled);
+        assert_eq!(result["iterations"], 200);
+        assert_eq!(result["elapsed_ms"], 10);
+        assert!(result["verifies_per_second"].as_f64().unwrap() > 0.0);
+        assert_eq!(result["verifies_per_second"], 20_000.0);
+
+        let malformed: Response<body::Body> =
+            handle_benchmark_request(Some("iterations=lots"), &headers, &config, ticking_clock()).unwrap();
+        assert_eq!(malformed.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_benchmark_route_requires_the_secret() {
+        let enabled: Config = Config { benchmark_enabled: true, ..Config::default() };
+        let configured: Config = Config { benchmark_secret: Some(b"swordfish".to_vec()), ..enabled.clone() };
+        let mut wrong: http::HeaderMap = http::HeaderMap::new();
+        wrong.insert(BENCHMARK_SECRET_HEADER, "swordfisH".parse().unwrap());
+        let mut right: http::HeaderMap = http::HeaderMap::new();
+        right.insert(BENCHMARK_SECRET_HEADER, "swordfish".parse().unwrap());
+
+        assert!(!presents_benchmark_secret(&right, &enabled), "no secret configured keeps the route closed");
+        assert!(!presents_benchmark_secret(&http::HeaderMap::new(), &configured));
+        assert!(!presents_benchmark_secret(&wrong, &configured));
+        assert!(presents_benchmark_secret(&right, &configured));
+
+        let response: Response<body::Body> =
+            handle_benchmark_request(Some("iterations=1"), &wrong, &configured, ticking_clock()).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_benchmark_iterations_clamped() {
+        assert_eq!(benchmark_iterations(None), Ok(DEFAULT_BENCHMARK_ITERATIONS));
+        assert_eq!(benchmark_iterations(Some("iterations=0")), Ok(1));
+        assert_eq!(benchmark_iterations(Some("x=1&iterations=99999999")), Ok(MAX_BENCHMARK_ITERATIONS));
+        assert!(benchmark_iterations(Some("iterations=-1")).is_err());
+    }
+}
@@ -1,10 +1,37 @@
 use axum::body;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use http::{header, Request, Response, StatusCode};
+use ironshield_types::{
+    concat_struct_base64url_decode,
+    leading_zeros_to_param,
+    mint_challenge_from,
+    verify_challenge_signature_with_key,
+    IronShieldChallenge,
+    IronShieldChallengeBuilder,
+    SigningKey,
+};
+use std::sync::OnceLock;
 use worker::{console_log, Body, Error};
+use crate::config::{
+    site_difficulty_multiplier,
+    ChallengeDelivery,
+    Config,
+    CookieLifetime,
+    MAX_POW_DIFFICULTY,
+    MIN_POW_DIFFICULTY,
+};
+use crate::bypass::issue_bypass_token;
 use crate::cors::add_cors_headers;
-use crate::http_handler::protected_content;
-use crate::constant::{CHALLENGE_HEADER, NONCE_HEADER, TIMESTAMP_HEADER, DIFFICULTY_HEADER, BYPASS_COOKIE_NAME, BYPASS_TOKEN_VALUE};
+use crate::difficulty::{assert_solvable, bot_score_to_difficulty, solve_time_to_difficulty, Solvability};
+use crate::issued::with_issued;
+use crate::nonce::{derive_nonce, nonce_bucket, verify_derived_nonce};
+use crate::outstanding::with_outstanding;
+use crate::protocol::{negotiate_protocol, ProtocolVersion};
+use crate::relief::with_relief;
+use crate::source::next_challenge_nonce;
+use crate::throttle::{with_failures, Escalation};
+use crate::http_handler::{cookie_value, has_duplicate_proof_of_work_headers, pow_header, protected_content, request_resource};
+use crate::constant::{ATTEMPTS_HEADER, BOT_SCORE_HEADER, CLIENT_IP_HEADER, CHALLENGE_HEADER, NONCE_HEADER, TIMESTAMP_HEADER, DIFFICULTY_HEADER, BYPASS_COOKIE_NAME, BYPASS_TOKEN_HEADER, CHALLENGE_COOKIE_NAME, PROTOCOL_HEADER, SOLVE_FAILED_HEADER, VERIFY_TIME_HEADER};
 
 /// Serves a multithreaded challenge template for WebAssembly if supported,
 /// denoted by the ` not ` function.
@@ -22,180 +49,2533 @@ pub const    CHALLENGE_TEMPLATE:  &str = include_str!("../../assets/challenge_te
 #[cfg(target_arch = "wasm32")]
 pub const         CHALLENGE_CSS:  &str = include_str!("../../assets/challenge.css");
 
-/// Number of leading zeros required in the hash.
-const            POW_DIFFICULTY: usize = 4;
 /// How long a challenge is valid.
 const MAX_CHALLENGE_AGE_SECONDS:   i64 = 60;
+/// Lifetime of the bypass cookie under `CookieLifetime::Fixed`.
+const BYPASS_COOKIE_MAX_AGE_SECONDS: i64 = 900;
+
+/// Query parameter that replays a specific challenge in test mode.
+const CHALLENGE_QUERY_PARAM:       &str = "challenge";
+/// Query parameter holding the number of challenges in a batch.
+const BATCH_COUNT_QUERY_PARAM:     &str = "count";
+/// Query parameter naming the resource a batch's challenges are bound to.
+const BATCH_RESOURCE_QUERY_PARAM:  &str = "resource";
+/// Most challenges a single batch request may mint.
+const MAX_CHALLENGE_BATCH:         usize = 16;
+/// How long batch challenges stay valid, long enough to solve them
+/// offline and submit later.
+const BATCH_CHALLENGE_TTL_MS:      i64 = 10 * 60 * 1000;
+/// Why a client holding `config.max_outstanding_challenges` is refused.
+const OUTSTANDING_LIMIT_MESSAGE:   &str = "Too many outstanding challenges, solve or let some expire first";
+/// How many times the expected solve time a submission may take before
+/// it is flagged as implausibly late.
+const SOLVE_LATENCY_FLAG_FACTOR:   i64 = 10;
+/// Latency never flagged regardless of difficulty, covering page load and
+/// network round trips on easy challenges.
+const SOLVE_LATENCY_GRACE_MS:      i64 = 5_000;
 
 /// Function to issue a new challenge.
-pub(crate) async fn issue_new_challenge(headers: &http::HeaderMap) -> worker::Result<Response<body::Body>> {
-    let challenge: String = hex::encode(&rand::random::<[u8; 16]>());
+///
+/// Protocol v1 challenges count against `config.max_outstanding_challenges`
+/// like signed ones (see `crate::outstanding`); a client holding the
+/// maximum gets `429 Too Many Requests` instead.
+pub(crate) async fn issue_new_challenge(
+    query: Option<&str>,
+    headers: &http::HeaderMap,
+    config: &Config,
+) -> worker::Result<Response<body::Body>> {
+    let challenge: String = match forced_challenge(query, config) {
+        Some(challenge) => {
+            console_log!("TEST_MODE: replaying challenge {}", challenge);
+            challenge
+        }
+        None => next_challenge_nonce(config, 16),
+    };
+    // Clients and the core solver reject empty challenges.
+    debug_assert!(!challenge.is_empty());
     let timestamp_ms: i64 = Utc::now().timestamp_millis();
-    generate_challenge_page(&challenge, timestamp_ms, &headers)
+    record_solve_failure_report(headers, config, timestamp_ms);
+    let escalation: Option<Escalation> = client_escalation(headers, config, timestamp_ms);
+    console_log!(
+        "Issuing WebAssembly challenge with timestamp: {}, difficulty: {}",
+        timestamp_ms,
+        issued_difficulty(headers, escalation.as_ref(), config)
+    );
+    if let Some(escalation) = escalation.filter(Escalation::is_throttled) {
+        console_log!("Throttling client after {} recent failures", escalation.failures);
+    }
+    if let Some(escalation) = escalation.filter(Escalation::is_relieved) {
+        console_log!("Relieving client by {} leading zeros after reported solve failures", escalation.relief);
+    }
+    if issues_json(headers, config) && negotiate_protocol(headers, escalation.as_ref(), config) == ProtocolVersion::V2 {
+        match mint_protocol_v2_challenge(headers, config, timestamp_ms) {
+            Ok(signed) => return generate_signed_challenge_json(&signed, headers),
+            Err(e) => console_log!("Falling back to protocol v1: {}", e),
+        }
+    }
+    if !reserve_outstanding_v1_challenge(headers, &challenge, config, timestamp_ms) {
+        console_log!("Refusing challenge: {}", OUTSTANDING_LIMIT_MESSAGE);
+        return outstanding_limit_response(headers);
+    }
+    record_issued_challenge(headers, &challenge, escalation.as_ref(), config, timestamp_ms);
+    if issues_json(headers, config) {
+        return generate_challenge_json(&challenge, timestamp_ms, escalation.as_ref(), headers, config);
+    }
+    generate_challenge_page(&challenge, timestamp_ms, escalation.as_ref(), &headers, config)
 }
 
-/// Function to generate the challenge page that uses WebAssembly.
-pub(crate) fn generate_challenge_page(
+/// Counts a v1 challenge against the requesting client's
+/// `config.max_outstanding_challenges` until it is solved or expires
+/// `MAX_CHALLENGE_AGE_SECONDS` after issuance.
+///
+/// # Returns
+/// * `bool`: Whether the challenge may be issued, i.e. the client had a
+///   free slot, or issuance is uncapped or the client IP unknown.
+pub(crate) fn reserve_outstanding_v1_challenge(
+    headers: &http::HeaderMap,
+    challenge: &str,
+    config: &Config,
+    now_millis: i64,
+) -> bool {
+    let client_ip: Option<&str> = headers.get(CLIENT_IP_HEADER).and_then(|v| v.to_str().ok());
+    match (outstanding_slots(client_ip, config, now_millis), client_ip) {
+        (Some(0), _) => false,
+        (Some(_), Some(ip)) => {
+            let expires: i64 = now_millis + MAX_CHALLENGE_AGE_SECONDS * 1000;
+            with_outstanding(|outstanding| outstanding.record(ip, challenge.as_bytes(), expires, now_millis));
+            true
+        }
+        _ => true,
+    }
+}
+
+/// Frees the outstanding slot of the v1 challenge a valid header
+/// submission solved.
+pub(crate) fn release_outstanding_v1_challenge(headers: &http::HeaderMap) {
+    let client_ip: Option<&str> = headers.get(CLIENT_IP_HEADER).and_then(|v| v.to_str().ok());
+    let challenge: Option<&str> = pow_header(headers, CHALLENGE_HEADER)
+        .or_else(|| cookie_value(headers, CHALLENGE_COOKIE_NAME));
+    if let (Some(ip), Some(challenge)) = (client_ip, challenge) {
+        with_outstanding(|outstanding| outstanding.record_consumed(ip, challenge.as_bytes()));
+    }
+}
+
+/// Builds the `429 Too Many Requests` answered to a client holding
+/// `config.max_outstanding_challenges` unsolved challenges.
+fn outstanding_limit_response(headers: &http::HeaderMap) -> worker::Result<Response<body::Body>> {
+    add_cors_headers(
+        Response::builder()
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .header(header::CONTENT_TYPE, "text/plain"),
+        headers,
+    )
+        .body(body::Body::from(OUTSTANDING_LIMIT_MESSAGE))
+        .map_err(|e: http::Error| Error::RustError(format!("Failed to build response: {}", e)))
+}
+
+/// Returns the requesting client's difficulty escalation, if
+/// `config.escalate_on_failure` or `config.relieve_on_solve_failure` is
+/// set and the client IP is known. The escalation includes any relief
+/// for reported solve failures, see `crate::relief`.
+pub(crate) fn client_escalation(
+    headers: &http::HeaderMap,
+    config: &Config,
+    now_millis: i64,
+) -> Option<Escalation> {
+    if !config.escalate_on_failure && !config.relieve_on_solve_failure {
+        return None;
+    }
+
+    let client_ip: &str = headers.get(CLIENT_IP_HEADER)?.to_str().ok()?;
+    let base_difficulty: usize = request_pow_difficulty(headers, config);
+    let escalation: Escalation = match config.escalate_on_failure {
+        true => with_failures(|failures| failures.escalation(client_ip, base_difficulty, now_millis)),
+        false => Escalation::none(base_difficulty),
+    };
+    let relief: usize = match config.relieve_on_solve_failure {
+        true => with_relief(|relief| relief.level(client_ip, now_millis)),
+        false => 0,
+    };
+    Some(escalation.relieved(relief))
+}
+
+/// Counts a verification result towards the requesting client's
+/// escalation when `config.escalate_on_failure` is set. A success also
+/// ends any relief.
+pub(crate) fn record_client_result(headers: &http::HeaderMap, config: &Config, valid: bool, now_millis: i64) {
+    let client_ip: Option<&str> = headers.get(CLIENT_IP_HEADER).and_then(|v| v.to_str().ok());
+    if let (true, Some(client_ip)) = (config.escalate_on_failure, client_ip) {
+        with_failures(|failures| match valid {
+            true => failures.record_success(client_ip),
+            false => {
+                failures.record_failure(client_ip, now_millis);
+            }
+        });
+    }
+    if let (true, true, Some(client_ip)) = (config.relieve_on_solve_failure, valid, client_ip) {
+        with_relief(|relief| relief.record_success(client_ip));
+    }
+}
+
+/// Counts an `X-IronShield-Solve-Failed` report on a challenge request
+/// towards the client's relief when `config.relieve_on_solve_failure` is
+/// set. The header holds the challenge the client gave up on, which must
+/// have been issued to it (see `crate::issued`).
+pub(crate) fn record_solve_failure_report(headers: &http::HeaderMap, config: &Config, now_millis: i64) {
+    let client_ip: Option<&str> = headers.get(CLIENT_IP_HEADER).and_then(|v| v.to_str().ok());
+    let reported: Option<&str> = headers.get(SOLVE_FAILED_HEADER).and_then(|v| v.to_str().ok());
+    if let (true, Some(reported), Some(client_ip)) = (config.relieve_on_solve_failure, reported, client_ip) {
+        let issued_millis: Option<i64> = with_issued(|issued| {
+            issued.get(client_ip, reported).map(|issued| issued.issued_millis)
+        });
+        with_relief(|relief| relief.record_report(client_ip, issued_millis, now_millis));
+    }
+}
+
+/// The leading-zero difficulty issued to a client: its escalated or
+/// relieved difficulty, otherwise `request_pow_difficulty`.
+fn issued_difficulty(headers: &http::HeaderMap, escalation: Option<&Escalation>, config: &Config) -> usize {
+    match escalation {
+        Some(escalation) => escalation.difficulty,
+        None => request_pow_difficulty(headers, config),
+    }
+}
+
+/// The leading-zero difficulty a request is challenged at before
+/// escalation or relief: `site_pow_difficulty` for `request_website_id`
+/// (`config.pow_difficulty` when the request names no site), raised by
+/// `scored_difficulty` and rounded to the nearest whole leading zero.
+fn request_pow_difficulty(headers: &http::HeaderMap, config: &Config) -> usize {
+    let zeros: usize = request_website_id(headers, config)
+        .map_or(config.pow_difficulty, |website_id: String| site_pow_difficulty(&website_id, config));
+    let attempts: u64 = scored_difficulty(16u64.saturating_pow(zeros as u32), request_bot_score(headers), config);
+    ((attempts as f64).log(16.0).round() as usize).clamp(MIN_POW_DIFFICULTY, MAX_POW_DIFFICULTY)
+}
+
+/// Cloudflare's bot score for a request, see `BOT_SCORE_HEADER`.
+///
+/// # Returns
+/// * `Option<u64>`: The score, from 1 to 99, or `None` if the request was
+///   not scored.
+pub(crate) fn request_bot_score(headers: &http::HeaderMap) -> Option<u64> {
+    headers
+        .get(BOT_SCORE_HEADER)?
+        .to_str()
+        .ok()?
+        .parse::<u64>()
+        .ok()
+        .filter(|score: &u64| (1..=99).contains(score))
+}
+
+/// Raises the expected attempts `base` for a request Cloudflare scored as
+/// a likely bot, see `bot_score_to_difficulty`.
+///
+/// # Returns
+/// * `u64`: The raised attempts, or `base` unchanged when the request was
+///   not scored or `config.bot_score_scaling` is unset.
+fn scored_difficulty(base: u64, bot_score: Option<u64>, config: &Config) -> u64 {
+    match (bot_score, config.bot_score_scaling) {
+        (Some(bot_score), Some(scaling)) => bot_score_to_difficulty(bot_score, base, scaling),
+        _ => base,
+    }
+}
+
+/// Records the difficulty a v1 challenge is issued at, so its submission
+/// is held to it (see `enforced_difficulty`). Only needed while escalation
+/// or relief can change the difficulty, i.e. when `escalation` is set.
+pub(crate) fn record_issued_challenge(
+    headers: &http::HeaderMap,
+    challenge: &str,
+    escalation: Option<&Escalation>,
+    config: &Config,
+    now_millis: i64,
+) {
+    let client_ip: Option<&str> = headers.get(CLIENT_IP_HEADER).and_then(|v| v.to_str().ok());
+    if let (Some(_), Some(client_ip)) = (escalation, client_ip) {
+        let difficulty: usize = issued_difficulty(headers, escalation, config);
+        with_issued(|issued| issued.record(client_ip, challenge, difficulty, now_millis));
+    }
+}
+
+/// The leading-zero difficulty enforced on a header submission: the one
+/// its challenge was issued at to this client (see `crate::issued`), so a
+/// challenge issued before an escalation stays valid, or the client's
+/// current difficulty for a challenge this isolate did not record.
+pub(crate) fn enforced_difficulty(headers: &http::HeaderMap, config: &Config, now_millis: i64) -> usize {
+    let client_ip: Option<&str> = headers.get(CLIENT_IP_HEADER).and_then(|v| v.to_str().ok());
+    let recorded: Option<usize> = match (client_ip, pow_header(headers, CHALLENGE_HEADER)) {
+        (Some(client_ip), Some(challenge)) => with_issued(|issued| {
+            issued.get(client_ip, challenge).map(|issued| issued.difficulty)
+        }),
+        _ => None,
+    };
+    recorded.unwrap_or_else(|| issued_difficulty(headers, client_escalation(headers, config, now_millis).as_ref(), config))
+}
+
+/// Starts a challenge response: `429 Too Many Requests` with
+/// `Retry-After` for throttled clients, `200 OK` otherwise.
+fn challenge_response_builder(escalation: Option<&Escalation>) -> http::response::Builder {
+    match escalation.filter(|escalation: &&Escalation| escalation.is_throttled()) {
+        Some(escalation) => Response::builder()
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .header(header::RETRY_AFTER, escalation.retry_after_seconds.to_string()),
+        None => Response::builder().status(StatusCode::OK),
+    }
+}
+
+/// Returns the challenge requested via `?challenge=<base64url>`, so a
+/// failing challenge can be replayed deterministically.
+///
+/// Only honored when `config.test_mode` is set; in production clients
+/// must never pick their own (easy) challenges.
+///
+/// # Arguments
+/// * `query`:  The raw request query string, if any.
+/// * `config`: The worker configuration.
+///
+/// # Returns
+/// * `Option<String>`: The decoded challenge, or `None` outside test mode
+///   or when the parameter is missing or not a safe challenge string.
+pub(crate) fn forced_challenge(query: Option<&str>, config: &Config) -> Option<String> {
+    if !config.test_mode {
+        return None;
+    }
+
+    let encoded: &str = query?
+        .split('&')
+        .find_map(|pair: &str| pair.strip_prefix(CHALLENGE_QUERY_PARAM)?.strip_prefix('='))?;
+    let challenge: String = concat_struct_base64url_decode(encoded.to_string()).ok()?;
+
+    // The challenge is echoed into headers and HTML, so keep it to the
+    // same alphabet as generated challenges.
+    let safe: bool = !challenge.is_empty()
+        && challenge.bytes().all(|b: u8| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    safe.then_some(challenge)
+}
+
+/// Returns whether a challenge is issued as JSON rather than the HTML
+/// page: always in `ChallengeDelivery::Headless`, otherwise when the
+/// client asks for it (see `wants_json`).
+pub(crate) fn issues_json(headers: &http::HeaderMap, config: &Config) -> bool {
+    config.challenge_delivery == ChallengeDelivery::Headless || wants_json(headers)
+}
+
+/// Returns whether the client asked for the JSON challenge API via
+/// `Accept: application/json`.
+pub(crate) fn wants_json(headers: &http::HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(|accept: &str| accept.contains("application/json"))
+        .unwrap_or(false)
+}
+
+/// JSON body of a challenge issued through the JSON challenge API.
+///
+/// * `protocol`:             Always `ProtocolVersion::V1`.
+/// * `expected_attempts`:    Mean number of hashes needed to find
+///   `difficulty` leading hex zeros, i.e. `16^difficulty`.
+/// * `recommended_attempts`: `IronShieldChallenge::recommended_attempts`
+///   for `expected_attempts`, for clients that want a safety margin.
+/// * `escalation`:           Present only when the client is throttled,
+///   explaining why `difficulty` is above the configured one.
+///
+/// Clients divide either by their own benchmarked hash rate for an ETA.
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ChallengePayload {
+    pub protocol:             u8,
+    pub challenge:            String,
+    pub timestamp:            i64,
+    pub difficulty:           usize,
+    pub expected_attempts:    u64,
+    pub recommended_attempts: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escalation:           Option<Escalation>,
+}
+
+impl ChallengePayload {
+    /// Builds the payload for a leading-zero challenge.
+    pub(crate) fn new(challenge: &str, timestamp: i64, difficulty: usize) -> Self {
+        let expected_attempts: u64 = 16u64.saturating_pow(difficulty as u32);
+        Self {
+            protocol: ProtocolVersion::V1.number(),
+            challenge: challenge.to_string(),
+            timestamp,
+            difficulty,
+            expected_attempts,
+            recommended_attempts: IronShieldChallenge::recommended_attempts(expected_attempts),
+            escalation: None,
+        }
+    }
+
+    /// Builds the payload issued to a client: its site's difficulty, or
+    /// the escalated one with its details when the client is throttled.
+    pub(crate) fn issued(
+        challenge: &str,
+        timestamp: i64,
+        escalation: Option<&Escalation>,
+        headers: &http::HeaderMap,
+        config: &Config,
+    ) -> Self {
+        Self {
+            escalation: escalation.filter(|escalation: &&Escalation| escalation.is_throttled()).copied(),
+            ..Self::new(challenge, timestamp, issued_difficulty(headers, escalation, config))
+        }
+    }
+}
+
+/// Function to generate a JSON challenge for API clients.
+///
+/// Throttled clients (see `crate::throttle`) get `429` with the escalated
+/// challenge and the escalation details in the body.
+pub(crate) fn generate_challenge_json(
     challenge_string: &str,
     timestamp: i64,
+    escalation: Option<&Escalation>,
     headers: &http::HeaderMap,
+    config: &Config,
 ) -> worker::Result<Response<body::Body>> {
-    console_log!(
-        "Issuing WebAssembly challenge with timestamp: {}",
-        timestamp
-    );
+    let payload: ChallengePayload = ChallengePayload::issued(challenge_string, timestamp, escalation, headers, config);
+    let difficulty: usize = payload.difficulty;
+    let json: String = serde_json::to_string(&payload)
+        .map_err(|e: serde_json::Error| Error::RustError(format!("Failed to serialize challenge: {}", e)))?;
 
-    // Create meta-tags for all parameters
-    let difficulty_meta_tag: String = format!(
-        "<meta name=\"x-ironshield-difficulty\" content=\"{}\">",
-        POW_DIFFICULTY
-    );
-    let timestamp_meta_tag: String = format!(
-        "<meta name=\"x-ironshield-timestamp\" content=\"{}\">",
-        timestamp
-    );
-    let challenge_meta_tag: String = format!(
-        "<meta name=\"x-ironshield-challenge\" content=\"{}\">",
-        challenge_string
-    );
+    add_cors_headers(
+        challenge_response_builder(escalation)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(DIFFICULTY_HEADER, difficulty.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(CHALLENGE_HEADER, challenge_string)
+            .header(PROTOCOL_HEADER, ProtocolVersion::V1.number().to_string()),
+        headers,
+    )
+        .body(body::Body::from(json))
+        .map_err(|e: http::Error| {
+            Error::RustError(format!("Failed to build challenge response: {}", e))
+        })
+}
+
+/// JSON body of a protocol v2 challenge.
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct SignedChallengePayload<'a> {
+    pub protocol:  u8,
+    pub challenge: &'a IronShieldChallenge,
+}
+
+/// The `website_id` challenges minted for a request are bound to:
+/// `config.site_id` when configured, otherwise the lowercase host of the
+/// request's `Host` header, without its port.
+///
+/// # Returns
+/// * `Option<String>`: The site id, or `None` if neither is available, in
+///   which case no challenge can be minted.
+pub(crate) fn request_website_id(headers: &http::HeaderMap, config: &Config) -> Option<String> {
+    if let Some(site_id) = &config.site_id {
+        return Some(site_id.clone());
+    }
+    headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|host: &str| host.trim().parse::<http::uri::Authority>().ok())
+        .map(|authority: http::uri::Authority| authority.host().to_ascii_lowercase())
+        .filter(|host: &String| !host.is_empty())
+}
+
+/// Mints the signed challenge issued to a protocol v2 client, bound to
+/// `request_website_id`, see `mint_challenge_batch`.
+fn mint_protocol_v2_challenge(
+    headers: &http::HeaderMap,
+    config: &Config,
+    now_millis: i64,
+) -> Result<IronShieldChallenge, String> {
+    let website_id: String = request_website_id(headers, config).ok_or("Missing Host header")?;
+    let client_ip: Option<&str> = headers.get(CLIENT_IP_HEADER).and_then(|v| v.to_str().ok());
 
-    // Replace placeholders in the template and add our meta-tags after the viewport meta.
-    let html_content = CHALLENGE_TEMPLATE
-        .replace("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">",
-                 &format!("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\n    {}\n    {}\n    {}",
-                          difficulty_meta_tag, timestamp_meta_tag, challenge_meta_tag))
-        // Keep these replacements for header names.
-        .replace("X-Challenge", CHALLENGE_HEADER)
-        .replace("X-Nonce", NONCE_HEADER)
-        .replace("X-Timestamp", TIMESTAMP_HEADER);
+    mint_challenge_batch(1, &website_id, None, client_ip, request_bot_score(headers), config, now_millis)?
+        .pop()
+        .ok_or_else(|| "No challenge minted".to_string())
+}
+
+/// Function to generate a protocol v2 JSON challenge, solved through a
+/// JSON submission.
+pub(crate) fn generate_signed_challenge_json(
+    challenge: &IronShieldChallenge,
+    headers: &http::HeaderMap,
+) -> worker::Result<Response<body::Body>> {
+    let payload: SignedChallengePayload = SignedChallengePayload { protocol: ProtocolVersion::V2.number(), challenge };
+    let json: String = serde_json::to_string(&payload)
+        .map_err(|e: serde_json::Error| Error::RustError(format!("Failed to serialize challenge: {}", e)))?;
 
     add_cors_headers(
         Response::builder()
             .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, "text/html")
-            .header(DIFFICULTY_HEADER, POW_DIFFICULTY.to_string())
-            .header(TIMESTAMP_HEADER, timestamp.to_string())
-            .header(CHALLENGE_HEADER, challenge_string),
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(PROTOCOL_HEADER, ProtocolVersion::V2.number().to_string()),
         headers,
     )
-        .body(body::Body::from(html_content))
+        .body(body::Body::from(json))
         .map_err(|e: http::Error| {
             Error::RustError(format!("Failed to build challenge response: {}", e))
         })
 }
 
-/// Function to verify the submitted solution.
-pub(crate) fn verify_solution(req: &Request<Body>) -> bool {
-    console_log!("Verifying checksum...");
-
-    let headers: &http::HeaderMap = req.headers();
-    let challenge_opt: Option<&str> = headers.get(CHALLENGE_HEADER).and_then(|v| v.to_str().ok());
-    let nonce_opt: Option<&str> = headers.get(NONCE_HEADER).and_then(|v| v.to_str().ok());
-    let timestamp_opt: Option<&str> = headers.get(TIMESTAMP_HEADER).and_then(|v| v.to_str().ok());
-    let difficulty_opt: Option<&str> = headers.get(DIFFICULTY_HEADER).and_then(|v| v.to_str().ok());
-
-    match (challenge_opt, nonce_opt, timestamp_opt, difficulty_opt) {
-        (Some(challenge), Some(nonce_str), Some(timestamp_str), Some(difficulty_str)) => {
-            // 1. Verify timestamp freshness
-            match timestamp_str.parse::<i64>() {
-                Ok(timestamp_millis) => {
-                    let now_millis: i64 = Utc::now().timestamp_millis();
-                    if now_millis.saturating_sub(timestamp_millis)
-                        > MAX_CHALLENGE_AGE_SECONDS * 1000
-                    {
-                        console_log!(
-                            "Challenge timestamp expired. Now: {}, Provided: {}",
-                            now_millis,
-                            timestamp_millis
-                        );
-                        return false;
-                    } 
-//                  // Optionally check if the timestamp is too far in the future as well?
-//                  if timestamp_millis > now_millis + 5000 { // e.g., 5 seconds tolerance
-//                      console_log!("Challenge timestamp is in the future.");
-//                      return false;
-//                  }
-                }
-                Err(_) => {
-                    console_log!(
-                        "Invalid timestamp format (expected Unix ms). Received: {}",
-                        timestamp_str
-                    );
-                    return false;
-                }
-            }
-
-            // 2. Parse difficulty
-            let difficulty: usize = match difficulty_str.parse::<usize>() {
-                Ok(d) => d,
-                Err(_) => {
-                    console_log!("Invalid difficulty format.");
-                    return false;
-                }
-            };
+/// Fills the `{{name}}` placeholders of the challenge page template.
+///
+/// Only placeholders named in `values` are replaced, each with its value
+/// HTML-attribute-escaped; all other text, including unknown
+/// placeholders, is copied unchanged. Values are never rescanned, so a
+/// value containing `{{...}}` is not substituted again.
+///
+/// # Arguments
+/// * `template`: The template, e.g. `CHALLENGE_TEMPLATE`.
+/// * `values`:   `(name, value)` pairs; values may contain any characters.
+///
+/// # Returns
+/// * `String`: The rendered page.
+pub(crate) fn render_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut rendered: String = String::with_capacity(template.len());
+    let mut rest: &str = template;
 
-            // 3. Verify the solution using our core library
-            let result: bool = ironshield_core::verify_solution(challenge, nonce_str, difficulty);
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after: &str = &rest[start + 2..];
+        let known: Option<(&str, usize)> = after.find("}}").and_then(|end: usize| {
+            values
+                .iter()
+                .find(|(name, _)| *name == &after[..end])
+                .map(|(_, value)| (*value, end))
+        });
 
-            if result {
-                console_log!("Checksum verification successful!");
-                true
-            } else {
-                console_log!("Checksum verification failed.");
-                false
+        match known {
+            Some((value, end)) => {
+                rendered.push_str(&escape_html_attribute(value));
+                rest = &after[end + 2..];
+            }
+            None => {
+                rendered.push_str("{{");
+                rest = after;
             }
         }
-        _ => {
-            console_log!("Missing required PoW headers.");
-            false // Missing headers
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Escapes the characters that could end a double-quoted HTML attribute
+/// or open a tag.
+fn escape_html_attribute(value: &str) -> String {
+    let mut escaped: String = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(c),
         }
     }
+    escaped
 }
 
-/// Function to handle solution verification and return the appropriate response.
-pub(crate) async fn handle_solution_verification(
-    req: &Request<Body>,
+/// Function to generate the challenge page that uses WebAssembly.
+pub(crate) fn generate_challenge_page(
+    challenge_string: &str,
+    timestamp: i64,
+    escalation: Option<&Escalation>,
     headers: &http::HeaderMap,
+    config: &Config,
 ) -> worker::Result<Response<body::Body>> {
-    // Early return for failed verification
-    if !verify_solution(&req) {
-        let response = add_cors_headers(
-            Response::builder()
-                .status(StatusCode::FORBIDDEN)
-                .header(header::CONTENT_TYPE, "text/plain"),
-            &headers,
-        )
-            .body(body::Body::from(
-                "Proof of Work verification failed. Please try again.",
-            ));
-
-        return response.map_err(|e: http::Error| {
-            Error::RustError(format!("Failed to build response: {}", e))
-        });
+    let difficulty: usize = issued_difficulty(headers, escalation, config);
+
+    let html_content: String = render_template(CHALLENGE_TEMPLATE, &[
+        ("difficulty", &difficulty.to_string()),
+        ("timestamp", &timestamp.to_string()),
+        ("challenge", challenge_string),
+    ]);
+
+    let mut builder: http::response::Builder = challenge_response_builder(escalation)
+        .header(header::CONTENT_TYPE, "text/html")
+        .header(DIFFICULTY_HEADER, difficulty.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(CHALLENGE_HEADER, challenge_string);
+
+    if config.challenge_cookie {
+        // Not HttpOnly: the JS solver reads it when headers are stripped.
+        builder = builder.header(
+            header::SET_COOKIE,
+            format!(
+                "{}={}; Max-Age={}; Secure; Path=/; SameSite=Lax",
+                CHALLENGE_COOKIE_NAME,
+                challenge_string,
+                MAX_CHALLENGE_AGE_SECONDS
+            ),
+        );
     }
 
-    // Verification successful - prepare success response
-    let cookie_value = format!(
-        "{}={}; Max-Age=900; HttpOnly; Secure; Path=/; SameSite=Lax",
-        BYPASS_COOKIE_NAME,
-        BYPASS_TOKEN_VALUE
-    );
+    add_cors_headers(builder, headers)
+        .body(body::Body::from(html_content))
+        .map_err(|e: http::Error| {
+            Error::RustError(format!("Failed to build challenge response: {}", e))
+        })
+}
 
-    #[allow(unused_variables)]
-    let content = protected_content().await;
+/// Parses the `count` query parameter of a batch request.
+///
+/// # Returns
+/// * `Result<usize, String>`: The count clamped to
+///   `1..=MAX_CHALLENGE_BATCH` (1 when absent), or an error if it is not
+///   a number.
+pub(crate) fn batch_count(query: Option<&str>) -> Result<usize, String> {
+    let raw: Option<&str> = query.and_then(|query: &str| {
+        query
+            .split('&')
+            .find_map(|pair: &str| pair.strip_prefix(BATCH_COUNT_QUERY_PARAM)?.strip_prefix('='))
+    });
 
-    let response = add_cors_headers(
+    match raw {
+        None => Ok(1),
+        Some(raw) => raw
+            .parse::<usize>()
+            .map(|count: usize| count.clamp(1, MAX_CHALLENGE_BATCH))
+            .map_err(|_| format!("Invalid count: {:?}", raw)),
+    }
+}
+
+/// Parses the `resource` query parameter of a batch request.
+///
+/// The value is decoded like the submission path, see `request_resource`,
+/// so `%2Freports%2Fa.pdf` binds to the same resource as `/reports/a.pdf`.
+///
+/// # Returns
+/// * `Option<String>`: The resource, or `None` when absent or empty, in
+///   which case the challenges are not bound to a resource.
+pub(crate) fn batch_resource(query: Option<&str>) -> Option<String> {
+    query?
+        .split('&')
+        .find_map(|pair: &str| pair.strip_prefix(BATCH_RESOURCE_QUERY_PARAM)?.strip_prefix('='))
+        .filter(|resource: &&str| !resource.is_empty())
+        .map(request_resource)
+}
+
+/// Difficulty of challenges minted for a site, scaled by the site's
+/// `site_difficulty_multiplier`. The base is the attempts a
+/// `reference_hashrate` client makes in `target_solve_ms` when both are
+/// configured, otherwise the `16^pow_difficulty` attempts implied by the
+/// configured leading zeros.
+/// Protocol v1 challenges are scaled by `site_pow_difficulty` instead.
+///
+/// # Returns
+/// * `u64`: The difficulty, at least 1 and saturating at `u64::MAX`.
+pub(crate) fn site_difficulty(website_id: &str, config: &Config) -> u64 {
+    let base: u64 = match (config.target_solve_ms, config.reference_hashrate) {
+        (Some(target_solve_ms), Some(hashrate)) => solve_time_to_difficulty(target_solve_ms, hashrate),
+        _ => 16u64.saturating_pow(config.pow_difficulty as u32),
+    };
+    let scaled: f64 = (base as f64 * site_difficulty_multiplier(website_id, config)).round();
+
+    // Float-to-int casts saturate, so huge multipliers clamp to u64::MAX.
+    (scaled as u64).max(1)
+}
+
+/// Leading zeros of protocol v1 challenges issued for a site:
+/// `config.pow_difficulty` scaled by the site's
+/// `site_difficulty_multiplier`. A leading zero multiplies the expected
+/// attempts by 16, so the multiplier is rounded to the nearest power of
+/// 16; `4.0` adds a zero, `2.0` adds none and `0.0625` removes one.
+///
+/// # Returns
+/// * `usize`: The leading zeros, clamped to
+///   `MIN_POW_DIFFICULTY..=MAX_POW_DIFFICULTY`.
+pub(crate) fn site_pow_difficulty(website_id: &str, config: &Config) -> usize {
+    let zeros: f64 = config.pow_difficulty as f64 + site_difficulty_multiplier(website_id, config).log(16.0).round();
+    (zeros.max(0.0) as usize).clamp(MIN_POW_DIFFICULTY, MAX_POW_DIFFICULTY)
+}
+
+/// The `site_difficulty` minted for `website_id`, raised for likely bots
+/// by `scored_difficulty` and capped by `assert_solvable` when
+/// `max_solve_ms` and `reference_hashrate` are both configured. Capping is
+/// logged on Workers, as it points at a misconfiguration.
+fn issuance_difficulty(website_id: &str, bot_score: Option<u64>, config: &Config) -> u64 {
+    let difficulty: u64 = scored_difficulty(site_difficulty(website_id, config), bot_score, config);
+    let (Some(max_solve_ms), Some(hashrate)) = (config.max_solve_ms, config.reference_hashrate) else {
+        return difficulty;
+    };
+
+    let solvability: Solvability = assert_solvable(difficulty, hashrate, max_solve_ms);
+    #[cfg(target_arch = "wasm32")]
+    if solvability.capped {
+        console_log!(
+            "Difficulty {} for {} needs ~{}ms on a reference client; capping to {}",
+            difficulty,
+            website_id,
+            solvability.expected_ms,
+            solvability.difficulty
+        );
+    }
+    solvability.difficulty
+}
+
+/// Challenge params for the leading-zero difficulties
+/// `16^MIN_POW_DIFFICULTY..=16^MAX_POW_DIFFICULTY`, computed once per
+/// isolate.
+fn leading_zero_params() -> &'static [[u8; 32]] {
+    static PARAMS: OnceLock<Vec<[u8; 32]>> = OnceLock::new();
+    PARAMS.get_or_init(|| (MIN_POW_DIFFICULTY..=MAX_POW_DIFFICULTY).map(leading_zeros_to_param).collect())
+}
+
+/// Returns `IronShieldChallenge::difficulty_to_challenge_param(difficulty)`,
+/// served from `leading_zero_params` for the configurable leading-zero
+/// difficulties so the common issuance path skips the conversion.
+pub(crate) fn challenge_param_for(difficulty: u64) -> [u8; 32] {
+    let zeros: usize = (difficulty.trailing_zeros() / 4) as usize;
+    let cached: bool = difficulty.is_power_of_two()
+        && difficulty.trailing_zeros().is_multiple_of(4)
+        && (MIN_POW_DIFFICULTY..=MAX_POW_DIFFICULTY).contains(&zeros);
+
+    match cached {
+        true => leading_zero_params()[zeros - MIN_POW_DIFFICULTY],
+        false => IronShieldChallenge::difficulty_to_challenge_param(difficulty),
+    }
+}
+
+/// Mints `count` independent challenges signed with `config.private_key`.
+///
+/// Each challenge is bound to `website_id`, requires `site_difficulty`
+/// attempts (see `issuance_difficulty`), and stays valid for
+/// `BATCH_CHALLENGE_TTL_MS` so it can be solved offline. Any of them
+/// verifies through `verify_json_submission`.
+///
+/// With `config.nonce_secret` set, nonces are derived from `client_ip`
+/// and the current time bucket (see `crate::nonce`). All such challenges
+/// share a nonce, so at most one is minted.
+///
+/// With `resource` set, every challenge is bound to it and only unlocks
+/// submissions posted to that path.
+///
+/// With `config.max_outstanding_challenges` set, at most as many are
+/// minted as `client_ip` has free slots (see `outstanding_slots`), and
+/// the minted challenges occupy those slots until consumed or expired.
+///
+/// # Arguments
+/// * `count`:      Number of challenges to mint.
+/// * `website_id`: The site the challenges are issued for.
+/// * `resource`:   The resource to bind the challenges to, if any.
+/// * `client_ip`:  The requesting client's IP, if known.
+/// * `bot_score`:  The request's Cloudflare bot score, if scored, see
+///   `issuance_difficulty`.
+/// * `config`:     The worker configuration holding the signing key.
+/// * `now_millis`: The current time in Unix milliseconds.
+///
+/// # Returns
+/// * `Result<Vec<IronShieldChallenge>, String>`: The signed challenges, or
+///   an error if no signing key is configured, a derived nonce needs a
+///   missing client IP, or the client has no free slots.
+pub(crate) fn mint_challenge_batch(
+    count: usize,
+    website_id: &str,
+    resource: Option<&str>,
+    client_ip: Option<&str>,
+    bot_score: Option<u64>,
+    config: &Config,
+    now_millis: i64,
+) -> Result<Vec<IronShieldChallenge>, String> {
+    let private_key: [u8; 32] = config.private_key.ok_or("Challenge signing key is not configured")?;
+    let signing_key: SigningKey = SigningKey::from_bytes(&private_key);
+    let difficulty: u64 = issuance_difficulty(website_id, bot_score, config);
+    let derived_nonce: Option<String> = match &config.nonce_secret {
+        Some(secret) => {
+            let ip: &str = client_ip.ok_or("Missing client IP for a derived nonce")?;
+            Some(derive_nonce(secret, nonce_bucket(now_millis), ip))
+        }
+        None => None,
+    };
+    let count: usize = if derived_nonce.is_some() { count.min(1) } else { count };
+    let count: usize = match outstanding_slots(client_ip, config, now_millis) {
+        Some(0) => return Err(OUTSTANDING_LIMIT_MESSAGE.to_string()),
+        Some(slots) => count.min(slots),
+        None => count,
+    };
+
+    let challenges: Vec<IronShieldChallenge> = (0..count)
+        .map(|_| {
+            let builder: IronShieldChallengeBuilder = IronShieldChallenge::builder()
+                .random_nonce(derived_nonce.clone().unwrap_or_else(|| next_challenge_nonce(config, 32)))
+                .website_id(website_id)
+                .difficulty(difficulty)
+                .challenge_param(challenge_param_for(difficulty))
+                .ttl_ms(BATCH_CHALLENGE_TTL_MS);
+            let builder: IronShieldChallengeBuilder = match resource {
+                Some(resource) => builder.resource(resource),
+                None => builder,
+            };
+            mint_challenge_from(builder, &signing_key, now_millis)
+        })
+        .collect();
+
+    if let (Some(_), Some(ip)) = (config.max_outstanding_challenges, client_ip) {
+        with_outstanding(|outstanding| outstanding.record_issued(ip, &challenges, now_millis));
+    }
+    Ok(challenges)
+}
+
+/// Returns how many more signed challenges `client_ip` may be minted
+/// under `config.max_outstanding_challenges`.
+///
+/// # Returns
+/// * `Option<usize>`: The free slots, or `None` if minting is uncapped
+///   or the client IP is unknown.
+pub(crate) fn outstanding_slots(client_ip: Option<&str>, config: &Config, now_millis: i64) -> Option<usize> {
+    let limit: u64 = config.max_outstanding_challenges?;
+    let client_ip: &str = client_ip?;
+    Some(with_outstanding(|outstanding| outstanding.available(client_ip, limit, now_millis)))
+}
+
+/// Function to issue a batch of signed challenges for offline solving.
+pub(crate) fn handle_challenge_batch(
+    query: Option<&str>,
+    headers: &http::HeaderMap,
+    config: &Config,
+) -> worker::Result<Response<body::Body>> {
+    let website_id: Option<String> = request_website_id(headers, config);
+    let client_ip: Option<&str> = headers.get(CLIENT_IP_HEADER).and_then(|v| v.to_str().ok());
+    let resource: Option<String> = batch_resource(query);
+    let now_millis: i64 = Utc::now().timestamp_millis();
+    let minted: Result<Vec<IronShieldChallenge>, (StatusCode, String)> = match (batch_count(query), website_id.as_deref()) {
+        (Err(e), _) => Err((StatusCode::BAD_REQUEST, e)),
+        (_, None) => Err((StatusCode::BAD_REQUEST, "Missing Host header".to_string())),
+        _ if outstanding_slots(client_ip, config, now_millis) == Some(0) => {
+            Err((StatusCode::TOO_MANY_REQUESTS, OUTSTANDING_LIMIT_MESSAGE.to_string()))
+        }
+        (Ok(count), Some(website_id)) => {
+            mint_challenge_batch(count, website_id, resource.as_deref(), client_ip, request_bot_score(headers), config, now_millis)
+                .map_err(|e: String| (StatusCode::SERVICE_UNAVAILABLE, e))
+        }
+    };
+
+    let (status, content_type, body) = match minted {
+        Ok(challenges) => {
+            console_log!("Issuing batch of {} signed challenges", challenges.len());
+            let json: String = serde_json::to_string(&challenges)
+                .map_err(|e: serde_json::Error| Error::RustError(format!("Failed to serialize challenges: {}", e)))?;
+            (StatusCode::OK, "application/json", json)
+        }
+        Err((status, message)) => {
+            console_log!("Refusing challenge batch: {}", message);
+            (status, "text/plain", message)
+        }
+    };
+
+    add_cors_headers(
         Response::builder()
-            .status(StatusCode::OK)
-            .header(header::SET_COOKIE, cookie_value)
-            .header(header::CONTENT_TYPE, "application/json"),
-        &headers,
+            .status(status)
+            .header(header::CONTENT_TYPE, content_type),
+        headers,
     )
-        .body(body::Body::from(
-            "{\"success\":true,\"message\":\"Verification successful.\",\"redirectUrl\":\"https://skip.ironshield.cloud\"}"
-        ));
+        .body(body::Body::from(body))
+        .map_err(|e: http::Error| Error::RustError(format!("Failed to build response: {}", e)))
+}
 
-    response.map_err(|e: http::Error| {
-        Error::RustError(format!("Failed to build response: {}", e))
-    })
-}
\ No newline at end of file
+/// Result of checking a proof-of-work submission against the
+/// difficulty enforced by this worker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum VerificationOutcome {
+    /// The nonce satisfies the enforced difficulty.
+    Valid,
+    /// The challenge (header or cookie), nonce, or timestamp is missing.
+    MissingHeaders,
+    /// The challenge is empty or the nonce is not a decimal or `0x`-hex `u64`.
+    Malformed,
+    /// The timestamp header is not a Unix millisecond value.
+    InvalidTimestamp,
+    /// The enforced difficulty is outside the configurable range.
+    InvalidDifficulty,
+    /// The challenge is older than `MAX_CHALLENGE_AGE_SECONDS`.
+    Expired,
+    /// The nonce does not meet the enforced difficulty.
+    InvalidSolution,
+    /// The challenge signature does not verify against the trusted key.
+    InvalidSignature,
+    /// The challenge nonce is not the one derived for this client and
+    /// time bucket (see `crate::nonce`).
+    UnknownNonce,
+    /// The challenge is bound to a resource other than the one requested.
+    ResourceMismatch,
+}
+
+/// Chooses the status and user-facing message reported for a
+/// verification outcome.
+///
+/// Messages say what the user can do without revealing which check
+/// failed beyond what the client already knows, e.g. never the enforced
+/// difficulty or key material.
+///
+/// # Arguments
+/// * `outcome`: The verification outcome.
+///
+/// # Returns
+/// * `(StatusCode, &'static str)`: `400` for requests that were not
+///   understood, `409` for an expired challenge, `403` for a rejected
+///   solution or challenge, and `500` when this worker is misconfigured.
+pub(crate) fn describe_failure(outcome: &VerificationOutcome) -> (StatusCode, &'static str) {
+    match outcome {
+        VerificationOutcome::Valid             => (StatusCode::OK, "Verification successful."),
+        VerificationOutcome::MissingHeaders    => (StatusCode::BAD_REQUEST, "Missing proof-of-work data, please retry"),
+        VerificationOutcome::Malformed         => (StatusCode::BAD_REQUEST, "Malformed submission, please retry"),
+        VerificationOutcome::InvalidTimestamp  => (StatusCode::BAD_REQUEST, "Invalid challenge timestamp, please retry"),
+        VerificationOutcome::InvalidDifficulty => (StatusCode::INTERNAL_SERVER_ERROR, "Verification unavailable, please try again later"),
+        VerificationOutcome::Expired           => (StatusCode::CONFLICT, "Challenge expired, please retry"),
+        VerificationOutcome::InvalidSolution   => (StatusCode::FORBIDDEN, "Proof of work verification failed, please retry"),
+        VerificationOutcome::InvalidSignature  => (StatusCode::FORBIDDEN, "Challenge not recognized, please retry"),
+        VerificationOutcome::UnknownNonce      => (StatusCode::FORBIDDEN, "Challenge was not issued to this client, please retry"),
+        VerificationOutcome::ResourceMismatch  => (StatusCode::FORBIDDEN, "Challenge was issued for another resource, please retry"),
+    }
+}
+
+/// Returns the difficulty claimed in the unsigned `X-IronShield-Difficulty`
+/// header when it differs from the difficulty this worker enforces.
+///
+/// The header is informational only. A mismatch means the client either
+/// solved a different challenge or tampered with the header in an attempt
+/// to downgrade the work required, so callers should log it.
+///
+/// # Arguments
+/// * `headers`:  The request headers.
+/// * `enforced`: The difficulty the worker issued and verifies against.
+///
+/// # Returns
+/// * `Option<String>`: The raw claimed value on mismatch, or `None`
+///   when the header is absent or agrees with `enforced`.
+pub(crate) fn claimed_difficulty_mismatch(
+    headers: &http::HeaderMap,
+    enforced: usize,
+) -> Option<String> {
+    let claimed: &str = pow_header(headers, DIFFICULTY_HEADER)?;
+
+    match claimed.trim().parse::<usize>() {
+        Ok(difficulty) if difficulty == enforced => None,
+        _ => Some(claimed.to_string()),
+    }
+}
+
+/// Describes the attempt count a client reports in the optional
+/// `X-IronShield-Attempts` header, for telemetry only.
+///
+/// The count is never used for acceptance. Logging it next to the outcome
+/// and the expected attempts helps spot clients reporting implausibly few
+/// attempts, e.g. because the work was done elsewhere.
+///
+/// # Arguments
+/// * `headers`:           The request headers.
+/// * `outcome`:           The verification outcome.
+/// * `expected_attempts`: Expected attempts for the enforced difficulty,
+///   if known.
+///
+/// # Returns
+/// * `Option<String>`: The log line, or `None` when the header is absent.
+pub(crate) fn reported_attempts_log(
+    headers: &http::HeaderMap,
+    outcome: &VerificationOutcome,
+    expected_attempts: Option<u64>,
+) -> Option<String> {
+    let reported: &str = headers.get(ATTEMPTS_HEADER).and_then(|v| v.to_str().ok())?;
+    let expected: String = expected_attempts.map_or("unknown".to_string(), |attempts: u64| format!("~{}", attempts));
+
+    Some(format!(
+        "Client reported {:?} attempts (outcome: {:?}, expected: {})",
+        reported.trim(),
+        outcome,
+        expected
+    ))
+}
+
+/// Checks a proof-of-work submission without trusting any difficulty
+/// supplied by the client.
+///
+/// Requests repeating any proof-of-work header are rejected as
+/// malformed. All cheap structural, range, and freshness checks run first; the
+/// SHA-256 recompute only happens once everything else has passed.
+///
+/// # Arguments
+/// * `headers`:    The request headers carrying the challenge (header or
+///   cookie), nonce, and timestamp.
+/// * `difficulty`: The number of leading zeros the worker requires.
+/// * `now_millis`: The current time in Unix milliseconds.
+///
+/// # Returns
+/// * `VerificationOutcome`: `Valid` if the submission is fresh and
+///   the nonce meets `difficulty`.
+pub(crate) fn check_submission(
+    headers: &http::HeaderMap,
+    difficulty: usize,
+    now_millis: i64,
+) -> VerificationOutcome {
+    if has_duplicate_proof_of_work_headers(headers) {
+        return VerificationOutcome::Malformed;
+    }
+
+    let challenge_opt: Option<&str> = pow_header(headers, CHALLENGE_HEADER)
+        .or_else(|| cookie_value(headers, CHALLENGE_COOKIE_NAME));
+    let nonce_opt: Option<&str> = pow_header(headers, NONCE_HEADER);
+    let timestamp_opt: Option<&str> = pow_header(headers, TIMESTAMP_HEADER);
+
+    let (challenge, nonce_str, timestamp_str) = match (challenge_opt, nonce_opt, timestamp_opt) {
+        (Some(challenge), Some(nonce_str), Some(timestamp_str)) => (challenge, nonce_str, timestamp_str),
+        _ => return VerificationOutcome::MissingHeaders,
+    };
+
+    // 1. Structural checks
+    if challenge.is_empty() || ironshield_core::parse_nonce(nonce_str).is_none() {
+        return VerificationOutcome::Malformed;
+    }
+    let timestamp_millis: i64 = match timestamp_str.parse::<i64>() {
+        Ok(t) => t,
+        Err(_) => return VerificationOutcome::InvalidTimestamp,
+    };
+
+    // 2. Difficulty range
+    if !(MIN_POW_DIFFICULTY..=MAX_POW_DIFFICULTY).contains(&difficulty) {
+        return VerificationOutcome::InvalidDifficulty;
+    }
+
+    // 3. Timestamp freshness
+    if now_millis.saturating_sub(timestamp_millis) > MAX_CHALLENGE_AGE_SECONDS * 1000 {
+        return VerificationOutcome::Expired;
+    }
+
+    // 4. Verify the solution against the server-side difficulty only.
+    if recompute_solution(challenge, nonce_str, difficulty) {
+        VerificationOutcome::Valid
+    } else {
+        VerificationOutcome::InvalidSolution
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Number of hash recomputations on this thread, so tests can assert
+    /// that rejected submissions never reach the hashing code.
+    static HASH_RECOMPUTATIONS: std::cell::Cell<u32> = const { std::cell::Cell::new(0) };
+}
+
+/// Recomputes the submission hash; the only expensive step of verification.
+fn recompute_solution(challenge: &str, nonce_str: &str, difficulty: usize) -> bool {
+    #[cfg(test)]
+    HASH_RECOMPUTATIONS.with(|count| count.set(count.get() + 1));
+
+    ironshield_core::verify_solution(challenge, nonce_str, difficulty)
+}
+
+/// A solution submitted to the JSON API.
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct JsonSubmission {
+    pub challenge: IronShieldChallenge,
+    pub solution:  i64,
+}
+
+/// Largest JSON submission body the worker will read, in bytes.
+pub(crate) const MAX_JSON_SUBMISSION_BYTES: usize = 16 * 1024;
+
+/// Fully verifies a JSON submission of the form
+/// `{ "challenge": <IronShieldChallenge>, "solution": <i64> }`.
+///
+/// Checks run cheapest first: structure, expiry, the threshold hash, and
+/// finally the Ed25519 signature against `config.public_key`. The key
+/// embedded in the challenge is never trusted on its own. A challenge
+/// bound to a resource is then only accepted for that `resource`.
+///
+/// A valid submission consumes the challenge, freeing its slot under
+/// `config.max_outstanding_challenges`.
+///
+/// The difficulty is read strictly from the signed `challenge_param`,
+/// never from `config.pow_difficulty` or `site_difficulty`, so changing
+/// either never invalidates challenges already in flight.
+///
+/// # Arguments
+/// * `body`:       The raw JSON request body.
+/// * `resource`:   The resource being unlocked, i.e. the request path.
+/// * `client_ip`:  The submitting client's IP, needed when
+///   `config.nonce_secret` is set.
+/// * `config`:     The worker configuration holding the trusted public key.
+/// * `now_millis`: The current time in Unix milliseconds.
+///
+/// # Returns
+/// * `VerificationOutcome`: `Valid` only if every check passes.
+/// * `Option<i64>`: The challenge's remaining validity at `now_millis`,
+///   or `None` if the body could not be parsed.
+/// * `Option<i64>`: For valid submissions only, the solve latency in
+///   milliseconds when `solve_latency_flag` considers it implausibly late.
+pub(crate) fn verify_json_submission(
+    body: &str,
+    resource: &str,
+    client_ip: Option<&str>,
+    config: &Config,
+    now_millis: i64,
+) -> (VerificationOutcome, Option<i64>, Option<i64>) {
+    let submission: JsonSubmission = match serde_json::from_str::<JsonSubmission>(body) {
+        Ok(submission) => submission,
+        Err(_) => return (VerificationOutcome::Malformed, None, None),
+    };
+
+    let challenge: &IronShieldChallenge = &submission.challenge;
+    let outcome: VerificationOutcome = check_json_submission(&submission, resource, client_ip, config, now_millis);
+    if let (VerificationOutcome::Valid, Some(ip)) = (&outcome, client_ip) {
+        with_outstanding(|outstanding| outstanding.record_consumed(ip, &challenge.challenge_signature));
+    }
+    let late_ms: Option<i64> = match outcome {
+        VerificationOutcome::Valid => solve_latency_flag(
+            challenge.created_time,
+            challenge.recommended_attempts,
+            now_millis,
+            config.reference_hashrate,
+        ),
+        _ => None,
+    };
+
+    (outcome, Some(challenge.time_until_expiration_at(now_millis)), late_ms)
+}
+
+/// Flags a submission whose solve latency far exceeds what the challenge
+/// should take, e.g. a ~1s challenge submitted 25s after issuance. Such
+/// solutions may have been farmed out; this is a soft signal for abuse
+/// analytics, never a reason to reject.
+///
+/// # Arguments
+/// * `issued_millis`:        When the challenge was issued, in Unix ms.
+/// * `recommended_attempts`: The challenge's expected attempt count.
+/// * `now_millis`:           The current time in Unix milliseconds.
+/// * `reference_hashrate`:   Typical client hashes per second, or `None`
+///   to disable the check.
+///
+/// # Returns
+/// * `Option<i64>`: The solve latency in milliseconds if it exceeds
+///   `SOLVE_LATENCY_FLAG_FACTOR` times the expected solve time (and
+///   `SOLVE_LATENCY_GRACE_MS`), otherwise `None`.
+pub(crate) fn solve_latency_flag(
+    issued_millis: i64,
+    recommended_attempts: u64,
+    now_millis: i64,
+    reference_hashrate: Option<u64>,
+) -> Option<i64> {
+    let hashrate: u64 = reference_hashrate.filter(|rate: &u64| *rate > 0)?;
+    let expected_ms: i64 = i64::try_from(recommended_attempts.saturating_mul(1000) / hashrate).unwrap_or(i64::MAX);
+    let threshold_ms: i64 = expected_ms.saturating_mul(SOLVE_LATENCY_FLAG_FACTOR).max(SOLVE_LATENCY_GRACE_MS);
+    let latency_ms: i64 = now_millis.saturating_sub(issued_millis);
+
+    (latency_ms > threshold_ms).then_some(latency_ms)
+}
+
+/// Runs the checks of `verify_json_submission` on a parsed submission.
+fn check_json_submission(
+    submission: &JsonSubmission,
+    resource: &str,
+    client_ip: Option<&str>,
+    config: &Config,
+    now_millis: i64,
+) -> VerificationOutcome {
+    let challenge: &IronShieldChallenge = &submission.challenge;
+
+    if challenge.validate().is_err() {
+        return VerificationOutcome::Malformed;
+    }
+
+    if challenge.is_expired_at(now_millis) {
+        return VerificationOutcome::Expired;
+    }
+
+    if !ironshield_core::verify_ironshield_solution(challenge, submission.solution) {
+        return VerificationOutcome::InvalidSolution;
+    }
+
+    let signed: bool = match config.public_key {
+        Some(public_key) => public_key == challenge.public_key
+            && verify_challenge_signature_with_key(challenge, &public_key).is_ok(),
+        None => false,
+    };
+    if !signed {
+        return VerificationOutcome::InvalidSignature;
+    }
+
+    if !challenge.is_bound_to(resource) {
+        return VerificationOutcome::ResourceMismatch;
+    }
+
+    // Only now is `created_time`, and so the nonce bucket, trustworthy.
+    match &config.nonce_secret {
+        Some(secret) if !client_ip.is_some_and(|ip: &str| verify_derived_nonce(secret, challenge, ip)) => {
+            VerificationOutcome::UnknownNonce
+        }
+        _ => VerificationOutcome::Valid,
+    }
+}
+
+/// Function to handle a JSON solution submission.
+pub(crate) async fn handle_json_submission(
+    req: Request<Body>,
+    redirect_url: &str,
+    config: &Config,
+) -> worker::Result<Response<body::Body>> {
+    let headers: http::HeaderMap = req.headers().clone();
+    let client_ip: Option<&str> = headers.get(CLIENT_IP_HEADER).and_then(|v| v.to_str().ok());
+    let resource: String = request_resource(req.uri().path());
+    let started: DateTime<Utc> = Utc::now();
+
+    let bytes = body::to_bytes(body::Body::new(req.into_body()), MAX_JSON_SUBMISSION_BYTES).await;
+    let text: Option<&str> = bytes.as_ref().ok().and_then(|bytes| std::str::from_utf8(bytes).ok());
+    let (outcome, remaining_ms, late_ms) = match text {
+        Some(text) => verify_json_submission(text, &resource, client_ip, config, started.timestamp_millis()),
+        None => (VerificationOutcome::Malformed, None, None),
+    };
+
+    let verify_us: i64 = (Utc::now() - started).num_microseconds().unwrap_or(i64::MAX);
+    console_log!("JSON submission verification: {:?} in {}us", outcome, verify_us);
+    if let Some(line) = reported_attempts_log(&headers, &outcome, None) {
+        console_log!("{}", line);
+    }
+    if let Some(latency_ms) = late_ms {
+        console_log!("Implausibly late solve: submitted {}ms after issuance", latency_ms);
+    }
+    record_client_result(&headers, config, outcome == VerificationOutcome::Valid, started.timestamp_millis());
+
+    let max_age: i64 = bypass_cookie_max_age(config.bypass_cookie_lifetime, remaining_ms);
+    let bypass_token: Result<String, VerificationOutcome> = match outcome {
+        VerificationOutcome::Valid => {
+            let (challenge_signature, resource_hash) = text
+                .and_then(|text: &str| serde_json::from_str::<JsonSubmission>(text).ok())
+                .map_or(([0u8; 64], None), |submission: JsonSubmission| {
+                    (submission.challenge.challenge_signature, submission.challenge.resource_hash)
+                });
+            Ok(issue_bypass_token(challenge_signature, resource_hash, max_age, Utc::now().timestamp_millis(), config))
+        }
+        failed => Err(failed),
+    };
+    verification_response(bypass_token.as_deref(), verify_us, max_age, redirect_url, &headers)
+}
+
+/// Function to verify the submitted solution.
+///
+/// `difficulty` is the value this worker issued the challenge at, see
+/// `enforced_difficulty`.
+/// The `X-IronShield-Difficulty` header is logged but never used.
+pub(crate) fn verify_solution(req: &Request<Body>, difficulty: usize, config: &Config) -> VerificationOutcome {
+    console_log!("Verifying checksum...");
+
+    let headers: &http::HeaderMap = req.headers();
+    if let Some(claimed) = claimed_difficulty_mismatch(headers, difficulty) {
+        console_log!(
+            "Possible tampering: client claimed difficulty {:?}, enforcing {}.",
+            claimed,
+            difficulty
+        );
+    }
+
+    let now_millis: i64 = Utc::now().timestamp_millis();
+    let outcome: VerificationOutcome = check_submission(headers, difficulty, now_millis);
+    if let Some(line) = reported_attempts_log(headers, &outcome, Some(16u64.saturating_pow(difficulty as u32))) {
+        console_log!("{}", line);
+    }
+    match outcome {
+        VerificationOutcome::Valid => {
+            console_log!("Checksum verification successful!");
+            let issued_millis: Option<i64> = pow_header(headers, TIMESTAMP_HEADER)
+                .and_then(|t: &str| t.parse::<i64>().ok());
+            let expected_attempts: u64 = IronShieldChallenge::recommended_attempts(
+                16u64.saturating_pow(difficulty as u32),
+            );
+            if let Some(latency_ms) = issued_millis.and_then(|issued_millis: i64| {
+                solve_latency_flag(issued_millis, expected_attempts, now_millis, config.reference_hashrate)
+            }) {
+                console_log!("Implausibly late solve: submitted {}ms after issuance", latency_ms);
+            }
+        }
+        VerificationOutcome::MissingHeaders => console_log!("Missing required PoW headers."),
+        VerificationOutcome::Malformed => console_log!("Malformed challenge or nonce."),
+        VerificationOutcome::InvalidTimestamp => console_log!("Invalid timestamp format (expected Unix ms)."),
+        VerificationOutcome::InvalidDifficulty => console_log!("Configured difficulty is out of range."),
+        VerificationOutcome::Expired => console_log!("Challenge timestamp expired."),
+        VerificationOutcome::InvalidSolution => console_log!("Checksum verification failed."),
+        VerificationOutcome::InvalidSignature => console_log!("Challenge signature verification failed."),
+        VerificationOutcome::UnknownNonce => console_log!("Challenge nonce was not issued to this client."),
+        VerificationOutcome::ResourceMismatch => console_log!("Challenge was bound to another resource."),
+    }
+
+    outcome
+}
+
+/// Function to handle solution verification and return the appropriate response.
+///
+/// The time spent verifying is logged and returned in the
+/// `X-IronShield-Verify-Us` header so operators can spot verification
+/// cost creeping up. Workers coarsen timers between I/O events, so
+/// small values may read as `0`.
+pub(crate) async fn handle_solution_verification(
+    req: &Request<Body>,
+    headers: &http::HeaderMap,
+    redirect_url: &str,
+    config: &Config,
+) -> worker::Result<Response<body::Body>> {
+    let started: DateTime<Utc> = Utc::now();
+    let difficulty: usize = enforced_difficulty(headers, config, started.timestamp_millis());
+    let outcome: VerificationOutcome = verify_solution(&req, difficulty, config);
+    let valid: bool = outcome == VerificationOutcome::Valid;
+    let verify_us: i64 = (Utc::now() - started).num_microseconds().unwrap_or(i64::MAX);
+    console_log!("Verification took {}us (valid: {})", verify_us, valid);
+    record_client_result(headers, config, valid, started.timestamp_millis());
+
+    if valid {
+        release_outstanding_v1_challenge(headers);
+        #[allow(unused_variables)]
+        let content = protected_content().await;
+    }
+
+    let remaining_ms: Option<i64> = submission_remaining_ms(headers, Utc::now().timestamp_millis());
+    let max_age: i64 = bypass_cookie_max_age(config.bypass_cookie_lifetime, remaining_ms);
+    // Header submissions carry no signed challenge to bind the token to.
+    let bypass_token: Result<String, VerificationOutcome> = match outcome {
+        VerificationOutcome::Valid => Ok(issue_bypass_token([0u8; 64], None, max_age, Utc::now().timestamp_millis(), config)),
+        failed => Err(failed),
+    };
+    verification_response(bypass_token.as_deref(), verify_us, max_age, redirect_url, headers)
+}
+
+/// Remaining validity of a header-based submission, which expires
+/// `MAX_CHALLENGE_AGE_SECONDS` after its timestamp.
+///
+/// # Returns
+/// * `Option<i64>`: Milliseconds left, or `None` if the timestamp header
+///   is missing or malformed.
+pub(crate) fn submission_remaining_ms(headers: &http::HeaderMap, now_millis: i64) -> Option<i64> {
+    let timestamp_millis: i64 = pow_header(headers, TIMESTAMP_HEADER)?.parse().ok()?;
+    Some(timestamp_millis.saturating_add(MAX_CHALLENGE_AGE_SECONDS * 1000).saturating_sub(now_millis))
+}
+
+/// Chooses the bypass cookie's `Max-Age`, so a near-expired challenge
+/// doesn't grant a full fresh session unless the policy says so.
+///
+/// # Arguments
+/// * `policy`:       The configured `CookieLifetime`.
+/// * `remaining_ms`: The solved challenge's remaining validity, if known.
+///
+/// # Returns
+/// * `i64`: The cookie lifetime in seconds, never negative. Falls back to
+///   `BYPASS_COOKIE_MAX_AGE_SECONDS` when the remaining validity is unknown.
+pub(crate) fn bypass_cookie_max_age(policy: CookieLifetime, remaining_ms: Option<i64>) -> i64 {
+    let remaining_seconds: Option<i64> = remaining_ms.map(|ms: i64| (ms / 1000).max(0));
+
+    match (policy, remaining_seconds) {
+        (CookieLifetime::ChallengeValidity, Some(remaining)) => remaining,
+        (CookieLifetime::Shorter, Some(remaining)) => remaining.min(BYPASS_COOKIE_MAX_AGE_SECONDS),
+        _ => BYPASS_COOKIE_MAX_AGE_SECONDS,
+    }
+}
+
+/// JSON body of a successful verification.
+///
+/// * `success`:      Always `true`; failures are answered in plain text.
+/// * `message`:      Human-readable outcome.
+/// * `redirect_url`: Where the challenge page sends the user, serialized
+///   as `redirectUrl`, see `success_redirect_target`.
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SuccessResponse {
+    pub success:      bool,
+    pub message:      String,
+    pub redirect_url: String,
+}
+
+impl SuccessResponse {
+    /// Builds the body for a verified solution redirecting to `redirect_url`.
+    pub(crate) fn verified(redirect_url: &str) -> Self {
+        Self {
+            success: true,
+            message: "Verification successful.".to_string(),
+            redirect_url: redirect_url.to_string(),
+        }
+    }
+}
+
+/// Builds the response to a verified (or rejected) solution.
+///
+/// On success the bypass token is set as the bypass cookie and returned in
+/// the `X-Ironshield-Token` header for clients that don't keep cookies.
+///
+/// # Arguments
+/// * `bypass_token`: The credential granted, see `issue_bypass_token`, or
+///   the outcome of a failed verification, reported as `describe_failure`
+///   chooses.
+/// * `verify_us`: Time spent verifying, in microseconds.
+/// * `max_age`:   Bypass cookie lifetime in seconds, see `bypass_cookie_max_age`.
+/// * `redirect_url`: Where the challenge page sends the user on success,
+///   see `success_redirect_target`.
+/// * `headers`:   The request headers, used for CORS.
+pub(crate) fn verification_response(
+    bypass_token: Result<&str, &VerificationOutcome>,
+    verify_us: i64,
+    max_age: i64,
+    redirect_url: &str,
+    headers: &http::HeaderMap,
+) -> worker::Result<Response<body::Body>> {
+    // Early return for failed verification
+    let bypass_token: &str = match bypass_token {
+        Ok(bypass_token) => bypass_token,
+        Err(outcome) => {
+            let (status, message) = describe_failure(outcome);
+            let response = add_cors_headers(
+                Response::builder()
+                    .status(status)
+                    .header(header::CONTENT_TYPE, "text/plain")
+                    .header(VERIFY_TIME_HEADER, verify_us.to_string()),
+                &headers,
+            )
+                .body(body::Body::from(message));
+
+            return response.map_err(|e: http::Error| {
+                Error::RustError(format!("Failed to build response: {}", e))
+            });
+        }
+    };
+
+    // Verification successful - prepare success response
+    let json: String = serde_json::to_string(&SuccessResponse::verified(redirect_url))
+        .map_err(|e: serde_json::Error| Error::RustError(format!("Failed to serialize success response: {}", e)))?;
+    let cookie_value = format!(
+        "{}={}; Max-Age={}; HttpOnly; Secure; Path=/; SameSite=Lax",
+        BYPASS_COOKIE_NAME,
+        bypass_token,
+        max_age
+    );
+
+    let response = add_cors_headers(
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::SET_COOKIE, cookie_value)
+            .header(BYPASS_TOKEN_HEADER, bypass_token)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(VERIFY_TIME_HEADER, verify_us.to_string()),
+        &headers,
+    )
+        .body(body::Body::from(json));
+
+    response.map_err(|e: http::Error| {
+        Error::RustError(format!("Failed to build response: {}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{signing_config, DEFAULT_POW_DIFFICULTY, DEFAULT_SUCCESS_REDIRECT_URL};
+    use crate::constant::BYPASS_TOKEN_VALUE;
+    use http::{HeaderMap, HeaderName, HeaderValue};
+
+    const CHALLENGE: &str = "4f1c2a9be07d8c35a6e2f0913b7d4c58";
+
+    #[test]
+    fn test_success_response_escapes_redirect_url() {
+        let redirect_url: &str = "https://example.com/return?next=/a&q=\"quoted\"\\path";
+        let json: String = serde_json::to_string(&SuccessResponse::verified(redirect_url)).unwrap();
+
+        assert!(json.contains(r#""redirectUrl":"https://example.com/return?next=/a&q=\"quoted\"\\path""#));
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["success"], true);
+        assert_eq!(parsed["message"], "Verification successful.");
+        assert_eq!(parsed["redirectUrl"], redirect_url);
+    }
+
+    fn submission(nonce: &str, timestamp: i64, claimed_difficulty: Option<&str>) -> HeaderMap {
+        let mut headers: HeaderMap = HeaderMap::new();
+        let mut insert = |name: &str, value: &str| {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        };
+        insert(CHALLENGE_HEADER, CHALLENGE);
+        insert(NONCE_HEADER, nonce);
+        insert(TIMESTAMP_HEADER, &timestamp.to_string());
+        if let Some(difficulty) = claimed_difficulty {
+            insert(DIFFICULTY_HEADER, difficulty);
+        }
+        headers
+    }
+
+    /// Finds a nonce that meets difficulty 1 but not `DEFAULT_POW_DIFFICULTY`.
+    fn weak_nonce() -> String {
+        (0u64..)
+            .map(|n| n.to_string())
+            .find(|n| {
+                ironshield_core::verify_solution(CHALLENGE, n, 1)
+                    && !ironshield_core::verify_solution(CHALLENGE, n, DEFAULT_POW_DIFFICULTY)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_valid_solution_accepted() {
+        let (nonce, _) = ironshield_core::find_solution(CHALLENGE, DEFAULT_POW_DIFFICULTY).unwrap();
+        let now: i64 = 1_700_000_000_000;
+        let headers: HeaderMap = submission(&nonce.to_string(), now, None);
+
+        assert_eq!(check_submission(&headers, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::Valid);
+    }
+
+    #[test]
+    fn test_hex_nonce_accepted() {
+        let (nonce, _) = ironshield_core::find_solution(CHALLENGE, DEFAULT_POW_DIFFICULTY).unwrap();
+        let now: i64 = 1_700_000_000_000;
+
+        let decimal: HeaderMap = submission(&nonce.to_string(), now, None);
+        let hex: HeaderMap = submission(&format!("{:#x}", nonce), now, None);
+        assert_eq!(check_submission(&decimal, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::Valid);
+        assert_eq!(check_submission(&hex, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::Valid);
+
+        let garbage: HeaderMap = submission("0xZZ", now, None);
+        assert_eq!(check_submission(&garbage, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::Malformed);
+    }
+
+    #[test]
+    fn test_duplicate_nonce_headers_rejected() {
+        let (nonce, _) = ironshield_core::find_solution(CHALLENGE, DEFAULT_POW_DIFFICULTY).unwrap();
+        let now: i64 = 1_700_000_000_000;
+        let mut headers: HeaderMap = submission(&nonce.to_string(), now, None);
+        headers.append(
+            HeaderName::from_bytes(NONCE_HEADER.as_bytes()).unwrap(),
+            HeaderValue::from_static("12345"),
+        );
+
+        HASH_RECOMPUTATIONS.with(|count| count.set(0));
+        assert_eq!(check_submission(&headers, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::Malformed);
+        assert_eq!(HASH_RECOMPUTATIONS.with(|count| count.get()), 0);
+    }
+
+    #[test]
+    fn test_legacy_header_names_accepted() {
+        let (nonce, _) = ironshield_core::find_solution(CHALLENGE, DEFAULT_POW_DIFFICULTY).unwrap();
+        let now: i64 = 1_700_000_000_000;
+        let mut legacy: HeaderMap = HeaderMap::new();
+        for (name, value) in [("X-Challenge", CHALLENGE.to_string()), ("X-Nonce", nonce.to_string()), ("X-Timestamp", now.to_string())] {
+            legacy.insert(HeaderName::from_bytes(name.as_bytes()).unwrap(), HeaderValue::from_str(&value).unwrap());
+        }
+
+        assert!(crate::http_handler::has_proof_of_work_headers(&legacy));
+        assert_eq!(check_submission(&legacy, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::Valid);
+        assert_eq!(submission_remaining_ms(&legacy, now), Some(MAX_CHALLENGE_AGE_SECONDS * 1000));
+
+        // With both present the canonical name wins, whichever is valid.
+        let mut both: HeaderMap = legacy.clone();
+        both.insert(HeaderName::from_bytes(NONCE_HEADER.as_bytes()).unwrap(), HeaderValue::from_static("0xZZ"));
+        assert_eq!(check_submission(&both, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::Malformed);
+
+        let mut both: HeaderMap = submission(&nonce.to_string(), now, None);
+        both.insert(HeaderName::from_static("x-nonce"), HeaderValue::from_static("0xZZ"));
+        assert_eq!(check_submission(&both, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::Valid);
+    }
+
+    #[test]
+    fn test_header_difficulty_never_lowers_requirement() {
+        let nonce: String = weak_nonce();
+        let now: i64 = 1_700_000_000_000;
+
+        for claimed in ["1", "0", "abc"] {
+            let headers: HeaderMap = submission(&nonce, now, Some(claimed));
+            assert_eq!(
+                check_submission(&headers, DEFAULT_POW_DIFFICULTY, now),
+                VerificationOutcome::InvalidSolution,
+                "claimed difficulty {} must not be honoured",
+                claimed
+            );
+        }
+    }
+
+    #[test]
+    fn test_header_difficulty_never_raises_requirement() {
+        let (nonce, _) = ironshield_core::find_solution(CHALLENGE, DEFAULT_POW_DIFFICULTY).unwrap();
+        let now: i64 = 1_700_000_000_000;
+        let headers: HeaderMap = submission(&nonce.to_string(), now, Some("64"));
+
+        assert_eq!(check_submission(&headers, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::Valid);
+    }
+
+    #[test]
+    fn test_claimed_difficulty_mismatch() {
+        let now: i64 = 1_700_000_000_000;
+
+        assert_eq!(claimed_difficulty_mismatch(&submission("0", now, None), DEFAULT_POW_DIFFICULTY), None);
+        assert_eq!(
+            claimed_difficulty_mismatch(&submission("0", now, Some(&DEFAULT_POW_DIFFICULTY.to_string())), DEFAULT_POW_DIFFICULTY),
+            None
+        );
+        assert_eq!(
+            claimed_difficulty_mismatch(&submission("0", now, Some("1")), DEFAULT_POW_DIFFICULTY),
+            Some("1".to_string())
+        );
+        assert_eq!(
+            claimed_difficulty_mismatch(&submission("0", now, Some("abc")), DEFAULT_POW_DIFFICULTY),
+            Some("abc".to_string())
+        );
+    }
+
+    #[test]
+    fn test_reported_attempts_are_logged_but_never_trusted() {
+        let (nonce, _) = ironshield_core::find_solution(CHALLENGE, DEFAULT_POW_DIFFICULTY).unwrap();
+        let now: i64 = 1_700_000_000_000;
+        let mut headers: HeaderMap = submission(&nonce.to_string(), now, None);
+
+        // Absent: nothing to log, and verification is unaffected.
+        assert_eq!(reported_attempts_log(&headers, &VerificationOutcome::Valid, Some(65_536)), None);
+        assert_eq!(check_submission(&headers, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::Valid);
+
+        headers.insert(HeaderName::from_static("x-ironshield-attempts"), HeaderValue::from_static("12"));
+        let line: String = reported_attempts_log(&headers, &VerificationOutcome::Valid, Some(65_536)).unwrap();
+        assert!(line.contains("\"12\""));
+        assert!(line.contains("Valid"));
+        assert!(line.contains("~65536"));
+        assert_eq!(check_submission(&headers, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::Valid);
+
+        // A wildly high claim does not rescue an invalid nonce.
+        let mut weak: HeaderMap = submission(&weak_nonce(), now, None);
+        weak.insert(HeaderName::from_static("x-ironshield-attempts"), HeaderValue::from_static("999999999"));
+        assert_eq!(check_submission(&weak, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::InvalidSolution);
+        assert!(reported_attempts_log(&weak, &VerificationOutcome::InvalidSolution, None).unwrap().contains("unknown"));
+    }
+
+    #[test]
+    fn test_expired_and_malformed_submissions() {
+        let now: i64 = 1_700_000_000_000;
+        let stale: i64 = now - MAX_CHALLENGE_AGE_SECONDS * 1000 - 1;
+
+        assert_eq!(
+            check_submission(&submission("0", stale, None), DEFAULT_POW_DIFFICULTY, now),
+            VerificationOutcome::Expired
+        );
+        assert_eq!(
+            check_submission(&HeaderMap::new(), DEFAULT_POW_DIFFICULTY, now),
+            VerificationOutcome::MissingHeaders
+        );
+
+        let mut headers: HeaderMap = submission("0", now, None);
+        headers.insert(
+            HeaderName::from_bytes(TIMESTAMP_HEADER.as_bytes()).unwrap(),
+            HeaderValue::from_static("yesterday"),
+        );
+        assert_eq!(
+            check_submission(&headers, DEFAULT_POW_DIFFICULTY, now),
+            VerificationOutcome::InvalidTimestamp
+        );
+    }
+
+    #[test]
+    fn test_configured_difficulty_is_issued_and_enforced() {
+        let config: Config = Config::from_lookup(|name: &str| {
+            (name == "POW_DIFFICULTY").then(|| "3".to_string())
+        });
+        let now: i64 = 1_700_000_000_000;
+
+        let response = generate_challenge_page(CHALLENGE, now, None, &HeaderMap::new(), &config).unwrap();
+        assert_eq!(response.headers().get(DIFFICULTY_HEADER).unwrap(), "3");
+
+        // A nonce good enough for difficulty 2 but not 3 is rejected.
+        let weak: String = (0u64..)
+            .map(|n| n.to_string())
+            .find(|n| {
+                ironshield_core::verify_solution(CHALLENGE, n, 2)
+                    && !ironshield_core::verify_solution(CHALLENGE, n, 3)
+            })
+            .unwrap();
+        assert_eq!(
+            check_submission(&submission(&weak, now, Some("2")), config.pow_difficulty, now),
+            VerificationOutcome::InvalidSolution
+        );
+
+        let (nonce, _) = ironshield_core::find_solution(CHALLENGE, 3).unwrap();
+        assert_eq!(
+            check_submission(&submission(&nonce.to_string(), now, Some("3")), config.pow_difficulty, now),
+            VerificationOutcome::Valid
+        );
+    }
+
+    #[test]
+    fn test_challenge_cookie_is_issued_when_enabled() {
+        let now: i64 = 1_700_000_000_000;
+
+        let response = generate_challenge_page(CHALLENGE, now, None, &HeaderMap::new(), &Config::default()).unwrap();
+        assert!(response.headers().get(header::SET_COOKIE).is_none());
+
+        let config: Config = Config { challenge_cookie: true, ..Config::default() };
+        let response = generate_challenge_page(CHALLENGE, now, None, &HeaderMap::new(), &config).unwrap();
+        let cookie: &str = response.headers().get(header::SET_COOKIE).unwrap().to_str().unwrap();
+        assert!(cookie.starts_with(&format!("{}={};", CHALLENGE_COOKIE_NAME, CHALLENGE)));
+        assert!(!cookie.contains("HttpOnly"));
+    }
+
+    #[test]
+    fn test_challenge_from_cookie_is_accepted() {
+        let (nonce, _) = ironshield_core::find_solution(CHALLENGE, DEFAULT_POW_DIFFICULTY).unwrap();
+        let now: i64 = 1_700_000_000_000;
+
+        let mut headers: HeaderMap = submission(&nonce.to_string(), now, None);
+        headers.remove(CHALLENGE_HEADER);
+        assert_eq!(check_submission(&headers, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::MissingHeaders);
+
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_str(&format!("theme=dark; {}={}", CHALLENGE_COOKIE_NAME, CHALLENGE)).unwrap(),
+        );
+        assert!(crate::http_handler::has_proof_of_work_headers(&headers));
+        assert_eq!(check_submission(&headers, DEFAULT_POW_DIFFICULTY, now), VerificationOutcome::Valid);
+    }
+
+    #[test]
+    fn test_json_payload_reports_attempts_for_issued_difficulty() {
+        let mut request_headers: HeaderMap = HeaderMap::new();
+        request_headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+        assert!(wants_json(&request_headers));
+        assert!(!wants_json(&HeaderMap::new()));
+
+        let config: Config = Config { pow_difficulty: 3, ..Config::default() };
+        let payload: ChallengePayload = ChallengePayload::new(CHALLENGE, 1_700_000_000_000, config.pow_difficulty);
+        let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
+
+        assert_eq!(json["difficulty"], 3);
+        assert_eq!(json["expectedAttempts"], 4096);
+        assert_eq!(json["recommendedAttempts"], IronShieldChallenge::recommended_attempts(4096));
+        assert_eq!(json["challenge"], CHALLENGE);
+    }
+
+    #[test]
+    fn test_doomed_submissions_never_reach_hashing() {
+        let now: i64 = 1_700_000_000_000;
+        let stale: i64 = now - MAX_CHALLENGE_AGE_SECONDS * 1000 - 1;
+        let hashes = || HASH_RECOMPUTATIONS.with(|count| count.get());
+        let before: u32 = hashes();
+
+        assert_eq!(
+            check_submission(&submission("12345", stale, None), DEFAULT_POW_DIFFICULTY, now),
+            VerificationOutcome::Expired
+        );
+        assert_eq!(
+            check_submission(&submission("not-a-nonce", now, None), DEFAULT_POW_DIFFICULTY, now),
+            VerificationOutcome::Malformed
+        );
+        assert_eq!(
+            check_submission(&submission("12345", now, Some("0")), 0, now),
+            VerificationOutcome::InvalidDifficulty
+        );
+        assert_eq!(hashes(), before);
+
+        // A well-formed, fresh submission is hashed exactly once.
+        check_submission(&submission("12345", now, None), DEFAULT_POW_DIFFICULTY, now);
+        assert_eq!(hashes(), before + 1);
+    }
+
+    #[test]
+    fn test_verification_response_reports_verify_time() {
+        let response = verification_response(Ok(BYPASS_TOKEN_VALUE), 42, BYPASS_COOKIE_MAX_AGE_SECONDS, DEFAULT_SUCCESS_REDIRECT_URL, &HeaderMap::new()).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(VERIFY_TIME_HEADER).unwrap(), "42");
+        assert!(response.headers().get(header::SET_COOKIE).is_some());
+
+        let response = verification_response(Err(&VerificationOutcome::InvalidSolution), 7, BYPASS_COOKIE_MAX_AGE_SECONDS, DEFAULT_SUCCESS_REDIRECT_URL, &HeaderMap::new()).unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(response.headers().get(VERIFY_TIME_HEADER).unwrap(), "7");
+    }
+
+    #[test]
+    fn test_describe_failure() {
+        let cases: [(VerificationOutcome, StatusCode, &str); 10] = [
+            (VerificationOutcome::Valid, StatusCode::OK, "Verification successful."),
+            (VerificationOutcome::MissingHeaders, StatusCode::BAD_REQUEST, "Missing proof-of-work data, please retry"),
+            (VerificationOutcome::Malformed, StatusCode::BAD_REQUEST, "Malformed submission, please retry"),
+            (VerificationOutcome::InvalidTimestamp, StatusCode::BAD_REQUEST, "Invalid challenge timestamp, please retry"),
+            (VerificationOutcome::InvalidDifficulty, StatusCode::INTERNAL_SERVER_ERROR, "Verification unavailable, please try again later"),
+            (VerificationOutcome::Expired, StatusCode::CONFLICT, "Challenge expired, please retry"),
+            (VerificationOutcome::InvalidSolution, StatusCode::FORBIDDEN, "Proof of work verification failed, please retry"),
+            (VerificationOutcome::InvalidSignature, StatusCode::FORBIDDEN, "Challenge not recognized, please retry"),
+            (VerificationOutcome::UnknownNonce, StatusCode::FORBIDDEN, "Challenge was not issued to this client, please retry"),
+            (VerificationOutcome::ResourceMismatch, StatusCode::FORBIDDEN, "Challenge was issued for another resource, please retry"),
+        ];
+        for (outcome, status, message) in cases {
+            assert_eq!(describe_failure(&outcome), (status, message), "{:?}", outcome);
+        }
+
+        // Rejections are reported with the described status.
+        let response = verification_response(Err(&VerificationOutcome::Expired), 0, 0, DEFAULT_SUCCESS_REDIRECT_URL, &HeaderMap::new()).unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    fn signed_submission(created_time: i64, solution: Option<i64>) -> (String, Config) {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let (private_b64, public_b64) = ironshield_types::generate_test_keypair();
+        let private_key: [u8; 32] = STANDARD.decode(private_b64).unwrap().try_into().unwrap();
+        let public_key: [u8; 32] = STANDARD.decode(public_b64).unwrap().try_into().unwrap();
+
+        let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "cafe1234".to_string(),
+            created_time,
+            "example.com".to_string(),
+            IronShieldChallenge::difficulty_to_challenge_param(16),
+            public_key,
+            [0x00; 64],
+        );
+        challenge.challenge_signature = ironshield_types::sign_challenge_with_key(&challenge, &private_key);
+
+        let solution: i64 = solution.unwrap_or_else(|| {
+            ironshield_core::find_solution_single_threaded(&challenge).unwrap().solution
+        });
+        let body: String = serde_json::json!({ "challenge": challenge, "solution": solution }).to_string();
+        let config: Config = Config { public_key: Some(public_key), ..Config::default() };
+        (body, config)
+    }
+
+    #[test]
+    fn test_verify_json_submission_valid() {
+        let now: i64 = Utc::now().timestamp_millis();
+        let (body, config) = signed_submission(now, None);
+        let (outcome, remaining_ms, late_ms) = verify_json_submission(&body, "/", None, &config, now);
+        assert_eq!(outcome, VerificationOutcome::Valid);
+        assert!(remaining_ms.is_some_and(|ms: i64| ms > 0 && ms <= ironshield_types::DEFAULT_CHALLENGE_TTL_MS));
+        assert_eq!(late_ms, None);
+
+        // Without a trusted key nothing is accepted.
+        assert_eq!(verify_json_submission(&body, "/", None, &Config::default(), now).0, VerificationOutcome::InvalidSignature);
+    }
+
+    #[test]
+    fn test_verify_json_submission_expired() {
+        let now: i64 = Utc::now().timestamp_millis();
+        let (body, config) = signed_submission(now - 60_000, None);
+        assert_eq!(verify_json_submission(&body, "/", None, &config, now).0, VerificationOutcome::Expired);
+    }
+
+    #[test]
+    fn test_verify_json_submission_at_fixed_time() {
+        let created: i64 = 1_700_000_000_000;
+        let expires: i64 = created + ironshield_types::DEFAULT_CHALLENGE_TTL_MS;
+        let (body, config) = signed_submission(created, None);
+
+        let (outcome, remaining_ms, _) = verify_json_submission(&body, "/", None, &config, created + 1_000);
+        assert_eq!(outcome, VerificationOutcome::Valid);
+        assert_eq!(remaining_ms, Some(ironshield_types::DEFAULT_CHALLENGE_TTL_MS - 1_000));
+
+        assert_eq!(verify_json_submission(&body, "/", None, &config, expires).0, VerificationOutcome::Valid);
+        let (outcome, remaining_ms, _) = verify_json_submission(&body, "/", None, &config, expires + 1);
+        assert_eq!(outcome, VerificationOutcome::Expired);
+        assert_eq!(remaining_ms, Some(-1));
+    }
+
+    #[test]
+    fn test_verify_json_submission_bad_signature() {
+        let now: i64 = Utc::now().timestamp_millis();
+        let (body, config) = signed_submission(now, None);
+
+        // Re-target the challenge at another site without re-signing.
+        let tampered: String = body.replace("example.com", "evil.com");
+        assert_eq!(verify_json_submission(&tampered, "/", None, &config, now).0, VerificationOutcome::InvalidSignature);
+
+        // A correctly signed challenge from a different key is rejected too.
+        let (other_body, _) = signed_submission(now, None);
+        assert_eq!(verify_json_submission(&other_body, "/", None, &config, now).0, VerificationOutcome::InvalidSignature);
+    }
+
+    #[test]
+    fn test_verify_json_submission_malformed() {
+        let config: Config = Config::default();
+        assert_eq!(verify_json_submission("not json", "/", None, &config, 0).0, VerificationOutcome::Malformed);
+        assert_eq!(verify_json_submission("{\"solution\": 1}", "/", None, &config, 0).0, VerificationOutcome::Malformed);
+    }
+
+    #[test]
+    fn test_forced_challenge_only_in_test_mode() {
+        let query: String = format!("lang=en&challenge={}", ironshield_types::concat_struct_base64url_encode("deadbeef42"));
+        let test_mode: Config = Config { test_mode: true, ..Config::default() };
+
+        assert_eq!(forced_challenge(Some(&query), &test_mode), Some("deadbeef42".to_string()));
+        assert_eq!(forced_challenge(Some(&query), &Config::default()), None);
+        assert_eq!(forced_challenge(None, &test_mode), None);
+        assert_eq!(forced_challenge(Some("challenges=ZGVhZGJlZWY"), &test_mode), None);
+
+        // Markup can't be smuggled into the page or headers.
+        let injected: String = format!("challenge={}", ironshield_types::concat_struct_base64url_encode("\"><script>"));
+        assert_eq!(forced_challenge(Some(&injected), &test_mode), None);
+    }
+
+    #[test]
+    fn test_bypass_cookie_max_age_policies() {
+        // 12.5s of challenge validity left.
+        let remaining: Option<i64> = Some(12_500);
+
+        assert_eq!(bypass_cookie_max_age(CookieLifetime::Fixed, remaining), BYPASS_COOKIE_MAX_AGE_SECONDS);
+        assert_eq!(bypass_cookie_max_age(CookieLifetime::ChallengeValidity, remaining), 12);
+        assert_eq!(bypass_cookie_max_age(CookieLifetime::Shorter, remaining), 12);
+        assert_eq!(bypass_cookie_max_age(CookieLifetime::Shorter, Some(3_600_000)), BYPASS_COOKIE_MAX_AGE_SECONDS);
+        assert_eq!(bypass_cookie_max_age(CookieLifetime::ChallengeValidity, Some(-5_000)), 0);
+        assert_eq!(bypass_cookie_max_age(CookieLifetime::ChallengeValidity, None), BYPASS_COOKIE_MAX_AGE_SECONDS);
+    }
+
+    #[test]
+    fn test_cookie_lifetime_tracks_remaining_validity() {
+        let now: i64 = 1_700_000_000_000;
+        // Solved 45s into its 60s window.
+        let headers: HeaderMap = submission("0", now - 45_000, None);
+        let remaining_ms: Option<i64> = submission_remaining_ms(&headers, now);
+        assert_eq!(remaining_ms, Some(15_000));
+
+        let max_age: i64 = bypass_cookie_max_age(CookieLifetime::ChallengeValidity, remaining_ms);
+        let response = verification_response(Ok(BYPASS_TOKEN_VALUE), 0, max_age, DEFAULT_SUCCESS_REDIRECT_URL, &HeaderMap::new()).unwrap();
+        let cookie: &str = response.headers().get(header::SET_COOKIE).unwrap().to_str().unwrap();
+        assert!(cookie.contains("Max-Age=15;"), "unexpected cookie: {}", cookie);
+    }
+
+    #[test]
+    fn test_headless_mode_always_issues_json() {
+        let html_accept: HeaderMap = HeaderMap::new();
+        let headless: Config = Config { challenge_delivery: ChallengeDelivery::Headless, ..Config::default() };
+
+        assert!(issues_json(&html_accept, &headless));
+        assert!(!issues_json(&html_accept, &Config::default()));
+
+        let mut json_accept: HeaderMap = HeaderMap::new();
+        json_accept.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+        assert!(issues_json(&json_accept, &Config::default()));
+    }
+
+    #[test]
+    fn test_solved_challenge_grants_signed_bypass_token() {
+        use crate::http_handler::{classify_request, Route};
+        use http::Method;
+
+        let now: i64 = Utc::now().timestamp_millis();
+        let config: Config = signing_config();
+        let challenge: IronShieldChallenge = mint_challenge_batch(1, "example.com", None, None, None, &config, now).unwrap().remove(0);
+        let solution: i64 = ironshield_core::find_solution_single_threaded(&challenge).unwrap().solution;
+        let body: String = serde_json::json!({ "challenge": challenge, "solution": solution }).to_string();
+        assert_eq!(verify_json_submission(&body, "/", None, &config, now).0, VerificationOutcome::Valid);
+
+        let token: String = issue_bypass_token(challenge.challenge_signature, challenge.resource_hash, BYPASS_COOKIE_MAX_AGE_SECONDS, now, &config);
+        let response = verification_response(Ok(&token), 0, BYPASS_COOKIE_MAX_AGE_SECONDS, DEFAULT_SUCCESS_REDIRECT_URL, &HeaderMap::new()).unwrap();
+        assert_eq!(response.headers().get(BYPASS_TOKEN_HEADER).unwrap(), token.as_str());
+
+        // The token is bound to the solved challenge and verifies under the worker's key.
+        let decoded: ironshield_types::IronShieldToken = ironshield_types::IronShieldToken::from_base64url_bytes(&token).unwrap();
+        assert_eq!(decoded.challenge_signature, challenge.challenge_signature);
+        assert_eq!(decoded.valid_for, now + BYPASS_COOKIE_MAX_AGE_SECONDS * 1000);
+        assert!(ironshield_types::verify_token_signature_with_key(&decoded, &config.public_key.unwrap()).is_ok());
+
+        // Subsequent requests carrying it as a cookie or header bypass the challenge.
+        let set_cookie: &str = response.headers().get(header::SET_COOKIE).unwrap().to_str().unwrap();
+        let cookie: &str = set_cookie.split(';').next().unwrap();
+        let mut cookie_headers: HeaderMap = HeaderMap::new();
+        cookie_headers.insert(header::COOKIE, HeaderValue::from_str(cookie).unwrap());
+        assert_eq!(classify_request(&Method::GET, "/", &cookie_headers, &config), Route::BypassCookie);
+
+        let mut token_headers: HeaderMap = HeaderMap::new();
+        token_headers.insert(HeaderName::from_static("x-ironshield-token"), HeaderValue::from_str(&token).unwrap());
+        assert_eq!(classify_request(&Method::GET, "/", &token_headers, &config), Route::BypassToken);
+
+        // The static test value no longer bypasses a worker with signing keys.
+        let mut static_cookie: HeaderMap = HeaderMap::new();
+        static_cookie.insert(
+            header::COOKIE,
+            HeaderValue::from_str(&format!("{}={}", BYPASS_COOKIE_NAME, BYPASS_TOKEN_VALUE)).unwrap(),
+        );
+        assert_eq!(classify_request(&Method::GET, "/", &static_cookie, &config), Route::IssueChallenge);
+    }
+
+    #[test]
+    fn test_protocol_negotiation_selects_challenge_format() {
+        let now: i64 = Utc::now().timestamp_millis();
+        let config: Config = signing_config();
+        let mut headers: HeaderMap = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("example.com"));
+
+        // A v1-only client gets a leading-zero challenge.
+        headers.insert(PROTOCOL_HEADER, HeaderValue::from_static("1"));
+        assert_eq!(negotiate_protocol(&headers, None, &config), ProtocolVersion::V1);
+        let response = generate_challenge_json(CHALLENGE, now, None, &headers, &config).unwrap();
+        assert_eq!(response.headers()[PROTOCOL_HEADER], "1");
+        let payload: serde_json::Value = serde_json::to_value(ChallengePayload::new(CHALLENGE, now, 1)).unwrap();
+        assert_eq!(payload["protocol"], 1);
+        assert_eq!(payload["challenge"], CHALLENGE);
+
+        // A v2 client gets a signed challenge it can submit as JSON.
+        headers.insert(PROTOCOL_HEADER, HeaderValue::from_static("1, 2"));
+        assert_eq!(negotiate_protocol(&headers, None, &config), ProtocolVersion::V2);
+        let challenge: IronShieldChallenge = mint_protocol_v2_challenge(&headers, &config, now).unwrap();
+        assert_eq!(challenge.website_id, "example.com");
+        let response = generate_signed_challenge_json(&challenge, &headers).unwrap();
+        assert_eq!(response.headers()[PROTOCOL_HEADER], "2");
+
+        let payload: serde_json::Value = serde_json::to_value(SignedChallengePayload { protocol: 2, challenge: &challenge }).unwrap();
+        assert_eq!(payload["protocol"], 2);
+        let issued: IronShieldChallenge = serde_json::from_value(payload["challenge"].clone()).unwrap();
+        let solution: i64 = ironshield_core::find_solution_single_threaded(&issued).unwrap().solution;
+        let body: String = serde_json::json!({ "challenge": issued, "solution": solution }).to_string();
+        assert_eq!(verify_json_submission(&body, "/", None, &config, now).0, VerificationOutcome::Valid);
+
+        // Without a Host to bind it to, no v2 challenge is minted.
+        headers.remove(header::HOST);
+        assert!(mint_protocol_v2_challenge(&headers, &config, now).is_err());
+    }
+
+    #[test]
+    fn test_minted_challenges_are_bound_to_the_request_host() {
+        let now: i64 = Utc::now().timestamp_millis();
+        let config: Config = signing_config();
+        let mut headers: HeaderMap = HeaderMap::new();
+        assert_eq!(request_website_id(&headers, &config), None);
+
+        for (host, website_id) in [
+            ("example.com", "example.com"),
+            ("Shop.Example.com:8443", "shop.example.com"),
+            ("[2001:db8::1]:443", "[2001:db8::1]"),
+        ] {
+            headers.insert(header::HOST, HeaderValue::from_static(host));
+            assert_eq!(request_website_id(&headers, &config).as_deref(), Some(website_id));
+            assert_eq!(mint_protocol_v2_challenge(&headers, &config, now).unwrap().website_id, website_id);
+        }
+        headers.insert(header::HOST, HeaderValue::from_static("not a host"));
+        assert_eq!(request_website_id(&headers, &config), None);
+
+        // A configured site id wins over the Host header, and works without one.
+        let configured: Config = Config { site_id: Some("example.com".to_string()), ..config.clone() };
+        assert_eq!(mint_protocol_v2_challenge(&headers, &configured, now).unwrap().website_id, "example.com");
+        headers.remove(header::HOST);
+        assert_eq!(request_website_id(&headers, &configured).as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn test_batch_count() {
+        assert_eq!(batch_count(None), Ok(1));
+        assert_eq!(batch_count(Some("count=5")), Ok(5));
+        assert_eq!(batch_count(Some("lang=en&count=0")), Ok(1));
+        assert_eq!(batch_count(Some("count=1000")), Ok(MAX_CHALLENGE_BATCH));
+        assert!(batch_count(Some("count=many")).is_err());
+    }
+
+    #[test]
+    fn test_batch_resource() {
+        assert_eq!(batch_resource(None), None);
+        assert_eq!(batch_resource(Some("count=2")), None);
+        assert_eq!(batch_resource(Some("resource=")), None);
+        assert_eq!(batch_resource(Some("count=2&resource=/reports/a.pdf")).as_deref(), Some("/reports/a.pdf"));
+        assert_eq!(batch_resource(Some("resource=%2Freports%2Fa%20b.pdf")).as_deref(), Some("/reports/a b.pdf"));
+        assert_eq!(batch_resource(Some("resource=%zz")).as_deref(), Some("%zz"));
+    }
+
+    #[test]
+    fn test_resource_bound_challenge_only_unlocks_its_resource() {
+        let config: Config = signing_config();
+        let now: i64 = Utc::now().timestamp_millis();
+        let challenge: IronShieldChallenge =
+            mint_challenge_batch(1, "example.com", Some("/a"), None, None, &config, now).unwrap().remove(0);
+        let solution: i64 = ironshield_core::find_solution_single_threaded(&challenge).unwrap().solution;
+        let body: String = serde_json::json!({ "challenge": challenge, "solution": solution }).to_string();
+
+        assert_eq!(verify_json_submission(&body, "/a", None, &config, now).0, VerificationOutcome::Valid);
+        assert_eq!(verify_json_submission(&body, "/b", None, &config, now).0, VerificationOutcome::ResourceMismatch);
+
+        // Stripping the binding invalidates the signature.
+        let mut unbound: IronShieldChallenge = challenge.clone();
+        unbound.resource_hash = None;
+        let body: String = serde_json::json!({ "challenge": unbound, "solution": solution }).to_string();
+        assert_eq!(verify_json_submission(&body, "/b", None, &config, now).0, VerificationOutcome::InvalidSignature);
+    }
+
+    #[test]
+    fn test_challenge_batch_is_distinct_and_each_verifies() {
+        let config: Config = signing_config();
+        let now: i64 = Utc::now().timestamp_millis();
+        let batch: Vec<IronShieldChallenge> = mint_challenge_batch(4, "example.com", None, None, None, &config, now).unwrap();
+        assert_eq!(batch.len(), 4);
+
+        let nonces: std::collections::HashSet<&str> = batch.iter().map(|c| c.random_nonce.as_str()).collect();
+        assert_eq!(nonces.len(), 4);
+
+        for challenge in &batch {
+            assert_eq!(challenge.expiration_time, now + BATCH_CHALLENGE_TTL_MS);
+            let solution: i64 = ironshield_core::find_solution_single_threaded(challenge).unwrap().solution;
+            let body: String = serde_json::json!({ "challenge": challenge, "solution": solution }).to_string();
+            assert_eq!(verify_json_submission(&body, "/", None, &config, now).0, VerificationOutcome::Valid);
+        }
+    }
+
+    #[test]
+    fn test_outstanding_challenges_are_capped_per_client() {
+        // A fresh thread is a fresh isolate with no outstanding challenges.
+        std::thread::spawn(|| {
+            let config: Config = Config { max_outstanding_challenges: Some(3), ..signing_config() };
+            let now: i64 = Utc::now().timestamp_millis();
+            let ip: Option<&str> = Some("203.0.113.5");
+
+            let batch: Vec<IronShieldChallenge> = mint_challenge_batch(5, "example.com", None, ip, None, &config, now).unwrap();
+            assert_eq!(batch.len(), 3, "clamped to the free slots");
+            assert_eq!(
+                mint_challenge_batch(1, "example.com", None, ip, None, &config, now).unwrap_err(),
+                OUTSTANDING_LIMIT_MESSAGE
+            );
+            assert_eq!(mint_challenge_batch(1, "example.com", None, Some("198.51.100.7"), None, &config, now).unwrap().len(), 1);
+
+            // Consuming a challenge frees its slot; a failed submission does not.
+            let challenge: &IronShieldChallenge = &batch[0];
+            let solution: i64 = ironshield_core::find_solution_single_threaded(challenge).unwrap().solution;
+            let invalid: i64 = (0..).find(|nonce: &i64| !ironshield_core::verify_ironshield_solution(challenge, *nonce)).unwrap();
+            let wrong: String = serde_json::json!({ "challenge": challenge, "solution": invalid }).to_string();
+            assert_ne!(verify_json_submission(&wrong, "/", ip, &config, now).0, VerificationOutcome::Valid);
+            assert_eq!(outstanding_slots(ip, &config, now), Some(0));
+            let body: String = serde_json::json!({ "challenge": challenge, "solution": solution }).to_string();
+            assert_eq!(verify_json_submission(&body, "/", ip, &config, now).0, VerificationOutcome::Valid);
+            assert_eq!(mint_challenge_batch(2, "example.com", None, ip, None, &config, now).unwrap().len(), 1);
+
+            // Expiry frees every slot.
+            let later: i64 = now + BATCH_CHALLENGE_TTL_MS + 1;
+            assert_eq!(outstanding_slots(ip, &config, later), Some(3));
+            assert_eq!(outstanding_slots(ip, &signing_config(), now), None, "uncapped by default");
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_v1_challenges_count_against_the_outstanding_cap() {
+        std::thread::spawn(|| {
+            let config: Config = Config { max_outstanding_challenges: Some(3), ..signing_config() };
+            let now: i64 = Utc::now().timestamp_millis();
+            let mut headers: HeaderMap = HeaderMap::new();
+            headers.insert(CLIENT_IP_HEADER, HeaderValue::from_static("203.0.113.5"));
+            let ip: Option<&str> = Some("203.0.113.5");
+
+            // v1 and signed challenges share the client's slots.
+            assert!(reserve_outstanding_v1_challenge(&headers, "v1-a", &config, now));
+            assert!(reserve_outstanding_v1_challenge(&headers, "v1-b", &config, now));
+            assert_eq!(mint_challenge_batch(5, "example.com", None, ip, None, &config, now).unwrap().len(), 1);
+            assert!(!reserve_outstanding_v1_challenge(&headers, "v1-c", &config, now));
+            assert!(reserve_outstanding_v1_challenge(&HeaderMap::new(), "v1-c", &config, now), "unknown IPs are uncapped");
+
+            // Solving a v1 challenge frees its slot.
+            let mut solved: HeaderMap = headers.clone();
+            solved.insert(CHALLENGE_HEADER, HeaderValue::from_static("v1-a"));
+            release_outstanding_v1_challenge(&solved);
+            assert_eq!(outstanding_slots(ip, &config, now), Some(1));
+
+            // v1 challenges expire with their submission window.
+            let later: i64 = now + MAX_CHALLENGE_AGE_SECONDS * 1000 + 1;
+            assert_eq!(outstanding_slots(ip, &config, later), Some(2));
+            assert!(reserve_outstanding_v1_challenge(&headers, "v1-c", &signing_config(), now), "uncapped by default");
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_challenge_batch_requires_signing_key() {
+        assert!(mint_challenge_batch(1, "example.com", None, None, None, &Config::default(), 0).is_err());
+    }
+
+    #[test]
+    fn test_solve_latency_flag() {
+        // Difficulty 4096 at 10k hashes/s: ~1.2s expected, flagged past 12.3s.
+        let attempts: u64 = IronShieldChallenge::recommended_attempts(4096);
+        assert_eq!(solve_latency_flag(0, attempts, 25_000, Some(10_000)), Some(25_000));
+        assert_eq!(solve_latency_flag(0, attempts, 2_000, Some(10_000)), None);
+
+        // Easy challenges get the grace period; a disabled check never flags.
+        assert_eq!(solve_latency_flag(0, 48, 4_000, Some(10_000)), None);
+        assert_eq!(solve_latency_flag(0, attempts, 3_600_000, None), None);
+        assert_eq!(solve_latency_flag(0, attempts, 3_600_000, Some(0)), None);
+    }
+
+    #[test]
+    fn test_verify_json_submission_flags_late_solve() {
+        let config: Config = Config { reference_hashrate: Some(10_000), ..signing_config() };
+        let now: i64 = Utc::now().timestamp_millis();
+
+        for (issued, expect_flag) in [(now, false), (now - 60_000, true)] {
+            let challenge: IronShieldChallenge = mint_challenge_batch(1, "example.com", None, None, None, &config, issued).unwrap().remove(0);
+            let solution: i64 = ironshield_core::find_solution_single_threaded(&challenge).unwrap().solution;
+            let body: String = serde_json::json!({ "challenge": challenge, "solution": solution }).to_string();
+
+            // A late solve is still accepted; it is only flagged.
+            let (outcome, _, late_ms) = verify_json_submission(&body, "/", None, &config, now);
+            assert_eq!(outcome, VerificationOutcome::Valid);
+            assert_eq!(late_ms.is_some(), expect_flag);
+        }
+    }
+
+    #[test]
+    fn test_site_difficulty_multiplier_applied_to_minted_challenges() {
+        let mut config: Config = signing_config();
+        config.site_difficulty_multipliers.insert("shop.example.com".to_string(), 4.0);
+        assert_eq!(site_difficulty("shop.example.com", &config), 64);
+        assert_eq!(site_difficulty("example.com", &config), 16);
+
+        let shop: IronShieldChallenge = mint_challenge_batch(1, "shop.example.com", None, None, None, &config, 0).unwrap().remove(0);
+        assert_eq!(IronShieldChallenge::challenge_param_to_difficulty(&shop.challenge_param), 64);
+        assert_eq!(shop.recommended_attempts, IronShieldChallenge::recommended_attempts(64));
+
+        let other: IronShieldChallenge = mint_challenge_batch(1, "example.com", None, None, None, &config, 0).unwrap().remove(0);
+        assert_eq!(IronShieldChallenge::challenge_param_to_difficulty(&other.challenge_param), 16);
+    }
+
+    #[test]
+    fn test_site_difficulty_multiplier_applied_to_v1_challenges() {
+        let mut config: Config = Config { pow_difficulty: 3, ..Config::default() };
+        for (site, multiplier) in [("shop.example.com", 4.0), ("docs.example.com", 2.0), ("cdn.example.com", 0.0625)] {
+            config.site_difficulty_multipliers.insert(site.to_string(), multiplier);
+        }
+        assert_eq!(site_pow_difficulty("shop.example.com", &config), 4);
+        assert_eq!(site_pow_difficulty("docs.example.com", &config), 3);
+        assert_eq!(site_pow_difficulty("cdn.example.com", &config), 2);
+        assert_eq!(site_pow_difficulty("example.com", &config), 3);
+
+        config.site_difficulty_multipliers.insert("huge.example.com".to_string(), 1e12);
+        config.site_difficulty_multipliers.insert("tiny.example.com".to_string(), 1e-12);
+        assert_eq!(site_pow_difficulty("huge.example.com", &config), MAX_POW_DIFFICULTY);
+        assert_eq!(site_pow_difficulty("tiny.example.com", &config), MIN_POW_DIFFICULTY);
+
+        // Issuance and verification agree on the scaled difficulty.
+        let mut headers: HeaderMap = HeaderMap::new();
+        headers.insert(header::HOST, "Shop.Example.com:443".parse().unwrap());
+        let response = generate_challenge_json(CHALLENGE, 0, None, &headers, &config).unwrap();
+        assert_eq!(response.headers()[DIFFICULTY_HEADER], "4");
+        assert_eq!(enforced_difficulty(&headers, &config, 0), 4);
+        assert_eq!(enforced_difficulty(&HeaderMap::new(), &config, 0), 3, "no site, no multiplier");
+
+        // Escalation and relief start from the site's difficulty.
+        headers.insert(CLIENT_IP_HEADER, "203.0.113.5".parse().unwrap());
+        let escalating: Config = Config { escalate_on_failure: true, ..config.clone() };
+        assert_eq!(client_escalation(&headers, &escalating, 0).unwrap().difficulty, 4);
+    }
+
+    #[test]
+    fn test_target_solve_time_sets_minted_difficulty() {
+        let mut config: Config = signing_config();
+        config.target_solve_ms = Some(2_000);
+        assert_eq!(site_difficulty("example.com", &config), 16, "needs a reference hashrate too");
+
+        config.reference_hashrate = Some(500_000);
+        config.site_difficulty_multipliers.insert("shop.example.com".to_string(), 4.0);
+        assert_eq!(site_difficulty("example.com", &config), 1_000_000);
+        assert_eq!(site_difficulty("shop.example.com", &config), 4_000_000);
+
+        config.target_solve_ms = Some(4_000);
+        assert_eq!(site_difficulty("example.com", &config), 2_000_000);
+    }
+
+    #[test]
+    fn test_solvability_guard_caps_only_unsolvable_difficulties() {
+        let mut config: Config = signing_config();
+        config.target_solve_ms = Some(2_000);
+        config.reference_hashrate = Some(500_000);
+        assert_eq!(issuance_difficulty("example.com", None, &config), 1_000_000, "guard disabled");
+
+        // Within the limit, minted challenges keep the configured difficulty.
+        config.max_solve_ms = Some(10_000);
+        assert_eq!(issuance_difficulty("example.com", None, &config), 1_000_000);
+        let minted: IronShieldChallenge = mint_challenge_batch(1, "example.com", None, None, None, &config, 0).unwrap().remove(0);
+        assert_eq!(minted.recommended_attempts, IronShieldChallenge::recommended_attempts(1_000_000));
+
+        // Over the limit, issuance is capped to what a reference client
+        // solves within `max_solve_ms`.
+        config.max_solve_ms = Some(1_000);
+        assert_eq!(issuance_difficulty("example.com", None, &config), 500_000);
+        let minted: IronShieldChallenge = mint_challenge_batch(1, "example.com", None, None, None, &config, 0).unwrap().remove(0);
+        assert_eq!(minted.recommended_attempts, IronShieldChallenge::recommended_attempts(500_000));
+    }
+
+    #[test]
+    fn test_bot_score_raises_every_issued_difficulty() {
+        let mut config: Config = signing_config();
+        config.target_solve_ms = Some(2_000);
+        config.reference_hashrate = Some(500_000);
+        config.site_difficulty_multipliers.insert("shop.example.com".to_string(), 2.0);
+        assert_eq!(issuance_difficulty("shop.example.com", Some(1), &config), 2_000_000, "scaling unset");
+
+        // Minted challenges add (99 - score)^2 * scaling to the site's
+        // difficulty, then the solvability guard still caps them.
+        config.bot_score_scaling = Some(100);
+        assert_eq!(issuance_difficulty("shop.example.com", None, &config), 2_000_000);
+        assert_eq!(issuance_difficulty("shop.example.com", Some(99), &config), 2_000_000);
+        assert_eq!(issuance_difficulty("shop.example.com", Some(1), &config), 2_960_400);
+        let minted: IronShieldChallenge =
+            mint_challenge_batch(1, "shop.example.com", None, None, Some(1), &config, 0).unwrap().remove(0);
+        assert_eq!(minted.recommended_attempts, IronShieldChallenge::recommended_attempts(2_960_400));
+        config.max_solve_ms = Some(5_000);
+        assert_eq!(issuance_difficulty("shop.example.com", Some(1), &config), 2_500_000);
+
+        // Protocol v1 challenges round the raised attempts to leading
+        // zeros: 16^3 + 960_400 is closest to 16^5.
+        let config: Config = Config { pow_difficulty: 3, bot_score_scaling: Some(100), ..Config::default() };
+        let scored = |score: &str| {
+            let mut headers: HeaderMap = HeaderMap::new();
+            headers.insert(BOT_SCORE_HEADER, score.parse().unwrap());
+            headers.insert(CLIENT_IP_HEADER, "203.0.113.5".parse().unwrap());
+            headers
+        };
+        assert_eq!(request_bot_score(&scored("150")), None);
+        assert_eq!(enforced_difficulty(&scored("150"), &config, 0), 3);
+        assert_eq!(enforced_difficulty(&scored("99"), &config, 0), 3);
+        assert_eq!(enforced_difficulty(&scored("1"), &config, 0), 5);
+        let response = generate_challenge_json(CHALLENGE, 0, None, &scored("1"), &config).unwrap();
+        assert_eq!(response.headers()[DIFFICULTY_HEADER], "5");
+
+        // Escalation and relief start from the scored difficulty.
+        let escalating: Config = Config { escalate_on_failure: true, ..config.clone() };
+        assert_eq!(client_escalation(&scored("1"), &escalating, 0).unwrap().difficulty, 5);
+    }
+
+    #[test]
+    fn test_render_template_fills_only_known_placeholders() {
+        let template: &str = "<meta name=\"x-ironshield-challenge\" content=\"{{challenge}}\">\
+                              <meta name=\"x-ironshield-timestamp\" content=\"{{timestamp}}\">\
+                              {{unknown}} {{challenge {{ }}";
+        let rendered: String = render_template(template, &[
+            ("challenge", "\"><script>alert('x')</script>&{{timestamp}}"),
+            ("timestamp", "1700000000000"),
+        ]);
+
+        assert_eq!(
+            rendered,
+            "<meta name=\"x-ironshield-challenge\" \
+             content=\"&quot;&gt;&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;&amp;{{timestamp}}\">\
+             <meta name=\"x-ironshield-timestamp\" content=\"1700000000000\">\
+             {{unknown}} {{challenge {{ }}"
+        );
+        assert!(!rendered.contains("<script>"));
+
+        // Text that merely looks like a header name is left alone.
+        assert_eq!(render_template("X-Challenge X-Nonce", &[("challenge", "c")]), "X-Challenge X-Nonce");
+    }
+
+    #[test]
+    fn test_verify_json_submission_honors_embedded_difficulty() {
+        let mut config: Config = signing_config();
+        let now: i64 = Utc::now().timestamp_millis();
+
+        // One challenge minted before a difficulty change, one after.
+        let before: IronShieldChallenge = mint_challenge_batch(1, "example.com", None, None, None, &config, now).unwrap().remove(0);
+        config.pow_difficulty = 2;
+        let after: IronShieldChallenge = mint_challenge_batch(1, "example.com", None, None, None, &config, now).unwrap().remove(0);
+        assert_ne!(before.challenge_param, after.challenge_param);
+
+        // Both verify under a single verifier, whatever it currently issues.
+        config.pow_difficulty = MAX_POW_DIFFICULTY;
+        for challenge in [&before, &after] {
+            let solution: i64 = ironshield_core::find_solution_single_threaded(challenge).unwrap().solution;
+            let body: String = serde_json::json!({ "challenge": challenge, "solution": solution }).to_string();
+            assert_eq!(verify_json_submission(&body, "/", None, &config, now).0, VerificationOutcome::Valid);
+        }
+    }
+
+    #[test]
+    fn test_derived_nonce_bound_to_client_ip() {
+        let config: Config = Config { nonce_secret: Some(b"nonce-secret".to_vec()), ..signing_config() };
+        let now: i64 = Utc::now().timestamp_millis();
+        let ip: &str = "203.0.113.5";
+
+        // Derived nonces are one per client and bucket, so batches collapse.
+        assert!(mint_challenge_batch(1, "example.com", None, None, None, &config, now).is_err());
+        let mut batch: Vec<IronShieldChallenge> = mint_challenge_batch(4, "example.com", None, Some(ip), None, &config, now).unwrap();
+        assert_eq!(batch.len(), 1);
+
+        let challenge: IronShieldChallenge = batch.remove(0);
+        assert_eq!(challenge.random_nonce, derive_nonce(b"nonce-secret", nonce_bucket(now), ip));
+        let solution: i64 = ironshield_core::find_solution_single_threaded(&challenge).unwrap().solution;
+        let body: String = serde_json::json!({ "challenge": challenge, "solution": solution }).to_string();
+
+        assert_eq!(verify_json_submission(&body, "/", Some(ip), &config, now).0, VerificationOutcome::Valid);
+        assert_eq!(verify_json_submission(&body, "/", Some("198.51.100.7"), &config, now).0, VerificationOutcome::UnknownNonce);
+        assert_eq!(verify_json_submission(&body, "/", None, &config, now).0, VerificationOutcome::UnknownNonce);
+    }
+
+    #[test]
+    fn test_repeated_failures_escalate_to_throttled_challenges() {
+        use crate::throttle::{FailureTracker, FAILURES_PER_ESCALATION};
+
+        let config: Config = Config { pow_difficulty: 2, escalate_on_failure: true, ..Config::default() };
+        let mut tracker: FailureTracker = FailureTracker::default();
+        let mut previous_difficulty: usize = 0;
+
+        for failures in 0..3 * FAILURES_PER_ESCALATION {
+            let escalation: Escalation = tracker.escalation("203.0.113.5", config.pow_difficulty, 0);
+            let response = generate_challenge_json(CHALLENGE, 0, Some(&escalation), &HeaderMap::new(), &config).unwrap();
+            let difficulty: usize = response.headers()[DIFFICULTY_HEADER].to_str().unwrap().parse().unwrap();
+            assert!(difficulty >= previous_difficulty);
+
+            if failures < FAILURES_PER_ESCALATION {
+                assert_eq!(response.status(), StatusCode::OK);
+                assert_eq!(difficulty, config.pow_difficulty);
+                assert!(response.headers().get(header::RETRY_AFTER).is_none());
+            } else {
+                assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+                assert_eq!(difficulty, config.pow_difficulty + (failures / FAILURES_PER_ESCALATION) as usize);
+                assert!(response.headers().contains_key(header::RETRY_AFTER));
+
+                let payload: ChallengePayload = ChallengePayload::issued(CHALLENGE, 0, Some(&escalation), &HeaderMap::new(), &config);
+                let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
+                assert_eq!(json["difficulty"], difficulty);
+                assert_eq!(json["escalation"]["failures"], failures);
+                assert_eq!(json["escalation"]["baseDifficulty"], config.pow_difficulty);
+            }
+
+            previous_difficulty = difficulty;
+            tracker.record_failure("203.0.113.5", 0);
+        }
+    }
+
+    #[test]
+    fn test_reported_solve_failures_relieve_difficulty_up_to_cap() {
+        use crate::relief::{MAX_RELIEF_LEVELS, MIN_REPORT_INTERVAL_MS};
+
+        // A fresh thread is a fresh isolate with no recorded reports.
+        std::thread::spawn(|| {
+            let config: Config = Config {
+                // Far enough above the floor that the cap, not the floor,
+                // stops the relief.
+                pow_difficulty: MIN_POW_DIFFICULTY + MAX_RELIEF_LEVELS + 2,
+                relieve_on_solve_failure: true,
+                ..Config::default()
+            };
+            let mut headers: HeaderMap = HeaderMap::new();
+            headers.insert(CLIENT_IP_HEADER, HeaderValue::from_static("203.0.113.5"));
+            let mut reporting: HeaderMap = headers.clone();
+            reporting.insert(SOLVE_FAILED_HEADER, HeaderValue::from_static(CHALLENGE));
+
+            let issued = |now: i64| -> usize {
+                let escalation: Option<Escalation> = client_escalation(&headers, &config, now);
+                record_issued_challenge(&headers, CHALLENGE, escalation.as_ref(), &config, now);
+                let response = generate_challenge_json(CHALLENGE, now, escalation.as_ref(), &headers, &config).unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                response.headers()[DIFFICULTY_HEADER].to_str().unwrap().parse().unwrap()
+            };
+            assert_eq!(issued(0), config.pow_difficulty);
+
+            // Reports naming a challenge not issued to the client, or given
+            // up on sooner than an honest client would, are not counted.
+            let mut unissued: HeaderMap = headers.clone();
+            unissued.insert(SOLVE_FAILED_HEADER, HeaderValue::from_static("not-issued"));
+            record_solve_failure_report(&unissued, &config, MIN_REPORT_INTERVAL_MS);
+            record_solve_failure_report(&reporting, &config, MIN_REPORT_INTERVAL_MS - 1);
+            assert_eq!(issued(0), config.pow_difficulty);
+
+            // Each report lowers the next issued difficulty by one leading
+            // zero, until the cap.
+            const { assert!(MAX_RELIEF_LEVELS >= 2) };
+            for report in 1..=MAX_RELIEF_LEVELS {
+                let now: i64 = report as i64 * MIN_REPORT_INTERVAL_MS;
+                record_solve_failure_report(&reporting, &config, now);
+                assert_eq!(issued(now), config.pow_difficulty - report);
+            }
+
+            // Further reports are capped, well above the floor.
+            let capped: usize = config.pow_difficulty - MAX_RELIEF_LEVELS;
+            for report in MAX_RELIEF_LEVELS + 1..=MAX_RELIEF_LEVELS + 2 {
+                let now: i64 = report as i64 * MIN_REPORT_INTERVAL_MS;
+                record_solve_failure_report(&reporting, &config, now);
+                assert_eq!(issued(now), capped);
+            }
+            assert!(capped > MIN_POW_DIFFICULTY);
+
+            // Relief never goes below the floor, and a success ends it.
+            let low: Config = Config { pow_difficulty: MIN_POW_DIFFICULTY + 1, ..config.clone() };
+            assert_eq!(client_escalation(&headers, &low, 0).unwrap().difficulty, MIN_POW_DIFFICULTY);
+            record_client_result(&headers, &config, true, 0);
+            assert_eq!(issued(0), config.pow_difficulty);
+
+            // Requests without the header, or with the feature off, are not counted.
+            record_solve_failure_report(&headers, &config, MIN_REPORT_INTERVAL_MS);
+            let off: Config = Config { relieve_on_solve_failure: false, ..config.clone() };
+            record_solve_failure_report(&reporting, &off, 2 * MIN_REPORT_INTERVAL_MS);
+            assert_eq!(issued(2 * MIN_REPORT_INTERVAL_MS), config.pow_difficulty);
+            assert!(client_escalation(&reporting, &off, 0).is_none());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_throttled_clients_are_not_relieved() {
+        use crate::relief::MIN_REPORT_INTERVAL_MS;
+        use crate::throttle::FAILURES_PER_ESCALATION;
+
+        std::thread::spawn(|| {
+            let config: Config = Config {
+                pow_difficulty: 3,
+                escalate_on_failure: true,
+                relieve_on_solve_failure: true,
+                ..Config::default()
+            };
+            let mut headers: HeaderMap = HeaderMap::new();
+            headers.insert(CLIENT_IP_HEADER, HeaderValue::from_static("203.0.113.5"));
+            headers.insert(SOLVE_FAILED_HEADER, HeaderValue::from_static(CHALLENGE));
+
+            let escalation: Option<Escalation> = client_escalation(&headers, &config, 0);
+            record_issued_challenge(&headers, CHALLENGE, escalation.as_ref(), &config, 0);
+            record_solve_failure_report(&headers, &config, MIN_REPORT_INTERVAL_MS);
+            assert_eq!(client_escalation(&headers, &config, MIN_REPORT_INTERVAL_MS).unwrap().difficulty, 2);
+
+            for _ in 0..FAILURES_PER_ESCALATION {
+                record_client_result(&headers, &config, false, 0);
+            }
+            let escalation: Escalation = client_escalation(&headers, &config, 0).unwrap();
+            assert!(escalation.is_throttled() && !escalation.is_relieved());
+            assert_eq!(escalation.difficulty, 4);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_in_flight_challenges_keep_their_issued_difficulty() {
+        use crate::throttle::FAILURES_PER_ESCALATION;
+
+        std::thread::spawn(|| {
+            let config: Config = Config { pow_difficulty: 2, escalate_on_failure: true, ..Config::default() };
+            let mut headers: HeaderMap = HeaderMap::new();
+            headers.insert(CLIENT_IP_HEADER, HeaderValue::from_static("203.0.113.5"));
+
+            let escalation: Option<Escalation> = client_escalation(&headers, &config, 0);
+            record_issued_challenge(&headers, CHALLENGE, escalation.as_ref(), &config, 0);
+            for _ in 0..FAILURES_PER_ESCALATION {
+                record_client_result(&headers, &config, false, 0);
+            }
+            assert_eq!(client_escalation(&headers, &config, 0).unwrap().difficulty, 3);
+
+            // The challenge issued before the escalation is held to its own difficulty.
+            let mut submission: HeaderMap = headers.clone();
+            submission.insert(CHALLENGE_HEADER, HeaderValue::from_static(CHALLENGE));
+            assert_eq!(enforced_difficulty(&submission, &config, 1_000), 2);
+
+            // Unknown challenges get the client's current difficulty, other clients their own.
+            submission.insert(CHALLENGE_HEADER, HeaderValue::from_static("unknown"));
+            assert_eq!(enforced_difficulty(&submission, &config, 1_000), 3);
+            let mut other: HeaderMap = HeaderMap::new();
+            other.insert(CLIENT_IP_HEADER, HeaderValue::from_static("198.51.100.7"));
+            other.insert(CHALLENGE_HEADER, HeaderValue::from_static(CHALLENGE));
+            assert_eq!(enforced_difficulty(&other, &config, 1_000), 2);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_cached_challenge_params_match_computed() {
+        for zeros in MIN_POW_DIFFICULTY..=MAX_POW_DIFFICULTY {
+            let difficulty: u64 = 16u64.pow(zeros as u32);
+            assert_eq!(
+                challenge_param_for(difficulty),
+                IronShieldChallenge::difficulty_to_challenge_param(difficulty),
+            );
+        }
+
+        // Difficulties outside the cached presets are computed on demand.
+        for difficulty in [1u64, 2, 1_000, 16u64.pow(MAX_POW_DIFFICULTY as u32 + 1)] {
+            assert_eq!(
+                challenge_param_for(difficulty),
+                IronShieldChallenge::difficulty_to_challenge_param(difficulty),
+            );
+        }
+    }
+}
@@ -0,0 +1,930 @@
+//! Runtime configuration read from the worker's environment bindings.
+//!
+//! Every setting has a compiled-in default so the worker runs unchanged
+//! when a binding is missing or malformed.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ironshield_types::TokenAuthMode;
+use std::collections::HashMap;
+use std::fmt;
+use worker::Env;
+
+use crate::constant::BYPASS_TOKEN_HEADER;
+use crate::http_handler::percent_decode;
+
+/// Number of leading zeros required in the hash when
+/// `POW_DIFFICULTY` is not configured.
+pub const DEFAULT_POW_DIFFICULTY: usize = 4;
+/// Lowest difficulty an operator may configure.
+pub const     MIN_POW_DIFFICULTY: usize = 1;
+/// Highest difficulty an operator may configure. Each step multiplies
+/// the expected client work by 16, so this keeps solve times reasonable.
+pub const     MAX_POW_DIFFICULTY: usize = 6;
+/// Where users are sent after passing a check when `SUCCESS_REDIRECT_URL`
+/// is not configured.
+pub const DEFAULT_SUCCESS_REDIRECT_URL: &str = "https://skip.ironshield.cloud";
+/// Default window over which adaptive activation counts requests.
+pub const DEFAULT_ACTIVATION_WINDOW_MS: i64 = 60_000;
+
+/// Name of the env binding holding the leading-zero difficulty.
+const   POW_DIFFICULTY_VAR: &str = "POW_DIFFICULTY";
+/// Name of the env binding that enables the challenge cookie.
+const CHALLENGE_COOKIE_VAR: &str = "CHALLENGE_COOKIE";
+/// Name of the env binding listing bypass token header names,
+/// comma-separated.
+const BYPASS_TOKEN_HEADERS_VAR: &str = "BYPASS_TOKEN_HEADERS";
+/// Name of the env binding that accepts `Authorization: Bearer` tokens.
+const  BYPASS_ALLOW_BEARER_VAR: &str = "BYPASS_ALLOW_BEARER";
+/// Name of the env binding choosing how long the bypass cookie lasts.
+const BYPASS_COOKIE_LIFETIME_VAR: &str = "BYPASS_COOKIE_LIFETIME";
+/// Name of the env binding choosing HTML or headless challenge delivery.
+const CHALLENGE_DELIVERY_VAR: &str = "CHALLENGE_DELIVERY";
+/// Name of the env binding that enables development-only behavior.
+const        TEST_MODE_VAR: &str = "TEST_MODE";
+/// Name of the env binding seeding challenge nonces in test mode.
+const   CHALLENGE_SEED_VAR: &str = "CHALLENGE_SEED";
+/// Name of the env binding holding the base64 Ed25519 public key that
+/// signs `IronShieldChallenge`s.
+const       PUBLIC_KEY_VAR: &str = "IRONSHIELD_PUBLIC_KEY";
+/// Name of the secret holding the base64 Ed25519 private key used to
+/// sign challenges this worker mints.
+const      PRIVATE_KEY_VAR: &str = "IRONSHIELD_PRIVATE_KEY";
+/// Name of the env binding choosing how bypass tokens are authenticated.
+const  TOKEN_AUTH_MODE_VAR: &str = "TOKEN_AUTH_MODE";
+/// Name of the secret keying HMAC-authenticated bypass tokens.
+const TOKEN_HMAC_SECRET_VAR: &str = "IRONSHIELD_TOKEN_SECRET";
+/// Name of the env binding holding the reference client hash rate, in
+/// hashes per second, used to flag implausibly slow solves.
+const REFERENCE_HASHRATE_VAR: &str = "REFERENCE_HASHRATE";
+/// Name of the env binding holding the target solve time on the reference
+/// client, in milliseconds.
+const  TARGET_SOLVE_MS_VAR: &str = "TARGET_SOLVE_MS";
+/// Name of the env binding holding the longest acceptable honest solve
+/// time, in milliseconds.
+const     MAX_SOLVE_MS_VAR: &str = "MAX_SOLVE_MS";
+/// Name of the env binding holding the attempts added per squared point
+/// of Cloudflare bot score below 99.
+const BOT_SCORE_SCALING_VAR: &str = "BOT_SCORE_SCALING";
+/// Name of the env binding holding the site id minted challenges are
+/// bound to.
+const          SITE_ID_VAR: &str = "SITE_ID";
+/// Name of the env binding mapping site ids to difficulty multipliers,
+/// as comma-separated `site=multiplier` pairs.
+const SITE_DIFFICULTY_MULTIPLIERS_VAR: &str = "SITE_DIFFICULTY_MULTIPLIERS";
+/// Name of the secret keying stateless challenge nonces.
+const     NONCE_SECRET_VAR: &str = "IRONSHIELD_NONCE_SECRET";
+/// Name of the env binding that escalates difficulty for failing clients.
+const ESCALATE_ON_FAILURE_VAR: &str = "ESCALATE_ON_FAILURE";
+/// Name of the env binding that relieves difficulty for clients reporting
+/// solve failures.
+const RELIEVE_ON_SOLVE_FAILURE_VAR: &str = "RELIEVE_ON_SOLVE_FAILURE";
+/// Name of the env binding holding how many unconsumed signed challenges
+/// one client may hold at once.
+const MAX_OUTSTANDING_CHALLENGES_VAR: &str = "MAX_OUTSTANDING_CHALLENGES";
+/// Name of the env binding holding where users are sent after passing a
+/// check when no return URL is given or allowed.
+const SUCCESS_REDIRECT_URL_VAR: &str = "SUCCESS_REDIRECT_URL";
+/// Name of the env binding listing extra hosts return URLs may point to,
+/// comma-separated.
+const REDIRECT_ALLOWED_HOSTS_VAR: &str = "REDIRECT_ALLOWED_HOSTS";
+/// Name of the env binding holding the origin verified requests are
+/// forwarded to.
+const         ORIGIN_URL_VAR: &str = "ORIGIN_URL";
+/// Name of the env binding choosing how NDJSON submissions are verified.
+const  NDJSON_VERIFY_MODE_VAR: &str = "NDJSON_VERIFY_MODE";
+/// Name of the env binding holding how many requests per window, across
+/// all clients, activate challenges.
+const ACTIVATION_GLOBAL_REQUESTS_VAR: &str = "ACTIVATION_GLOBAL_REQUESTS";
+/// Name of the env binding holding how many requests per window from one
+/// client activate challenges for it.
+const ACTIVATION_CLIENT_REQUESTS_VAR: &str = "ACTIVATION_CLIENT_REQUESTS";
+/// Name of the env binding holding the activation counting window.
+const ACTIVATION_WINDOW_SECONDS_VAR: &str = "ACTIVATION_WINDOW_SECONDS";
+/// Name of the env binding listing paths served without proof-of-work,
+/// comma-separated.
+const       EXEMPT_PATHS_VAR: &str = "EXEMPT_PATHS";
+/// Name of the env binding that enables the `/benchmark` route.
+const  BENCHMARK_ENABLED_VAR: &str = "BENCHMARK_ENABLED";
+/// Name of the secret `/benchmark` requests must present.
+const  BENCHMARK_SECRET_VAR: &str = "IRONSHIELD_BENCHMARK_SECRET";
+/// Name of the env binding that enables the `/metrics` route.
+const    METRICS_ENABLED_VAR: &str = "METRICS_ENABLED";
+/// Name of the secret `/metrics` requests must present.
+const     METRICS_SECRET_VAR: &str = "IRONSHIELD_METRICS_SECRET";
+
+/// How the `Max-Age` of the bypass cookie set after a successful
+/// verification is chosen.
+///
+/// * `Fixed`:             Always `BYPASS_COOKIE_MAX_AGE_SECONDS`.
+/// * `ChallengeValidity`: The solved challenge's remaining validity.
+/// * `Shorter`:           Whichever of the two is shorter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CookieLifetime {
+    Fixed,
+    ChallengeValidity,
+    Shorter,
+}
+
+impl CookieLifetime {
+    /// Parses a configuration value (`"fixed"`, `"challenge"`, or
+    /// `"shorter"`, case-insensitive).
+    ///
+    /// # Returns
+    /// * `Option<Self>`: The policy, or `None` if the value is not recognized.
+    fn from_config_value(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "fixed"     => Some(Self::Fixed),
+            "challenge" => Some(Self::ChallengeValidity),
+            "shorter"   => Some(Self::Shorter),
+            _           => None,
+        }
+    }
+}
+
+/// How challenges are delivered to clients.
+///
+/// * `Html`:     Serve the challenge page and its JS/WASM assets; JSON on
+///   request via `Accept: application/json`.
+/// * `Headless`: Pure API deployments. Asset routes return 404 and every
+///   challenge is issued as JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChallengeDelivery {
+    Html,
+    Headless,
+}
+
+impl ChallengeDelivery {
+    /// Parses a configuration value (`"html"` or `"headless"`,
+    /// case-insensitive).
+    ///
+    /// # Returns
+    /// * `Option<Self>`: The mode, or `None` if the value is not recognized.
+    fn from_config_value(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "html"     => Some(Self::Html),
+            "headless" => Some(Self::Headless),
+            _          => None,
+        }
+    }
+}
+
+/// How the entries of a bundled NDJSON submission are verified.
+///
+/// * `FailFast`:  Stop reading the body at the first invalid entry.
+/// * `VerifyAll`: Verify every entry, e.g. to log which ones failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NdjsonVerifyMode {
+    FailFast,
+    VerifyAll,
+}
+
+impl NdjsonVerifyMode {
+    /// Parses a configuration value (`"fail-fast"` or `"all"`,
+    /// case-insensitive).
+    ///
+    /// # Returns
+    /// * `Option<Self>`: The mode, or `None` if the value is not recognized.
+    fn from_config_value(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "fail-fast" => Some(Self::FailFast),
+            "all"       => Some(Self::VerifyAll),
+            _           => None,
+        }
+    }
+}
+
+/// Worker configuration.
+///
+/// * `pow_difficulty`:   Number of leading zeros required in the hash,
+///   issued to clients and enforced on verification.
+/// * `challenge_cookie`: Also deliver the challenge in a JS-readable
+///   cookie, for clients behind proxies that strip custom headers.
+/// * `bypass_token_headers`: Header names checked, in order, for a
+///   bypass token.
+/// * `bypass_allow_bearer`: Also accept the bypass token as
+///   `Authorization: Bearer <token>`.
+/// * `public_key`: Trusted Ed25519 key for challenge signatures. Without
+///   it, signed challenges are always rejected.
+/// * `private_key`: Ed25519 key for signing minted challenges. Without it,
+///   the worker cannot issue signed challenges.
+/// * `token_auth_mode`: Whether bypass tokens are signed with
+///   `private_key` or tagged with `token_hmac_secret`, see
+///   `crate::bypass`.
+/// * `token_hmac_secret`: Key for HMAC-authenticated bypass tokens.
+///   Without it, `TokenAuthMode::Hmac` falls back to the static token.
+/// * `bypass_cookie_lifetime`: How the bypass cookie's `Max-Age` is
+///   chosen, see `CookieLifetime`.
+/// * `challenge_delivery`: Whether the HTML challenge page and assets are
+///   served, see `ChallengeDelivery`.
+/// * `test_mode`: Enables development-only behavior such as replaying a
+///   chosen challenge. Must stay off in production.
+/// * `challenge_seed`: In test mode, seeds the PRNG challenge nonces are
+///   drawn from so runs are reproducible, see `crate::source`. Ignored
+///   otherwise.
+/// * `reference_hashrate`: Hashes per second of a typical client. When
+///   set, submissions arriving far later than the expected solve time are
+///   logged as possibly farmed out. `None` disables the check.
+/// * `target_solve_ms`: Desired solve time on a `reference_hashrate`
+///   client. When both are set, signed challenges are minted with that
+///   many expected attempts instead of `16^pow_difficulty`.
+/// * `max_solve_ms`: Longest acceptable solve time on a
+///   `reference_hashrate` client. When both are set, minted challenges
+///   are capped to that many expected attempts, see `assert_solvable`.
+/// * `bot_score_scaling`: Attempts added per squared point of Cloudflare
+///   bot score below 99, see `bot_score_to_difficulty`. Raises the
+///   difficulty of every issued challenge for requests Bot Management
+///   scored; `None` ignores bot scores.
+/// * `site_id`: Lowercase `website_id` minted challenges are bound to.
+///   `None` uses the request's `Host`, see `request_website_id`.
+/// * `site_difficulty_multipliers`: Per-site multipliers applied to the
+///   difficulty of issued challenges, keyed by lowercase `website_id`.
+///   Protocol v1 leading-zero challenges round them to whole leading
+///   zeros, see `site_difficulty` and `site_pow_difficulty`.
+/// * `nonce_secret`: Key for deriving minted challenges' nonces from the
+///   client IP and time bucket, see `crate::nonce`. `None` issues random
+///   nonces.
+/// * `escalate_on_failure`: Raise the difficulty issued to clients that
+///   keep failing verification and answer them with `429`, see
+///   `crate::throttle`.
+/// * `relieve_on_solve_failure`: Lower the difficulty issued to clients
+///   that report running out of attempts, see `crate::relief`.
+/// * `max_outstanding_challenges`: Most unexpired, unconsumed challenges,
+///   signed or protocol v1, issued to one client IP at once, see
+///   `crate::outstanding`. `None` leaves issuance uncapped.
+/// * `ndjson_verify_mode`: Whether bundled NDJSON submissions stop at the
+///   first invalid entry, see `NdjsonVerifyMode`.
+/// * `success_redirect_url`: Where bypassed requests are redirected
+///   without an allowed return URL, see `success_redirect_target` for
+///   where users go after passing a check.
+/// * `redirect_allowed_hosts`: Lowercase hosts, besides the request's own,
+///   that return URLs may point to.
+/// * `origin_url`: Origin that bypassed and unchallenged requests are
+///   proxied to, see `forward_to_origin`. `None` redirects bypassed
+///   requests and passes the rest through to the route's own origin.
+/// * `activation_global_requests`: Requests per window, across all
+///   clients of one isolate, above which challenges are issued. When this
+///   or `activation_client_requests` is set, visitors below both
+///   thresholds pass through unchallenged, see `crate::activation`.
+/// * `activation_client_requests`: Requests per window from one client IP
+///   above which that client is challenged.
+/// * `activation_window_ms`: The window both thresholds count over.
+/// * `exempt_paths`: Path patterns passed through to the origin without
+///   proof-of-work, see `is_exempt_path`.
+/// * `benchmark_enabled`: Serve the verification throughput benchmark,
+///   see `crate::benchmark`. Off, the route answers `404`.
+/// * `benchmark_secret`: Secret benchmark requests must present. `None`
+///   keeps the route answering `404` even when enabled.
+/// * `metrics_enabled`: Serve the counters at `/metrics`. Off, the path is
+///   handled like any other and reaches the origin.
+/// * `metrics_secret`: Secret metrics requests must present. `None` keeps
+///   the route answering `404` even when enabled.
+///
+/// `Debug` redacts `private_key`, `token_hmac_secret`, `nonce_secret`,
+/// `benchmark_secret` and `metrics_secret` so the configuration can be
+/// logged.
+#[derive(Clone, PartialEq)]
+pub(crate) struct Config {
+    pub pow_difficulty:       usize,
+    pub challenge_cookie:     bool,
+    pub bypass_token_headers: Vec<String>,
+    pub bypass_allow_bearer:  bool,
+    pub public_key:           Option<[u8; 32]>,
+    pub private_key:          Option<[u8; 32]>,
+    pub token_auth_mode:      TokenAuthMode,
+    pub token_hmac_secret:    Option<Vec<u8>>,
+    pub bypass_cookie_lifetime: CookieLifetime,
+    pub challenge_delivery:   ChallengeDelivery,
+    pub test_mode:            bool,
+    pub challenge_seed:       Option<u64>,
+    pub reference_hashrate:   Option<u64>,
+    pub target_solve_ms:      Option<u64>,
+    pub max_solve_ms:         Option<u64>,
+    pub bot_score_scaling:    Option<u64>,
+    pub site_id:              Option<String>,
+    pub site_difficulty_multipliers: HashMap<String, f64>,
+    pub nonce_secret:         Option<Vec<u8>>,
+    pub escalate_on_failure:  bool,
+    pub relieve_on_solve_failure: bool,
+    pub max_outstanding_challenges: Option<u64>,
+    pub ndjson_verify_mode:   NdjsonVerifyMode,
+    pub success_redirect_url: String,
+    pub redirect_allowed_hosts: Vec<String>,
+    pub origin_url:           Option<http::Uri>,
+    pub activation_global_requests: Option<u64>,
+    pub activation_client_requests: Option<u64>,
+    pub activation_window_ms: i64,
+    pub exempt_paths:         Vec<String>,
+    pub benchmark_enabled:    bool,
+    pub benchmark_secret:     Option<Vec<u8>>,
+    pub metrics_enabled:      bool,
+    pub metrics_secret:       Option<Vec<u8>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            pow_difficulty:       DEFAULT_POW_DIFFICULTY,
+            challenge_cookie:     false,
+            bypass_token_headers: vec![BYPASS_TOKEN_HEADER.to_string()],
+            bypass_allow_bearer:  false,
+            public_key:           None,
+            private_key:          None,
+            token_auth_mode:      TokenAuthMode::Ed25519,
+            token_hmac_secret:    None,
+            bypass_cookie_lifetime: CookieLifetime::Fixed,
+            challenge_delivery:   ChallengeDelivery::Html,
+            test_mode:            false,
+            challenge_seed:       None,
+            reference_hashrate:   None,
+            target_solve_ms:      None,
+            max_solve_ms:         None,
+            bot_score_scaling:    None,
+            site_id:              None,
+            site_difficulty_multipliers: HashMap::new(),
+            nonce_secret:         None,
+            escalate_on_failure:  false,
+            relieve_on_solve_failure: false,
+            max_outstanding_challenges: None,
+            ndjson_verify_mode:   NdjsonVerifyMode::FailFast,
+            success_redirect_url: DEFAULT_SUCCESS_REDIRECT_URL.to_string(),
+            redirect_allowed_hosts: Vec::new(),
+            origin_url:           None,
+            activation_global_requests: None,
+            activation_client_requests: None,
+            activation_window_ms: DEFAULT_ACTIVATION_WINDOW_MS,
+            exempt_paths:         Vec::new(),
+            benchmark_enabled:    false,
+            benchmark_secret:     None,
+            metrics_enabled:      false,
+            metrics_secret:       None,
+        }
+    }
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const REDACTED: &str = "<redacted>";
+
+        f.debug_struct("Config")
+            .field("pow_difficulty", &self.pow_difficulty)
+            .field("challenge_cookie", &self.challenge_cookie)
+            .field("bypass_token_headers", &self.bypass_token_headers)
+            .field("bypass_allow_bearer", &self.bypass_allow_bearer)
+            .field("public_key", &self.public_key)
+            .field("private_key", &self.private_key.map(|_| REDACTED))
+            .field("token_auth_mode", &self.token_auth_mode)
+            .field("token_hmac_secret", &self.token_hmac_secret.as_ref().map(|_| REDACTED))
+            .field("bypass_cookie_lifetime", &self.bypass_cookie_lifetime)
+            .field("challenge_delivery", &self.challenge_delivery)
+            .field("test_mode", &self.test_mode)
+            .field("challenge_seed", &self.challenge_seed)
+            .field("reference_hashrate", &self.reference_hashrate)
+            .field("target_solve_ms", &self.target_solve_ms)
+            .field("max_solve_ms", &self.max_solve_ms)
+            .field("bot_score_scaling", &self.bot_score_scaling)
+            .field("site_id", &self.site_id)
+            .field("site_difficulty_multipliers", &self.site_difficulty_multipliers)
+            .field("nonce_secret", &self.nonce_secret.as_ref().map(|_| REDACTED))
+            .field("escalate_on_failure", &self.escalate_on_failure)
+            .field("relieve_on_solve_failure", &self.relieve_on_solve_failure)
+            .field("max_outstanding_challenges", &self.max_outstanding_challenges)
+            .field("ndjson_verify_mode", &self.ndjson_verify_mode)
+            .field("success_redirect_url", &self.success_redirect_url)
+            .field("redirect_allowed_hosts", &self.redirect_allowed_hosts)
+            .field("origin_url", &self.origin_url)
+            .field("activation_global_requests", &self.activation_global_requests)
+            .field("activation_client_requests", &self.activation_client_requests)
+            .field("activation_window_ms", &self.activation_window_ms)
+            .field("exempt_paths", &self.exempt_paths)
+            .field("benchmark_enabled", &self.benchmark_enabled)
+            .field("benchmark_secret", &self.benchmark_secret.as_ref().map(|_| REDACTED))
+            .field("metrics_enabled", &self.metrics_enabled)
+            .field("metrics_secret", &self.metrics_secret.as_ref().map(|_| REDACTED))
+            .finish()
+    }
+}
+
+impl Config {
+    /// Reads the configuration from the worker's env bindings.
+    ///
+    /// # Arguments
+    /// * `env`: The worker environment passed to the fetch handler.
+    pub(crate) fn from_env(env: &Env) -> Self {
+        Self::from_lookup(|name: &str| env.var(name).ok().map(|var| var.to_string()))
+    }
+
+    /// Builds the configuration from an arbitrary variable lookup.
+    ///
+    /// # Arguments
+    /// * `lookup`: Returns the raw value of a binding, or `None` if unset.
+    pub(crate) fn from_lookup<F>(lookup: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        Self {
+            pow_difficulty:       parse_pow_difficulty(lookup(POW_DIFFICULTY_VAR).as_deref()),
+            challenge_cookie:     parse_flag(lookup(CHALLENGE_COOKIE_VAR).as_deref()),
+            bypass_token_headers: parse_header_list(lookup(BYPASS_TOKEN_HEADERS_VAR).as_deref())
+                .unwrap_or_else(|| vec![BYPASS_TOKEN_HEADER.to_string()]),
+            bypass_allow_bearer:  parse_flag(lookup(BYPASS_ALLOW_BEARER_VAR).as_deref()),
+            public_key:           parse_ed25519_key(lookup(PUBLIC_KEY_VAR).as_deref()),
+            private_key:          parse_ed25519_key(lookup(PRIVATE_KEY_VAR).as_deref()),
+            token_auth_mode:      lookup(TOKEN_AUTH_MODE_VAR)
+                .as_deref()
+                .and_then(TokenAuthMode::from_config_value)
+                .unwrap_or(TokenAuthMode::Ed25519),
+            token_hmac_secret:    lookup(TOKEN_HMAC_SECRET_VAR)
+                .filter(|secret: &String| !secret.is_empty())
+                .map(String::into_bytes),
+            bypass_cookie_lifetime: lookup(BYPASS_COOKIE_LIFETIME_VAR)
+                .as_deref()
+                .and_then(CookieLifetime::from_config_value)
+                .unwrap_or(CookieLifetime::Fixed),
+            challenge_delivery:   lookup(CHALLENGE_DELIVERY_VAR)
+                .as_deref()
+                .and_then(ChallengeDelivery::from_config_value)
+                .unwrap_or(ChallengeDelivery::Html),
+            test_mode:            parse_flag(lookup(TEST_MODE_VAR).as_deref()),
+            challenge_seed:       lookup(CHALLENGE_SEED_VAR).and_then(|seed: String| seed.trim().parse::<u64>().ok()),
+            reference_hashrate:   parse_positive_u64(lookup(REFERENCE_HASHRATE_VAR).as_deref()),
+            target_solve_ms:      parse_positive_u64(lookup(TARGET_SOLVE_MS_VAR).as_deref()),
+            max_solve_ms:         parse_positive_u64(lookup(MAX_SOLVE_MS_VAR).as_deref()),
+            bot_score_scaling:    parse_positive_u64(lookup(BOT_SCORE_SCALING_VAR).as_deref()),
+            site_id:              lookup(SITE_ID_VAR)
+                .map(|site_id: String| site_id.trim().to_ascii_lowercase())
+                .filter(|site_id: &String| !site_id.is_empty()),
+            site_difficulty_multipliers: parse_site_multipliers(lookup(SITE_DIFFICULTY_MULTIPLIERS_VAR).as_deref()),
+            nonce_secret:         lookup(NONCE_SECRET_VAR)
+                .filter(|secret: &String| !secret.is_empty())
+                .map(String::into_bytes),
+            escalate_on_failure:  parse_flag(lookup(ESCALATE_ON_FAILURE_VAR).as_deref()),
+            relieve_on_solve_failure: parse_flag(lookup(RELIEVE_ON_SOLVE_FAILURE_VAR).as_deref()),
+            max_outstanding_challenges: parse_positive_u64(lookup(MAX_OUTSTANDING_CHALLENGES_VAR).as_deref()),
+            ndjson_verify_mode:   lookup(NDJSON_VERIFY_MODE_VAR)
+                .as_deref()
+                .and_then(NdjsonVerifyMode::from_config_value)
+                .unwrap_or(NdjsonVerifyMode::FailFast),
+            success_redirect_url: lookup(SUCCESS_REDIRECT_URL_VAR)
+                .map(|url: String| url.trim().to_string())
+                .filter(|url: &String| !url.is_empty())
+                .unwrap_or_else(|| DEFAULT_SUCCESS_REDIRECT_URL.to_string()),
+            redirect_allowed_hosts: parse_header_list(lookup(REDIRECT_ALLOWED_HOSTS_VAR).as_deref())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|host: String| host.to_ascii_lowercase())
+                .collect(),
+            origin_url:           parse_origin_url(lookup(ORIGIN_URL_VAR).as_deref()),
+            activation_global_requests: parse_positive_u64(lookup(ACTIVATION_GLOBAL_REQUESTS_VAR).as_deref()),
+            activation_client_requests: parse_positive_u64(lookup(ACTIVATION_CLIENT_REQUESTS_VAR).as_deref()),
+            activation_window_ms: parse_positive_u64(lookup(ACTIVATION_WINDOW_SECONDS_VAR).as_deref())
+                .and_then(|seconds: u64| i64::try_from(seconds.saturating_mul(1000)).ok())
+                .unwrap_or(DEFAULT_ACTIVATION_WINDOW_MS),
+            exempt_paths:         parse_header_list(lookup(EXEMPT_PATHS_VAR).as_deref()).unwrap_or_default(),
+            benchmark_enabled:    parse_flag(lookup(BENCHMARK_ENABLED_VAR).as_deref()),
+            benchmark_secret:     lookup(BENCHMARK_SECRET_VAR)
+                .filter(|secret: &String| !secret.is_empty())
+                .map(String::into_bytes),
+            metrics_enabled:      parse_flag(lookup(METRICS_ENABLED_VAR).as_deref()),
+            metrics_secret:       lookup(METRICS_SECRET_VAR)
+                .filter(|secret: &String| !secret.is_empty())
+                .map(String::into_bytes),
+        }
+    }
+}
+
+/// Parses a configured difficulty and clamps it to
+/// `MIN_POW_DIFFICULTY..=MAX_POW_DIFFICULTY`.
+///
+/// # Arguments
+/// * `raw`: The raw binding value, if any.
+///
+/// # Returns
+/// * `usize`: The clamped difficulty, or `DEFAULT_POW_DIFFICULTY` when
+///   the value is missing or not a number.
+fn parse_pow_difficulty(raw: Option<&str>) -> usize {
+    raw.and_then(|value: &str| value.trim().parse::<usize>().ok())
+        .map(|difficulty: usize| difficulty.clamp(MIN_POW_DIFFICULTY, MAX_POW_DIFFICULTY))
+        .unwrap_or(DEFAULT_POW_DIFFICULTY)
+}
+
+/// Parses a boolean binding. `"true"`, `"1"`, and `"yes"` (any case)
+/// enable the flag; anything else, including a missing value, disables it.
+fn parse_flag(raw: Option<&str>) -> bool {
+    matches!(
+        raw.map(|value: &str| value.trim().to_ascii_lowercase()).as_deref(),
+        Some("true" | "1" | "yes")
+    )
+}
+
+/// Parses a comma-separated list of header names (or hosts), skipping
+/// blanks.
+///
+/// # Returns
+/// * `Option<Vec<String>>`: The names, or `None` if the value is missing
+///   or contains no names.
+fn parse_header_list(raw: Option<&str>) -> Option<Vec<String>> {
+    let names: Vec<String> = raw?
+        .split(',')
+        .map(|name: &str| name.trim())
+        .filter(|name: &&str| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    (!names.is_empty()).then_some(names)
+}
+
+/// Decodes a base64 (standard alphabet) Ed25519 key, the same format as
+/// `IRONSHIELD_PUBLIC_KEY` and `IRONSHIELD_PRIVATE_KEY` in `ironshield-types`.
+///
+/// # Returns
+/// * `Option<[u8; 32]>`: The key, or `None` if missing or malformed.
+fn parse_ed25519_key(raw: Option<&str>) -> Option<[u8; 32]> {
+    STANDARD.decode(raw?.trim()).ok()?.try_into().ok()
+}
+
+/// Parses a positive count such as `REFERENCE_HASHRATE`, `MAX_SOLVE_MS`,
+/// or an activation threshold, where `0` means unset.
+///
+/// # Returns
+/// * `Option<u64>`: The value, or `None` if missing, zero, or not a number.
+fn parse_positive_u64(raw: Option<&str>) -> Option<u64> {
+    raw?.trim().parse::<u64>().ok().filter(|value: &u64| *value > 0)
+}
+
+/// Parses an origin URL such as `"https://origin.example.com"`.
+///
+/// # Returns
+/// * `Option<http::Uri>`: The URL, or `None` if missing, not absolute
+///   `http(s)`, or carrying a query.
+fn parse_origin_url(raw: Option<&str>) -> Option<http::Uri> {
+    let origin: http::Uri = raw?.trim().parse().ok()?;
+    let is_http: bool = matches!(origin.scheme_str(), Some("http" | "https"));
+    (is_http && origin.authority().is_some() && origin.query().is_none()).then_some(origin)
+}
+
+/// Parses comma-separated `site=multiplier` pairs, e.g.
+/// `"shop.example.com=4,blog.example.com=0.5"`. Pairs without a positive,
+/// finite multiplier are skipped.
+///
+/// # Returns
+/// * `HashMap<String, f64>`: The multipliers keyed by lowercase site id.
+fn parse_site_multipliers(raw: Option<&str>) -> HashMap<String, f64> {
+    raw.unwrap_or_default()
+        .split(',')
+        .filter_map(|pair: &str| {
+            let (site, multiplier) = pair.split_once('=')?;
+            let multiplier: f64 = multiplier.trim().parse::<f64>().ok()?;
+            let site: String = site.trim().to_ascii_lowercase();
+            (!site.is_empty() && multiplier.is_finite() && multiplier > 0.0).then_some((site, multiplier))
+        })
+        .collect()
+}
+
+/// Looks up the difficulty multiplier configured for a site.
+///
+/// # Arguments
+/// * `website_id`: The site a challenge is issued for; matched
+///   case-insensitively.
+/// * `config`:     The worker configuration.
+///
+/// # Returns
+/// * `f64`: The configured multiplier, or `1.0` for unknown sites.
+pub(crate) fn site_difficulty_multiplier(website_id: &str, config: &Config) -> f64 {
+    config
+        .site_difficulty_multipliers
+        .get(&website_id.to_ascii_lowercase())
+        .copied()
+        .unwrap_or(1.0)
+}
+
+/// Returns whether a request path is exempt from proof-of-work.
+///
+/// Patterns are globs in which `*` matches any run of characters,
+/// including `/`, so `/static/*` exempts everything under `/static/`.
+/// A pattern without `*` must match the whole path. Matching is
+/// case-sensitive, like paths.
+///
+/// The path is percent-decoded before matching. Paths with a `.` or `..`
+/// segment or a backslash, encoded or not, are never exempt, so
+/// `/static/../admin` cannot reach the origin through `/static/*`.
+///
+/// # Arguments
+/// * `path`:   The request path, without the query string.
+/// * `config`: The worker configuration.
+///
+/// # Returns
+/// * `bool`: Whether any of `config.exempt_paths` matches `path`.
+pub(crate) fn is_exempt_path(path: &str, config: &Config) -> bool {
+    if config.exempt_paths.is_empty() {
+        return false;
+    }
+    let Some(path) = percent_decode(path) else {
+        return false;
+    };
+    if path.contains('\\') || path.split('/').any(|segment: &str| segment == "." || segment == "..") {
+        return false;
+    }
+
+    config
+        .exempt_paths
+        .iter()
+        .any(|pattern: &String| glob_matches(pattern, &path))
+}
+
+/// Matches `text` against a glob whose only wildcard is `*`.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts: std::str::Split<char> = pattern.split('*');
+    let first: &str = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut pending: Option<&str> = parts.next();
+    while let Some(part) = pending {
+        pending = parts.next();
+        if pending.is_none() {
+            // The last part anchors at the end of the text.
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None        => return false,
+        }
+    }
+
+    rest.is_empty()
+}
+
+/// A configuration with a fresh test signing keypair, for tests that mint
+/// and verify signed challenges or bypass tokens. `pow_difficulty` is 1 so
+/// solving them is quick.
+#[cfg(test)]
+pub(crate) fn signing_config() -> Config {
+    let (private_b64, public_b64) = ironshield_types::generate_test_keypair();
+    Config {
+        pow_difficulty: 1,
+        private_key: parse_ed25519_key(Some(&private_b64)),
+        public_key: parse_ed25519_key(Some(&public_b64)),
+        ..Config::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let config: Config = Config {
+            private_key:  Some([0xAB; 32]),
+            nonce_secret: Some(b"hunter2".to_vec()),
+            token_hmac_secret: Some(b"opensesame".to_vec()),
+            benchmark_secret: Some(b"swordfish".to_vec()),
+            metrics_secret: Some(b"letmein".to_vec()),
+            public_key:   Some([0xCD; 32]),
+            ..Config::default()
+        };
+
+        let debug: String = format!("{:?}", config);
+        assert!(debug.contains("private_key: Some(\"<redacted>\")"));
+        assert!(debug.contains("nonce_secret: Some(\"<redacted>\")"));
+        assert!(!debug.contains("171"), "private key bytes leaked: {}", debug);
+        assert!(!debug.contains("104, 117"), "nonce secret bytes leaked: {}", debug);
+        assert!(debug.contains("token_hmac_secret: Some(\"<redacted>\")"));
+        assert!(!debug.contains("111, 112"), "token secret bytes leaked: {}", debug);
+        assert!(debug.contains("benchmark_secret: Some(\"<redacted>\")"));
+        assert!(!debug.contains("115, 119"), "benchmark secret bytes leaked: {}", debug);
+        assert!(debug.contains("metrics_secret: Some(\"<redacted>\")"));
+        assert!(!debug.contains("108, 101"), "metrics secret bytes leaked: {}", debug);
+        assert!(debug.contains("205"), "public key should still be shown");
+        assert!(format!("{:?}", Config::default()).contains("private_key: None"));
+    }
+
+    #[test]
+    fn test_pow_difficulty_defaults_and_clamps() {
+        assert_eq!(Config::from_lookup(|_| None), Config::default());
+        assert_eq!(parse_pow_difficulty(Some("5")), 5);
+        assert_eq!(parse_pow_difficulty(Some(" 3 ")), 3);
+        assert_eq!(parse_pow_difficulty(Some("0")), MIN_POW_DIFFICULTY);
+        assert_eq!(parse_pow_difficulty(Some("64")), MAX_POW_DIFFICULTY);
+        assert_eq!(parse_pow_difficulty(Some("hard")), DEFAULT_POW_DIFFICULTY);
+        assert_eq!(parse_pow_difficulty(Some("-2")), DEFAULT_POW_DIFFICULTY);
+    }
+
+    #[test]
+    fn test_from_lookup_reads_pow_difficulty() {
+        let config: Config = Config::from_lookup(|name: &str| {
+            (name == "POW_DIFFICULTY").then(|| "2".to_string())
+        });
+        assert_eq!(config.pow_difficulty, 2);
+    }
+
+    #[test]
+    fn test_parse_flag() {
+        assert!(parse_flag(Some("true")));
+        assert!(parse_flag(Some(" YES ")));
+        assert!(parse_flag(Some("1")));
+        assert!(!parse_flag(Some("false")));
+        assert!(!parse_flag(Some("")));
+        assert!(!parse_flag(None));
+    }
+
+    #[test]
+    fn test_bypass_token_headers() {
+        assert_eq!(Config::default().bypass_token_headers, vec![BYPASS_TOKEN_HEADER.to_string()]);
+        assert_eq!(
+            parse_header_list(Some("X-Ironshield-Token, CF-Ironshield-Token,")),
+            Some(vec!["X-Ironshield-Token".to_string(), "CF-Ironshield-Token".to_string()])
+        );
+        assert_eq!(parse_header_list(Some(" , ")), None);
+
+        let config: Config = Config::from_lookup(|name: &str| {
+            (name == "BYPASS_ALLOW_BEARER").then(|| "true".to_string())
+        });
+        assert!(config.bypass_allow_bearer);
+        assert_eq!(config.bypass_token_headers, vec![BYPASS_TOKEN_HEADER.to_string()]);
+    }
+
+    #[test]
+    fn test_max_outstanding_challenges() {
+        assert_eq!(Config::default().max_outstanding_challenges, None);
+        let parse = |raw: &str| Config::from_lookup(|name: &str| {
+            (name == "MAX_OUTSTANDING_CHALLENGES").then(|| raw.to_string())
+        }).max_outstanding_challenges;
+        assert_eq!(parse("8"), Some(8));
+        assert_eq!(parse("0"), None);
+        assert_eq!(parse("many"), None);
+    }
+
+    #[test]
+    fn test_origin_url() {
+        assert_eq!(Config::default().origin_url, None);
+        let parse = |raw: &str| Config::from_lookup(|name: &str| {
+            (name == "ORIGIN_URL").then(|| raw.to_string())
+        }).origin_url.map(|origin: http::Uri| origin.to_string());
+        assert_eq!(parse(" https://origin.example.com "), Some("https://origin.example.com/".to_string()));
+        assert_eq!(parse("http://10.0.0.2:8080/app"), Some("http://10.0.0.2:8080/app".to_string()));
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("origin.example.com"), None);
+        assert_eq!(parse("ftp://origin.example.com"), None);
+        assert_eq!(parse("https://origin.example.com/?a=1"), None);
+    }
+
+    #[test]
+    fn test_activation_thresholds() {
+        let defaults: Config = Config::default();
+        assert_eq!(defaults.activation_global_requests, None);
+        assert_eq!(defaults.activation_client_requests, None);
+        assert_eq!(defaults.activation_window_ms, DEFAULT_ACTIVATION_WINDOW_MS);
+
+        let config: Config = Config::from_lookup(|name: &str| match name {
+            "ACTIVATION_GLOBAL_REQUESTS" => Some("5000".to_string()),
+            "ACTIVATION_CLIENT_REQUESTS" => Some("0".to_string()),
+            "ACTIVATION_WINDOW_SECONDS"  => Some("10".to_string()),
+            _ => None,
+        });
+        assert_eq!(config.activation_global_requests, Some(5_000));
+        assert_eq!(config.activation_client_requests, None);
+        assert_eq!(config.activation_window_ms, 10_000);
+    }
+
+    #[test]
+    fn test_exempt_paths() {
+        let config: Config = Config::from_lookup(|name: &str| {
+            (name == "EXEMPT_PATHS").then(|| "/healthz, /static/*, /*.ico, /api/*/status".to_string())
+        });
+        assert_eq!(config.exempt_paths.len(), 4);
+
+        for path in ["/healthz", "/static/app.js", "/static/", "/favicon.ico", "/api/v1/status"] {
+            assert!(is_exempt_path(path, &config), "{} should be exempt", path);
+        }
+        for path in ["/", "/healthz/more", "/Static/app.js", "/static", "/api/v1/status/x", "/icons/a.png"] {
+            assert!(!is_exempt_path(path, &config), "{} should be protected", path);
+        }
+
+        // Escapes are decoded, and traversal out of an exempt prefix is not exempt.
+        assert!(is_exempt_path("/static/%61pp.js", &config));
+        for path in [
+            "/static/../admin",
+            "/static/%2e%2e/admin",
+            "/static/%2E%2e/admin",
+            "/static/.%2e/admin",
+            "/static/./app.js",
+            "/static/..%5cadmin",
+            "/static/%zz",
+            "/api/../status",
+        ] {
+            assert!(!is_exempt_path(path, &config), "{} should be protected", path);
+        }
+        assert!(!is_exempt_path("/healthz", &Config::default()));
+    }
+
+    #[test]
+    fn test_challenge_seed() {
+        let seeded = |raw: &str| Config::from_lookup(|name: &str| (name == "CHALLENGE_SEED").then(|| raw.to_string()));
+        assert_eq!(seeded(" 0 ").challenge_seed, Some(0));
+        assert_eq!(seeded("42").challenge_seed, Some(42));
+        assert_eq!(seeded("").challenge_seed, None);
+        assert_eq!(seeded("-1").challenge_seed, None);
+        assert_eq!(Config::default().challenge_seed, None);
+    }
+
+    #[test]
+    fn test_parse_ed25519_key() {
+        let encoded: String = STANDARD.encode([0x42u8; 32]);
+        assert_eq!(parse_ed25519_key(Some(&encoded)), Some([0x42; 32]));
+        assert_eq!(parse_ed25519_key(Some(&STANDARD.encode([0x42u8; 16]))), None);
+        assert_eq!(parse_ed25519_key(Some("not base64!")), None);
+        assert_eq!(parse_ed25519_key(None), None);
+    }
+
+    #[test]
+    fn test_bypass_cookie_lifetime() {
+        assert_eq!(Config::default().bypass_cookie_lifetime, CookieLifetime::Fixed);
+        assert_eq!(CookieLifetime::from_config_value(" Challenge "), Some(CookieLifetime::ChallengeValidity));
+        assert_eq!(CookieLifetime::from_config_value("shorter"), Some(CookieLifetime::Shorter));
+        assert_eq!(CookieLifetime::from_config_value("forever"), None);
+
+        let config: Config = Config::from_lookup(|name: &str| {
+            (name == "BYPASS_COOKIE_LIFETIME").then(|| "forever".to_string())
+        });
+        assert_eq!(config.bypass_cookie_lifetime, CookieLifetime::Fixed);
+    }
+
+    #[test]
+    fn test_challenge_delivery() {
+        assert_eq!(Config::default().challenge_delivery, ChallengeDelivery::Html);
+
+        let config: Config = Config::from_lookup(|name: &str| {
+            (name == "CHALLENGE_DELIVERY").then(|| "Headless".to_string())
+        });
+        assert_eq!(config.challenge_delivery, ChallengeDelivery::Headless);
+        assert_eq!(ChallengeDelivery::from_config_value("pdf"), None);
+    }
+
+    #[test]
+    fn test_ndjson_verify_mode() {
+        assert_eq!(Config::default().ndjson_verify_mode, NdjsonVerifyMode::FailFast);
+        let config: Config = Config::from_lookup(|name: &str| {
+            (name == "NDJSON_VERIFY_MODE").then(|| " ALL ".to_string())
+        });
+        assert_eq!(config.ndjson_verify_mode, NdjsonVerifyMode::VerifyAll);
+        assert_eq!(NdjsonVerifyMode::from_config_value("fail-fast"), Some(NdjsonVerifyMode::FailFast));
+        assert_eq!(NdjsonVerifyMode::from_config_value("some"), None);
+    }
+
+    #[test]
+    fn test_token_auth_mode() {
+        assert_eq!(Config::default().token_auth_mode, TokenAuthMode::Ed25519);
+        let config: Config = Config::from_lookup(|name: &str| match name {
+            "TOKEN_AUTH_MODE"         => Some("HMAC".to_string()),
+            "IRONSHIELD_TOKEN_SECRET" => Some("opensesame".to_string()),
+            _                         => None,
+        });
+        assert_eq!(config.token_auth_mode, TokenAuthMode::Hmac);
+        assert_eq!(config.token_hmac_secret.as_deref(), Some(&b"opensesame"[..]));
+
+        let unknown: Config = Config::from_lookup(|name: &str| (name == "TOKEN_AUTH_MODE").then(|| "rsa".to_string()));
+        assert_eq!(unknown.token_auth_mode, TokenAuthMode::Ed25519);
+    }
+
+    #[test]
+    fn test_parse_positive_u64() {
+        assert_eq!(Config::default().reference_hashrate, None);
+        assert_eq!(parse_positive_u64(Some(" 500000 ")), Some(500_000));
+        assert_eq!(parse_positive_u64(Some("0")), None);
+        assert_eq!(parse_positive_u64(Some("fast")), None);
+        assert_eq!(parse_positive_u64(None), None);
+    }
+
+    #[test]
+    fn test_from_lookup_reads_target_solve_ms() {
+        assert_eq!(Config::default().target_solve_ms, None);
+        assert_eq!(Config::default().max_solve_ms, None);
+        assert_eq!(Config::default().bot_score_scaling, None);
+        let config: Config = Config::from_lookup(|name: &str| match name {
+            "TARGET_SOLVE_MS"    => Some("2000".to_string()),
+            "MAX_SOLVE_MS"       => Some("15000".to_string()),
+            "REFERENCE_HASHRATE" => Some("500000".to_string()),
+            "BOT_SCORE_SCALING"  => Some("100".to_string()),
+            _                    => None,
+        });
+        assert_eq!(config.target_solve_ms, Some(2_000));
+        assert_eq!(config.max_solve_ms, Some(15_000));
+        assert_eq!(config.reference_hashrate, Some(500_000));
+        assert_eq!(config.bot_score_scaling, Some(100));
+    }
+
+    #[test]
+    fn test_site_difficulty_multiplier() {
+        let raw: &str = "Shop.Example.com=4, blog.example.com=0.5,bad=-1,nan=NaN,junk";
+        let config: Config = Config::from_lookup(|name: &str| {
+            (name == "SITE_DIFFICULTY_MULTIPLIERS").then(|| raw.to_string())
+        });
+        assert_eq!(config.site_difficulty_multipliers.len(), 2);
+        assert_eq!(site_difficulty_multiplier("shop.example.com", &config), 4.0);
+        assert_eq!(site_difficulty_multiplier("BLOG.example.com", &config), 0.5);
+        assert_eq!(site_difficulty_multiplier("bad", &config), 1.0);
+        assert_eq!(site_difficulty_multiplier("unknown.example.com", &config), 1.0);
+    }
+}
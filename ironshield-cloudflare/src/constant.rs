@@ -2,10 +2,34 @@
 //! 
 //! Thanks.
 
-pub const    CHALLENGE_HEADER: &str = "X-IronShield-Challenge";
-pub const        NONCE_HEADER: &str = "X-IronShield-Nonce";
-pub const    TIMESTAMP_HEADER: &str = "X-IronShield-Timestamp";
-pub const   DIFFICULTY_HEADER: &str = "X-IronShield-Difficulty";
-pub const BYPASS_TOKEN_HEADER: &str = "X-Ironshield-Token";
-pub const  BYPASS_TOKEN_VALUE: &str = "test_approved";
-pub const  BYPASS_COOKIE_NAME: &str = "ironshield_token";
\ No newline at end of file
+pub const      CHALLENGE_HEADER: &str = "X-IronShield-Challenge";
+pub const          NONCE_HEADER: &str = "X-IronShield-Nonce";
+pub const      TIMESTAMP_HEADER: &str = "X-IronShield-Timestamp";
+pub const     DIFFICULTY_HEADER: &str = "X-IronShield-Difficulty";
+pub const   BYPASS_TOKEN_HEADER: &str = "X-Ironshield-Token";
+pub const    BYPASS_TOKEN_VALUE: &str = "test_approved";
+pub const    BYPASS_COOKIE_NAME: &str = "ironshield_token";
+pub const CHALLENGE_COOKIE_NAME: &str = "ironshield_challenge";
+pub const    VERIFY_TIME_HEADER: &str = "X-IronShield-Verify-Us";
+pub const  CHALLENGE_BATCH_PATH: &str = "/challenges";
+pub const          METRICS_PATH: &str = "/metrics";
+pub const        BENCHMARK_PATH: &str = "/benchmark";
+pub const      CLIENT_IP_HEADER: &str = "CF-Connecting-IP";
+pub const       ATTEMPTS_HEADER: &str = "X-IronShield-Attempts";
+pub const       PROTOCOL_HEADER: &str = "X-IronShield-Protocol";
+pub const   SOLVE_FAILED_HEADER: &str = "X-IronShield-Solve-Failed";
+pub const BENCHMARK_SECRET_HEADER: &str = "X-IronShield-Benchmark-Secret";
+pub const METRICS_SECRET_HEADER: &str = "X-IronShield-Metrics-Secret";
+/// Carries Cloudflare's bot score for the request. Set by the worker from
+/// `request.cf`, never taken from the client; see `set_bot_score_header`.
+pub const      BOT_SCORE_HEADER: &str = "X-IronShield-Bot-Score";
+
+/// Former names still accepted for each proof-of-work header, so that
+/// clients loaded before a rename keep verifying while it deploys. The
+/// canonical name always wins when a request carries both.
+pub const LEGACY_HEADER_ALIASES: &[(&str, &[&str])] = &[
+    (CHALLENGE_HEADER,  &["X-Challenge"]),
+    (NONCE_HEADER,      &["X-Nonce"]),
+    (TIMESTAMP_HEADER,  &["X-Timestamp"]),
+    (DIFFICULTY_HEADER, &["X-Difficulty"]),
+];
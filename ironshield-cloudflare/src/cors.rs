@@ -81,7 +81,8 @@ pub fn add_cors_headers(
     // Add other CORS headers.
     builder = builder
         .header(header::ACCESS_CONTROL_ALLOW_METHODS, "GET, POST, OPTIONS")
-        .header(header::ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type, X-IronShield-Challenge, X-IronShield-Nonce, X-IronShield-Timestamp, X-IronShield-Difficulty, X-Ironshield-Token")
+        .header(header::ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type, X-IronShield-Challenge, X-IronShield-Nonce, X-IronShield-Timestamp, X-IronShield-Difficulty, X-IronShield-Attempts, X-IronShield-Protocol, X-IronShield-Solve-Failed, X-Ironshield-Token")
+        .header(header::ACCESS_CONTROL_EXPOSE_HEADERS, "X-IronShield-Verify-Us, X-IronShield-Protocol")
         .header(header::VARY, "Origin"); // Important for caching.
 
     // Only add a credential header if we have a specific origin (not wildcard).
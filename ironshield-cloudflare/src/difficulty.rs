@@ -7,20 +7,109 @@
 /// in the cf.bot_management.score API call
 /// A score of 1 is the highest liklihood of being a bot,
 /// A score of 99 is the highest liklihood of being human.
+/// Scores above 99 are treated as 99, and the result saturates at `u64::MAX`.
 pub fn bot_score_to_difficulty(bot_score: u64, base_difficulty: u64, scaling_factor: u64) -> u64 {
-    let inverted_score: u64 = 99 - bot_score;
-    let difficulty: u64 = ((inverted_score * inverted_score) * scaling_factor) + base_difficulty;
+    let inverted_score: u64 = 99u64.saturating_sub(bot_score);
+    let difficulty: u64 = (inverted_score * inverted_score)
+        .saturating_mul(scaling_factor)
+        .saturating_add(base_difficulty);
     difficulty
 }
 
+/// Converts a target solve time into a difficulty, i.e. the expected
+/// number of attempts a reference client makes in that time.
+///
+/// The result can serve as `base_difficulty` for `bot_score_to_difficulty`.
+///
+/// # Arguments
+/// * `target_solve_ms`:    Desired solve time on the reference client.
+/// * `reference_hashrate`: The reference client's hashes per second.
+///
+/// # Returns
+/// * `u64`: `target_solve_ms / 1000 * reference_hashrate`, at least 1 and
+///   saturating at `u64::MAX`.
+pub fn solve_time_to_difficulty(target_solve_ms: u64, reference_hashrate: u64) -> u64 {
+    let attempts: u128 = target_solve_ms as u128 * reference_hashrate as u128 / 1000;
+    u64::try_from(attempts).unwrap_or(u64::MAX).max(1)
+}
+
+/// Result of `assert_solvable`.
+///
+/// * `difficulty`:  The difficulty to issue.
+/// * `expected_ms`: Expected honest solve time of the requested
+///   difficulty, in milliseconds.
+/// * `capped`:      Whether the requested difficulty was lowered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Solvability {
+    pub difficulty:  u64,
+    pub expected_ms: u64,
+    pub capped:      bool,
+}
+
+/// Guards against issuing a difficulty honest clients cannot solve in
+/// reasonable time, e.g. after a misconfigured multiplier.
+///
+/// # Arguments
+/// * `difficulty`:         Expected attempts of the challenge to issue.
+/// * `reference_hashrate`: Hashes per second of a typical client.
+/// * `max_acceptable_ms`:  Longest acceptable solve time on that client.
+///
+/// # Returns
+/// * `Solvability`: `difficulty` unchanged if a reference client is
+///   expected to solve it within `max_acceptable_ms`, otherwise capped to
+///   `solve_time_to_difficulty(max_acceptable_ms, reference_hashrate)`.
+pub fn assert_solvable(difficulty: u64, reference_hashrate: u64, max_acceptable_ms: u64) -> Solvability {
+    let expected_ms: u128 = difficulty as u128 * 1000 / reference_hashrate.max(1) as u128;
+    let expected_ms: u64 = u64::try_from(expected_ms).unwrap_or(u64::MAX);
+
+    match expected_ms > max_acceptable_ms {
+        true => Solvability {
+            difficulty: solve_time_to_difficulty(max_acceptable_ms, reference_hashrate.max(1)).min(difficulty),
+            expected_ms,
+            capped:     true,
+        },
+        false => Solvability { difficulty, expected_ms, capped: false },
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_solve_time_to_difficulty() {
+        assert_eq!(solve_time_to_difficulty(2_000, 500_000), 1_000_000);
+        assert_eq!(solve_time_to_difficulty(4_000, 500_000), 2_000_000);
+        assert_eq!(solve_time_to_difficulty(1, 1), 1);
+        assert_eq!(solve_time_to_difficulty(u64::MAX, u64::MAX), u64::MAX);
+
+        // Doubling the target time doubles the difficulty, up to rounding.
+        for target_solve_ms in [250u64, 1_000, 1_337, 2_500] {
+            let single: u64 = solve_time_to_difficulty(target_solve_ms, 123_457);
+            let double: u64 = solve_time_to_difficulty(2 * target_solve_ms, 123_457);
+            assert!(double.abs_diff(2 * single) <= 1);
+        }
+    }
+
+    #[test]
+    fn test_assert_solvable() {
+        // 1M attempts at 500k H/s is a 2s solve.
+        let normal: Solvability = assert_solvable(1_000_000, 500_000, 10_000);
+        assert_eq!(normal, Solvability { difficulty: 1_000_000, expected_ms: 2_000, capped: false });
+        assert!(!assert_solvable(5_000_000, 500_000, 10_000).capped, "exactly at the limit");
+
+        // A 100x multiplier gone wrong is capped to 10s of honest solving.
+        let runaway: Solvability = assert_solvable(100_000_000, 500_000, 10_000);
+        assert_eq!(runaway, Solvability { difficulty: 5_000_000, expected_ms: 200_000, capped: true });
+        assert_eq!(assert_solvable(u64::MAX, 1, 1_000).difficulty, 1);
+    }
+
     #[test]
     fn test_bot_score_to_difficulty() {
         assert_eq!(bot_score_to_difficulty(99, 10_000, 1040), 10_000);
         assert_eq!(bot_score_to_difficulty(0, 10_000, 1040), 10_203_040);
+        assert_eq!(bot_score_to_difficulty(100, 10_000, 1040), 10_000);
+        assert_eq!(bot_score_to_difficulty(1, 10_000, u64::MAX), u64::MAX);
         assert_eq!(bot_score_to_difficulty(1, 10_000, 1040), 9_998_160);
         assert_eq!(bot_score_to_difficulty(1, 10_000, 1040), 9_998_160);
         assert_eq!(bot_score_to_difficulty(1, 10_000, 1040), 9_998_160);
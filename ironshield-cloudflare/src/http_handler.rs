@@ -1,8 +1,25 @@
 use axum::body;
-use http::{header, Request, Response, StatusCode};
-use worker::{console_log, Body, Error};
-use crate::challenge::{handle_solution_verification, issue_new_challenge};
-use crate::constant::{CHALLENGE_HEADER, DIFFICULTY_HEADER, NONCE_HEADER, TIMESTAMP_HEADER};
+use http::{header, Method, Request, Response, StatusCode};
+use std::future::Future;
+use worker::js_sys::Reflect;
+use worker::wasm_bindgen::JsValue;
+use worker::{console_log, Body, Error, Fetch, HttpResponse, RequestInit, RequestRedirect};
+use crate::bypass::{has_valid_bypass_cookie, has_valid_bypass_token};
+use crate::config::{is_exempt_path, Config};
+use crate::constant::{
+    BENCHMARK_PATH,
+    BOT_SCORE_HEADER,
+    BYPASS_COOKIE_NAME,
+    CHALLENGE_BATCH_PATH,
+    CHALLENGE_COOKIE_NAME,
+    CHALLENGE_HEADER,
+    DIFFICULTY_HEADER,
+    LEGACY_HEADER_ALIASES,
+    METRICS_PATH,
+    METRICS_SECRET_HEADER,
+    NONCE_HEADER,
+    TIMESTAMP_HEADER,
+};
 use crate::cors::add_cors_headers;
 
 // Simple placeholder for successful access
@@ -10,16 +27,251 @@ pub(crate) async fn protected_content() -> &'static str {
     "Access Granted: Checksum Approved."
 }
 
-/// Function to handle GET requests (challenge/verification)
-pub(crate) async fn handle_get_request(
-    req: &Request<Body>,
-    headers: &http::HeaderMap,
-    has_pow_headers: bool,
+/// Where a (non-asset) request is dispatched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Route {
+    /// Path is on the exemption list; pass it through to the origin.
+    Exempt,
+    /// Carries a valid bypass cookie; redirect without any PoW work.
+    BypassCookie,
+    /// Carries a valid bypass token; redirect without any PoW work.
+    BypassToken,
+    /// CORS preflight.
+    Preflight,
+    /// GET with proof-of-work headers; verify the solution.
+    SubmitSolution,
+    /// POST with a JSON body; verify a signed challenge and solution.
+    SubmitJson,
+    /// POST with an NDJSON body; verify a bundle of JSON submissions.
+    SubmitNdjson,
+    /// GET without proof-of-work headers; issue a new challenge.
+    IssueChallenge,
+    /// GET `/challenges`; issue a batch of signed challenges.
+    IssueChallengeBatch,
+    /// GET `/metrics`; export the core instrumentation counters, if enabled.
+    Metrics,
+    /// GET `/benchmark`; measure verification throughput, if enabled.
+    Benchmark,
+    /// Any other method.
+    UnsupportedMethod,
+}
+
+/// Decides how to handle a request without doing any of the work.
+///
+/// Exempt paths are passed through before anything else, since matching
+/// them is a few string comparisons. The remaining checks run cheapest
+/// and most common first:
+/// 1. The bypass cookie is a single `Cookie` lookup and signature check,
+///    and returning visitors, the bulk of traffic, carry it.
+/// 2. The bypass token needs a header scan, a plausibility check and the
+///    same signature check.
+/// 3. Everything else needs the challenge machinery: random generation
+///    and templating to issue, or a SHA-256 recompute to verify.
+///
+/// # Arguments
+/// * `method`:  The request method.
+/// * `path`:    The request path.
+/// * `headers`: The request headers.
+/// * `config`:  The worker configuration.
+///
+/// # Returns
+/// * `Route`: The handler to dispatch to.
+pub(crate) fn classify_request(method: &Method, path: &str, headers: &http::HeaderMap, config: &Config) -> Route {
+    if is_exempt_path(path, config) {
+        return Route::Exempt;
+    }
+
+    if has_valid_bypass_cookie(path, headers, config) {
+        return Route::BypassCookie;
+    }
+
+    if has_valid_bypass_token(path, headers, config) {
+        return Route::BypassToken;
+    }
+
+    match *method {
+        Method::GET if path == CHALLENGE_BATCH_PATH => Route::IssueChallengeBatch,
+        Method::GET if path == METRICS_PATH && config.metrics_enabled => Route::Metrics,
+        Method::GET if path == BENCHMARK_PATH => Route::Benchmark,
+        Method::GET if has_proof_of_work_headers(headers) => Route::SubmitSolution,
+        Method::GET => Route::IssueChallenge,
+        Method::POST if is_json_content_type(headers) => Route::SubmitJson,
+        Method::POST if is_ndjson_content_type(headers) => Route::SubmitNdjson,
+        Method::OPTIONS => Route::Preflight,
+        _ => Route::UnsupportedMethod,
+    }
+}
+
+/// Reads Cloudflare's bot score from an incoming request's
+/// `cf.botManagement.score`.
+///
+/// # Arguments
+/// * `request`: The raw incoming request, see `worker::Request::inner`.
+///
+/// # Returns
+/// * `Option<u64>`: The score, from 1 (surely a bot) to 99 (surely
+///   human), or `None` when the zone has no Bot Management.
+pub(crate) fn cf_bot_score(request: &JsValue) -> Option<u64> {
+    let cf: JsValue = Reflect::get(request, &JsValue::from("cf")).ok()?;
+    let bot_management: JsValue = Reflect::get(&cf, &JsValue::from("botManagement")).ok()?;
+    let score: f64 = Reflect::get(&bot_management, &JsValue::from("score")).ok()?.as_f64()?;
+    (1.0..=99.0).contains(&score).then_some(score as u64)
+}
+
+/// Replaces any `BOT_SCORE_HEADER` the client sent with the score
+/// Cloudflare assigned, so issuance can trust the header.
+pub(crate) fn set_bot_score_header(headers: &mut http::HeaderMap, bot_score: Option<u64>) {
+    headers.remove(BOT_SCORE_HEADER);
+    if let Some(bot_score) = bot_score {
+        headers.insert(BOT_SCORE_HEADER, http::HeaderValue::from(bot_score));
+    }
+}
+
+/// Function to forward a request to the origin unchanged and return its
+/// response, for paths exempt from proof-of-work. Only the
+/// `BOT_SCORE_HEADER` the worker added is dropped.
+///
+/// Redirects are not followed: the origin's redirect response is returned
+/// as-is, so the client follows it and IronShield sees the next request.
+pub(crate) async fn pass_through(req: Request<Body>) -> worker::Result<Response<body::Body>> {
+    let request: worker::Request = worker::Request::try_from(req)?;
+    let mut headers: worker::Headers = request.headers().clone();
+    headers.delete(BOT_SCORE_HEADER)?;
+    let mut init: RequestInit = RequestInit::new();
+    init.with_method(request.method())
+        .with_headers(headers)
+        .with_redirect(RequestRedirect::Manual)
+        .with_body(request.inner().body().map(JsValue::from));
+
+    let response: HttpResponse = Fetch::Request(worker::Request::new_with_init(request.url()?.as_str(), &init)?)
+        .send()
+        .await?
+        .try_into()?;
+    Ok(response.map(body::Body::new))
+}
+
+/// Function to send a request that needs no proof-of-work on to the
+/// origin: `ORIGIN_URL` when configured, else the route's own origin.
+pub(crate) async fn serve_from_origin(req: Request<Body>, config: &Config) -> worker::Result<Response<body::Body>> {
+    match &config.origin_url {
+        Some(origin) => forward_to_origin(req, origin, config).await,
+        None => pass_through(req).await,
+    }
+}
+
+/// Proxies a request to `origin` and streams back its response, making
+/// the worker a reverse-proxy gate in front of it.
+///
+/// # Arguments
+/// * `req`:    The verified or bypassed request.
+/// * `origin`: The configured `ORIGIN_URL`.
+/// * `config`: The worker configuration, naming the credentials to strip.
+///
+/// # Returns
+/// * `worker::Result<Response<body::Body>>`: The origin's response,
+///   redirects included, unchanged.
+pub(crate) async fn forward_to_origin(
+    req: Request<Body>,
+    origin: &http::Uri,
+    config: &Config,
 ) -> worker::Result<Response<body::Body>> {
-    if !has_pow_headers {
-        issue_new_challenge(headers).await
-    } else {
-        handle_solution_verification(req, headers).await
+    console_log!("Forwarding {} {} to origin", req.method(), req.uri().path());
+    forward_to_origin_with(req, origin, config, pass_through).await
+}
+
+/// `forward_to_origin` with the fetch injected, so tests can mock it.
+async fn forward_to_origin_with<F, Fut>(
+    req: Request<Body>,
+    origin: &http::Uri,
+    config: &Config,
+    fetch: F,
+) -> worker::Result<Response<body::Body>>
+where
+    F: FnOnce(Request<Body>) -> Fut,
+    Fut: Future<Output = worker::Result<Response<body::Body>>>,
+{
+    fetch(origin_request(req, origin, config).map_err(Error::RustError)?).await
+}
+
+/// Retargets a request at `origin`, keeping its method, path, query,
+/// body, and headers other than IronShield's own credentials.
+///
+/// The path is appended to any path `origin` has, and `Host` is set to
+/// the origin's so it serves the request as its own. Bypass tokens,
+/// cookies and proof-of-work headers are removed (see
+/// `strip_ironshield_credentials`), so the origin never sees or logs them.
+///
+/// # Returns
+/// * `Result<Request<Body>, String>`: The retargeted request, or an error
+///   if the combined URL is invalid.
+pub(crate) fn origin_request(req: Request<Body>, origin: &http::Uri, config: &Config) -> Result<Request<Body>, String> {
+    let (mut parts, body) = req.into_parts();
+    strip_ironshield_credentials(&mut parts.headers, config);
+    let path_and_query: &str = parts.uri.path_and_query().map_or("/", |p: &http::uri::PathAndQuery| p.as_str());
+    let authority: &http::uri::Authority = origin.authority().ok_or("Origin URL has no host")?;
+
+    parts.uri = http::Uri::builder()
+        .scheme(origin.scheme_str().unwrap_or("https"))
+        .authority(authority.as_str())
+        .path_and_query(format!("{}{}", origin.path().trim_end_matches('/'), path_and_query))
+        .build()
+        .map_err(|e: http::Error| format!("Invalid origin request URL: {}", e))?;
+    parts.headers.insert(
+        header::HOST,
+        http::HeaderValue::from_str(authority.as_str()).map_err(|e| format!("Invalid origin host: {}", e))?,
+    );
+    Ok(Request::from_parts(parts, body))
+}
+
+/// Removes the headers IronShield reads credentials from: every
+/// `X-IronShield-*` header (proof-of-work, bypass token, route secrets),
+/// the legacy proof-of-work aliases, `config.bypass_token_headers`,
+/// `Authorization: Bearer` when `config.bypass_allow_bearer` makes it a
+/// bypass token, and the bypass and challenge cookies. Other cookies are
+/// kept.
+fn strip_ironshield_credentials(headers: &mut http::HeaderMap, config: &Config) {
+    let credential_headers: Vec<http::HeaderName> = headers
+        .keys()
+        .filter(|name: &&http::HeaderName| {
+            name.as_str().starts_with("x-ironshield-")
+                || LEGACY_HEADER_ALIASES
+                    .iter()
+                    .any(|(_, aliases)| aliases.iter().any(|alias: &&str| name.as_str().eq_ignore_ascii_case(alias)))
+                || config.bypass_token_headers.iter().any(|header: &String| name.as_str().eq_ignore_ascii_case(header))
+        })
+        .cloned()
+        .collect();
+    for name in credential_headers {
+        headers.remove(name);
+    }
+
+    let bearer: bool = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v: &http::HeaderValue| v.to_str().ok())
+        .and_then(|authorization: &str| authorization.trim().split_once(' '))
+        .is_some_and(|(scheme, _)| scheme.eq_ignore_ascii_case("Bearer"));
+    if config.bypass_allow_bearer && bearer {
+        headers.remove(header::AUTHORIZATION);
+    }
+
+    let cookies: Vec<String> = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v: &http::HeaderValue| v.to_str().ok())
+        .flat_map(|cookies: &str| cookies.split(';'))
+        .map(str::trim)
+        .filter(|cookie: &&str| {
+            let name: &str = cookie.split_once('=').map_or(*cookie, |(name, _)| name);
+            !cookie.is_empty() && name != BYPASS_COOKIE_NAME && name != CHALLENGE_COOKIE_NAME
+        })
+        .map(str::to_string)
+        .collect();
+    headers.remove(header::COOKIE);
+    if let Some(value) = Some(cookies.join("; "))
+        .filter(|cookies: &String| !cookies.is_empty())
+        .and_then(|cookies: String| http::HeaderValue::from_str(&cookies).ok())
+    {
+        headers.insert(header::COOKIE, value);
     }
 }
 
@@ -38,6 +290,49 @@ pub(crate) fn handle_options_request(headers: &http::HeaderMap) -> worker::Resul
         })
 }
 
+/// Checks a secret header against a configured secret, comparing every
+/// byte so the time taken does not reveal how much of a guess matched.
+///
+/// # Arguments
+/// * `headers`: The request headers.
+/// * `name`:    The header carrying the secret.
+/// * `secret`:  The configured secret, if any.
+///
+/// # Returns
+/// * `bool`: Whether a secret is configured and the request presented it.
+pub(crate) fn presents_secret(headers: &http::HeaderMap, name: &str, secret: Option<&[u8]>) -> bool {
+    let (Some(secret), Some(presented)) = (secret, headers.get(name)) else {
+        return false;
+    };
+    let presented: &[u8] = presented.as_bytes();
+
+    presented.len() == secret.len()
+        && presented.iter().zip(secret).fold(0u8, |diff: u8, (a, b): (&u8, &u8)| diff | (a ^ b)) == 0
+}
+
+/// Function to export the `ironshield-core` counters in the Prometheus
+/// text format.
+///
+/// Counters live in isolate memory, so each scrape reports the isolate
+/// that served it since that isolate started. Requests without
+/// `config.metrics_secret` in `X-IronShield-Metrics-Secret` get `404`.
+pub(crate) fn handle_metrics_request(headers: &http::HeaderMap, config: &Config) -> worker::Result<Response<body::Body>> {
+    let (status, content_type, body) =
+        match presents_secret(headers, METRICS_SECRET_HEADER, config.metrics_secret.as_deref()) {
+            false => (StatusCode::NOT_FOUND, "text/plain", "Not found".to_string()),
+            true => (StatusCode::OK, "text/plain; version=0.0.4", ironshield_core::snapshot().to_prometheus()),
+        };
+
+    add_cors_headers(
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, content_type),
+        headers,
+    )
+        .body(body::Body::from(body))
+        .map_err(|e: http::Error| Error::RustError(format!("Failed to build metrics response: {}", e)))
+}
+
 /// Function to handle unsupported HTTP methods
 pub(crate) fn handle_unsupported_method(headers: &http::HeaderMap) -> worker::Result<Response<body::Body>> {
     add_cors_headers(
@@ -50,10 +345,325 @@ pub(crate) fn handle_unsupported_method(headers: &http::HeaderMap) -> worker::Re
         .map_err(|e: http::Error| Error::RustError(format!("Failed to build response: {}", e)))
 }
 
-/// Function to check if a request has Proof of Work headers
+/// Function to check if a request has Proof of Work headers.
+///
+/// The challenge may arrive in the challenge header or the challenge cookie.
 pub(crate) fn has_proof_of_work_headers(headers: &http::HeaderMap) -> bool {
-    headers.contains_key(CHALLENGE_HEADER)
-        && headers.contains_key(NONCE_HEADER)
-        && headers.contains_key(TIMESTAMP_HEADER)
-        && headers.contains_key(DIFFICULTY_HEADER)
+    (pow_header_present(headers, CHALLENGE_HEADER) || cookie_value(headers, CHALLENGE_COOKIE_NAME).is_some())
+        && pow_header_present(headers, NONCE_HEADER)
+        && pow_header_present(headers, TIMESTAMP_HEADER)
+}
+
+/// Returns whether any proof-of-work header appears more than once.
+///
+/// A legitimate client sends exactly one of each; a second copy (e.g.
+/// appended by a proxy) would otherwise be silently ignored by
+/// `HeaderMap::get`, masking tampering. Legacy aliases are checked too,
+/// but a canonical header alongside its alias is not a duplicate.
+pub(crate) fn has_duplicate_proof_of_work_headers(headers: &http::HeaderMap) -> bool {
+    [CHALLENGE_HEADER, NONCE_HEADER, TIMESTAMP_HEADER, DIFFICULTY_HEADER]
+        .iter()
+        .flat_map(|name: &&str| pow_header_names(name))
+        .any(|name: &str| headers.get_all(name).iter().count() > 1)
+}
+
+/// Lists the names a proof-of-work header is accepted under: the
+/// canonical name first, then its `LEGACY_HEADER_ALIASES`.
+fn pow_header_names(canonical: &'static str) -> impl Iterator<Item = &'static str> {
+    let aliases: &'static [&'static str] = LEGACY_HEADER_ALIASES
+        .iter()
+        .find(|(name, _)| *name == canonical)
+        .map_or(&[], |(_, aliases)| *aliases);
+    std::iter::once(canonical).chain(aliases.iter().copied())
+}
+
+/// Returns whether a proof-of-work header is present under any of its names.
+fn pow_header_present(headers: &http::HeaderMap, canonical: &'static str) -> bool {
+    pow_header_names(canonical).any(|name: &str| headers.contains_key(name))
+}
+
+/// Reads a proof-of-work header, accepting its legacy aliases.
+///
+/// # Arguments
+/// * `headers`:   The request headers.
+/// * `canonical`: The current header name, e.g. `NONCE_HEADER`.
+///
+/// # Returns
+/// * `Option<&str>`: The value under the first name present, preferring `canonical`.
+pub(crate) fn pow_header<'a>(headers: &'a http::HeaderMap, canonical: &'static str) -> Option<&'a str> {
+    pow_header_names(canonical)
+        .find_map(|name: &str| headers.get(name))
+        .and_then(|v: &http::HeaderValue| v.to_str().ok())
+}
+
+/// Returns whether the request body is declared as JSON.
+fn is_json_content_type(headers: &http::HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v: &http::HeaderValue| v.to_str().ok())
+        .map(|content_type: &str| content_type.trim_start().starts_with("application/json"))
+        .unwrap_or(false)
+}
+
+/// Returns whether the request body is newline-delimited JSON
+/// (`application/x-ndjson`).
+fn is_ndjson_content_type(headers: &http::HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v: &http::HeaderValue| v.to_str().ok())
+        .map(|content_type: &str| content_type.trim_start().starts_with("application/x-ndjson"))
+        .unwrap_or(false)
+}
+
+/// Looks up a cookie by name in the request's `Cookie` header.
+///
+/// # Arguments
+/// * `headers`: The request headers.
+/// * `name`:    The cookie name to find.
+///
+/// # Returns
+/// * `Option<&str>`: The value of the first cookie named `name`.
+pub(crate) fn cookie_value<'a>(headers: &'a http::HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v: &http::HeaderValue| v.to_str().ok())
+        .flat_map(|cookies: &str| cookies.split(';'))
+        .filter_map(|cookie: &str| cookie.trim().split_once('='))
+        .find(|(cookie_name, _)| *cookie_name == name)
+        .map(|(_, value)| value)
+}
+
+/// Decodes `%XX` escapes in a URL component.
+///
+/// # Returns
+/// * `Option<String>`: The decoded text, or `None` if an escape is
+///   malformed or the result is not UTF-8.
+pub(crate) fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(text.len());
+    let mut rest: &[u8] = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let escape: &[u8] = tail.get(..2).filter(|escape: &&[u8]| escape.iter().all(u8::is_ascii_hexdigit))?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(escape).ok()?, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// The resource a request unlocks: its percent-decoded path, or the raw
+/// path if it does not decode. Compared against the resources challenges
+/// and bypass tokens are bound to.
+pub(crate) fn request_resource(path: &str) -> String {
+    percent_decode(path).unwrap_or_else(|| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constant::BYPASS_TOKEN_HEADER;
+
+    fn cookie_headers(cookie: &str) -> http::HeaderMap {
+        let mut headers: http::HeaderMap = http::HeaderMap::new();
+        headers.insert(header::COOKIE, http::HeaderValue::from_str(cookie).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_bot_score_header_only_carries_the_cloudflare_score() {
+        let mut headers: http::HeaderMap = http::HeaderMap::new();
+        headers.insert(BOT_SCORE_HEADER, "99".parse().unwrap());
+        set_bot_score_header(&mut headers, None);
+        assert!(headers.get(BOT_SCORE_HEADER).is_none(), "client-sent scores are dropped");
+
+        headers.insert(BOT_SCORE_HEADER, "99".parse().unwrap());
+        set_bot_score_header(&mut headers, Some(3));
+        assert_eq!(headers.get_all(BOT_SCORE_HEADER).iter().collect::<Vec<_>>(), ["3"]);
+    }
+
+    #[test]
+    fn test_valid_cookie_never_reaches_challenge_code() {
+        let config: Config = Config::default();
+        let mut headers: http::HeaderMap = cookie_headers("ironshield_token=test_approved");
+
+        assert_eq!(classify_request(&Method::GET, "/", &headers, &config), Route::BypassCookie);
+
+        // Even a request that also looks like a PoW submission is bypassed.
+        for name in [CHALLENGE_HEADER, NONCE_HEADER, TIMESTAMP_HEADER] {
+            headers.insert(http::HeaderName::from_bytes(name.as_bytes()).unwrap(), http::HeaderValue::from_static("1"));
+        }
+        assert_eq!(classify_request(&Method::GET, "/", &headers, &config), Route::BypassCookie);
+    }
+
+    #[test]
+    fn test_classify_request() {
+        let config: Config = Config::default();
+        let empty: http::HeaderMap = http::HeaderMap::new();
+
+        assert_eq!(classify_request(&Method::GET, "/", &empty, &config), Route::IssueChallenge);
+        assert_eq!(classify_request(&Method::OPTIONS, "/", &empty, &config), Route::Preflight);
+        assert_eq!(classify_request(&Method::GET, "/benchmark", &empty, &config), Route::Benchmark);
+        assert_eq!(classify_request(&Method::DELETE, "/", &empty, &config), Route::UnsupportedMethod);
+        assert_eq!(classify_request(&Method::POST, "/", &empty, &config), Route::UnsupportedMethod);
+
+        let mut json: http::HeaderMap = http::HeaderMap::new();
+        json.insert(header::CONTENT_TYPE, http::HeaderValue::from_static("application/json; charset=utf-8"));
+        assert_eq!(classify_request(&Method::POST, "/", &json, &config), Route::SubmitJson);
+
+        let mut ndjson: http::HeaderMap = http::HeaderMap::new();
+        ndjson.insert(header::CONTENT_TYPE, http::HeaderValue::from_static("application/x-ndjson"));
+        assert_eq!(classify_request(&Method::POST, "/", &ndjson, &config), Route::SubmitNdjson);
+        assert_eq!(classify_request(&Method::GET, "/challenges", &empty, &config), Route::IssueChallengeBatch);
+        assert_eq!(classify_request(&Method::POST, "/challenges", &empty, &config), Route::UnsupportedMethod);
+        assert_eq!(classify_request(&Method::GET, "/metrics", &empty, &config), Route::IssueChallenge);
+        let metrics: Config = Config { metrics_enabled: true, ..Config::default() };
+        assert_eq!(classify_request(&Method::GET, "/metrics", &empty, &metrics), Route::Metrics);
+        assert_eq!(
+            classify_request(&Method::GET, "/", &cookie_headers("ironshield_token=forged"), &config),
+            Route::IssueChallenge
+        );
+
+        let mut token: http::HeaderMap = http::HeaderMap::new();
+        token.insert("x-ironshield-token", http::HeaderValue::from_static("test_approved"));
+        assert_eq!(classify_request(&Method::GET, "/", &token, &config), Route::BypassToken);
+    }
+
+    #[test]
+    fn test_metrics_route_requires_the_secret() {
+        let config: Config = Config {
+            metrics_enabled: true,
+            metrics_secret:  Some(b"letmein".to_vec()),
+            ..Config::default()
+        };
+        let mut wrong: http::HeaderMap = http::HeaderMap::new();
+        wrong.insert(METRICS_SECRET_HEADER, http::HeaderValue::from_static("letmeiN"));
+        let mut right: http::HeaderMap = http::HeaderMap::new();
+        right.insert(METRICS_SECRET_HEADER, http::HeaderValue::from_static("letmein"));
+        let unconfigured: Config = Config { metrics_secret: None, ..config.clone() };
+
+        assert_eq!(handle_metrics_request(&http::HeaderMap::new(), &config).unwrap().status(), StatusCode::NOT_FOUND);
+        assert_eq!(handle_metrics_request(&wrong, &config).unwrap().status(), StatusCode::NOT_FOUND);
+        assert_eq!(handle_metrics_request(&right, &unconfigured).unwrap().status(), StatusCode::NOT_FOUND);
+
+        let response: Response<body::Body> = handle_metrics_request(&right, &config).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain; version=0.0.4");
+    }
+
+    #[test]
+    fn test_exempt_paths_skip_challenges() {
+        let config: Config = Config { exempt_paths: vec!["/static/*".to_string(), "/healthz".to_string()], ..Config::default() };
+        let empty: http::HeaderMap = http::HeaderMap::new();
+
+        assert_eq!(classify_request(&Method::GET, "/static/app.js", &empty, &config), Route::Exempt);
+        assert_eq!(classify_request(&Method::POST, "/healthz", &empty, &config), Route::Exempt);
+        assert_eq!(classify_request(&Method::GET, "/", &empty, &config), Route::IssueChallenge);
+        assert_eq!(classify_request(&Method::GET, "/account", &empty, &config), Route::IssueChallenge);
+    }
+
+    #[test]
+    fn test_cookie_value() {
+        let mut headers: http::HeaderMap = http::HeaderMap::new();
+        headers.insert(header::COOKIE, http::HeaderValue::from_static("theme=dark; ironshield_challenge=abc123;other=1"));
+
+        assert_eq!(cookie_value(&headers, "ironshield_challenge"), Some("abc123"));
+        assert_eq!(cookie_value(&headers, "other"), Some("1"));
+        assert_eq!(cookie_value(&headers, "missing"), None);
+        assert_eq!(cookie_value(&http::HeaderMap::new(), "theme"), None);
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("/static/%2e%2E/admin").as_deref(), Some("/static/../admin"));
+        assert_eq!(percent_decode("/caf%C3%A9?x=1+2").as_deref(), Some("/café?x=1+2"));
+        assert_eq!(percent_decode("100%25").as_deref(), Some("100%"));
+        for malformed in ["%", "%2", "%zz", "%+1", "%ff"] {
+            assert!(percent_decode(malformed).is_none(), "{} should be rejected", malformed);
+        }
+    }
+
+    #[test]
+    fn test_verified_request_forwarded_to_origin() {
+        use std::task::{Context, Poll, Waker};
+
+        let origin: http::Uri = "https://origin.example.com/app/".parse().unwrap();
+        let req: Request<Body> = Request::builder()
+            .method(Method::POST)
+            .uri("https://shield.example.com/orders?id=7")
+            .header(header::HOST, "shield.example.com")
+            .header(header::COOKIE, "theme=dark; ironshield_token=test_approved; ironshield_challenge=abc")
+            .header(header::COOKIE, "session=42")
+            .header(header::AUTHORIZATION, "Bearer test_approved")
+            .header(BYPASS_TOKEN_HEADER, "test_approved")
+            .header("X-Bypass", "test_approved")
+            .header(CHALLENGE_HEADER, "abc")
+            .header(NONCE_HEADER, "1")
+            .header("X-Timestamp", "2")
+            .header("X-Request-Id", "7")
+            .body(Body::empty())
+            .unwrap();
+        let config: Config = Config {
+            bypass_token_headers: vec![BYPASS_TOKEN_HEADER.to_string(), "X-Bypass".to_string()],
+            bypass_allow_bearer: true,
+            ..Config::default()
+        };
+
+        // A mock origin that checks what it received and answers 201.
+        let mut seen: Option<(Method, String, http::HeaderMap)> = None;
+        let mock_fetch = |forwarded: Request<Body>| {
+            seen = Some((forwarded.method().clone(), forwarded.uri().to_string(), forwarded.headers().clone()));
+            std::future::ready(
+                Response::builder()
+                    .status(StatusCode::CREATED)
+                    .header("x-origin", "yes")
+                    .body(body::Body::from("created"))
+                    .map_err(|e: http::Error| Error::RustError(e.to_string())),
+            )
+        };
+        let response: Response<body::Body> = {
+            let mut forwarding = std::pin::pin!(forward_to_origin_with(req, &origin, &config, mock_fetch));
+            let Poll::Ready(response) = forwarding.as_mut().poll(&mut Context::from_waker(Waker::noop())) else {
+                panic!("the mock origin answers immediately");
+            };
+            response.unwrap()
+        };
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["x-origin"], "yes");
+
+        let (method, uri, headers) = seen.unwrap();
+        assert_eq!(method, Method::POST);
+        assert_eq!(uri, "https://origin.example.com/app/orders?id=7");
+        assert_eq!(headers[header::HOST], "origin.example.com");
+
+        // IronShield's credentials stay at the edge; everything else reaches the origin.
+        assert_eq!(headers[header::COOKIE], "theme=dark; session=42");
+        assert_eq!(headers["x-request-id"], "7");
+        for stripped in [header::AUTHORIZATION.as_str(), BYPASS_TOKEN_HEADER, "X-Bypass", CHALLENGE_HEADER, NONCE_HEADER, "X-Timestamp"] {
+            assert!(!headers.contains_key(stripped), "{} reached the origin", stripped);
+        }
+    }
+
+    #[test]
+    fn test_origin_keeps_authorization_unless_it_is_a_bypass_token() {
+        let origin: http::Uri = "https://origin.example.com".parse().unwrap();
+        let request = || {
+            Request::builder()
+                .uri("https://shield.example.com/")
+                .header(header::AUTHORIZATION, "Bearer origin-session")
+                .header(header::COOKIE, "ironshield_token=test_approved")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let forwarded: Request<Body> = origin_request(request(), &origin, &Config::default()).unwrap();
+        assert_eq!(forwarded.headers()[header::AUTHORIZATION], "Bearer origin-session");
+        assert!(!forwarded.headers().contains_key(header::COOKIE));
+
+        let bearer: Config = Config { bypass_allow_bearer: true, ..Config::default() };
+        assert!(!origin_request(request(), &origin, &bearer).unwrap().headers().contains_key(header::AUTHORIZATION));
+    }
 }
\ No newline at end of file
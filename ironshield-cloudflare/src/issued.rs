@@ -0,0 +1,123 @@
+//! Difficulties of recently issued protocol v1 challenges.
+//!
+//! Escalation (see `crate::throttle`) and relief (see `crate::relief`)
+//! change the difficulty issued to a client over time, but a v1 challenge
+//! is a bare string that does not carry its difficulty. Each one issued
+//! while either is enabled is recorded here with its difficulty, so a
+//! submission is held to the difficulty it was issued at rather than the
+//! client's current one, and challenges in flight stay valid. Relief also
+//! only counts solve-failure reports naming a challenge recorded here.
+//!
+//! Like `crate::throttle`, the challenges are tracked per client IP in
+//! isolate memory, so each isolate records independently and forgets them
+//! when evicted.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Most challenges remembered per client; older ones are forgotten first.
+pub(crate) const MAX_ISSUED_PER_CLIENT: usize = 8;
+/// Most clients tracked at once, bounding isolate memory.
+const MAX_TRACKED_CLIENTS:              usize = 10_000;
+
+thread_local! {
+    /// Recently issued challenges of this isolate.
+    static ISSUED: RefCell<IssuedChallenges> = RefCell::new(IssuedChallenges::default());
+}
+
+/// Runs `f` against this isolate's issued challenges.
+pub(crate) fn with_issued<R>(f: impl FnOnce(&mut IssuedChallenges) -> R) -> R {
+    ISSUED.with(|issued: &RefCell<IssuedChallenges>| f(&mut issued.borrow_mut()))
+}
+
+/// A challenge issued to a client.
+///
+/// * `challenge`:     The challenge string.
+/// * `difficulty`:    The leading-zero difficulty it was issued at.
+/// * `issued_millis`: When it was issued, in Unix milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct IssuedChallenge {
+    pub challenge:     String,
+    pub difficulty:    usize,
+    pub issued_millis: i64,
+}
+
+/// Recently issued challenges per client, oldest first.
+#[derive(Debug, Default)]
+pub(crate) struct IssuedChallenges {
+    clients: HashMap<String, Vec<IssuedChallenge>>,
+}
+
+impl IssuedChallenges {
+    /// Records a challenge issued to `client`, forgetting the client's
+    /// oldest one beyond `MAX_ISSUED_PER_CLIENT`.
+    pub(crate) fn record(&mut self, client: &str, challenge: &str, difficulty: usize, now_millis: i64) {
+        if self.clients.len() >= MAX_TRACKED_CLIENTS && !self.clients.contains_key(client) {
+            let oldest: Option<String> = self.clients
+                .iter()
+                .min_by_key(|(_, issued)| issued.last().map_or(i64::MIN, |newest| newest.issued_millis))
+                .map(|(client, _)| client.clone());
+            if let Some(oldest) = oldest {
+                self.clients.remove(&oldest);
+            }
+        }
+
+        let issued: &mut Vec<IssuedChallenge> = self.clients.entry(client.to_string()).or_default();
+        issued.retain(|issued: &IssuedChallenge| issued.challenge != challenge);
+        if issued.len() >= MAX_ISSUED_PER_CLIENT {
+            issued.remove(0);
+        }
+        issued.push(IssuedChallenge {
+            challenge: challenge.to_string(),
+            difficulty,
+            issued_millis: now_millis,
+        });
+    }
+
+    /// Looks up a challenge issued to `client`.
+    ///
+    /// # Arguments
+    /// * `client`:    The client IP.
+    /// * `challenge`: The challenge string the client presented.
+    ///
+    /// # Returns
+    /// * `Option<&IssuedChallenge>`: The recorded challenge, or `None` if
+    ///   it was not issued to `client` by this isolate or was forgotten.
+    pub(crate) fn get(&self, client: &str, challenge: &str) -> Option<&IssuedChallenge> {
+        self.clients
+            .get(client)?
+            .iter()
+            .find(|issued: &&IssuedChallenge| issued.challenge == challenge)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: &str = "203.0.113.5";
+
+    #[test]
+    fn test_recorded_difficulty_is_per_client_and_challenge() {
+        let mut issued: IssuedChallenges = IssuedChallenges::default();
+        issued.record(CLIENT, "aaaa", 2, 100);
+        issued.record(CLIENT, "bbbb", 3, 200);
+
+        assert_eq!(issued.get(CLIENT, "aaaa").map(|issued| (issued.difficulty, issued.issued_millis)), Some((2, 100)));
+        assert_eq!(issued.get(CLIENT, "bbbb").map(|issued| issued.difficulty), Some(3));
+        assert!(issued.get(CLIENT, "cccc").is_none());
+        assert!(issued.get("198.51.100.7", "aaaa").is_none(), "other clients did not receive it");
+    }
+
+    #[test]
+    fn test_oldest_challenges_are_forgotten() {
+        let mut issued: IssuedChallenges = IssuedChallenges::default();
+        for index in 0..=MAX_ISSUED_PER_CLIENT {
+            issued.record(CLIENT, &index.to_string(), 2, index as i64);
+        }
+
+        assert!(issued.get(CLIENT, "0").is_none());
+        assert!(issued.get(CLIENT, "1").is_some());
+        assert!(issued.get(CLIENT, &MAX_ISSUED_PER_CLIENT.to_string()).is_some());
+    }
+}
@@ -1,53 +1,105 @@
+mod activation;
+mod benchmark;
 mod bypass;
 mod challenge;
+mod config;
 mod cors;
 mod difficulty;
 mod http_handler;
+mod issued;
+mod ndjson;
+mod nonce;
+mod outstanding;
+mod protocol;
+mod relief;
+mod source;
+mod throttle;
 mod asset;
 mod constant;
 
 use axum::{
     body::{self},
-    http::{Method as AxumMethod, Request, Response},
+    http::{Request, Response},
 };
 use worker::*;
 
+use activation::challenge_required;
 use asset::handle_asset_request;
-use bypass::{check_bypass_cookie, check_bypass_token};
+use benchmark::handle_benchmark_request;
+use bypass::{create_redirect_response, return_to_target, success_redirect_target};
+use challenge::{handle_challenge_batch, handle_json_submission, handle_solution_verification, issue_new_challenge};
+use config::Config;
 use cors::add_cors_headers;
+use ndjson::handle_ndjson_submission;
 use http_handler::{
-    handle_get_request, handle_options_request, handle_unsupported_method,
-    has_proof_of_work_headers,
+    classify_request,
+    handle_metrics_request,
+    handle_options_request,
+    handle_unsupported_method,
+    cf_bot_score,
+    forward_to_origin,
+    serve_from_origin,
+    set_bot_score_header,
+    Route,
 };
 
 /// Main Worker entry point
+///
+/// See `classify_request` for why authenticated requests are checked
+/// before any challenge logic.
+///
+/// The request is taken raw so Cloudflare's bot score can be read from
+/// `request.cf`, which the `http` conversion does not expose, and handed
+/// to issuance in `BOT_SCORE_HEADER`.
 #[event(fetch)]
-pub async fn main(req: Request<Body>, _env: Env, _ctx: Context) -> Result<Response<body::Body>> {
+pub async fn main(raw: worker::Request, env: Env, _ctx: Context) -> Result<Response<body::Body>> {
     // Optionally, set a panic hook for better error messages in the browser console.
     utils::set_panic_hook();
 
-    if let Some(asset_response) = handle_asset_request(req.uri().path()).await {
-        return asset_response;
-    }
-
-    let headers = req.headers();
+    let bot_score: Option<u64> = cf_bot_score(raw.inner());
+    let mut req: Request<Body> = Request::try_from(raw)?;
+    set_bot_score_header(req.headers_mut(), bot_score);
 
-    if let Some(response) = check_bypass_token(&headers) {
-        return response;
-    }
+    let config: Config = Config::from_env(&env);
 
-    if let Some(response) = check_bypass_cookie(&headers) {
-        return response;
+    if let Some(asset_response) = handle_asset_request(req.uri().path(), req.headers(), &config).await {
+        return asset_response;
     }
+    let headers = req.headers();
+    let redirect_url: String = success_redirect_target(&req.uri().to_string(), &config);
+    // Already-bypassed requests are not sent back to their own URL, which
+    // would loop.
+    let bypass_redirect_url: String = return_to_target(&req.uri().to_string(), &config)
+        .unwrap_or_else(|| config.success_redirect_url.clone());
 
-    // Check for Proof of Work headers
-    let has_pow_headers = has_proof_of_work_headers(&headers);
-
-    // Route based on HTTP method
-    match *req.method() {
-        AxumMethod::GET => handle_get_request(&req, &headers, has_pow_headers).await,
-        AxumMethod::OPTIONS => handle_options_request(&headers),
-        _ => handle_unsupported_method(&headers),
+    match classify_request(req.method(), req.uri().path(), headers, &config) {
+        Route::Exempt => serve_from_origin(req, &config).await,
+        Route::BypassCookie => {
+            console_log!("Bypass cookie found and valid, skipping PoW verification");
+            match &config.origin_url {
+                Some(origin) => forward_to_origin(req, origin, &config).await,
+                None => create_redirect_response(headers, &bypass_redirect_url),
+            }
+        }
+        Route::BypassToken => {
+            console_log!("Bypass token found and valid, skipping PoW verification");
+            match &config.origin_url {
+                Some(origin) => forward_to_origin(req, origin, &config).await,
+                None => create_redirect_response(headers, &bypass_redirect_url),
+            }
+        }
+        Route::SubmitSolution => handle_solution_verification(&req, headers, &redirect_url, &config).await,
+        Route::SubmitJson => handle_json_submission(req, &redirect_url, &config).await,
+        Route::SubmitNdjson => handle_ndjson_submission(req, &redirect_url, &config).await,
+        Route::IssueChallenge => match challenge_required(headers, &config, chrono::Utc::now().timestamp_millis()) {
+            true => issue_new_challenge(req.uri().query(), headers, &config).await,
+            false => serve_from_origin(req, &config).await,
+        },
+        Route::IssueChallengeBatch => handle_challenge_batch(req.uri().query(), headers, &config),
+        Route::Metrics => handle_metrics_request(headers, &config),
+        Route::Benchmark => handle_benchmark_request(req.uri().query(), headers, &config, || chrono::Utc::now().timestamp_millis()),
+        Route::Preflight => handle_options_request(headers),
+        Route::UnsupportedMethod => handle_unsupported_method(headers),
     }
 }
 
@@ -0,0 +1,388 @@
+//! Bundled submissions for multi-resource unlocks.
+//!
+//! A client may POST several JSON submissions at once as newline-delimited
+//! JSON (`application/x-ndjson`), one `{ "challenge": ..., "solution": ... }`
+//! per line. Lines are verified as they arrive, so memory stays bounded by
+//! one line, and with `NdjsonVerifyMode::FailFast` the rest of the body is
+//! never read once an entry fails.
+
+use std::future::poll_fn;
+use std::pin::Pin;
+
+use axum::body::{self, HttpBody};
+use chrono::{DateTime, Utc};
+use http::{Request, Response};
+use worker::{console_log, Body};
+
+use crate::bypass::issue_bypass_token;
+use crate::challenge::{
+    bypass_cookie_max_age,
+    record_client_result,
+    verification_response,
+    verify_json_submission,
+    JsonSubmission,
+    VerificationOutcome,
+    MAX_JSON_SUBMISSION_BYTES,
+};
+use crate::config::{Config, NdjsonVerifyMode};
+use crate::constant::CLIENT_IP_HEADER;
+use crate::http_handler::request_resource;
+
+/// Most entries one bundle may hold. A bundle with more is rejected as
+/// `Malformed` as soon as the first extra entry starts.
+pub(crate) const MAX_NDJSON_ENTRIES: usize = 64;
+
+/// Incremental verifier for an NDJSON submission body.
+///
+/// Feed it body chunks with `feed` while `wants_more` holds, then call
+/// `finish`. Blank lines are skipped, and only the first
+/// `MAX_JSON_SUBMISSION_BYTES` of a line are buffered.
+pub(crate) struct NdjsonVerifier<'a> {
+    resource:     &'a str,
+    client_ip:    Option<&'a str>,
+    config:       &'a Config,
+    now_millis:   i64,
+    pending:      Vec<u8>,
+    overlong:     bool,
+    outcomes:      Vec<VerificationOutcome>,
+    remaining_ms:  Option<i64>,
+    resource_hash: Option<[u8; 32]>,
+}
+
+impl<'a> NdjsonVerifier<'a> {
+    /// Creates a verifier for one request.
+    ///
+    /// # Arguments
+    /// * `resource`:   The resource being unlocked, i.e. the request path.
+    /// * `client_ip`:  The requesting client's IP, if known.
+    /// * `config`:     The worker configuration.
+    /// * `now_millis`: The verification time in Unix milliseconds.
+    pub(crate) fn new(resource: &'a str, client_ip: Option<&'a str>, config: &'a Config, now_millis: i64) -> Self {
+        Self {
+            resource,
+            client_ip,
+            config,
+            now_millis,
+            pending:      Vec::new(),
+            overlong:     false,
+            outcomes:      Vec::new(),
+            remaining_ms:  None,
+            resource_hash: None,
+        }
+    }
+
+    /// Returns whether more of the body should be read: `false` once an
+    /// entry failed in fail-fast mode or the bundle exceeded
+    /// `MAX_NDJSON_ENTRIES`.
+    pub(crate) fn wants_more(&self) -> bool {
+        let failed: bool = self.config.ndjson_verify_mode == NdjsonVerifyMode::FailFast
+            && self.outcomes.iter().any(|outcome: &VerificationOutcome| *outcome != VerificationOutcome::Valid);
+        !failed && self.outcomes.len() <= MAX_NDJSON_ENTRIES
+    }
+
+    /// Verifies every complete line in `chunk`, buffering a trailing
+    /// partial line until the next chunk.
+    pub(crate) fn feed(&mut self, chunk: &[u8]) {
+        for byte in chunk {
+            if !self.wants_more() {
+                return;
+            }
+            match byte {
+                // Past the limit, only trailing whitespace is allowed.
+                _ if self.outcomes.len() == MAX_NDJSON_ENTRIES => {
+                    if !byte.is_ascii_whitespace() {
+                        self.outcomes.push(VerificationOutcome::Malformed);
+                    }
+                }
+                b'\n' => self.verify_pending(),
+                _ if self.pending.len() < MAX_JSON_SUBMISSION_BYTES => self.pending.push(*byte),
+                // An overlong line is dropped and rejected once it ends.
+                _ => self.overlong = true,
+            }
+        }
+    }
+
+    /// Verifies a final line without a trailing newline.
+    ///
+    /// # Returns
+    /// * `NdjsonReport`: The outcome of every verified entry.
+    pub(crate) fn finish(mut self) -> NdjsonReport {
+        if self.wants_more() {
+            self.verify_pending();
+        }
+        NdjsonReport { outcomes: self.outcomes, remaining_ms: self.remaining_ms, resource_hash: self.resource_hash }
+    }
+
+    /// Records that the body could not be read to the end.
+    pub(crate) fn abort(&mut self) {
+        self.pending.clear();
+        self.overlong = false;
+        self.outcomes.push(VerificationOutcome::Malformed);
+    }
+
+    fn verify_pending(&mut self) {
+        let line: Vec<u8> = std::mem::take(&mut self.pending);
+        let overlong: bool = std::mem::take(&mut self.overlong);
+        let outcome: VerificationOutcome = match std::str::from_utf8(&line) {
+            _ if overlong => VerificationOutcome::Malformed,
+            Ok(text) if text.trim().is_empty() => return,
+            Ok(text) => {
+                let (outcome, remaining_ms, late_ms) =
+                    verify_json_submission(text, self.resource, self.client_ip, self.config, self.now_millis);
+                if let Some(latency_ms) = late_ms {
+                    console_log!("Implausibly late solve: submitted {}ms after issuance", latency_ms);
+                }
+                self.remaining_ms = match (self.remaining_ms, remaining_ms) {
+                    (Some(shortest), Some(remaining)) => Some(shortest.min(remaining)),
+                    (shortest, remaining) => shortest.or(remaining),
+                };
+                if outcome == VerificationOutcome::Valid {
+                    let bound: Option<[u8; 32]> = serde_json::from_str::<JsonSubmission>(text)
+                        .ok()
+                        .and_then(|submission: JsonSubmission| submission.challenge.resource_hash);
+                    self.resource_hash = self.resource_hash.or(bound);
+                }
+                outcome
+            }
+            _ => VerificationOutcome::Malformed,
+        };
+        self.outcomes.push(outcome);
+    }
+}
+
+/// Result of verifying an NDJSON submission.
+///
+/// * `outcomes`:     Outcome of each entry in order, up to the first
+///   failure in fail-fast mode.
+/// * `remaining_ms`:  Shortest remaining validity among the challenges.
+/// * `resource_hash`: The resource the valid entries are bound to, if any
+///   is. Every bound entry was verified against the request path, so they
+///   all share it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NdjsonReport {
+    pub outcomes:      Vec<VerificationOutcome>,
+    pub remaining_ms:  Option<i64>,
+    pub resource_hash: Option<[u8; 32]>,
+}
+
+impl NdjsonReport {
+    /// Returns whether the bundle contained entries and all were valid.
+    pub(crate) fn is_valid(&self) -> bool {
+        !self.outcomes.is_empty()
+            && self.outcomes.iter().all(|outcome: &VerificationOutcome| *outcome == VerificationOutcome::Valid)
+    }
+
+    /// Returns the outcome the bundle is rejected with: its first failed
+    /// entry, or `Malformed` if it had no entries.
+    ///
+    /// # Returns
+    /// * `Option<VerificationOutcome>`: The failure, or `None` if the
+    ///   bundle is valid.
+    pub(crate) fn failure(&self) -> Option<VerificationOutcome> {
+        match self.outcomes.iter().find(|outcome: &&VerificationOutcome| **outcome != VerificationOutcome::Valid) {
+            Some(failed) => Some(failed.clone()),
+            None if self.outcomes.is_empty() => Some(VerificationOutcome::Malformed),
+            None => None,
+        }
+    }
+}
+
+/// Verifies an NDJSON bundle of JSON submissions while streaming the body,
+/// granting the bypass cookie only if every entry is valid.
+pub(crate) async fn handle_ndjson_submission(
+    req: Request<Body>,
+    redirect_url: &str,
+    config: &Config,
+) -> worker::Result<Response<body::Body>> {
+    let headers: http::HeaderMap = req.headers().clone();
+    let client_ip: Option<&str> = headers.get(CLIENT_IP_HEADER).and_then(|v| v.to_str().ok());
+    let resource: String = request_resource(req.uri().path());
+    let started: DateTime<Utc> = Utc::now();
+
+    let mut verifier: NdjsonVerifier = NdjsonVerifier::new(&resource, client_ip, config, started.timestamp_millis());
+    let mut stream: body::Body = body::Body::new(req.into_body());
+    while verifier.wants_more() {
+        match poll_fn(|cx| Pin::new(&mut stream).poll_frame(cx)).await {
+            Some(Ok(frame)) => {
+                if let Ok(data) = frame.into_data() {
+                    verifier.feed(&data);
+                }
+            }
+            Some(Err(_)) => {
+                verifier.abort();
+                break;
+            }
+            None => break,
+        }
+    }
+    let report: NdjsonReport = verifier.finish();
+
+    let verify_us: i64 = (Utc::now() - started).num_microseconds().unwrap_or(i64::MAX);
+    console_log!("NDJSON submission verification: {:?} in {}us", report.outcomes, verify_us);
+    record_client_result(&headers, config, report.is_valid(), started.timestamp_millis());
+
+    let max_age: i64 = bypass_cookie_max_age(config.bypass_cookie_lifetime, report.remaining_ms);
+    let bypass_token: Result<String, VerificationOutcome> = match report.failure() {
+        None => Ok(issue_bypass_token([0u8; 64], report.resource_hash, max_age, Utc::now().timestamp_millis(), config)),
+        Some(failed) => Err(failed),
+    };
+    verification_response(bypass_token.as_deref(), verify_us, max_age, redirect_url, &headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::challenge::mint_challenge_batch;
+    use ironshield_types::IronShieldChallenge;
+
+    fn signing_config(mode: NdjsonVerifyMode) -> Config {
+        Config { ndjson_verify_mode: mode, ..crate::config::signing_config() }
+    }
+
+    /// Builds an NDJSON body of solved submissions, with the solution of
+    /// entry `invalid` (if any) replaced by one that fails.
+    fn bundle(config: &Config, now: i64, count: usize, invalid: Option<usize>) -> String {
+        mint_challenge_batch(count, "example.com", None, None, None, config, now)
+            .unwrap()
+            .into_iter()
+            .enumerate()
+            .map(|(index, challenge): (usize, IronShieldChallenge)| {
+                let solution: i64 = match Some(index) == invalid {
+                    true => (0..)
+                        .find(|nonce: &i64| !ironshield_core::verify_ironshield_solution(&challenge, *nonce))
+                        .unwrap(),
+                    false => ironshield_core::find_solution_single_threaded(&challenge).unwrap().solution,
+                };
+                format!("{}\n", serde_json::json!({ "challenge": challenge, "solution": solution }))
+            })
+            .collect()
+    }
+
+    /// Feeds `body` in small chunks that split lines, like a network stream.
+    fn verify_streamed(body: &str, config: &Config, now: i64) -> (NdjsonReport, usize) {
+        let mut verifier: NdjsonVerifier = NdjsonVerifier::new("/", None, config, now);
+        let mut read: usize = 0;
+        for chunk in body.as_bytes().chunks(97) {
+            if !verifier.wants_more() {
+                break;
+            }
+            verifier.feed(chunk);
+            read += chunk.len();
+        }
+        (verifier.finish(), read)
+    }
+
+    #[test]
+    fn test_all_valid_stream() {
+        let now: i64 = Utc::now().timestamp_millis();
+        for mode in [NdjsonVerifyMode::FailFast, NdjsonVerifyMode::VerifyAll] {
+            let config: Config = signing_config(mode);
+            let body: String = bundle(&config, now, 3, None);
+            let (report, read) = verify_streamed(&body, &config, now);
+
+            assert_eq!(report.outcomes, vec![VerificationOutcome::Valid; 3]);
+            assert!(report.is_valid());
+            assert_eq!(report.failure(), None);
+            assert!(report.remaining_ms.is_some());
+            assert_eq!(report.resource_hash, None);
+            assert_eq!(read, body.len());
+        }
+    }
+
+    #[test]
+    fn test_bound_entries_bind_the_report() {
+        let now: i64 = Utc::now().timestamp_millis();
+        let config: Config = signing_config(NdjsonVerifyMode::FailFast);
+        let bound: IronShieldChallenge =
+            mint_challenge_batch(1, "example.com", Some("/"), None, None, &config, now).unwrap().remove(0);
+        let solution: i64 = ironshield_core::find_solution_single_threaded(&bound).unwrap().solution;
+        let body: String = format!(
+            "{}{}\n",
+            bundle(&config, now, 2, None),
+            serde_json::json!({ "challenge": bound, "solution": solution })
+        );
+
+        let (report, _) = verify_streamed(&body, &config, now);
+        assert!(report.is_valid());
+        assert_eq!(report.resource_hash, Some(IronShieldChallenge::resource_hash_for("/")));
+    }
+
+    #[test]
+    fn test_invalid_entry_fail_fast() {
+        let now: i64 = Utc::now().timestamp_millis();
+        let config: Config = signing_config(NdjsonVerifyMode::FailFast);
+        let body: String = bundle(&config, now, 5, Some(1));
+        let (report, read) = verify_streamed(&body, &config, now);
+
+        assert_eq!(report.outcomes.len(), 2);
+        assert_eq!(report.outcomes[0], VerificationOutcome::Valid);
+        assert_ne!(report.outcomes[1], VerificationOutcome::Valid);
+        assert!(!report.is_valid());
+        assert_eq!(report.failure(), Some(report.outcomes[1].clone()));
+        assert!(read < body.len(), "the rest of the body is never read");
+    }
+
+    #[test]
+    fn test_invalid_entry_verify_all() {
+        let now: i64 = Utc::now().timestamp_millis();
+        let config: Config = signing_config(NdjsonVerifyMode::VerifyAll);
+        let body: String = bundle(&config, now, 5, Some(1));
+        let (report, read) = verify_streamed(&body, &config, now);
+
+        assert_eq!(report.outcomes.len(), 5);
+        assert_ne!(report.outcomes[1], VerificationOutcome::Valid);
+        assert!(report.outcomes.iter().enumerate().all(|(index, outcome)| (index == 1) != (*outcome == VerificationOutcome::Valid)));
+        assert!(!report.is_valid());
+        assert_eq!(read, body.len());
+    }
+
+    #[test]
+    fn test_trailing_line_blank_lines_and_empty_bundles() {
+        let now: i64 = Utc::now().timestamp_millis();
+        let config: Config = signing_config(NdjsonVerifyMode::FailFast);
+        let body: String = format!("\n{}", bundle(&config, now, 2, None).trim_end());
+        assert_eq!(verify_streamed(&body, &config, now).0.outcomes, vec![VerificationOutcome::Valid; 2]);
+
+        assert!(!verify_streamed("\n\n", &config, now).0.is_valid());
+        assert_eq!(verify_streamed("\n\n", &config, now).0.failure(), Some(VerificationOutcome::Malformed));
+        assert_eq!(verify_streamed("not json", &config, now).0.outcomes, vec![VerificationOutcome::Malformed]);
+
+        let overlong: String = "x".repeat(MAX_JSON_SUBMISSION_BYTES + 1);
+        assert_eq!(verify_streamed(&overlong, &config, now).0.outcomes, vec![VerificationOutcome::Malformed]);
+    }
+
+    #[test]
+    fn test_overlong_line_is_not_buffered() {
+        let now: i64 = Utc::now().timestamp_millis();
+        let config: Config = signing_config(NdjsonVerifyMode::VerifyAll);
+        let mut verifier: NdjsonVerifier = NdjsonVerifier::new("/", None, &config, now);
+
+        for _ in 0..16 {
+            verifier.feed(&vec![b'x'; MAX_JSON_SUBMISSION_BYTES]);
+        }
+        assert_eq!(verifier.pending.len(), MAX_JSON_SUBMISSION_BYTES);
+
+        // The overlong line is rejected, and the next one starts afresh.
+        verifier.feed(format!("\n{}", bundle(&config, now, 1, None)).as_bytes());
+        assert_eq!(verifier.finish().outcomes, vec![VerificationOutcome::Malformed, VerificationOutcome::Valid]);
+    }
+
+    #[test]
+    fn test_bundles_over_the_entry_limit_are_rejected() {
+        let now: i64 = Utc::now().timestamp_millis();
+        let config: Config = signing_config(NdjsonVerifyMode::FailFast);
+        let line: String = bundle(&config, now, 1, None);
+
+        let full: String = line.repeat(MAX_NDJSON_ENTRIES) + "\n \n";
+        let (report, read) = verify_streamed(&full, &config, now);
+        assert!(report.is_valid());
+        assert_eq!(read, full.len());
+
+        let over: String = line.repeat(MAX_NDJSON_ENTRIES + 1);
+        let (report, read) = verify_streamed(&over, &config, now);
+        assert!(!report.is_valid());
+        assert_eq!(report.outcomes.len(), MAX_NDJSON_ENTRIES + 1);
+        assert_eq!(report.failure(), Some(VerificationOutcome::Malformed));
+        assert!(read < over.len(), "the extra entry is not read");
+    }
+}
@@ -0,0 +1,120 @@
+//! Stateless challenge nonces.
+//!
+//! Instead of storing issued challenges to enforce single use, the worker
+//! can derive each challenge's `random_nonce` as an HMAC of a server
+//! secret, the issuance time bucket, and the client IP. On submission it
+//! recomputes the HMAC and only accepts nonces it would have issued to
+//! that client, without any per-challenge storage. Every challenge issued
+//! to one client within a bucket shares a nonce, so this is
+//! pseudo-single-use rather than strictly single-use.
+
+use hmac::{Hmac, Mac};
+use ironshield_types::IronShieldChallenge;
+use sha2::Sha256;
+
+/// Width of the time buckets nonces are derived for, in milliseconds.
+pub(crate) const NONCE_BUCKET_MS: i64 = 5 * 60 * 1000;
+
+/// Returns the time bucket containing `millis`.
+pub(crate) fn nonce_bucket(millis: i64) -> i64 {
+    millis.div_euclid(NONCE_BUCKET_MS)
+}
+
+/// Builds the HMAC-SHA256 over a bucket and client IP.
+fn nonce_mac(secret: &[u8], bucket: i64, ip: &str) -> Hmac<Sha256> {
+    let mut mac: Hmac<Sha256> = Hmac::<Sha256>::new_from_slice(secret)
+        .expect("HMAC accepts keys of any length");
+    mac.update(&bucket.to_be_bytes());
+    mac.update(ip.as_bytes());
+    mac
+}
+
+/// Derives the `random_nonce` issued to `ip` during `bucket`.
+///
+/// # Arguments
+/// * `secret`: The server's nonce secret.
+/// * `bucket`: The issuance time bucket, see `nonce_bucket`.
+/// * `ip`:     The client IP the challenge is issued to.
+///
+/// # Returns
+/// * `String`: The hex-encoded HMAC-SHA256, usable as `random_nonce`.
+pub(crate) fn derive_nonce(secret: &[u8], bucket: i64, ip: &str) -> String {
+    hex::encode(nonce_mac(secret, bucket, ip).finalize().into_bytes())
+}
+
+/// Checks that a challenge's `random_nonce` is the one this worker would
+/// have derived for `ip` in the bucket of the challenge's `created_time`.
+///
+/// The comparison is constant-time. `created_time` is only trustworthy
+/// once the challenge signature has been verified.
+///
+/// # Arguments
+/// * `secret`:    The server's nonce secret.
+/// * `challenge`: The presented challenge.
+/// * `ip`:        The IP of the client presenting it.
+///
+/// # Returns
+/// * `bool`: Whether the nonce matches.
+pub(crate) fn verify_derived_nonce(secret: &[u8], challenge: &IronShieldChallenge, ip: &str) -> bool {
+    match hex::decode(&challenge.random_nonce) {
+        Ok(nonce) => nonce_mac(secret, nonce_bucket(challenge.created_time), ip)
+            .verify_slice(&nonce)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"nonce-test-secret";
+
+    fn challenge_with_nonce(random_nonce: String, created_time: i64) -> IronShieldChallenge {
+        IronShieldChallenge::new(
+            random_nonce,
+            created_time,
+            "example.com".to_string(),
+            [0xFF; 32],
+            [0x00; 32],
+            [0x00; 64],
+        )
+    }
+
+    #[test]
+    fn test_nonce_bucket() {
+        assert_eq!(nonce_bucket(0), 0);
+        assert_eq!(nonce_bucket(NONCE_BUCKET_MS - 1), 0);
+        assert_eq!(nonce_bucket(NONCE_BUCKET_MS), 1);
+        assert_eq!(nonce_bucket(-1), -1);
+    }
+
+    #[test]
+    fn test_derive_nonce_is_deterministic() {
+        assert_eq!(derive_nonce(SECRET, 7, "203.0.113.5"), derive_nonce(SECRET, 7, "203.0.113.5"));
+        assert_eq!(derive_nonce(SECRET, 7, "203.0.113.5").len(), 64);
+        assert_ne!(derive_nonce(SECRET, 7, "203.0.113.5"), derive_nonce(b"other", 7, "203.0.113.5"));
+    }
+
+    #[test]
+    fn test_verify_derived_nonce() {
+        let created: i64 = 3 * NONCE_BUCKET_MS + 1_000;
+        let bucket: i64 = nonce_bucket(created);
+        let ip: &str = "203.0.113.5";
+
+        let issued: IronShieldChallenge = challenge_with_nonce(derive_nonce(SECRET, bucket, ip), created);
+        assert!(verify_derived_nonce(SECRET, &issued, ip));
+
+        // Presented by another client.
+        assert!(!verify_derived_nonce(SECRET, &issued, "198.51.100.7"));
+
+        // Derived for another bucket than the challenge claims.
+        let wrong_bucket: IronShieldChallenge = challenge_with_nonce(derive_nonce(SECRET, bucket - 1, ip), created);
+        assert!(!verify_derived_nonce(SECRET, &wrong_bucket, ip));
+
+        // Random or malformed nonces, and other secrets.
+        assert!(!verify_derived_nonce(SECRET, &challenge_with_nonce("cafe1234".to_string(), created), ip));
+        assert!(!verify_derived_nonce(SECRET, &challenge_with_nonce("not hex".to_string(), created), ip));
+        assert!(!verify_derived_nonce(b"other", &issued, ip));
+    }
+}
@@ -0,0 +1,192 @@
+//! Caps on the signed challenges one client holds at once.
+//!
+//! Batch minting lets a client collect many challenges and grind them in
+//! parallel. With `MAX_OUTSTANDING_CHALLENGES` set, every challenge issued
+//! to a client IP is tracked until it is consumed by a valid submission or
+//! expires, and no more are issued while the client holds the maximum.
+//! This bounds the parallelism batching gains an attacker. Signed
+//! challenges are tracked by their signature, and protocol v1 challenges
+//! by the challenge string itself.
+//!
+//! Like `crate::throttle`, the challenges are tracked in isolate memory,
+//! so each isolate caps independently and forgets them when evicted. Once
+//! `MAX_TRACKED_CLIENTS` clients hold unexpired challenges, new clients
+//! are refused until some expire, rather than evicting a client and
+//! handing it a fresh allowance.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use ironshield_types::IronShieldChallenge;
+
+/// Most clients tracked at once, bounding isolate memory.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+thread_local! {
+    /// Outstanding challenges of this isolate.
+    static OUTSTANDING: RefCell<OutstandingChallenges> = RefCell::new(OutstandingChallenges::default());
+}
+
+/// Runs `f` against this isolate's outstanding challenges.
+pub(crate) fn with_outstanding<R>(f: impl FnOnce(&mut OutstandingChallenges) -> R) -> R {
+    OUTSTANDING.with(|outstanding: &RefCell<OutstandingChallenges>| f(&mut outstanding.borrow_mut()))
+}
+
+/// Unconsumed challenges per client, keyed by challenge signature (or v1
+/// challenge string) and mapped to their expiration time in Unix
+/// milliseconds.
+#[derive(Debug, Default)]
+pub(crate) struct OutstandingChallenges {
+    clients: HashMap<String, HashMap<Vec<u8>, i64>>,
+}
+
+impl OutstandingChallenges {
+    /// Forgets `client`'s expired challenges and counts the rest.
+    ///
+    /// # Arguments
+    /// * `client`:     The client IP.
+    /// * `now_millis`: The current time in Unix milliseconds.
+    ///
+    /// # Returns
+    /// * `usize`: The client's unexpired, unconsumed challenges.
+    pub(crate) fn held(&mut self, client: &str, now_millis: i64) -> usize {
+        let Some(challenges) = self.clients.get_mut(client) else {
+            return 0;
+        };
+        challenges.retain(|_, expires: &mut i64| *expires >= now_millis);
+        let held: usize = challenges.len();
+        if held == 0 {
+            self.clients.remove(client);
+        }
+        held
+    }
+
+    /// Computes how many more challenges `client` may be issued.
+    ///
+    /// # Arguments
+    /// * `client`:     The client IP.
+    /// * `limit`:      The most challenges a client may hold at once.
+    /// * `now_millis`: The current time in Unix milliseconds.
+    ///
+    /// # Returns
+    /// * `usize`: The free slots, `0` once the client holds `limit`, or
+    ///   while `MAX_TRACKED_CLIENTS` other clients are tracked.
+    pub(crate) fn available(&mut self, client: &str, limit: u64, now_millis: i64) -> usize {
+        if !self.has_room_for(client, now_millis) {
+            return 0;
+        }
+        let limit: usize = usize::try_from(limit).unwrap_or(usize::MAX);
+        limit.saturating_sub(self.held(client, now_millis))
+    }
+
+    /// Returns whether `client` is tracked or there is room to track it,
+    /// first forgetting every client whose challenges all expired.
+    fn has_room_for(&mut self, client: &str, now_millis: i64) -> bool {
+        if self.clients.len() >= MAX_TRACKED_CLIENTS && !self.clients.contains_key(client) {
+            self.clients.retain(|_, held: &mut HashMap<Vec<u8>, i64>| {
+                held.retain(|_, expires: &mut i64| *expires >= now_millis);
+                !held.is_empty()
+            });
+        }
+        self.clients.len() < MAX_TRACKED_CLIENTS || self.clients.contains_key(client)
+    }
+
+    /// Tracks signed challenges issued to `client` until they are consumed
+    /// or expire.
+    pub(crate) fn record_issued(&mut self, client: &str, challenges: &[IronShieldChallenge], now_millis: i64) {
+        for challenge in challenges {
+            self.record(client, &challenge.challenge_signature, challenge.expiration_time, now_millis);
+        }
+    }
+
+    /// Tracks one challenge issued to `client` until it is consumed or
+    /// expires. Nothing is tracked for a new client while there is no room
+    /// for it, see `available`.
+    ///
+    /// # Arguments
+    /// * `client`:          The client IP.
+    /// * `challenge`:       The challenge signature, or v1 challenge string.
+    /// * `expiration_time`: When the challenge expires, in Unix milliseconds.
+    /// * `now_millis`:      The current time in Unix milliseconds.
+    pub(crate) fn record(&mut self, client: &str, challenge: &[u8], expiration_time: i64, now_millis: i64) {
+        if self.has_room_for(client, now_millis) {
+            self.clients
+                .entry(client.to_string())
+                .or_default()
+                .insert(challenge.to_vec(), expiration_time);
+        }
+    }
+
+    /// Frees the slot of a challenge `client` solved, given its signature
+    /// or v1 challenge string.
+    pub(crate) fn record_consumed(&mut self, client: &str, challenge: &[u8]) {
+        if let Some(held) = self.clients.get_mut(client) {
+            held.remove(challenge);
+            if held.is_empty() {
+                self.clients.remove(client);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: &str = "203.0.113.5";
+
+    fn challenge(signature_byte: u8, expiration_time: i64) -> IronShieldChallenge {
+        let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "cafe1234".to_string(),
+            0,
+            "example.com".to_string(),
+            [0xFF; 32],
+            [0x00; 32],
+            [signature_byte; 64],
+        );
+        challenge.expiration_time = expiration_time;
+        challenge
+    }
+
+    #[test]
+    fn test_slots_freed_by_consumption_and_expiry() {
+        let mut outstanding: OutstandingChallenges = OutstandingChallenges::default();
+        assert_eq!(outstanding.available(CLIENT, 3, 0), 3);
+
+        outstanding.record_issued(CLIENT, &[challenge(1, 1_000), challenge(2, 2_000), challenge(3, 2_000)], 0);
+        assert_eq!(outstanding.available(CLIENT, 3, 0), 0);
+        assert_eq!(outstanding.available("198.51.100.7", 3, 0), 3, "other clients are unaffected");
+
+        outstanding.record_consumed(CLIENT, &[2; 64]);
+        assert_eq!(outstanding.available(CLIENT, 3, 0), 1);
+
+        // Consuming an untracked challenge frees nothing.
+        outstanding.record_consumed(CLIENT, &[9; 64]);
+        assert_eq!(outstanding.held(CLIENT, 0), 2);
+
+        assert_eq!(outstanding.available(CLIENT, 3, 1_000), 1, "valid through its expiration time");
+        assert_eq!(outstanding.available(CLIENT, 3, 1_001), 2);
+        assert_eq!(outstanding.available(CLIENT, 3, 2_001), 3);
+        assert!(outstanding.clients.is_empty());
+    }
+
+    #[test]
+    fn test_new_clients_refused_while_full() {
+        let mut outstanding: OutstandingChallenges = OutstandingChallenges::default();
+        for client in 0..MAX_TRACKED_CLIENTS {
+            outstanding.record(&client.to_string(), b"challenge", 1_000, 0);
+        }
+
+        // Tracked clients keep their allowance; no one is evicted for a newcomer.
+        assert_eq!(outstanding.available("0", 3, 0), 2);
+        assert_eq!(outstanding.available(CLIENT, 3, 0), 0);
+        outstanding.record(CLIENT, b"challenge", 1_000, 0);
+        assert_eq!(outstanding.held(CLIENT, 0), 0);
+        assert_eq!(outstanding.clients.len(), MAX_TRACKED_CLIENTS);
+
+        // Once challenges expire, there is room again.
+        assert_eq!(outstanding.available(CLIENT, 3, 1_001), 3);
+        outstanding.record(CLIENT, b"challenge", 2_000, 1_001);
+        assert_eq!(outstanding.held(CLIENT, 1_001), 1);
+    }
+}
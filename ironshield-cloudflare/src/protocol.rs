@@ -0,0 +1,124 @@
+//! Wire protocol versions and their negotiation.
+//!
+//! Clients list the versions they speak in the `X-IronShield-Protocol`
+//! request header, e.g. `1, 2`. The worker issues JSON challenges in the
+//! highest version both sides support, and clients that send no header
+//! get v1. The chosen version is echoed in the response header and in the
+//! challenge body's `protocol` field.
+
+use crate::config::Config;
+use crate::constant::PROTOCOL_HEADER;
+use crate::throttle::Escalation;
+
+/// A version of the challenge wire protocol.
+///
+/// * `V1`: A leading-zero challenge string (`ChallengePayload`), solved
+///   through the `X-IronShield-*` headers.
+/// * `V2`: A signed `IronShieldChallenge`, solved through a JSON
+///   submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ProtocolVersion {
+    V1,
+    V2,
+}
+
+impl ProtocolVersion {
+    /// Parses a version number as advertised in `X-IronShield-Protocol`.
+    ///
+    /// # Returns
+    /// * `Option<Self>`: The version, or `None` if this worker doesn't
+    ///   know it.
+    pub(crate) fn from_number(number: &str) -> Option<Self> {
+        match number.trim().trim_start_matches(['v', 'V']) {
+            "1" => Some(Self::V1),
+            "2" => Some(Self::V2),
+            _   => None,
+        }
+    }
+
+    /// Returns the version number sent on the wire.
+    pub(crate) fn number(self) -> u8 {
+        match self {
+            Self::V1 => 1,
+            Self::V2 => 2,
+        }
+    }
+}
+
+/// Chooses the protocol version for a challenge issued as JSON.
+///
+/// v2 challenges are signed, so they are only issued when the worker has
+/// signing keys. Throttled clients are held to v1, whose difficulty
+/// escalates (see `crate::throttle`).
+///
+/// # Arguments
+/// * `headers`:    The request headers.
+/// * `escalation`: The client's escalation, if tracked.
+/// * `config`:     The worker configuration.
+///
+/// # Returns
+/// * `ProtocolVersion`: The highest version the client advertises that
+///   the worker can issue, or `V1` if there is none.
+pub(crate) fn negotiate_protocol(
+    headers: &http::HeaderMap,
+    escalation: Option<&Escalation>,
+    config: &Config,
+) -> ProtocolVersion {
+    let can_sign: bool = config.private_key.is_some() && config.public_key.is_some();
+    let throttled: bool = escalation.is_some_and(Escalation::is_throttled);
+
+    headers
+        .get_all(PROTOCOL_HEADER)
+        .iter()
+        .filter_map(|value: &http::HeaderValue| value.to_str().ok())
+        .flat_map(|value: &str| value.split(','))
+        .filter_map(ProtocolVersion::from_number)
+        .filter(|version: &ProtocolVersion| *version == ProtocolVersion::V1 || (can_sign && !throttled))
+        .max()
+        .unwrap_or(ProtocolVersion::V1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn advertising(versions: &str) -> http::HeaderMap {
+        let mut headers: http::HeaderMap = http::HeaderMap::new();
+        headers.insert(PROTOCOL_HEADER, http::HeaderValue::from_str(versions).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_negotiates_highest_shared_version() {
+        let signing: Config = Config { private_key: Some([1; 32]), public_key: Some([2; 32]), ..Config::default() };
+
+        assert_eq!(negotiate_protocol(&http::HeaderMap::new(), None, &signing), ProtocolVersion::V1);
+        assert_eq!(negotiate_protocol(&advertising("1"), None, &signing), ProtocolVersion::V1);
+        assert_eq!(negotiate_protocol(&advertising("1, 2"), None, &signing), ProtocolVersion::V2);
+        assert_eq!(negotiate_protocol(&advertising("v2"), None, &signing), ProtocolVersion::V2);
+
+        // Unknown versions are ignored.
+        assert_eq!(negotiate_protocol(&advertising("3, 2"), None, &signing), ProtocolVersion::V2);
+        assert_eq!(negotiate_protocol(&advertising("3"), None, &signing), ProtocolVersion::V1);
+
+        // Without signing keys v2 cannot be issued.
+        assert_eq!(negotiate_protocol(&advertising("1, 2"), None, &Config::default()), ProtocolVersion::V1);
+    }
+
+    #[test]
+    fn test_throttled_clients_stay_on_v1() {
+        let signing: Config = Config { private_key: Some([1; 32]), public_key: Some([2; 32]), ..Config::default() };
+        let throttled: Escalation = Escalation {
+            failures:            3,
+            level:               1,
+            base_difficulty:     2,
+            difficulty:          3,
+            retry_after_seconds: 60,
+            relief:              0,
+        };
+
+        assert_eq!(negotiate_protocol(&advertising("2"), Some(&throttled), &signing), ProtocolVersion::V1);
+        let calm: Escalation = Escalation { level: 0, difficulty: 2, ..throttled };
+        assert_eq!(negotiate_protocol(&advertising("2"), Some(&calm), &signing), ProtocolVersion::V2);
+    }
+}
@@ -0,0 +1,177 @@
+//! Difficulty relief for clients that report failing to solve.
+//!
+//! A client that exhausts its attempt budget without finding a solution
+//! sends `X-IronShield-Solve-Failed`, holding the challenge it gave up on,
+//! with its next challenge request. A counted report removes a leading
+//! zero from the difficulty issued to that client, down to
+//! `MIN_POW_DIFFICULTY`, so slow devices are not locked out. Reports are
+//! otherwise taken on trust, so only one naming a challenge issued to the
+//! client (see `crate::issued`) at least `MIN_REPORT_INTERVAL_MS` earlier
+//! counts, reports closer together than that count once, relief is capped
+//! at `MAX_RELIEF_LEVELS`, and throttled clients (see `crate::throttle`)
+//! get none. Like failures, reports are counted per client IP in isolate
+//! memory.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// How long relief lasts after the client's most recent counted report,
+/// in milliseconds.
+pub(crate) const RELIEF_WINDOW_MS:       i64 = 10 * 60 * 1000;
+/// Most leading zeros relief may remove.
+pub(crate) const MAX_RELIEF_LEVELS:      usize = 2;
+/// Least time between issuing a challenge and a report on it, and between
+/// two reports that both count, roughly the least time an honest client
+/// spends on a challenge before giving up.
+pub(crate) const MIN_REPORT_INTERVAL_MS: i64 = 5_000;
+/// Most clients tracked at once, bounding isolate memory.
+const MAX_TRACKED_CLIENTS:               usize = 10_000;
+
+thread_local! {
+    /// Solve-failure reports of this isolate.
+    static RELIEF: RefCell<ReliefTracker> = RefCell::new(ReliefTracker::default());
+}
+
+/// Runs `f` against this isolate's relief tracker.
+pub(crate) fn with_relief<R>(f: impl FnOnce(&mut ReliefTracker) -> R) -> R {
+    RELIEF.with(|tracker: &RefCell<ReliefTracker>| f(&mut tracker.borrow_mut()))
+}
+
+/// A client's counted reports, forgotten `RELIEF_WINDOW_MS` after the
+/// last one.
+#[derive(Debug, Clone, Copy)]
+struct ReliefRecord {
+    reports:            usize,
+    last_report_millis: i64,
+}
+
+impl ReliefRecord {
+    fn is_active(&self, now_millis: i64) -> bool {
+        now_millis.saturating_sub(self.last_report_millis) < RELIEF_WINDOW_MS
+    }
+}
+
+/// Recent solve-failure reports per client.
+#[derive(Debug, Default)]
+pub(crate) struct ReliefTracker {
+    records: HashMap<String, ReliefRecord>,
+}
+
+impl ReliefTracker {
+    /// Counts a solve-failure report from `client`, unless the challenge
+    /// it names was not issued to the client, was issued within
+    /// `MIN_REPORT_INTERVAL_MS`, or the report follows the previous
+    /// counted one within that interval.
+    ///
+    /// # Arguments
+    /// * `client`:        The client IP.
+    /// * `issued_millis`: When the reported challenge was issued to
+    ///   `client`, or `None` if it was not (see `IssuedChallenges::get`).
+    /// * `now_millis`:    The current time, in Unix milliseconds.
+    ///
+    /// # Returns
+    /// * `usize`: The client's relief level after this report.
+    pub(crate) fn record_report(&mut self, client: &str, issued_millis: Option<i64>, now_millis: i64) -> usize {
+        let worked_on: bool = issued_millis
+            .is_some_and(|issued_millis: i64| now_millis.saturating_sub(issued_millis) >= MIN_REPORT_INTERVAL_MS);
+        if !worked_on {
+            return self.level(client, now_millis);
+        }
+
+        if self.records.len() >= MAX_TRACKED_CLIENTS && !self.records.contains_key(client) {
+            self.records.retain(|_, record: &mut ReliefRecord| record.is_active(now_millis));
+            if self.records.len() >= MAX_TRACKED_CLIENTS {
+                let oldest: Option<String> = self.records
+                    .iter()
+                    .min_by_key(|(_, record)| record.last_report_millis)
+                    .map(|(client, _)| client.clone());
+                if let Some(oldest) = oldest {
+                    self.records.remove(&oldest);
+                }
+            }
+        }
+
+        let record: &mut ReliefRecord = self.records
+            .entry(client.to_string())
+            .or_insert(ReliefRecord { reports: 0, last_report_millis: i64::MIN });
+        if !record.is_active(now_millis) {
+            record.reports = 0;
+        }
+        if record.reports == 0 || now_millis.saturating_sub(record.last_report_millis) >= MIN_REPORT_INTERVAL_MS {
+            record.reports = record.reports.saturating_add(1);
+            record.last_report_millis = now_millis;
+        }
+        record.reports.min(MAX_RELIEF_LEVELS)
+    }
+
+    /// Forgets `client`'s reports after it passes verification.
+    pub(crate) fn record_success(&mut self, client: &str) {
+        self.records.remove(client);
+    }
+
+    /// Returns how many leading zeros to remove from challenges issued to
+    /// `client`, at most `MAX_RELIEF_LEVELS`.
+    pub(crate) fn level(&self, client: &str, now_millis: i64) -> usize {
+        self.records
+            .get(client)
+            .filter(|record: &&ReliefRecord| record.is_active(now_millis))
+            .map_or(0, |record: &ReliefRecord| record.reports.min(MAX_RELIEF_LEVELS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: &str = "203.0.113.5";
+
+    #[test]
+    fn test_reports_raise_relief_up_to_the_cap() {
+        let mut tracker: ReliefTracker = ReliefTracker::default();
+        assert_eq!(tracker.level(CLIENT, 0), 0);
+
+        for report in 1..=MAX_RELIEF_LEVELS + 2 {
+            let now: i64 = report as i64 * MIN_REPORT_INTERVAL_MS;
+            let issued: Option<i64> = Some(now - MIN_REPORT_INTERVAL_MS);
+            assert_eq!(tracker.record_report(CLIENT, issued, now), report.min(MAX_RELIEF_LEVELS));
+            assert_eq!(tracker.level(CLIENT, now), report.min(MAX_RELIEF_LEVELS));
+        }
+        assert_eq!(tracker.level("198.51.100.7", 0), 0);
+    }
+
+    #[test]
+    fn test_only_reports_on_challenges_worked_on_count() {
+        let mut tracker: ReliefTracker = ReliefTracker::default();
+        let now: i64 = 10 * MIN_REPORT_INTERVAL_MS;
+
+        assert_eq!(tracker.record_report(CLIENT, None, now), 0, "the challenge was never issued to the client");
+        assert_eq!(tracker.record_report(CLIENT, Some(now - MIN_REPORT_INTERVAL_MS + 1), now), 0, "issued too recently");
+        assert_eq!(tracker.record_report(CLIENT, Some(now), now), 0);
+        assert_eq!(tracker.record_report(CLIENT, Some(now - MIN_REPORT_INTERVAL_MS), now), 1);
+    }
+
+    #[test]
+    fn test_rapid_reports_count_once() {
+        let mut tracker: ReliefTracker = ReliefTracker::default();
+        let issued: Option<i64> = Some(-MIN_REPORT_INTERVAL_MS);
+        for offset in 0..10 {
+            tracker.record_report(CLIENT, issued, offset);
+        }
+        assert_eq!(tracker.level(CLIENT, 10), 1);
+        assert_eq!(tracker.records[CLIENT].reports, 1);
+        tracker.record_report(CLIENT, issued, MIN_REPORT_INTERVAL_MS);
+        assert_eq!(tracker.records[CLIENT].reports, 2);
+    }
+
+    #[test]
+    fn test_relief_resets_on_success_and_expiry() {
+        let mut tracker: ReliefTracker = ReliefTracker::default();
+        tracker.record_report(CLIENT, Some(-MIN_REPORT_INTERVAL_MS), 0);
+        assert_eq!(tracker.level(CLIENT, RELIEF_WINDOW_MS - 1), 1);
+        assert_eq!(tracker.level(CLIENT, RELIEF_WINDOW_MS), 0);
+        assert_eq!(tracker.record_report(CLIENT, Some(0), RELIEF_WINDOW_MS), 1);
+
+        tracker.record_success(CLIENT);
+        assert_eq!(tracker.level(CLIENT, RELIEF_WINDOW_MS), 0);
+    }
+}
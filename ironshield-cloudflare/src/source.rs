@@ -0,0 +1,147 @@
+//! Randomness behind challenge nonces.
+//!
+//! Issued and minted challenges draw their nonces from a
+//! `ChallengeSource`. In production that is the OS RNG. For simulation
+//! and benchmarking, `TEST_MODE` with `CHALLENGE_SEED` set switches to a
+//! ChaCha20 stream seeded from it, so a run reproduces the exact sequence
+//! of challenges the way the `_at` functions pin the clock.
+//!
+//! Like `crate::throttle`, the stream lives in isolate memory: each
+//! isolate starts the sequence over, and so does changing the seed.
+
+use std::cell::RefCell;
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::config::Config;
+
+thread_local! {
+    /// Source of this isolate's challenge nonces.
+    static SOURCE: RefCell<ChallengeSource> = const { RefCell::new(ChallengeSource::Os) };
+}
+
+/// Where challenge nonces come from.
+///
+/// * `Os`:     The operating system RNG, unpredictable to clients.
+/// * `Seeded`: A ChaCha20 stream that repeats for the same `seed`.
+pub(crate) enum ChallengeSource {
+    Os,
+    Seeded { seed: u64, rng: Box<ChaCha20Rng> },
+}
+
+impl ChallengeSource {
+    /// Creates a source that yields the same nonces for the same seed.
+    pub(crate) fn seeded(seed: u64) -> Self {
+        Self::Seeded { seed, rng: Box::new(ChaCha20Rng::seed_from_u64(seed)) }
+    }
+
+    /// Creates the source `config` selects: seeded only in test mode, so
+    /// production challenges can never be predicted.
+    pub(crate) fn for_config(config: &Config) -> Self {
+        match configured_seed(config) {
+            Some(seed) => Self::seeded(seed),
+            None => Self::Os,
+        }
+    }
+
+    /// Returns the seed of a seeded source.
+    pub(crate) fn seed(&self) -> Option<u64> {
+        match self {
+            Self::Os => None,
+            Self::Seeded { seed, .. } => Some(*seed),
+        }
+    }
+
+    /// Draws the next nonce.
+    ///
+    /// # Arguments
+    /// * `len`: Number of random bytes.
+    ///
+    /// # Returns
+    /// * `String`: The bytes, hex-encoded.
+    pub(crate) fn next_nonce(&mut self, len: usize) -> String {
+        let mut bytes: Vec<u8> = vec![0u8; len];
+        match self {
+            Self::Os => rand::thread_rng().fill_bytes(&mut bytes),
+            Self::Seeded { rng, .. } => rng.fill_bytes(&mut bytes),
+        }
+        hex::encode(bytes)
+    }
+}
+
+/// The seed `config` asks for, honored only in test mode.
+fn configured_seed(config: &Config) -> Option<u64> {
+    config.challenge_seed.filter(|_| config.test_mode)
+}
+
+/// Draws the next challenge nonce from this isolate's source, first
+/// switching to the source `config` selects if it changed.
+///
+/// # Arguments
+/// * `config`: The worker configuration.
+/// * `len`:    Number of random bytes.
+///
+/// # Returns
+/// * `String`: The nonce, hex-encoded.
+pub(crate) fn next_challenge_nonce(config: &Config, len: usize) -> String {
+    SOURCE.with(|source| {
+        let mut source = source.borrow_mut();
+        if source.seed() != configured_seed(config) {
+            *source = ChallengeSource::for_config(config);
+        }
+        source.next_nonce(len)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::challenge::mint_challenge_batch;
+    use ironshield_types::IronShieldChallenge;
+
+    fn seeded_config(seed: u64) -> Config {
+        Config { test_mode: true, challenge_seed: Some(seed), ..crate::config::signing_config() }
+    }
+
+    /// Issues a v1 challenge and a minted batch in a fresh isolate, i.e.
+    /// a fresh thread with its own `SOURCE`.
+    fn simulate_run(config: Config) -> (Vec<String>, Vec<String>) {
+        std::thread::spawn(move || {
+            let issued: Vec<String> = (0..3).map(|_| next_challenge_nonce(&config, 16)).collect();
+            let minted: Vec<String> = mint_challenge_batch(4, "example.com", None, None, None, &config, 0)
+                .unwrap()
+                .into_iter()
+                .map(|challenge: IronShieldChallenge| challenge.random_nonce)
+                .collect();
+            (issued, minted)
+        })
+        .join()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_same_seed_reproduces_challenge_sequence() {
+        let first: (Vec<String>, Vec<String>) = simulate_run(seeded_config(42));
+        assert_eq!(first, simulate_run(seeded_config(42)));
+        assert_ne!(first, simulate_run(seeded_config(43)));
+        assert_eq!(first.0[0].len(), 32);
+        assert_eq!(first.1[0].len(), 64);
+
+        // Outside test mode the seed is ignored.
+        let production: Config = Config { test_mode: false, ..seeded_config(42) };
+        assert_ne!(simulate_run(production.clone()), simulate_run(production));
+    }
+
+    #[test]
+    fn test_seeded_source_matches_stream() {
+        let mut source: ChallengeSource = ChallengeSource::seeded(7);
+        let mut rng: ChaCha20Rng = ChaCha20Rng::seed_from_u64(7);
+        let mut expected: [u8; 16] = [0u8; 16];
+        rng.fill_bytes(&mut expected);
+
+        assert_eq!(source.next_nonce(16), hex::encode(expected));
+        assert_eq!(ChallengeSource::for_config(&Config::default()).seed(), None);
+        assert_eq!(ChallengeSource::for_config(&seeded_config(7)).seed(), Some(7));
+    }
+}
@@ -0,0 +1,218 @@
+//! Difficulty escalation for clients that keep failing verification.
+//!
+//! Failures are counted per client IP in isolate memory, so each isolate
+//! throttles independently and counts reset when it is evicted. Every
+//! `FAILURES_PER_ESCALATION` recent failures add one leading zero to the
+//! difficulty issued to that client, and the challenge is returned with
+//! `429 Too Many Requests` so well-behaved clients can back off.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::config::{MAX_POW_DIFFICULTY, MIN_POW_DIFFICULTY};
+
+/// How long a failure counts towards escalation after the client's most
+/// recent failure, in milliseconds.
+pub(crate) const FAILURE_WINDOW_MS:       i64 = 10 * 60 * 1000;
+/// Recent failures per extra leading zero of difficulty.
+pub(crate) const FAILURES_PER_ESCALATION: u32 = 3;
+/// Most clients tracked at once, bounding isolate memory.
+const MAX_TRACKED_CLIENTS:                usize = 10_000;
+
+thread_local! {
+    /// Failure counts of this isolate.
+    static FAILURES: RefCell<FailureTracker> = RefCell::new(FailureTracker::default());
+}
+
+/// Runs `f` against this isolate's failure tracker.
+pub(crate) fn with_failures<R>(f: impl FnOnce(&mut FailureTracker) -> R) -> R {
+    FAILURES.with(|tracker: &RefCell<FailureTracker>| f(&mut tracker.borrow_mut()))
+}
+
+/// A client's recent failures, forgotten `FAILURE_WINDOW_MS` after the
+/// last one.
+#[derive(Debug, Clone, Copy)]
+struct FailureRecord {
+    count:               u32,
+    last_failure_millis: i64,
+}
+
+impl FailureRecord {
+    fn is_active(&self, now_millis: i64) -> bool {
+        now_millis.saturating_sub(self.last_failure_millis) < FAILURE_WINDOW_MS
+    }
+}
+
+/// Recent verification failures per client.
+#[derive(Debug, Default)]
+pub(crate) struct FailureTracker {
+    records: HashMap<String, FailureRecord>,
+}
+
+impl FailureTracker {
+    /// Counts a failed verification by `client`.
+    ///
+    /// # Returns
+    /// * `u32`: The client's recent failures, including this one.
+    pub(crate) fn record_failure(&mut self, client: &str, now_millis: i64) -> u32 {
+        if self.records.len() >= MAX_TRACKED_CLIENTS && !self.records.contains_key(client) {
+            self.records.retain(|_, record: &mut FailureRecord| record.is_active(now_millis));
+            if self.records.len() >= MAX_TRACKED_CLIENTS {
+                let oldest: Option<String> = self.records
+                    .iter()
+                    .min_by_key(|(_, record)| record.last_failure_millis)
+                    .map(|(client, _)| client.clone());
+                if let Some(oldest) = oldest {
+                    self.records.remove(&oldest);
+                }
+            }
+        }
+
+        let record: &mut FailureRecord = self.records
+            .entry(client.to_string())
+            .or_insert(FailureRecord { count: 0, last_failure_millis: now_millis });
+        if !record.is_active(now_millis) {
+            record.count = 0;
+        }
+        record.count = record.count.saturating_add(1);
+        record.last_failure_millis = now_millis;
+        record.count
+    }
+
+    /// Forgets `client`'s failures after it passes verification.
+    pub(crate) fn record_success(&mut self, client: &str) {
+        self.records.remove(client);
+    }
+
+    /// Computes the escalation applied to challenges issued to `client`.
+    ///
+    /// # Arguments
+    /// * `client`:          The client IP.
+    /// * `base_difficulty`: The configured leading-zero difficulty.
+    /// * `now_millis`:      The current time in Unix milliseconds.
+    ///
+    /// # Returns
+    /// * `Escalation`: The client's recent failures and the resulting
+    ///   difficulty, clamped to `MAX_POW_DIFFICULTY`.
+    pub(crate) fn escalation(&self, client: &str, base_difficulty: usize, now_millis: i64) -> Escalation {
+        let record: Option<&FailureRecord> = self.records
+            .get(client)
+            .filter(|record: &&FailureRecord| record.is_active(now_millis));
+        let failures: u32 = record.map_or(0, |record: &FailureRecord| record.count);
+        let level: usize = (failures / FAILURES_PER_ESCALATION) as usize;
+        let retry_after_seconds: i64 = record.map_or(0, |record: &FailureRecord| {
+            let remaining_ms: i64 = (record.last_failure_millis + FAILURE_WINDOW_MS - now_millis).max(0);
+            (remaining_ms + 999) / 1000
+        });
+
+        Escalation {
+            failures,
+            level,
+            base_difficulty,
+            difficulty: base_difficulty.saturating_add(level).min(MAX_POW_DIFFICULTY.max(base_difficulty)),
+            retry_after_seconds,
+            relief: 0,
+        }
+    }
+}
+
+/// Difficulty escalation for one client, reported to throttled clients.
+///
+/// * `failures`:            Recent failed verifications.
+/// * `level`:               Leading zeros added for those failures.
+/// * `base_difficulty`:     The configured difficulty before escalation.
+/// * `difficulty`:          The difficulty issued and enforced.
+/// * `retry_after_seconds`: Seconds until the failures are forgotten.
+/// * `relief`:              Leading zeros removed for reported solve
+///   failures, see `crate::relief`. Always `0` when throttled, so it is
+///   not reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Escalation {
+    pub failures:            u32,
+    pub level:               usize,
+    pub base_difficulty:     usize,
+    pub difficulty:          usize,
+    pub retry_after_seconds: i64,
+    #[serde(skip)]
+    pub relief:              usize,
+}
+
+impl Escalation {
+    /// The escalation of a client with no recent failures.
+    pub(crate) fn none(base_difficulty: usize) -> Self {
+        Self {
+            failures: 0,
+            level: 0,
+            base_difficulty,
+            difficulty: base_difficulty,
+            retry_after_seconds: 0,
+            relief: 0,
+        }
+    }
+
+    /// Lowers the difficulty by `relief` leading zeros, down to
+    /// `MIN_POW_DIFFICULTY`. Throttled clients are not relieved, so
+    /// reporting solve failures cannot offset failed verifications.
+    pub(crate) fn relieved(self, relief: usize) -> Self {
+        if self.is_throttled() {
+            return self;
+        }
+        let difficulty: usize = self.difficulty.saturating_sub(relief).max(MIN_POW_DIFFICULTY.min(self.difficulty));
+        Self { difficulty, relief: self.difficulty - difficulty, ..self }
+    }
+
+    /// Returns whether the client is relieved, i.e. receives an easier
+    /// challenge than the configured one.
+    pub(crate) fn is_relieved(&self) -> bool {
+        self.relief > 0
+    }
+
+    /// Returns whether the client is throttled, i.e. receives a harder
+    /// challenge than the configured one.
+    pub(crate) fn is_throttled(&self) -> bool {
+        self.level > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: &str = "203.0.113.5";
+
+    #[test]
+    fn test_escalation_grows_with_failures() {
+        let mut tracker: FailureTracker = FailureTracker::default();
+        assert!(!tracker.escalation(CLIENT, 2, 0).is_throttled());
+
+        for _ in 0..FAILURES_PER_ESCALATION {
+            tracker.record_failure(CLIENT, 0);
+        }
+        let escalation: Escalation = tracker.escalation(CLIENT, 2, 1_000);
+        assert_eq!(escalation.failures, FAILURES_PER_ESCALATION);
+        assert_eq!(escalation.difficulty, 3);
+        assert_eq!(escalation.retry_after_seconds, FAILURE_WINDOW_MS / 1000 - 1);
+        assert!(escalation.is_throttled());
+
+        // Other clients are unaffected, and difficulty never exceeds the maximum.
+        assert_eq!(tracker.escalation("198.51.100.7", 2, 0).difficulty, 2);
+        for _ in 0..100 {
+            tracker.record_failure(CLIENT, 0);
+        }
+        assert_eq!(tracker.escalation(CLIENT, 2, 0).difficulty, MAX_POW_DIFFICULTY);
+    }
+
+    #[test]
+    fn test_failures_reset_on_success_and_expiry() {
+        let mut tracker: FailureTracker = FailureTracker::default();
+        for _ in 0..FAILURES_PER_ESCALATION {
+            tracker.record_failure(CLIENT, 0);
+        }
+        assert!(!tracker.escalation(CLIENT, 1, FAILURE_WINDOW_MS).is_throttled());
+        assert_eq!(tracker.record_failure(CLIENT, FAILURE_WINDOW_MS), 1);
+
+        tracker.record_success(CLIENT);
+        assert_eq!(tracker.escalation(CLIENT, 1, FAILURE_WINDOW_MS).failures, 0);
+    }
+}
@@ -0,0 +1,74 @@
+//! Hash backends for leading-zero proof of work.
+//!
+//! A backend hashes the `"challenge:nonce"` input of the legacy string
+//! challenges. SHA-256 is the default and what `calculate_hash` uses. With
+//! the `blake3` feature, BLAKE3 is available as a faster alternative for
+//! WASM clients. A solution only verifies under the backend it was solved
+//! with, so solver and verifier must agree on it.
+
+use sha2::{Digest, Sha256};
+
+/// A hash function a leading-zero proof of work can be computed with.
+pub trait PowHasher {
+    /// Hashes a challenge and nonce.
+    ///
+    /// # Arguments
+    /// * `challenge` - The challenge string.
+    /// * `nonce` - The nonce value to try.
+    ///
+    /// # Returns
+    /// * The 32-byte digest of `"challenge:nonce"`.
+    fn hash(challenge: &str, nonce: u64) -> [u8; 32];
+}
+
+/// SHA-256, the default backend.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256Hasher;
+
+impl PowHasher for Sha256Hasher {
+    fn hash(challenge: &str, nonce: u64) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{}:{}", challenge, nonce).as_bytes());
+        hasher.finalize().into()
+    }
+}
+
+/// BLAKE3, roughly twice as fast as SHA-256 in pure WASM.
+#[cfg(feature = "blake3")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Blake3Hasher;
+
+#[cfg(feature = "blake3")]
+impl PowHasher for Blake3Hasher {
+    fn hash(challenge: &str, nonce: u64) -> [u8; 32] {
+        blake3::hash(format!("{}:{}", challenge, nonce).as_bytes()).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate_hash, find_solution, find_solution_with_hasher, verify_solution, verify_solution_with_hasher};
+
+    #[test]
+    fn test_sha256_backend_roundtrip() {
+        let (nonce, hash) = find_solution_with_hasher::<Sha256Hasher>("hasher_challenge", 3).unwrap();
+        assert_eq!((nonce, hash.clone()), find_solution("hasher_challenge", 3).unwrap());
+        assert_eq!(hash, calculate_hash("hasher_challenge", nonce));
+        assert!(verify_solution_with_hasher::<Sha256Hasher>("hasher_challenge", &nonce.to_string(), 3));
+        assert!(verify_solution("hasher_challenge", &nonce.to_string(), 3));
+        assert!(find_solution_with_hasher::<Sha256Hasher>("", 3).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "blake3")]
+    fn test_blake3_backend_roundtrip() {
+        let (nonce, hash) = find_solution_with_hasher::<Blake3Hasher>("hasher_challenge", 3).unwrap();
+        assert!(hash.starts_with("000"));
+        assert_eq!(hash, blake3::hash(format!("hasher_challenge:{}", nonce).as_bytes()).to_hex().as_str());
+        assert!(verify_solution_with_hasher::<Blake3Hasher>("hasher_challenge", &nonce.to_string(), 3));
+
+        // A BLAKE3 solution is not a SHA-256 one.
+        assert!(!verify_solution_with_hasher::<Sha256Hasher>("hasher_challenge", &nonce.to_string(), 3));
+    }
+}
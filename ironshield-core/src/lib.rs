@@ -5,24 +5,72 @@
 
 pub use ironshield_types::*; // Re-export types from ironshield-types
 
+mod hasher;
+mod metrics;
+mod pow;
 mod solve;
 mod verify;
+mod vector;
 
 // Re-export public functions from modules
 pub use solve::{
     find_solution,
+    find_solution_with_progress,
+    find_solution_cancellable,
+    find_solution_with_best,
+    find_solution_with_hint,
+    find_solution_with_difficulty,
+    find_solution_with_hasher,
+    find_solution_bytes,
     find_solution_single_threaded,
+    find_solution_single_threaded_with_progress,
+    find_solution_single_threaded_with_budget,
+    find_solution_single_threaded_with_stats,
     calculate_hash,
+    calculate_hash_bytes,
+    leading_zero_bits,
+    DEFAULT_BUDGET_SAFETY_FACTOR,
+    PROGRESS_INTERVAL,
+    CANCELLED_ERROR,
+    SolveStats,
 };
 
 #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
-pub use solve::{find_solution_parallel, find_solution_multi_threaded};
+pub use solve::{
+    find_solution_parallel,
+    find_solution_parallel_cancellable,
+    find_solution_parallel_with_fallback,
+    find_solution_multi_threaded,
+    set_global_pool,
+    DEFAULT_STALL_BUDGET,
+};
 
 pub use verify::{
     verify_solution,
+    verify_solution_with_difficulty,
+    verify_solution_with_hasher,
+    parse_nonce,
     verify_ironshield_solution,
+    verify_ironshield_solution_for_resource,
+    verify_ironshield_solution_borrowed,
+    verify_ironshield_concat,
+    solution_margin,
+    verify_against_id,
+    verify_with_client_key,
+    MIN_WORK_BITS,
 };
 
+pub use hasher::{PowHasher, Sha256Hasher};
+#[cfg(feature = "blake3")]
+pub use hasher::Blake3Hasher;
+
+pub use pow::{Challenge, ProofOfWork, Solution};
+
+pub use vector::*;
+
+#[cfg(feature = "metrics")]
+pub use metrics::{snapshot, MetricsSnapshot};
+
 #[cfg(test)]
 mod tests {
     use super::*;
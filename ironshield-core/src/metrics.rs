@@ -0,0 +1,143 @@
+//! Process-wide instrumentation counters for server deployments.
+//!
+//! With the `metrics` feature enabled, solving and verification update
+//! global atomic counters that `snapshot` exports, e.g. for a
+//! Prometheus-style `/metrics` endpoint. Without it the recording hooks
+//! are empty inline functions and compile away entirely.
+
+#[cfg(feature = "metrics")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "metrics")]
+static SOLUTIONS_FOUND:      AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "metrics")]
+static VERIFICATIONS_TOTAL:  AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "metrics")]
+static VERIFICATIONS_FAILED: AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "metrics")]
+static HASHES_COMPUTED:      AtomicU64 = AtomicU64::new(0);
+
+/// Point-in-time copy of the instrumentation counters.
+///
+/// * `solutions_found`:      Successful calls to the public solvers.
+/// * `verifications_total`:  Calls to `verify_solution` and
+///   `verify_ironshield_solution`.
+/// * `verifications_failed`: Those verifications that returned `false`.
+/// * `hashes_computed`:      SHA-256 hashes computed by solvers and
+///   verifiers.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub solutions_found:      u64,
+    pub verifications_total:  u64,
+    pub verifications_failed: u64,
+    pub hashes_computed:      u64,
+}
+
+#[cfg(feature = "metrics")]
+impl MetricsSnapshot {
+    /// Renders the snapshot in the Prometheus text exposition format, with
+    /// every counter prefixed `ironshield_`.
+    pub fn to_prometheus(&self) -> String {
+        [
+            ("solutions_found_total", self.solutions_found),
+            ("verifications_total", self.verifications_total),
+            ("verifications_failed_total", self.verifications_failed),
+            ("hashes_computed_total", self.hashes_computed),
+        ]
+            .iter()
+            .map(|(name, value)| format!("# TYPE ironshield_{name} counter\nironshield_{name} {value}\n"))
+            .collect()
+    }
+}
+
+/// Reads all counters.
+///
+/// Counters are read individually with relaxed ordering, so a snapshot
+/// taken during concurrent activity may be off by in-flight updates.
+///
+/// # Returns
+/// * `MetricsSnapshot`: The counter values since process start.
+#[cfg(feature = "metrics")]
+pub fn snapshot() -> MetricsSnapshot {
+    MetricsSnapshot {
+        solutions_found:      SOLUTIONS_FOUND.load(Ordering::Relaxed),
+        verifications_total:  VERIFICATIONS_TOTAL.load(Ordering::Relaxed),
+        verifications_failed: VERIFICATIONS_FAILED.load(Ordering::Relaxed),
+        hashes_computed:      HASHES_COMPUTED.load(Ordering::Relaxed),
+    }
+}
+
+/// Counts a successful solve and passes the result through.
+#[inline]
+pub(crate) fn record_solve<T, E>(result: Result<T, E>) -> Result<T, E> {
+    #[cfg(feature = "metrics")]
+    if result.is_ok() {
+        SOLUTIONS_FOUND.fetch_add(1, Ordering::Relaxed);
+    }
+    result
+}
+
+/// Counts a verification and, if `valid` is `false`, a failure.
+#[inline]
+pub(crate) fn record_verification(valid: bool) -> bool {
+    #[cfg(feature = "metrics")]
+    {
+        VERIFICATIONS_TOTAL.fetch_add(1, Ordering::Relaxed);
+        if !valid {
+            VERIFICATIONS_FAILED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    valid
+}
+
+/// Adds `count` computed hashes. Solvers call this once per search or
+/// chunk, never per hash.
+#[inline]
+pub(crate) fn record_hashes(count: u64) {
+    #[cfg(feature = "metrics")]
+    HASHES_COMPUTED.fetch_add(count, Ordering::Relaxed);
+    #[cfg(not(feature = "metrics"))]
+    let _ = count;
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_increments_counters() {
+        let challenge: &str = "metrics_test";
+        let (nonce, _) = crate::find_solution(challenge, 1).unwrap();
+
+        // Other tests run concurrently, so only lower bounds are reliable.
+        let before: MetricsSnapshot = snapshot();
+        assert!(crate::verify_solution(challenge, &nonce.to_string(), 1));
+        let after_valid: MetricsSnapshot = snapshot();
+        assert!(after_valid.verifications_total > before.verifications_total);
+        assert!(after_valid.hashes_computed > before.hashes_computed);
+
+        assert!(!crate::verify_solution(challenge, "not a nonce", 1));
+        let after_invalid: MetricsSnapshot = snapshot();
+        assert!(after_invalid.verifications_total > after_valid.verifications_total);
+        assert!(after_invalid.verifications_failed > after_valid.verifications_failed);
+    }
+
+    #[test]
+    fn test_solve_increments_counters() {
+        let before: MetricsSnapshot = snapshot();
+        let (nonce, _) = crate::find_solution("metrics_solve_test", 2).unwrap();
+        let after: MetricsSnapshot = snapshot();
+
+        assert!(after.solutions_found > before.solutions_found);
+        assert!(after.hashes_computed > before.hashes_computed + nonce);
+    }
+
+    #[test]
+    fn test_to_prometheus() {
+        let text: String = MetricsSnapshot { verifications_failed: 3, ..MetricsSnapshot::default() }.to_prometheus();
+        assert!(text.contains("# TYPE ironshield_verifications_failed_total counter\n"));
+        assert!(text.contains("ironshield_verifications_failed_total 3\n"));
+        assert!(text.contains("ironshield_solutions_found_total 0\n"));
+    }
+}
@@ -0,0 +1,174 @@
+//! High-level proof-of-work API.
+//!
+//! `ProofOfWork` wraps the leading-zero functions
+//! (`find_solution_with_difficulty`, `verify_solution_with_difficulty`)
+//! for integrators who just want to issue a challenge, solve it, and check
+//! the result, without handling challenge strings, nonce formats, or
+//! expiry themselves.
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use ironshield_types::{Difficulty, DEFAULT_CHALLENGE_TTL_MS};
+
+use crate::solve::find_solution_with_difficulty;
+use crate::verify::verify_solution_with_difficulty;
+
+/// A challenge issued by `ProofOfWork::issue`.
+///
+/// * `value`:      Random hex string the solver hashes with its nonce.
+/// * `difficulty`: Required leading zeros, in hex digits or bits.
+/// * `created_at`: Issuance time in Unix milliseconds.
+/// * `expires_at`: Expiry time in Unix milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Challenge {
+    pub value:      String,
+    pub difficulty: Difficulty,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+impl Challenge {
+    /// Returns whether the challenge has expired at `now_millis`.
+    pub fn is_expired_at(&self, now_millis: i64) -> bool {
+        now_millis > self.expires_at
+    }
+}
+
+/// A solution found by `ProofOfWork::solve`.
+///
+/// * `nonce`: The nonce satisfying the challenge.
+/// * `hash`:  The resulting hex-encoded SHA-256 hash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Solution {
+    pub nonce: u64,
+    pub hash:  String,
+}
+
+/// Issues, solves, and verifies leading-zero challenges with a consistent
+/// lifetime.
+///
+/// `verify` trusts the challenge it is given, so servers should verify
+/// against the challenge they issued and stored, not one sent back by the
+/// client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofOfWork {
+    ttl_ms: i64,
+}
+
+impl Default for ProofOfWork {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHALLENGE_TTL_MS)
+    }
+}
+
+impl ProofOfWork {
+    /// Creates a facade issuing challenges valid for `ttl_ms` milliseconds.
+    pub fn new(ttl_ms: i64) -> Self {
+        Self { ttl_ms }
+    }
+
+    /// Issues a new challenge with a random value.
+    ///
+    /// # Arguments
+    /// * `difficulty`: Required leading zeros, in hex digits or bits.
+    ///
+    /// # Returns
+    /// * `Challenge`: The challenge, expiring `ttl_ms` from now.
+    pub fn issue(&self, difficulty: Difficulty) -> Challenge {
+        let created_at: i64 = Utc::now().timestamp_millis();
+        Challenge {
+            value:      hex::encode(rand::random::<[u8; 16]>()),
+            difficulty,
+            created_at,
+            expires_at: created_at.saturating_add(self.ttl_ms),
+        }
+    }
+
+    /// Solves a challenge.
+    ///
+    /// # Arguments
+    /// * `challenge`: The challenge to solve.
+    ///
+    /// # Returns
+    /// * `Result<Solution, String>`: The solution, or an error if no
+    ///   solution is found within the solver's attempt limit.
+    pub fn solve(&self, challenge: &Challenge) -> Result<Solution, String> {
+        find_solution_with_difficulty(&challenge.value, challenge.difficulty)
+            .map(|(nonce, hash): (u64, String)| Solution { nonce, hash })
+    }
+
+    /// Verifies a solution against an unexpired challenge.
+    ///
+    /// # Arguments
+    /// * `challenge`: The issued challenge.
+    /// * `solution`:  The proposed solution.
+    ///
+    /// # Returns
+    /// * `bool`: Whether the challenge is unexpired and the nonce meets its
+    ///   difficulty.
+    pub fn verify(&self, challenge: &Challenge, solution: &Solution) -> bool {
+        self.verify_at(challenge, solution, Utc::now().timestamp_millis())
+    }
+
+    /// Verifies like `verify`, at the given time in Unix milliseconds.
+    pub fn verify_at(&self, challenge: &Challenge, solution: &Solution, now_millis: i64) -> bool {
+        !challenge.is_expired_at(now_millis)
+            && verify_solution_with_difficulty(&challenge.value, &solution.nonce.to_string(), challenge.difficulty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issue_solve_verify_cycle() {
+        let pow: ProofOfWork = ProofOfWork::default();
+        let challenge: Challenge = pow.issue(Difficulty::HexDigits(2));
+        assert_eq!(challenge.expires_at - challenge.created_at, DEFAULT_CHALLENGE_TTL_MS);
+        assert_ne!(challenge.value, pow.issue(Difficulty::HexDigits(2)).value);
+
+        let solution: Solution = pow.solve(&challenge).unwrap();
+        assert!(solution.hash.starts_with("00"));
+        assert!(pow.verify(&challenge, &solution));
+
+        // The solution does not satisfy a harder challenge with the same value.
+        let harder: Challenge = Challenge { difficulty: Difficulty::HexDigits(64), ..challenge.clone() };
+        assert!(!pow.verify(&harder, &solution));
+    }
+
+    #[test]
+    fn test_verify_rejects_expired_challenges() {
+        let pow: ProofOfWork = ProofOfWork::new(1_000);
+        let challenge: Challenge = pow.issue(Difficulty::Bits(4));
+        let solution: Solution = pow.solve(&challenge).unwrap();
+
+        assert!(pow.verify_at(&challenge, &solution, challenge.expires_at));
+        assert!(!pow.verify_at(&challenge, &solution, challenge.expires_at + 1));
+    }
+
+    #[test]
+    fn test_bit_difficulty_is_not_read_as_hex_digits() {
+        let pow: ProofOfWork = ProofOfWork::default();
+
+        // The hash of nonce 4 starts with "07": 5 zero bits, under one hex digit.
+        let challenge: Challenge = Challenge {
+            value:      "bit-difficulty-vector".to_string(),
+            difficulty: Difficulty::Bits(5),
+            created_at: 0,
+            expires_at: i64::MAX,
+        };
+        let solution: Solution = Solution { nonce: 4, hash: crate::calculate_hash(&challenge.value, 4) };
+        assert_eq!(crate::leading_zero_bits(&crate::calculate_hash_bytes(&challenge.value, 4)), 5);
+
+        assert!(pow.verify_at(&challenge, &solution, 0));
+        let as_hex: Challenge = Challenge { difficulty: Difficulty::HexDigits(5), ..challenge.clone() };
+        assert!(!pow.verify_at(&as_hex, &solution, 0));
+
+        // The unit survives the wire format.
+        let json: String = serde_json::to_string(&challenge).unwrap();
+        assert!(json.contains(r#""difficulty":{"bits":5}"#));
+        assert_eq!(serde_json::from_str::<Challenge>(&json).unwrap().difficulty, Difficulty::Bits(5));
+    }
+}
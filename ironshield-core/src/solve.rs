@@ -7,13 +7,40 @@
 use hex;
 #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
 use rayon::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+use std::sync::{atomic::AtomicU64, mpsc, Arc, OnceLock};
 use sha2::{Digest, Sha256};
 use ironshield_types::*;
 
+use crate::hasher::{PowHasher, Sha256Hasher};
+use crate::metrics;
+
 const MAX_ATTEMPTS: u64 = 10_000_000; // Maximum number of nonce values to try before giving up.
+const HINT_WINDOW: u64 = 1_024; // Number of nonce values tried from a hint before the sequential search.
+/// Error returned for an empty legacy challenge string. An empty challenge
+/// is almost certainly a bug and gives every client the same solutions.
+pub(crate) const EMPTY_CHALLENGE_ERROR: &str = "Challenge string is empty";
+/// Error returned by the cancellable solvers once their cancel flag is set.
+pub const CANCELLED_ERROR: &str = "cancelled";
+const CHUNK_SIZE: usize = 10_000; // Number of nonce values processed in each parallel chunk, and between cancel checks.
+/// Default number of hashes the calling thread computes between two
+/// checks of the pool's progress in `find_solution_parallel_with_fallback`.
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+pub const DEFAULT_STALL_BUDGET: u64 = 100_000;
+/// Consecutive `stall_budget` slices without pool progress after which
+/// `find_solution_parallel_with_fallback` treats the pool as stalled, so a
+/// pool that is merely slow to start is not written off.
 #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
-const CHUNK_SIZE: usize = 10_000; // Number of nonce values processed in each parallel chunk.
-const MAX_ATTEMPTS_SINGLE_THREADED: i64 = 100_000_000; // Maximum number of nonce values to try in the new algorithm before giving up.
+const STALL_SLICES: u32 = 3;
+/// Default multiple of the recommended attempt count (3x the expected
+/// attempts) the threshold solver tries before giving up. The chance of
+/// failing an honest search is about e^-(3 * factor), so 20 makes a
+/// spurious failure vanishingly rare.
+pub const DEFAULT_BUDGET_SAFETY_FACTOR: u64 = 20;
+/// Attempts between two calls of a sequential solver's progress callback,
+/// see `find_solution_with_progress`.
+pub const PROGRESS_INTERVAL: u64 = 65_536;
 
 // Optimized constants for multi-threaded PoW
 #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
@@ -21,6 +48,19 @@ const MULTI_THREADED_CHUNK_SIZE: i64 = 50_000; // Larger chunks for better cache
 #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
 const MAX_ATTEMPTS_MULTI_THREADED: i64 = 1_000_000_000; // Higher limit for parallel execution
 
+/// Telemetry from a single-threaded IronShield solve, see
+/// `find_solution_single_threaded_with_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SolveStats {
+    /// Nonces hashed, including the winning one.
+    pub attempts:   u64,
+    /// The winning hash, below the challenge's `challenge_param`.
+    pub hash:       [u8; 32],
+    /// Wall-clock solve time in milliseconds, measured with `chrono`
+    /// since `Instant` panics on WASM.
+    pub elapsed_ms: u128,
+}
+
 /// Find a solution for the given challenge and difficulty level
 /// using sequential search.
 /// 
@@ -39,19 +79,295 @@ const MAX_ATTEMPTS_MULTI_THREADED: i64 = 1_000_000_000; // Higher limit for para
 /// # Performance
 /// Sequential search is suitable for single-threaded environments like WASM.
 pub fn find_solution(challenge: &str, difficulty: usize) -> Result<(u64, String), String> {
-    let target_prefix = "0".repeat(difficulty);
+    find_solution_with_progress(challenge, difficulty, None)
+}
+
+/// Find a solution like `find_solution`, reporting progress while searching.
+///
+/// # Arguments
+/// * `challenge` - The challenge string to hash (typically server-provided).
+/// * `difficulty` - Number of leading zeros required in the hash (higher = more difficult).
+/// * `progress` - Called with the attempts so far after every
+///   `PROGRESS_INTERVAL` attempts, e.g. to render a progress bar or an
+///   ETA against `recommended_attempts`. `None` adds no per-hash work.
+///
+/// # Returns
+/// * `Ok((nonce, hash))` - The successful nonce value and resulting hash.
+/// * `Err(message)` - Error if no solution is found within `MAX_ATTEMPTS`.
+pub fn find_solution_with_progress(
+    challenge: &str,
+    difficulty: usize,
+    progress: Option<&mut dyn FnMut(u64)>,
+) -> Result<(u64, String), String> {
+    let is_solution = |hash: &[u8; 32]| has_leading_zero_digits(hash, difficulty);
+    let found: Result<(u64, [u8; 32]), String> = match progress {
+        Some(report) => search_solution_bytes(challenge, None, is_solution, report, None),
+        None => search_solution_bytes(challenge, None, is_solution, |_| {}, None),
+    };
+    metrics::record_solve(found).map(|(nonce, hash)| (nonce, hex::encode(hash)))
+}
+
+/// Find a solution like `find_solution`, giving up once `cancel` is set.
+///
+/// The flag is checked every `CHUNK_SIZE` attempts, so a solve whose
+/// result is no longer needed (e.g. the user navigated away) stops within
+/// that many hashes instead of running to `MAX_ATTEMPTS`.
+///
+/// # Arguments
+/// * `challenge` - The challenge string to hash (typically server-provided).
+/// * `difficulty` - Number of leading zeros required in the hash (higher = more difficult).
+/// * `cancel` - Set from any thread to stop the search.
+///
+/// # Returns
+/// * `Ok((nonce, hash))` - The successful nonce value and resulting hash.
+/// * `Err(CANCELLED_ERROR)` - If `cancel` was set before a solution was found.
+/// * `Err(message)` - Error if no solution is found within `MAX_ATTEMPTS`.
+pub fn find_solution_cancellable(
+    challenge: &str,
+    difficulty: usize,
+    cancel: &AtomicBool,
+) -> Result<(u64, String), String> {
+    metrics::record_solve(search_solution_bytes(challenge, None, |hash: &[u8; 32]| {
+        has_leading_zero_digits(hash, difficulty)
+    }, |_| {}, Some(cancel)))
+        .map(|(nonce, hash)| (nonce, hex::encode(hash)))
+}
+
+/// Find a solution like `find_solution`, reporting each new lowest hash
+/// seen along the way.
+///
+/// Meant for research and difficulty tuning: plotting the best hash
+/// against attempts shows how close the search gets over time, which can
+/// be checked against the expected attempts for a difficulty. Since a
+/// solution is lower than every hash that is not, the last report is
+/// always the solution itself.
+///
+/// # Arguments
+/// * `challenge` - The challenge string to hash (typically server-provided).
+/// * `difficulty` - Number of leading zeros required in the hash (higher = more difficult).
+/// * `on_best` - Called with the attempts so far and the hash whenever a
+///   hash lower than all before it is found, so reported hashes strictly
+///   decrease.
+///
+/// # Returns
+/// * `Ok((nonce, hash))` - The successful nonce value and resulting hash.
+/// * `Err(message)` - Error if no solution is found within `MAX_ATTEMPTS`.
+pub fn find_solution_with_best(
+    challenge: &str,
+    difficulty: usize,
+    on_best: &mut dyn FnMut(u64, &[u8; 32]),
+) -> Result<(u64, String), String> {
+    let mut best: Option<[u8; 32]> = None;
+    let mut attempts: u64 = 0;
+    let is_solution = |hash: &[u8; 32]| {
+        attempts += 1;
+        if best.is_none_or(|best: [u8; 32]| *hash < best) {
+            best = Some(*hash);
+            on_best(attempts, hash);
+        }
+        has_leading_zero_digits(hash, difficulty)
+    };
+    metrics::record_solve(search_solution_bytes(challenge, None, is_solution, |_| {}, None))
+        .map(|(nonce, hash)| (nonce, hex::encode(hash)))
+}
+
+/// Find a solution like `find_solution`, returning the raw hash bytes
+/// instead of a hex string.
+///
+/// Useful for callers that re-hash or compare the hash, saving a hex
+/// encode/decode round-trip.
+///
+/// # Arguments
+/// * `challenge` - The challenge string to hash (typically server-provided).
+/// * `difficulty` - Number of leading zero hex digits required in the hash.
+///
+/// # Returns
+/// * `Ok((nonce, hash))` - The successful nonce value and resulting hash bytes.
+/// * `Err(message)` - Error if no solution is found within `MAX_ATTEMPTS`.
+pub fn find_solution_bytes(challenge: &str, difficulty: usize) -> Result<(u64, [u8; 32]), String> {
+    metrics::record_solve(search_solution_bytes(challenge, None, |hash: &[u8; 32]| {
+        has_leading_zero_digits(hash, difficulty)
+    }, |_| {}, None))
+}
+
+/// Find a solution for the given challenge, trying a likely-good nonce
+/// region before the usual sequential search.
+///
+/// When `hint` is given, the nonces `hint..hint + HINT_WINDOW` are tried
+/// first; if none of them is valid the search falls back to scanning from
+/// 0 exactly like `find_solution`. Any returned nonce is a genuinely
+/// valid solution, so a bad hint only costs `HINT_WINDOW` extra hashes.
+///
+/// # Arguments
+/// * `challenge` - The challenge string to hash (typically server-provided).
+/// * `difficulty` - Number of leading zeros required in the hash (higher = more difficult).
+/// * `hint` - Optional nonce to start searching from, e.g. cached from earlier work.
+///
+/// # Returns
+/// * `Ok((nonce, hash))` - The successful nonce value and resulting hash.
+/// * `Err(message)` - Error if no solution is found within `MAX_ATTEMPTS`.
+pub fn find_solution_with_hint(
+    challenge: &str,
+    difficulty: usize,
+    hint: Option<u64>,
+) -> Result<(u64, String), String> {
+    metrics::record_solve(search_solution_bytes(challenge, hint, |hash: &[u8; 32]| {
+        has_leading_zero_digits(hash, difficulty)
+    }, |_| {}, None))
+        .map(|(nonce, hash)| (nonce, hex::encode(hash)))
+}
+
+/// Find a solution like `find_solution`, with the difficulty's unit
+/// explicit so bit and hex-digit difficulties cannot be confused.
+///
+/// # Arguments
+/// * `challenge` - The challenge string to hash (typically server-provided).
+/// * `difficulty` - Required leading zeros, in hex digits or bits.
+///
+/// # Returns
+/// * `Ok((nonce, hash))` - The successful nonce value and resulting hash.
+/// * `Err(message)` - Error if no solution is found within `MAX_ATTEMPTS`.
+pub fn find_solution_with_difficulty(challenge: &str, difficulty: Difficulty) -> Result<(u64, String), String> {
+    let required_bits: u32 = difficulty.required_bits();
+    metrics::record_solve(search_solution_bytes(challenge, None, |hash: &[u8; 32]| {
+        leading_zero_bits(hash) >= required_bits
+    }, |_| {}, None))
+        .map(|(nonce, hash)| (nonce, hex::encode(hash)))
+}
+
+/// Find a solution like `find_solution`, hashing with the backend `H`.
+///
+/// The solution only verifies under the same backend, see
+/// `verify_solution_with_hasher`.
+///
+/// # Arguments
+/// * `challenge` - The challenge string to hash (typically server-provided).
+/// * `difficulty` - Number of leading zeros required in the hash (higher = more difficult).
+///
+/// # Returns
+/// * `Ok((nonce, hash))` - The successful nonce value and resulting hash.
+/// * `Err(message)` - Error if no solution is found within `MAX_ATTEMPTS`.
+pub fn find_solution_with_hasher<H: PowHasher>(challenge: &str, difficulty: usize) -> Result<(u64, String), String> {
+    if challenge.is_empty() {
+        return Err(EMPTY_CHALLENGE_ERROR.into());
+    }
+
+    let found: Option<(u64, [u8; 32])> = (0..MAX_ATTEMPTS)
+        .map(|nonce: u64| (nonce, H::hash(challenge, nonce)))
+        .find(|(_, hash)| has_leading_zero_digits(hash, difficulty));
+    metrics::record_hashes(found.map_or(MAX_ATTEMPTS, |(nonce, _)| nonce + 1));
+    metrics::record_solve(found.ok_or_else(|| "Could not find solution within attempt limit".to_string()))
+        .map(|(nonce, hash)| (nonce, hex::encode(hash)))
+}
 
-    for nonce in 0..MAX_ATTEMPTS {
-        let hash = calculate_hash(challenge, nonce);
+/// Sequential search shared by the legacy solvers for a hash accepted by
+/// `is_solution`, see `find_solution_with_hint` for how `hint` is used.
+///
+/// `report` is called every `PROGRESS_INTERVAL` attempts; it is generic
+/// so a no-op closure compiles away entirely. `cancel` is checked every
+/// `CHUNK_SIZE` attempts, see `find_solution_cancellable`.
+fn search_solution_bytes(
+    challenge: &str,
+    hint: Option<u64>,
+    mut is_solution: impl FnMut(&[u8; 32]) -> bool,
+    mut report: impl FnMut(u64),
+    cancel: Option<&AtomicBool>,
+) -> Result<(u64, [u8; 32]), String> {
+    if challenge.is_empty() {
+        return Err(EMPTY_CHALLENGE_ERROR.into());
+    }
 
-        if hash.starts_with(&target_prefix) {
+    let hint_range = hint.map(|hint: u64| hint..hint.saturating_add(HINT_WINDOW)).unwrap_or_default();
+    let hasher: PrefixHasher = PrefixHasher::for_challenge(challenge);
+    let mut hashes: u64 = 0;
+
+    for nonce in hint_range.chain(0..MAX_ATTEMPTS) {
+        let hash: [u8; 32] = hasher.hash_decimal(nonce);
+        hashes += 1;
+
+        if is_solution(&hash) {
+            metrics::record_hashes(hashes);
             return Ok((nonce, hash));
         }
+        if hashes.is_multiple_of(PROGRESS_INTERVAL) {
+            report(hashes);
+        }
+        if hashes.is_multiple_of(CHUNK_SIZE as u64) && cancel.is_some_and(|cancel: &AtomicBool| cancel.load(Ordering::Relaxed)) {
+            metrics::record_hashes(hashes);
+            return Err(CANCELLED_ERROR.into());
+        }
     }
 
+    metrics::record_hashes(hashes);
     Err("Could not find solution within attempt limit".into())
 }
 
+/// Returns whether the hex form of `hash` starts with `difficulty` zeros,
+/// without encoding it.
+fn has_leading_zero_digits(hash: &[u8; 32], difficulty: usize) -> bool {
+    // Each hex digit is four bits.
+    leading_zero_bits(hash) as usize >= difficulty.saturating_mul(4)
+}
+
+/// Counts the leading zero bits of a SHA-256 hash.
+///
+/// Scans byte by byte and stops at the first non-zero byte.
+///
+/// # Arguments
+/// * `hash` - The 32-byte hash.
+///
+/// # Returns
+/// * The number of leading zero bits, from 0 to 256 (all zeros).
+pub fn leading_zero_bits(hash: &[u8; 32]) -> u32 {
+    let mut bits: u32 = 0;
+    for &byte in hash {
+        if byte != 0 {
+            return bits + byte.leading_zeros();
+        }
+        bits += 8;
+    }
+    bits
+}
+
+/// Shared pool all parallel solves run on once `set_global_pool` is called.
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+static GLOBAL_POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+
+/// Installs a shared thread pool for every parallel solve in this process.
+///
+/// Without it each parallel solve uses rayon's global pool, sized to the
+/// machine, so a native server running several solves at once can
+/// oversubscribe its cores. With it, concurrent solves queue their work
+/// onto `threads` workers in total. Call it once at startup, before the
+/// first solve.
+///
+/// # Arguments
+/// * `threads` - Number of worker threads; `0` lets rayon choose.
+///
+/// # Returns
+/// * `Ok(())` - The pool is installed.
+/// * `Err(message)` - A pool was already installed or could not be built.
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+pub fn set_global_pool(threads: usize) -> Result<(), String> {
+    let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|index: usize| format!("ironshield-solve-{}", index))
+        .build()
+        .map_err(|e: rayon::ThreadPoolBuildError| format!("Failed to build thread pool: {}", e))?;
+
+    GLOBAL_POOL.set(pool).map_err(|_| "Global thread pool is already set".to_string())
+}
+
+/// Runs `op` on the pool installed by `set_global_pool`, or directly
+/// (on rayon's global pool) if none is installed.
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+fn in_solve_pool<R: Send>(op: impl FnOnce() -> R + Send) -> R {
+    match GLOBAL_POOL.get() {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
 /// Find a solution using parallel processing
 /// 
 /// Something Ethan is working on. 
@@ -61,28 +377,226 @@ pub fn find_solution_parallel(
     difficulty: usize,
     num_threads: usize,
 ) -> Result<(u64, String), String> {
+    metrics::record_solve(in_solve_pool(|| {
+        find_solution_parallel_with_progress(challenge, difficulty, num_threads, &AtomicU64::new(0), None)
+    }))
+}
+
+/// Find a solution like `find_solution_parallel`, giving up once `cancel`
+/// is set.
+///
+/// Every worker checks the shared flag every `CHUNK_SIZE` steps, and the
+/// first to see it set ends the search for all of them.
+///
+/// # Arguments
+/// * `challenge` - The challenge string to hash (typically server-provided).
+/// * `difficulty` - Number of leading zeros required in the hash (higher = more difficult).
+/// * `num_threads` - Number of parallel workers to use.
+/// * `cancel` - Set from any thread to stop the search.
+///
+/// # Returns
+/// * `Ok((nonce, hash))` - The successful nonce value and resulting hash.
+/// * `Err(CANCELLED_ERROR)` - If `cancel` was set before a solution was found.
+/// * `Err(message)` - Error if no solution is found within `MAX_ATTEMPTS`.
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+pub fn find_solution_parallel_cancellable(
+    challenge: &str,
+    difficulty: usize,
+    num_threads: usize,
+    cancel: &AtomicBool,
+) -> Result<(u64, String), String> {
+    metrics::record_solve(in_solve_pool(|| {
+        find_solution_parallel_with_progress(challenge, difficulty, num_threads, &AtomicU64::new(0), Some(cancel))
+    }))
+}
+
+/// Parallel search behind `find_solution_parallel` that also counts the
+/// hashes computed into `progress`, so a supervisor can tell a working
+/// pool from a stalled one, and stops once `cancel` is set.
+///
+/// Nonces are derived from a parallel range of step indices rather than a
+/// collected list, so memory use is O(1) in `MAX_ATTEMPTS` (a collected
+/// list of start nonces was 80MB for 10M attempts on one thread, enough to
+/// OOM a phone).
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+fn find_solution_parallel_with_progress(
+    challenge: &str,
+    difficulty: usize,
+    num_threads: usize,
+    progress: &AtomicU64,
+    cancel: Option<&AtomicBool>,
+) -> Result<(u64, String), String> {
+    if challenge.is_empty() {
+        return Err(EMPTY_CHALLENGE_ERROR.into());
+    }
+
     let target_prefix = "0".repeat(difficulty);
+    let hasher: PrefixHasher = PrefixHasher::for_challenge(challenge);
 
-    let result = (0..MAX_ATTEMPTS)
-        .step_by(num_threads)
-        .collect::<Vec<u64>>()
-        .par_chunks(CHUNK_SIZE)
-        .find_map_any(|chunk| {
-            chunk.iter().find_map(|&start_nonce| {
-                (0..num_threads).find_map(|thread_offset| {
-                    let nonce = start_nonce + thread_offset as u64;
-                    let hash = calculate_hash(challenge, nonce);
-
-                    if hash.starts_with(&target_prefix) {
-                        Some((nonce, hash))
-                    } else {
-                        None
-                    }
-                })
+    // `usize` so rayon can split the range by index; 10M fits on wasm32.
+    let steps: usize = MAX_ATTEMPTS.div_ceil(num_threads as u64) as usize;
+
+    let result = (0..steps)
+        .into_par_iter()
+        .with_min_len(CHUNK_SIZE)
+        .find_map_any(|step| {
+            if step.is_multiple_of(CHUNK_SIZE) && cancel.is_some_and(|cancel: &AtomicBool| cancel.load(Ordering::Relaxed)) {
+                return Some(Err(CANCELLED_ERROR.to_string()));
+            }
+            let start_nonce = (step * num_threads) as u64;
+            progress.fetch_add(num_threads as u64, Ordering::Relaxed);
+            (0..num_threads).find_map(|thread_offset| {
+                let nonce = start_nonce + thread_offset as u64;
+                let hash = hex::encode(hasher.hash_decimal(nonce));
+
+                if hash.starts_with(&target_prefix) {
+                    Some(Ok((nonce, hash)))
+                } else {
+                    None
+                }
             })
         });
 
-    result.ok_or_else(|| "Could not find solution within attempt limit".into())
+    metrics::record_hashes(progress.load(Ordering::Relaxed));
+    result.unwrap_or_else(|| Err("Could not find solution within attempt limit".into()))
+}
+
+/// Find a solution using parallel processing, finishing sequentially if
+/// the thread pool stops making progress.
+///
+/// The parallel search runs on the rayon pool over nonces below
+/// `MAX_ATTEMPTS` while the calling thread searches sequentially from the
+/// end of that range, in slices of `stall_budget` hashes, so the two never
+/// hash the same nonce. After each slice it takes the pool's result if one
+/// is ready; if the pool has made no progress for `STALL_SLICES` slices in
+/// a row (e.g. a worker died from OOM in a `SharedArrayBuffer` context), it
+/// stops watching and completes the search on its own. Progress is
+/// measured in hashes rather than wall-clock time, so this works on WASM
+/// where `Instant` panics. Once the caller is done, the pool's job is
+/// cancelled rather than left hashing.
+///
+/// # Arguments
+/// * `challenge` - The challenge string to hash (typically server-provided).
+/// * `difficulty` - Number of leading zeros required in the hash (higher = more difficult).
+/// * `num_threads` - Number of parallel workers to use.
+/// * `stall_budget` - Hashes the calling thread computes between pool
+///   progress checks, see `DEFAULT_STALL_BUDGET`.
+///
+/// # Returns
+/// * `Ok((nonce, hash))` - The successful nonce value and resulting hash.
+/// * `Err(message)` - Error if no solution is found within `MAX_ATTEMPTS`.
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+pub fn find_solution_parallel_with_fallback(
+    challenge: &str,
+    difficulty: usize,
+    num_threads: usize,
+    stall_budget: u64,
+) -> Result<(u64, String), String> {
+    let cancel: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    metrics::record_solve(find_solution_supervised(challenge, difficulty, num_threads, stall_budget, &cancel, |job| {
+        match GLOBAL_POOL.get() {
+            Some(pool) => pool.spawn(job),
+            None => rayon::spawn(job),
+        }
+    }))
+}
+
+/// Supervises a parallel search started through `spawn` while searching
+/// sequentially on the calling thread, see
+/// `find_solution_parallel_with_fallback`. `cancel` is handed to the pool's
+/// job and set once the caller returns.
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+fn find_solution_supervised<S>(
+    challenge: &str,
+    difficulty: usize,
+    num_threads: usize,
+    stall_budget: u64,
+    cancel: &Arc<AtomicBool>,
+    spawn: S,
+) -> Result<(u64, String), String>
+where
+    S: FnOnce(Box<dyn FnOnce() + Send>),
+{
+    if challenge.is_empty() {
+        return Err(EMPTY_CHALLENGE_ERROR.into());
+    }
+
+    let progress: Arc<AtomicU64> = Arc::new(AtomicU64::new(0));
+    let (sender, receiver) = mpsc::channel::<Result<(u64, String), String>>();
+
+    let pool_progress: Arc<AtomicU64> = Arc::clone(&progress);
+    let pool_cancel: Arc<AtomicBool> = Arc::clone(cancel);
+    let pool_challenge: String = challenge.to_string();
+    spawn(Box::new(move || {
+        let result = find_solution_parallel_with_progress(
+            &pool_challenge,
+            difficulty,
+            num_threads,
+            &pool_progress,
+            Some(&pool_cancel),
+        );
+        // The caller may already have finished on its own.
+        let _ = sender.send(result);
+    }));
+
+    let result = supervise_pool(challenge, difficulty, num_threads, stall_budget, &progress, &receiver);
+    cancel.store(true, Ordering::Relaxed);
+    result
+}
+
+/// Sequential half of `find_solution_supervised`, searching the nonces
+/// past the pool's range while watching `progress` and `receiver`.
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+fn supervise_pool(
+    challenge: &str,
+    difficulty: usize,
+    num_threads: usize,
+    stall_budget: u64,
+    progress: &AtomicU64,
+    receiver: &mpsc::Receiver<Result<(u64, String), String>>,
+) -> Result<(u64, String), String> {
+    let target_prefix = "0".repeat(difficulty);
+    let hasher: PrefixHasher = PrefixHasher::for_challenge(challenge);
+    let slice: u64 = stall_budget.max(1);
+    // First nonce the pool never tries, see `find_solution_parallel_with_progress`.
+    let first: u64 = MAX_ATTEMPTS.div_ceil(num_threads.max(1) as u64) * num_threads.max(1) as u64;
+    let last: u64 = first.saturating_add(MAX_ATTEMPTS);
+    let mut last_progress: u64 = 0;
+    let mut empty_slices: u32 = 0;
+    let mut pool_alive: bool = true;
+    let mut start: u64 = first;
+
+    while start < last {
+        let end: u64 = start.saturating_add(slice).min(last);
+        for nonce in start..end {
+            let hash = hex::encode(hasher.hash_decimal(nonce));
+
+            if hash.starts_with(&target_prefix) {
+                metrics::record_hashes(nonce + 1 - first);
+                return Ok((nonce, hash));
+            }
+        }
+        start = end;
+
+        if pool_alive {
+            match receiver.try_recv() {
+                Ok(result) => {
+                    metrics::record_hashes(start - first);
+                    return result;
+                }
+                Err(mpsc::TryRecvError::Empty) => {
+                    let current: u64 = progress.load(Ordering::Relaxed);
+                    empty_slices = if current == last_progress { empty_slices + 1 } else { 0 };
+                    pool_alive = empty_slices < STALL_SLICES;
+                    last_progress = current;
+                }
+                Err(mpsc::TryRecvError::Disconnected) => pool_alive = false,
+            }
+        }
+    }
+
+    metrics::record_hashes(start - first);
+    Err("Could not find solution within attempt limit".into())
 }
 
 /// Calculate the SHA-256 hash for a given challenge and nonce combination.
@@ -96,9 +610,63 @@ pub fn find_solution_parallel(
 /// # Returns
 /// * Hexadecimal string representation of the SHA-256 hash (64 chars long).
 pub fn calculate_hash(challenge: &str, nonce: u64) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(format!("{}:{}", challenge, nonce).as_bytes());
-    hex::encode(hasher.finalize())
+    hex::encode(calculate_hash_bytes(challenge, nonce))
+}
+
+/// Calculate the SHA-256 hash for a given challenge and nonce
+/// combination, as raw bytes. See `calculate_hash`.
+pub fn calculate_hash_bytes(challenge: &str, nonce: u64) -> [u8; 32] {
+    Sha256Hasher::hash(challenge, nonce)
+}
+
+/// SHA-256 state with a challenge's fixed prefix already absorbed.
+///
+/// Hashing a nonce clones the midstate and absorbs only the bytes after
+/// the prefix, so the complete 64-byte blocks of a long challenge are
+/// compressed once per solve instead of once per nonce.
+#[derive(Clone)]
+struct PrefixHasher {
+    midstate: Sha256,
+}
+
+impl PrefixHasher {
+    /// Absorbs `prefix`, the part of the hash input shared by every nonce.
+    fn new(prefix: &[u8]) -> Self {
+        let mut midstate = Sha256::new();
+        midstate.update(prefix);
+        Self { midstate }
+    }
+
+    /// Midstate for the `"challenge:"` prefix of `calculate_hash_bytes`.
+    fn for_challenge(challenge: &str) -> Self {
+        let mut hasher: Self = Self::new(challenge.as_bytes());
+        hasher.midstate.update(b":");
+        hasher
+    }
+
+    /// Hashes the prefix followed by `suffix`.
+    fn hash(&self, suffix: &[u8]) -> [u8; 32] {
+        let mut hasher = self.midstate.clone();
+        hasher.update(suffix);
+        hasher.finalize().into()
+    }
+
+    /// Equivalent to `calculate_hash_bytes(challenge, nonce)` for the
+    /// challenge passed to `for_challenge`, without allocating.
+    fn hash_decimal(&self, nonce: u64) -> [u8; 32] {
+        let mut digits: [u8; 20] = [0u8; 20];
+        let mut start: usize = digits.len();
+        let mut rest: u64 = nonce;
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (rest % 10) as u8;
+            rest /= 10;
+            if rest == 0 {
+                break;
+            }
+        }
+        self.hash(&digits[start..])
+    }
 }
 
 /// Find a solution for the given IronShieldChallenge using single-threaded computation.
@@ -120,7 +688,8 @@ pub fn calculate_hash(challenge: &str, nonce: u64) -> String {
 /// 
 /// # Returns
 /// * `Ok(IronShieldChallengeResponse)` - Contains the successful nonce and signature
-/// * `Err(String)` - Error message if no solution found within MAX_ATTEMPTS_SINGLE_THREADED
+/// * `Err(String)` - Error message if no solution is found within the attempt budget,
+///   see `attempt_budget` and `DEFAULT_BUDGET_SAFETY_FACTOR`
 /// 
 /// # Example
 /// The challenge contains:
@@ -132,38 +701,118 @@ pub fn calculate_hash(challenge: &str, nonce: u64) -> String {
 pub fn find_solution_single_threaded(
     challenge: &IronShieldChallenge,
 ) -> Result<IronShieldChallengeResponse, String> {
-    
-    // Parse the random_nonce from hex string to bytes
+    find_solution_single_threaded_with_progress(challenge, None)
+}
+
+/// Find a solution like `find_solution_single_threaded`, reporting
+/// progress while searching.
+///
+/// # Arguments
+/// * `challenge` - The IronShieldChallenge struct containing random_nonce and challenge_param
+/// * `progress` - Called with the attempts so far after every
+///   `PROGRESS_INTERVAL` attempts, e.g. to estimate an ETA against
+///   `challenge.recommended_attempts`. `None` adds no per-hash work.
+///
+/// # Returns
+/// * `Ok(IronShieldChallengeResponse)` - Contains the successful nonce and signature
+/// * `Err(String)` - Error message if the challenge is unsolvable or the budget is exhausted
+pub fn find_solution_single_threaded_with_progress(
+    challenge: &IronShieldChallenge,
+    progress: Option<&mut dyn FnMut(u64)>,
+) -> Result<IronShieldChallengeResponse, String> {
+    let found: Result<(IronShieldChallengeResponse, SolveStats), String> = match progress {
+        Some(report) => search_threshold(challenge, DEFAULT_BUDGET_SAFETY_FACTOR, report),
+        None => search_threshold(challenge, DEFAULT_BUDGET_SAFETY_FACTOR, |_| {}),
+    };
+    found.map(|(response, _)| response)
+}
+
+/// Find a solution like `find_solution_single_threaded`, also returning
+/// the winning hash, the attempts consumed and the time taken, e.g. to
+/// measure real-world hash rates across devices.
+///
+/// # Arguments
+/// * `challenge` - The IronShieldChallenge struct containing random_nonce and challenge_param
+///
+/// # Returns
+/// * `Ok((IronShieldChallengeResponse, SolveStats))` - The solution and its telemetry
+/// * `Err(String)` - Error message if the challenge is unsolvable or the budget is exhausted
+pub fn find_solution_single_threaded_with_stats(
+    challenge: &IronShieldChallenge,
+) -> Result<(IronShieldChallengeResponse, SolveStats), String> {
+    search_threshold(challenge, DEFAULT_BUDGET_SAFETY_FACTOR, |_| {})
+}
+
+/// Find a solution for the given IronShieldChallenge using single-threaded
+/// computation and a caller-chosen attempt budget.
+///
+/// # Arguments
+/// * `challenge` - The IronShieldChallenge struct containing random_nonce and challenge_param
+/// * `safety_factor` - Multiple of the recommended attempts to try, see `attempt_budget`
+///
+/// # Returns
+/// * `Ok(IronShieldChallengeResponse)` - Contains the successful nonce and signature
+/// * `Err(String)` - Error message if the challenge is unsolvable or the budget is exhausted
+pub fn find_solution_single_threaded_with_budget(
+    challenge: &IronShieldChallenge,
+    safety_factor: u64,
+) -> Result<IronShieldChallengeResponse, String> {
+    search_threshold(challenge, safety_factor, |_| {}).map(|(response, _)| response)
+}
+
+/// Sequential threshold search shared by the single-threaded solvers,
+/// calling `report` every `PROGRESS_INTERVAL` attempts like
+/// `search_solution_bytes`.
+fn search_threshold(
+    challenge: &IronShieldChallenge,
+    safety_factor: u64,
+    mut report: impl FnMut(u64),
+) -> Result<(IronShieldChallengeResponse, SolveStats), String> {
+    // An all-zero threshold can never be beaten.
+    if challenge.challenge_param == [0u8; 32] {
+        return Err("challenge_param is zero; no solution exists".to_string());
+    }
+
+    let difficulty: u64 = IronShieldChallenge::challenge_param_to_difficulty(&challenge.challenge_param);
+    let budget: i64 = i64::try_from(attempt_budget(difficulty, safety_factor)).unwrap_or(i64::MAX);
+
+    // Parse the random_nonce from hex string to bytes, absorbing it once
     let random_nonce_bytes: Vec<u8> = hex::decode(&challenge.random_nonce)
         .map_err(|e: hex::FromHexError| format!("Failed to decode random_nonce hex: {}", e))?;
+    let hasher: PrefixHasher = PrefixHasher::new(&random_nonce_bytes);
+    let started: chrono::DateTime<chrono::Utc> = chrono::Utc::now();
     
     // Get the target threshold from challenge_param
     let target_threshold: &[u8; 32] = &challenge.challenge_param;
     
     // Iterate through possible nonce values
-    for nonce in 0..MAX_ATTEMPTS_SINGLE_THREADED {
-        // Convert nonce to little-endian bytes (8 bytes for i64)
-        let nonce_bytes: [u8; 8] = nonce.to_le_bytes();
+    for nonce in 0..budget {
+        // Hash the random_nonce followed by the little-endian nonce
+        let hash_bytes: [u8; 32] = hasher.hash(&nonce.to_le_bytes());
         
-        // Calculate the hash of the random_nonce and nonce
-        let mut hasher = Sha256::new();
-        hasher.update(&random_nonce_bytes);  // First part of the input
-        hasher.update(&nonce_bytes);         // Second part of the input
-        let hash_result = hasher.finalize();
-        
-        // Convert hash and use byte-wise comparison with the target threshold
-        let hash_bytes: [u8; 32] = hash_result.into();
+        // Use byte-wise comparison with the target threshold
         if hash_bytes < *target_threshold {
             // Found a valid solution!
-            return Ok(IronShieldChallengeResponse::new(
+            let stats: SolveStats = SolveStats {
+                attempts:   nonce as u64 + 1,
+                hash:       hash_bytes,
+                elapsed_ms: u128::try_from((chrono::Utc::now() - started).num_milliseconds()).unwrap_or(0),
+            };
+            metrics::record_hashes(stats.attempts);
+            return metrics::record_solve(Ok((IronShieldChallengeResponse::new(
                 challenge.challenge_signature, // Copy the challenge signature
                 nonce, // The successful nonce value
-            ));
+            ), stats)));
+        }
+        let attempts: u64 = nonce as u64 + 1;
+        if attempts.is_multiple_of(PROGRESS_INTERVAL) {
+            report(attempts);
         }
     }
     
-    // No solution found within the attempt limit
-    Err(format!("Could not find solution within {} attempts", MAX_ATTEMPTS_SINGLE_THREADED))
+    // No solution found within the attempt budget
+    metrics::record_hashes(budget as u64);
+    Err(format!("Attempt budget of {} exhausted at difficulty {}", budget, difficulty))
 }
 
 /// Find a solution for the given IronShieldChallenge using optimized multi-threaded computation.
@@ -173,7 +822,8 @@ pub fn find_solution_single_threaded(
 /// as find_solution_single_threaded but with optimal work distribution and minimal overhead.
 /// 
 /// ## Algorithm:
-/// 1. Pre-computes the random_nonce bytes once to avoid repeated hex decoding
+/// 1. Absorbs the random_nonce into a SHA-256 midstate once, avoiding repeated
+///    hex decoding and rehashing of long nonces
 /// 2. Divides the nonce search space into optimal chunks for each CPU core
 /// 3. Uses Rayon's parallel iterator with find_map_any for early termination
 /// 4. Minimizes memory allocations and maximizes cache locality
@@ -227,13 +877,14 @@ pub fn find_solution_multi_threaded(
     // This avoids repeated hex decoding in each thread
     let random_nonce_bytes: Vec<u8> = hex::decode(&challenge.random_nonce)
         .map_err(|e: hex::FromHexError| format!("Failed to decode random_nonce hex: {}", e))?;
+    let hasher: PrefixHasher = PrefixHasher::new(&random_nonce_bytes);
     
     // Get the target threshold reference
     let target_threshold: &[u8; 32] = &challenge.challenge_param;
     
     // Create iterator over nonce ranges with optimal chunk size for parallel processing
     // Each chunk represents a contiguous range of nonces for a thread to process
-    let result = (0..MAX_ATTEMPTS_MULTI_THREADED)
+    let result = in_solve_pool(|| (0..MAX_ATTEMPTS_MULTI_THREADED)
         .step_by(MULTI_THREADED_CHUNK_SIZE as usize)
         .collect::<Vec<i64>>()
         .par_iter()
@@ -243,36 +894,30 @@ pub fn find_solution_multi_threaded(
             
             // Process this chunk sequentially within the thread for optimal cache performance
             for nonce in chunk_start..chunk_end {
-                // Convert nonce to little-endian bytes (8 bytes for i64)
-                let nonce_bytes: [u8; 8] = nonce.to_le_bytes();
+                // Hash the little-endian nonce onto the random_nonce midstate
+                let hash_bytes: [u8; 32] = hasher.hash(&nonce.to_le_bytes());
                 
-                // Calculate the hash of the random_nonce and nonce using optimized approach
-                // Use multiple update calls to avoid memory allocation for concatenation
-                let mut hasher = Sha256::new();
-                hasher.update(&random_nonce_bytes);  // First part of the input
-                hasher.update(&nonce_bytes);         // Second part of the input
-                let hash_result = hasher.finalize();
-                
-                // Convert hash to [u8; 32] and use byte-wise comparison with the target threshold
-                let hash_bytes: [u8; 32] = hash_result.into();
+                // Use byte-wise comparison with the target threshold
                 if hash_bytes < *target_threshold {
                     // Found a valid solution! Return immediately to stop all other threads
+                    metrics::record_hashes((nonce - chunk_start + 1) as u64);
                     return Some(nonce);
                 }
             }
             
             // No solution found in this chunk
+            metrics::record_hashes((chunk_end - chunk_start) as u64);
             None
-        });
+        }));
     
     // Check if a solution was found
     match result {
         Some(nonce) => {
             // Found a valid solution!
-            Ok(IronShieldChallengeResponse::new(
+            metrics::record_solve(Ok(IronShieldChallengeResponse::new(
                 challenge.challenge_signature, // Copy the challenge signature
                 nonce, // The successful nonce value
-            ))
+            )))
         }
         None => {
             // No solution found within the attempt limit
@@ -291,6 +936,34 @@ mod tests {
         assert!(!hash.is_empty());
     }
 
+    #[test]
+    fn test_prefix_midstate_matches_full_hash_for_long_challenges() {
+        // Several 64-byte SHA-256 blocks of fixed prefix.
+        let challenge: String = "a1b2c3d4-".repeat(20);
+        assert!(challenge.len() > 64);
+
+        let hasher: PrefixHasher = PrefixHasher::for_challenge(&challenge);
+        for nonce in [0, 7, 10, 99, 12345, u32::MAX as u64, u64::MAX] {
+            assert_eq!(hasher.hash_decimal(nonce), calculate_hash_bytes(&challenge, nonce));
+        }
+
+        let (nonce, hash) = find_solution(&challenge, 2).unwrap();
+        assert_eq!(hash, calculate_hash(&challenge, nonce));
+        assert!(hash.starts_with("00"));
+
+        // The threshold solvers absorb a long random_nonce the same way.
+        let long_challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "ab".repeat(100),
+            1000000,
+            "test_website".to_string(),
+            [0x20; 32],
+            [0x00; 32],
+            [0x11; 64],
+        );
+        let response = find_solution_single_threaded(&long_challenge).unwrap();
+        assert!(crate::verify::verify_ironshield_solution(&long_challenge, response.solution));
+    }
+
     #[test]
     fn test_find_solution() {
         let challenge = "test_challenge";
@@ -374,6 +1047,52 @@ mod tests {
         assert_eq!(hash1, hash2, "Optimized and traditional methods should produce identical hashes");
     }
 
+    #[test]
+    #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+    fn test_find_solution_parallel_with_fallback() {
+        let challenge: &str = "fallback_challenge";
+        let (nonce, hash) = find_solution_parallel_with_fallback(challenge, 3, 4, DEFAULT_STALL_BUDGET).unwrap();
+        assert!(hash.starts_with("000"));
+        assert_eq!(calculate_hash(challenge, nonce), hash);
+    }
+
+    #[test]
+    #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+    fn test_stalled_pool_falls_back_to_sequential() {
+        let challenge: &str = "stalled_challenge";
+        // The caller searches past the pool's range, so its solution is the
+        // first one from `MAX_ATTEMPTS` on (a multiple of 4 threads).
+        let expected: (u64, String) = (MAX_ATTEMPTS..)
+            .map(|nonce: u64| (nonce, calculate_hash(challenge, nonce)))
+            .find(|(_, hash)| hash.starts_with("000"))
+            .unwrap();
+
+        // A pool that accepts the job but never runs it: no progress, no
+        // result, and the channel stays open as if a worker had hung.
+        let cancel: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let result = find_solution_supervised(challenge, 3, 4, 64, &cancel, std::mem::forget);
+        assert_eq!(result, Ok(expected.clone()));
+        assert!(cancel.load(Ordering::Relaxed), "the orphaned pool job must be cancelled");
+
+        // A pool whose job is dropped (e.g. the worker panicked).
+        let result = find_solution_supervised(challenge, 3, 4, 64, &Arc::new(AtomicBool::new(false)), drop);
+        assert_eq!(result, Ok(expected));
+    }
+
+    #[test]
+    #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+    fn test_pool_result_is_taken_and_job_cancelled() {
+        let challenge: &str = "pool_challenge";
+        let cancel: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+
+        // The pool finishes its job before the caller's first slice ends.
+        let result = find_solution_supervised(challenge, 3, 4, 1, &cancel, |job: Box<dyn FnOnce() + Send>| job());
+        let (nonce, hash) = result.unwrap();
+        assert!(nonce < MAX_ATTEMPTS);
+        assert_eq!(calculate_hash(challenge, nonce), hash);
+        assert!(cancel.load(Ordering::Relaxed));
+    }
+
     #[test]
     #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
     fn test_find_solution_multi_threaded_easy() {
@@ -494,4 +1213,257 @@ mod tests {
         assert_eq!(response.challenge_signature, [0x55; 64], 
                 "Response should preserve challenge signature");
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_find_solution_single_threaded_budget_errors() {
+        let mut challenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1000000,
+            "test_website".to_string(),
+            [0x00; 32],
+            [0x00; 32],
+            [0x00; 64],
+        );
+        let result = find_solution_single_threaded(&challenge);
+        assert_eq!(result.unwrap_err(), "challenge_param is zero; no solution exists");
+
+        // A zero safety factor leaves no attempts at all.
+        challenge.challenge_param = IronShieldChallenge::difficulty_to_challenge_param(1 << 16);
+        let result = find_solution_single_threaded_with_budget(&challenge, 0);
+        assert_eq!(result.unwrap_err(), format!("Attempt budget of 0 exhausted at difficulty {}", 1 << 16));
+
+        // Huge safety factors saturate instead of overflowing.
+        challenge.challenge_param = [0xFF; 32];
+        assert!(find_solution_single_threaded_with_budget(&challenge, u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn test_find_solution_bytes_matches_hex() {
+        let challenge: &str = "bytes_challenge";
+        for difficulty in [0, 1, 2, 3] {
+            let (nonce, hash) = find_solution_bytes(challenge, difficulty).unwrap();
+            assert_eq!((nonce, hex::encode(hash)), find_solution(challenge, difficulty).unwrap());
+            assert_eq!(hash, calculate_hash_bytes(challenge, nonce));
+        }
+    }
+
+    #[test]
+    fn test_empty_challenge_rejected() {
+        assert_eq!(find_solution("", 1), Err(EMPTY_CHALLENGE_ERROR.to_string()));
+        assert_eq!(find_solution_bytes("", 1), Err(EMPTY_CHALLENGE_ERROR.to_string()));
+        assert_eq!(find_solution_with_hint("", 1, Some(0)), Err(EMPTY_CHALLENGE_ERROR.to_string()));
+    }
+
+    #[test]
+    #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+    fn test_parallel_solver_streams_nonces() {
+        // Thread counts that do and don't divide MAX_ATTEMPTS; the search
+        // range is iterated lazily, never collected.
+        for num_threads in [1, 3, 8] {
+            let (nonce, hash) = find_solution_parallel("streamed_range_challenge", 2, num_threads).unwrap();
+            assert!(nonce < MAX_ATTEMPTS + num_threads as u64);
+            assert_eq!(hash, calculate_hash("streamed_range_challenge", nonce));
+            assert!(hash.starts_with("00"));
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+    fn test_empty_challenge_rejected_parallel() {
+        assert_eq!(find_solution_parallel("", 1, 4), Err(EMPTY_CHALLENGE_ERROR.to_string()));
+        assert_eq!(
+            find_solution_parallel_with_fallback("", 1, 4, DEFAULT_STALL_BUDGET),
+            Err(EMPTY_CHALLENGE_ERROR.to_string())
+        );
+    }
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0x00; 32]), 256);
+        assert_eq!(leading_zero_bits(&[0xFF; 32]), 0);
+
+        let mut hash: [u8; 32] = [0x00; 32];
+        hash[1] = 0x80;
+        assert_eq!(leading_zero_bits(&hash), 8);
+
+        // Every bit position, with noise after the first set bit.
+        for position in 0..256u32 {
+            let mut hash: [u8; 32] = [0x00; 32];
+            hash[(position / 8) as usize] = 0x80 >> (position % 8);
+            for byte in hash.iter_mut().skip((position / 8) as usize + 1) {
+                *byte = 0xA5;
+            }
+            assert_eq!(leading_zero_bits(&hash), position);
+        }
+    }
+
+    #[test]
+    fn test_has_leading_zero_digits() {
+        let mut hash: [u8; 32] = [0xFF; 32];
+        assert!(has_leading_zero_digits(&hash, 0));
+        assert!(!has_leading_zero_digits(&hash, 1));
+
+        hash[0] = 0x00;
+        hash[1] = 0x0A;
+        assert!(has_leading_zero_digits(&hash, 3));
+        assert!(!has_leading_zero_digits(&hash, 4));
+        assert!(!has_leading_zero_digits(&[0x00; 32], 65));
+        assert!(has_leading_zero_digits(&[0x00; 32], 64));
+    }
+
+    #[test]
+    fn test_find_solution_with_hint() {
+        let challenge: &str = "hint_challenge";
+        let difficulty: usize = 3;
+        let (expected, expected_hash) = find_solution(challenge, difficulty).unwrap();
+
+        // The correct solution as a hint is returned immediately.
+        let (nonce, hash) = find_solution_with_hint(challenge, difficulty, Some(expected)).unwrap();
+        assert_eq!((nonce, hash), (expected, expected_hash.clone()));
+
+        // A hint far from any solution in its window still yields a valid one.
+        let far: u64 = u64::MAX - 10;
+        let (nonce, hash) = find_solution_with_hint(challenge, difficulty, Some(far)).unwrap();
+        assert!(hash.starts_with("000"));
+        assert_eq!(hash, calculate_hash(challenge, nonce));
+    }
+
+    #[test]
+    fn test_progress_reported_every_interval() {
+        // The first solution of this challenge at 4 zeros is nonce 182,975.
+        let mut reports: Vec<u64> = Vec::new();
+        let (nonce, hash) = find_solution_with_progress(
+            "progress_challenge_3",
+            4,
+            Some(&mut |attempts: u64| reports.push(attempts)),
+        ).unwrap();
+        assert_eq!(nonce, 182_975);
+        assert_eq!((nonce, hash), find_solution("progress_challenge_3", 4).unwrap());
+        assert_eq!(reports, vec![PROGRESS_INTERVAL, 2 * PROGRESS_INTERVAL]);
+
+        // Likewise for the threshold solver, first solved at nonce 159,337.
+        let challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "cafe1234".to_string(),
+            chrono::Utc::now().timestamp_millis(),
+            "example.com".to_string(),
+            leading_zeros_to_param(4),
+            [0x00; 32],
+            [0x00; 64],
+        );
+        let mut calls: u32 = 0;
+        let response = find_solution_single_threaded_with_progress(&challenge, Some(&mut |_| calls += 1)).unwrap();
+        assert_eq!(response.solution, 159_337);
+        assert_eq!(calls, 2);
+        assert_eq!(find_solution_single_threaded(&challenge).unwrap().solution, response.solution);
+    }
+
+    #[test]
+    fn test_best_hash_reports_never_increase() {
+        let mut reports: Vec<(u64, [u8; 32])> = Vec::new();
+        let (nonce, hash) = find_solution_with_best(
+            "progress_challenge_3",
+            4,
+            &mut |attempts: u64, hash: &[u8; 32]| reports.push((attempts, *hash)),
+        ).unwrap();
+        assert_eq!((nonce, hash.clone()), find_solution("progress_challenge_3", 4).unwrap());
+
+        assert_eq!(reports[0], (1, calculate_hash_bytes("progress_challenge_3", 0)));
+        assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0 && pair[0].1 >= pair[1].1));
+        assert_eq!(reports.last().unwrap(), &(nonce + 1, <[u8; 32]>::try_from(hex::decode(hash).unwrap()).unwrap()));
+    }
+
+    #[test]
+    fn test_single_threaded_stats() {
+        // First solved at nonce 159,337, see `test_progress_reported_every_interval`.
+        let challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "cafe1234".to_string(),
+            chrono::Utc::now().timestamp_millis(),
+            "example.com".to_string(),
+            leading_zeros_to_param(4),
+            [0x00; 32],
+            [0x00; 64],
+        );
+        let (response, stats) = find_solution_single_threaded_with_stats(&challenge).unwrap();
+        assert_eq!(response.solution, find_solution_single_threaded(&challenge).unwrap().solution);
+        assert_eq!(stats.attempts, 159_338);
+
+        let mut input: Vec<u8> = hex::decode(&challenge.random_nonce).unwrap();
+        input.extend_from_slice(&response.solution.to_le_bytes());
+        assert_eq!(stats.hash, <[u8; 32]>::from(Sha256::digest(&input)));
+        assert!(stats.hash < challenge.challenge_param);
+        assert!(stats.elapsed_ms < 60_000);
+    }
+
+    #[test]
+    fn test_cancelled_search_stops_within_a_chunk() {
+        // Cancel after 25,000 attempts; the next check is at 30,000.
+        let cancel: AtomicBool = AtomicBool::new(false);
+        let attempts: std::cell::Cell<u64> = std::cell::Cell::new(0);
+        let result = search_solution_bytes("cancel_challenge", None, |_: &[u8; 32]| {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() == 25_000 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            false
+        }, |_| {}, Some(&cancel));
+
+        assert_eq!(result.unwrap_err(), CANCELLED_ERROR);
+        assert_eq!(attempts.get(), 3 * CHUNK_SIZE as u64);
+    }
+
+    #[test]
+    fn test_find_solution_cancellable() {
+        // Far beyond `MAX_ATTEMPTS`, so only cancellation ends the search quickly.
+        let cancel: std::sync::Arc<AtomicBool> = std::sync::Arc::new(AtomicBool::new(false));
+        let solver_cancel: std::sync::Arc<AtomicBool> = std::sync::Arc::clone(&cancel);
+        let solver = std::thread::spawn(move || find_solution_cancellable("cancel_challenge", 32, &solver_cancel));
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(solver.join().unwrap().unwrap_err(), CANCELLED_ERROR);
+
+        // An unset flag changes nothing.
+        assert_eq!(
+            find_solution_cancellable("cancel_challenge", 2, &AtomicBool::new(false)).unwrap(),
+            find_solution("cancel_challenge", 2).unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+    fn test_find_solution_parallel_cancellable() {
+        let cancel: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let solver_cancel: Arc<AtomicBool> = Arc::clone(&cancel);
+        let solver = std::thread::spawn(move || find_solution_parallel_cancellable("cancel_challenge", 32, 4, &solver_cancel));
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(solver.join().unwrap().unwrap_err(), CANCELLED_ERROR);
+
+        let (nonce, hash) = find_solution_parallel_cancellable("cancel_challenge", 2, 4, &AtomicBool::new(false)).unwrap();
+        assert!(hash.starts_with("00"));
+        assert_eq!(calculate_hash("cancel_challenge", nonce), hash);
+    }
+
+    #[test]
+    #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+    fn test_concurrent_solves_share_global_pool() {
+        // This is the only test that installs the pool; others just run on it.
+        set_global_pool(2).unwrap();
+        assert_eq!(GLOBAL_POOL.get().unwrap().current_num_threads(), 2);
+        assert!(set_global_pool(4).is_err());
+
+        std::thread::scope(|scope| {
+            let solvers = ["pool_a", "pool_b"].map(|challenge: &str| {
+                scope.spawn(move || (challenge, find_solution_parallel(challenge, 3, 4)))
+            });
+
+            for solver in solvers {
+                let (challenge, result) = solver.join().unwrap();
+                let (nonce, hash) = result.unwrap();
+                assert!(hash.starts_with("000"));
+                assert!(crate::verify_solution(challenge, &nonce.to_string(), 3));
+            }
+        });
+    }
+}
@@ -0,0 +1,29 @@
+//! Known-answer test vector for the legacy string-based proof-of-work.
+//!
+//! Shared by the server and the WASM client so either side can confirm
+//! its hashing matches the other before relying on it.
+
+/// Challenge string of the test vector.
+pub const TEST_VECTOR_CHALLENGE:  &str = "ironshield_self_test";
+/// Difficulty (leading zero hex digits) of the test vector.
+pub const TEST_VECTOR_DIFFICULTY: usize = 2;
+/// First nonce `find_solution` returns for the test vector.
+pub const TEST_VECTOR_NONCE:      u64 = 204;
+/// Hash of `"ironshield_self_test:204"`.
+pub const TEST_VECTOR_HASH:       &str = "0035560d408b7db374c178dea965a68a6710e40bfea8fdbe9222f8e2325cc4b9";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate_hash, find_solution, verify_solution};
+
+    #[test]
+    fn test_vector_matches_solver() {
+        assert_eq!(
+            find_solution(TEST_VECTOR_CHALLENGE, TEST_VECTOR_DIFFICULTY),
+            Ok((TEST_VECTOR_NONCE, TEST_VECTOR_HASH.to_string()))
+        );
+        assert_eq!(calculate_hash(TEST_VECTOR_CHALLENGE, TEST_VECTOR_NONCE), TEST_VECTOR_HASH);
+        assert!(verify_solution(TEST_VECTOR_CHALLENGE, &TEST_VECTOR_NONCE.to_string(), TEST_VECTOR_DIFFICULTY));
+    }
+}
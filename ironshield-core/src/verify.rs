@@ -8,43 +8,116 @@ use hex;
 use sha2::{Digest, Sha256};
 use ironshield_types::*;
 
+use crate::hasher::{PowHasher, Sha256Hasher};
+use crate::metrics;
+use crate::solve::{calculate_hash_bytes, leading_zero_bits};
+
+/// Fewest leading zero bits a leading-zero solution must have, whatever
+/// difficulty it is verified against.
+///
+/// A defense-in-depth floor: a challenge whose stated difficulty was
+/// bugged or downgraded to zero still cannot be passed with a zero-work
+/// nonce. It is one hex digit, the lowest difficulty the worker issues
+/// (`MIN_POW_DIFFICULTY`), so solutions to properly issued challenges
+/// always clear it; raise both together.
+pub const MIN_WORK_BITS: u32 = 4;
+
 /// Verify that a given nonce produces a valid solution for the challenge.
 ///
 /// # Arguments
 /// * `challenge` - The original challenge string.
-/// * `nonce_str` - The proposed nonce as a string (will be parsed to u64).
+/// * `nonce_str` - The proposed nonce, in decimal or `0x`-prefixed hex (see `parse_nonce`).
 /// * `difficulty` - Required number of leading zeros in the hash.
 ///
 /// # Returns
 /// * `true` - If the nonce produces a hash meeting the difficulty requirement
-/// * `false` - If nonce is invalid, hash doesn't meet the requirement, or parsing fails.
+///   and `MIN_WORK_BITS`
+/// * `false` - If the challenge is empty, the nonce is invalid, the hash
+///   doesn't meet the requirement, or parsing fails.
 ///
 /// # Safety
 /// This function handles invalid nonce strings gracefully by returning false.
 pub fn verify_solution(challenge: &str, nonce_str: &str, difficulty: usize) -> bool {
-    nonce_str
-        .parse::<u64>()
-        .map(|nonce| {
-            let hash = calculate_hash(challenge, nonce);
-            hash.starts_with(&"0".repeat(difficulty))
+    verify_solution_with_hasher::<Sha256Hasher>(challenge, nonce_str, difficulty)
+}
+
+/// Verify a nonce like `verify_solution`, hashing with the backend `H`.
+///
+/// `H` must be the backend the client solved with, see
+/// `find_solution_with_hasher`.
+///
+/// # Arguments
+/// * `challenge` - The original challenge string.
+/// * `nonce_str` - The proposed nonce, in decimal or `0x`-prefixed hex (see `parse_nonce`).
+/// * `difficulty` - Required number of leading zeros in the hash.
+///
+/// # Returns
+/// * `true` - If the nonce's `H` hash meets the difficulty requirement
+///   and `MIN_WORK_BITS`
+/// * `false` - If the challenge is empty, the nonce is invalid, or the hash
+///   doesn't meet the requirement.
+pub fn verify_solution_with_hasher<H: PowHasher>(challenge: &str, nonce_str: &str, difficulty: usize) -> bool {
+    if challenge.is_empty() {
+        return metrics::record_verification(false);
+    }
+
+    let valid: bool = parse_nonce(nonce_str)
+        .map(|nonce: u64| {
+            metrics::record_hashes(1);
+            let zero_bits: u32 = leading_zero_bits(&H::hash(challenge, nonce));
+            zero_bits as usize >= difficulty.saturating_mul(4) && zero_bits >= MIN_WORK_BITS
         })
-        .unwrap_or(false)
+        .unwrap_or(false);
+    metrics::record_verification(valid)
 }
 
-/// Calculate the SHA-256 hash for a given challenge and nonce combination.
+/// Verify a nonce like `verify_solution`, with the difficulty's unit
+/// explicit so a bit difficulty is never checked as hex digits or vice
+/// versa.
 ///
-/// The input format is "challenge:nonce" (e.g., "hello_world:12345").
+/// # Arguments
+/// * `challenge` - The original challenge string.
+/// * `nonce_str` - The proposed nonce, in decimal or `0x`-prefixed hex (see `parse_nonce`).
+/// * `difficulty` - Required leading zeros, in hex digits or bits.
+///
+/// # Returns
+/// * `true` - If the nonce's hash has at least `difficulty.required_bits()`
+///   and `MIN_WORK_BITS` leading zero bits.
+/// * `false` - If the challenge is empty, the nonce is invalid, or the hash
+///   doesn't meet the requirement.
+pub fn verify_solution_with_difficulty(challenge: &str, nonce_str: &str, difficulty: Difficulty) -> bool {
+    if challenge.is_empty() {
+        return metrics::record_verification(false);
+    }
+
+    let valid: bool = parse_nonce(nonce_str)
+        .map(|nonce: u64| {
+            metrics::record_hashes(1);
+            leading_zero_bits(&calculate_hash_bytes(challenge, nonce)) >= difficulty.required_bits().max(MIN_WORK_BITS)
+        })
+        .unwrap_or(false);
+    metrics::record_verification(valid)
+}
+
+/// Parses a submitted nonce.
+///
+/// Decimal is the default format (what the WASM solver sends); a `0x` or
+/// `0X` prefix selects hex for clients that prefer it.
 ///
 /// # Arguments
-/// * `challenge` - The challenge string.
-/// * `nonce` - The nonce value to try.
+/// * `nonce_str` - The nonce, e.g. `"12345"` or `"0x3039"`.
 ///
 /// # Returns
-/// * Hexadecimal string representation of the SHA-256 hash (64 chars long).
-fn calculate_hash(challenge: &str, nonce: u64) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(format!("{}:{}", challenge, nonce).as_bytes());
-    hex::encode(hasher.finalize())
+/// * `Some(nonce)` if the string is a valid `u64` in either format, `None` otherwise.
+pub fn parse_nonce(nonce_str: &str) -> Option<u64> {
+    match nonce_str.strip_prefix("0x").or_else(|| nonce_str.strip_prefix("0X")) {
+        // `from_str_radix` would also accept a leading sign.
+        Some(hex_digits) if hex_digits.bytes().all(|b: u8| b.is_ascii_hexdigit()) => {
+            u64::from_str_radix(hex_digits, 16).ok()
+        }
+        Some(_) => None,
+        None => nonce_str.parse::<u64>().ok(),
+    }
 }
 
 /// Verify that a solution is valid for a given IronShieldChallenge.
@@ -58,28 +131,192 @@ fn calculate_hash(challenge: &str, nonce: u64) -> String {
 /// 
 /// # Returns
 /// * `true` if the nonce produces a hash less than the challenge_param
-/// * `false` if the nonce is invalid or doesn't meet the requirement
+/// * `false` if the nonce is invalid or doesn't meet the requirement, or
+///   the challenge's `difficulty` disagrees with its `challenge_param`
 pub fn verify_ironshield_solution(challenge: &IronShieldChallenge, nonce: i64) -> bool {
-    // Parse the random_nonce from hex string to bytes
-    let random_nonce_bytes = match hex::decode(&challenge.random_nonce) {
-        Ok(bytes) => bytes,
-        Err(_) => return false, // Invalid hex string
+    if !challenge.difficulty_matches_param() {
+        return metrics::record_verification(false);
+    }
+    verify_ironshield_solution_borrowed(&challenge.random_nonce, &challenge.challenge_param, nonce)
+}
+
+/// Verify a solution against borrowed challenge fields, without building
+/// an owned `IronShieldChallenge` or allocating.
+/// 
+/// Only the proof of work is checked; callers that need the signature or
+/// expiry checked must still parse the full challenge.
+/// 
+/// # Arguments
+/// * `random_nonce` - The challenge's hex-encoded `random_nonce`
+/// * `challenge_param` - The challenge's threshold
+/// * `nonce` - The proposed solution nonce
+/// 
+/// # Returns
+/// * `true` if the nonce produces a hash less than `challenge_param`
+/// * `false` if `random_nonce` is not valid hex or the hash is too large
+pub fn verify_ironshield_solution_borrowed(random_nonce: &str, challenge_param: &[u8; 32], nonce: i64) -> bool {
+    let Some(hash_bytes) = ironshield_hash(random_nonce, nonce) else {
+        return metrics::record_verification(false); // Invalid hex string
     };
-    
-    // Convert nonce to little-endian bytes
-    let nonce_bytes: [u8; 8] = nonce.to_le_bytes();
-    
-    // Use the same optimized hashing approach as the main function
-    let mut hasher = Sha256::new();
-    hasher.update(&random_nonce_bytes);  // First part of the input
-    hasher.update(&nonce_bytes);         // Second part of the input
-    let hash_result = hasher.finalize();
-    
-    // Convert hash to [u8; 32] for comparison
-    let hash_bytes: [u8; 32] = hash_result.into();
-    
+
     // Compare with the challenge parameter
-    hash_bytes < challenge.challenge_param
+    metrics::record_hashes(1);
+    metrics::record_verification(hash_bytes < *challenge_param)
+}
+
+/// Verify that a solution is valid for a given IronShieldChallenge and
+/// that the challenge may be spent on `resource`.
+/// 
+/// # Arguments
+/// * `challenge` - The original IronShieldChallenge
+/// * `nonce` - The proposed solution nonce
+/// * `resource` - The resource being unlocked, e.g. a request path
+/// 
+/// # Returns
+/// * `true` if the challenge is unbound or bound to `resource`, and the
+///   nonce solves it
+/// * `false` otherwise
+pub fn verify_ironshield_solution_for_resource(challenge: &IronShieldChallenge, nonce: i64, resource: &str) -> bool {
+    challenge.is_bound_to(resource) && verify_ironshield_solution(challenge, nonce)
+}
+
+/// Measures how far below the threshold a solution's hash landed.
+/// 
+/// Solutions are found by trying nonces in order, so their margins are
+/// spread across the whole range below the threshold. Margins from many
+/// clients that cluster near zero suggest solutions drawn from a shared
+/// precomputed table rather than solved independently.
+/// 
+/// # Arguments
+/// * `challenge` - The original IronShieldChallenge
+/// * `nonce` - The proposed solution nonce
+/// 
+/// # Returns
+/// * `Some([u8; 32])` - `challenge_param - hash` as a big-endian integer,
+///   greater than zero, if the nonce solves the challenge
+/// * `None` - if the nonce is invalid or `random_nonce` is not valid hex
+pub fn solution_margin(challenge: &IronShieldChallenge, nonce: i64) -> Option<[u8; 32]> {
+    let hash_bytes: [u8; 32] = ironshield_hash(&challenge.random_nonce, nonce)?;
+    metrics::record_hashes(1);
+    if hash_bytes >= challenge.challenge_param {
+        return None;
+    }
+
+    // Big-endian subtraction, least significant byte first.
+    let mut margin: [u8; 32] = [0u8; 32];
+    let mut borrow: bool = false;
+    for index in (0..32).rev() {
+        let (difference, underflow) = challenge.challenge_param[index].overflowing_sub(hash_bytes[index]);
+        let (difference, borrow_underflow) = difference.overflowing_sub(borrow as u8);
+        margin[index] = difference;
+        borrow = underflow || borrow_underflow;
+    }
+    Some(margin)
+}
+
+/// Computes `SHA256(hex::decode(random_nonce) || nonce.to_le_bytes())`,
+/// decoding the random_nonce in stack-sized chunks straight into the
+/// hasher.
+/// 
+/// # Returns
+/// * `Option<[u8; 32]>` - The hash, or `None` if `random_nonce` is not
+///   valid hex
+fn ironshield_hash(random_nonce: &str, nonce: i64) -> Option<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    for hex_chunk in random_nonce.as_bytes().chunks(64) {
+        let mut buffer: [u8; 32] = [0u8; 32];
+        let decoded: &mut [u8] = &mut buffer[..hex_chunk.len() / 2];
+        hex::decode_to_slice(hex_chunk, decoded).ok()?;
+        hasher.update(decoded);
+    }
+    hasher.update(nonce.to_le_bytes());
+    Some(hasher.finalize().into())
+}
+
+/// Verify a solution against a challenge in its `concat_struct` form,
+/// e.g. as decoded from the `X-IronShield-Challenge-Data` header, reading
+/// only the `random_nonce` and `challenge_param` slices.
+/// 
+/// # Arguments
+/// * `concat_str` - The `|`-separated challenge
+/// * `nonce` - The proposed solution nonce
+/// 
+/// # Returns
+/// * `true` if the nonce solves the challenge
+/// * `false` if the string does not have the seven challenge fields
+///   (up to nine with the optional ones), a carried difficulty disagrees
+///   with the challenge param, or the solution is invalid
+pub fn verify_ironshield_concat(concat_str: &str, nonce: i64) -> bool {
+    let mut parts = concat_str.split('|');
+    let random_nonce: Option<&str> = parts.next();
+    let challenge_param_hex: Option<&str> = parts.nth(3);
+    let difficulty: Option<&str> = parts.clone().nth(3);
+    let field_count: usize = 5 + parts.count();
+
+    let mut challenge_param: [u8; 32] = [0u8; 32];
+    match (random_nonce, challenge_param_hex) {
+        (Some(random_nonce), Some(challenge_param_hex))
+            if (7..=9).contains(&field_count)
+                && hex::decode_to_slice(challenge_param_hex, &mut challenge_param).is_ok()
+                && difficulty.is_none_or(|difficulty: &str| difficulty_matches_param(difficulty, &challenge_param)) =>
+        {
+            verify_ironshield_solution_borrowed(random_nonce, &challenge_param, nonce)
+        }
+        _ => metrics::record_verification(false),
+    }
+}
+
+/// Checks a concatenated challenge's `difficulty` field against its param,
+/// the same way `IronShieldChallenge::difficulty_matches_param` does.
+fn difficulty_matches_param(difficulty: &str, challenge_param: &[u8; 32]) -> bool {
+    match difficulty.parse::<u64>() {
+        Ok(difficulty) if difficulty > 0 => IronShieldChallenge::difficulty_to_challenge_param(difficulty) == *challenge_param,
+        _ => false,
+    }
+}
+
+/// Verify a response against a stored challenge using the response's
+/// `challenge_id`.
+///
+/// The id is checked first so a response cannot be replayed against a
+/// different challenge in an audit trail; only then is the solution
+/// itself verified.
+///
+/// # Arguments
+/// * `challenge` - The stored IronShieldChallenge.
+/// * `response`  - The response carrying the solution and challenge id.
+///
+/// # Returns
+/// * `true` if the response's id matches `challenge` and the solution is valid.
+/// * `false` if the id is missing, does not match, or the solution is invalid.
+pub fn verify_against_id(challenge: &IronShieldChallenge, response: &IronShieldChallengeResponse) -> bool {
+    match response.challenge_id {
+        Some(challenge_id) if challenge_id == challenge.challenge_id() => {
+            verify_ironshield_solution(challenge, response.solution)
+        }
+        _ => false,
+    }
+}
+
+/// Verify a device-bound response: the proof-of-work solution and the
+/// client's signature over it.
+///
+/// Layers device binding (e.g. a WebAuthn passkey) on top of PoW. The
+/// response must echo the challenge's signature, so a signed solution
+/// cannot be moved to another challenge.
+///
+/// # Arguments
+/// * `challenge` - The original IronShieldChallenge.
+/// * `response`  - The response carrying the solution and client signature.
+///
+/// # Returns
+/// * `true` if the solution is valid and the client signature verifies.
+/// * `false` if the response targets another challenge, the solution is
+///   invalid, or the client signature is missing or forged.
+pub fn verify_with_client_key(challenge: &IronShieldChallenge, response: &IronShieldChallengeResponse) -> bool {
+    response.challenge_signature == challenge.challenge_signature
+        && verify_ironshield_solution(challenge, response.solution)
+        && verify_client_signature(response).is_ok()
 }
 
 #[cfg(test)]
@@ -98,6 +335,60 @@ mod tests {
         assert!(!verify_solution(challenge, "999999", difficulty));
     }
 
+    #[test]
+    fn test_parse_nonce_formats() {
+        assert_eq!(parse_nonce("12345"), Some(12345));
+        assert_eq!(parse_nonce("0x3039"), Some(12345));
+        assert_eq!(parse_nonce("0X3039"), Some(12345));
+        assert_eq!(parse_nonce("0x"), None);
+        assert_eq!(parse_nonce("0x+1"), None);
+        assert_eq!(parse_nonce("3039h"), None);
+        assert_eq!(parse_nonce("-1"), None);
+    }
+
+    #[test]
+    fn test_verify_solution_hex_nonce() {
+        let challenge = "test_challenge";
+        for difficulty in 0..3 {
+            assert_eq!(
+                verify_solution(challenge, "0x3039", difficulty),
+                verify_solution(challenge, "12345", difficulty)
+            );
+        }
+
+        let (nonce, _) = find_solution(challenge, 2).unwrap();
+        assert!(verify_solution(challenge, &format!("{:#x}", nonce), 2));
+    }
+
+    #[test]
+    fn test_verify_solution_empty_challenge() {
+        // At difficulty 0 only the work floor applies, so a nonce meeting
+        // it fails only the empty check.
+        let (nonce, _) = find_solution("test_challenge", 1).unwrap();
+        assert!(verify_solution("test_challenge", &nonce.to_string(), 0));
+        assert!(!verify_solution("", &nonce.to_string(), 0));
+    }
+
+    #[test]
+    fn test_solutions_below_work_floor_are_rejected() {
+        let challenge: &str = "work_floor_challenge";
+        let zero_bits = |nonce: u64| leading_zero_bits(&calculate_hash_bytes(challenge, nonce));
+
+        // Nonces that meet a too-low stated difficulty but not the floor.
+        let zero_work: u64 = (0..).find(|nonce: &u64| zero_bits(*nonce) == 0).unwrap();
+        let low_work: u64 = (0..).find(|nonce: &u64| (1..MIN_WORK_BITS).contains(&zero_bits(*nonce))).unwrap();
+        for nonce in [zero_work, low_work] {
+            assert!(!verify_solution(challenge, &nonce.to_string(), 0), "nonce {}", nonce);
+            assert!(!verify_solution_with_difficulty(challenge, &nonce.to_string(), Difficulty::Bits(0)));
+            assert!(!verify_solution_with_difficulty(challenge, &nonce.to_string(), Difficulty::Bits(1)));
+        }
+
+        // A nonce at the floor passes a stated difficulty below it.
+        let floor: u64 = (0..).find(|nonce: &u64| zero_bits(*nonce) >= MIN_WORK_BITS).unwrap();
+        assert!(verify_solution(challenge, &floor.to_string(), 0));
+        assert!(verify_solution_with_difficulty(challenge, &floor.to_string(), Difficulty::Bits(1)));
+    }
+
     #[test]
     fn test_verify_solution_invalid_nonce() {
         let challenge = "test_challenge";
@@ -151,6 +442,131 @@ mod tests {
                 "Challenge with invalid hex should fail verification");
     }
 
+    #[test]
+    fn test_mismatched_difficulty_is_rejected() {
+        let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "cafe1234".to_string(),
+            1000000,
+            "test_website".to_string(),
+            IronShieldChallenge::difficulty_to_challenge_param(2),
+            [0x00; 32],
+            [0x22; 64],
+        );
+        let nonce: i64 = crate::solve::find_solution_single_threaded(&challenge).unwrap().solution;
+
+        challenge.difficulty = Some(2);
+        assert!(verify_ironshield_solution(&challenge, nonce));
+        assert!(verify_ironshield_concat(&challenge.concat_struct(), nonce));
+
+        // The same valid solution is rejected once the two encodings diverge.
+        challenge.difficulty = Some(4);
+        assert!(!verify_ironshield_solution(&challenge, nonce));
+        assert!(!verify_ironshield_concat(&challenge.concat_struct(), nonce));
+
+        challenge.resource_hash = Some([0x33; 32]);
+        assert!(!verify_ironshield_concat(&challenge.concat_struct(), nonce));
+        challenge.difficulty = Some(2);
+        assert!(verify_ironshield_concat(&challenge.concat_struct(), nonce));
+    }
+
+    #[test]
+    fn test_borrowed_verifiers_agree_with_owned_path() {
+        // A random_nonce spanning several decode chunks, with a threshold
+        // roughly half of all hashes meet.
+        let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "0123456789abcdef".repeat(9),
+            1000000,
+            "test_website".to_string(),
+            [0x80; 32],
+            [0x00; 32],
+            [0x22; 64],
+        );
+        let reference = |challenge: &IronShieldChallenge, nonce: i64| {
+            let mut hasher = Sha256::new();
+            hasher.update(hex::decode(&challenge.random_nonce).unwrap());
+            hasher.update(nonce.to_le_bytes());
+            <[u8; 32]>::from(hasher.finalize()) < challenge.challenge_param
+        };
+
+        for nonce in -8..64 {
+            let owned: bool = verify_ironshield_solution(&challenge, nonce);
+            assert_eq!(owned, reference(&challenge, nonce));
+            assert_eq!(verify_ironshield_solution_borrowed(&challenge.random_nonce, &challenge.challenge_param, nonce), owned);
+            assert_eq!(verify_ironshield_concat(&challenge.concat_struct(), nonce), owned);
+        }
+
+        // Malformed input fails on every path.
+        for random_nonce in ["abc", "zz", &"0".repeat(65)] {
+            challenge.random_nonce = random_nonce.to_string();
+            assert!(!verify_ironshield_solution(&challenge, 0));
+            assert!(!verify_ironshield_concat(&challenge.concat_struct(), 0));
+        }
+        assert!(!verify_ironshield_concat("cafe|1|2|site|80", 0));
+        assert!(!verify_ironshield_concat("cafe|1|2|site|not_hex|00|00", 0));
+        assert!(!verify_ironshield_concat("cafe|1|2|site|80|00|00|00|00", 0));
+    }
+
+    #[test]
+    fn test_solution_bound_to_one_resource_is_rejected_for_another() {
+        let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1000000,
+            "test_website".to_string(),
+            [0x80; 32],
+            [0x00; 32],
+            [0x00; 64],
+        );
+        challenge.resource_hash = Some(IronShieldChallenge::resource_hash_for("/reports/a.pdf"));
+        let nonce: i64 = (0..64).find(|nonce: &i64| verify_ironshield_solution(&challenge, *nonce)).unwrap();
+
+        assert!(verify_ironshield_solution_for_resource(&challenge, nonce, "/reports/a.pdf"));
+        assert!(!verify_ironshield_solution_for_resource(&challenge, nonce, "/reports/b.pdf"));
+        assert!(verify_ironshield_concat(&challenge.concat_struct(), nonce));
+
+        // Unbound challenges unlock any resource.
+        challenge.resource_hash = None;
+        assert!(verify_ironshield_solution_for_resource(&challenge, nonce, "/reports/b.pdf"));
+    }
+
+    #[test]
+    fn test_solution_margin() {
+        let challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1000000,
+            "test_website".to_string(),
+            [0x80; 32],
+            [0x00; 32],
+            [0x00; 64],
+        );
+
+        let (mut valid, mut invalid): (usize, usize) = (0, 0);
+        for nonce in 0..64 {
+            let margin: Option<[u8; 32]> = solution_margin(&challenge, nonce);
+            assert_eq!(margin.is_some(), verify_ironshield_solution(&challenge, nonce));
+            let Some(margin) = margin else {
+                invalid += 1;
+                continue;
+            };
+            valid += 1;
+            assert_ne!(margin, [0u8; 32], "valid solutions land strictly below the threshold");
+
+            // hash + margin == threshold
+            let hash: [u8; 32] = ironshield_hash(&challenge.random_nonce, nonce).unwrap();
+            let mut sum: [u8; 32] = [0u8; 32];
+            let mut carry: u16 = 0;
+            for index in (0..32).rev() {
+                let total: u16 = hash[index] as u16 + margin[index] as u16 + carry;
+                sum[index] = total as u8;
+                carry = total >> 8;
+            }
+            assert_eq!((sum, carry), (challenge.challenge_param, 0));
+        }
+        assert!(valid > 0 && invalid > 0, "both cases are exercised");
+
+        let malformed: IronShieldChallenge = IronShieldChallenge { random_nonce: "zz".to_string(), ..challenge };
+        assert_eq!(solution_margin(&malformed, 0), None);
+    }
+
     #[test]
     fn test_verify_ironshield_solution_edge_cases() {
         // Test with very easy challenge (all 0xFF)
@@ -183,4 +599,120 @@ mod tests {
         assert!(!verify_ironshield_solution(&impossible_challenge, 1));
         assert!(!verify_ironshield_solution(&impossible_challenge, 12345));
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_verify_against_id() {
+        let challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "cafe1234".to_string(),
+            1000000,
+            "test_website".to_string(),
+            [0x80; 32],
+            [0x00; 32],
+            [0x22; 64],
+        );
+        let solution: i64 = crate::solve::find_solution_single_threaded(&challenge).unwrap().solution;
+
+        let response: IronShieldChallengeResponse = IronShieldChallengeResponse::new_with_challenge_id(
+            challenge.challenge_signature,
+            solution,
+            challenge.challenge_id(),
+        );
+        assert!(verify_against_id(&challenge, &response));
+
+        // A response without an id cannot be correlated.
+        let no_id: IronShieldChallengeResponse = IronShieldChallengeResponse::new(challenge.challenge_signature, solution);
+        assert!(!verify_against_id(&challenge, &no_id));
+    }
+
+    #[test]
+    fn test_verify_against_id_rejects_wrong_id() {
+        let challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "cafe1234".to_string(),
+            1000000,
+            "test_website".to_string(),
+            [0xFF; 32], // Any nonce is a valid solution
+            [0x00; 32],
+            [0x22; 64],
+        );
+        let mut other: IronShieldChallenge = challenge.clone();
+        other.website_id = "other_website".to_string();
+
+        let response: IronShieldChallengeResponse = IronShieldChallengeResponse::new_with_challenge_id(
+            challenge.challenge_signature,
+            0,
+            other.challenge_id(),
+        );
+        assert!(verify_ironshield_solution(&challenge, response.solution));
+        assert!(!verify_against_id(&challenge, &response));
+    }
+
+    fn client_bound_response() -> (IronShieldChallenge, IronShieldChallengeResponse) {
+        let challenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1000000,
+            "test_website".to_string(),
+            [0xFF; 32],
+            [0x00; 32],
+            [0x11; 64],
+        );
+        let mut response = crate::solve::find_solution_single_threaded(&challenge).unwrap();
+        attach_client_signature(&mut response, &[0x07; 32]);
+        (challenge, response)
+    }
+
+    #[test]
+    fn test_verify_with_client_key_valid() {
+        let (challenge, response) = client_bound_response();
+        assert!(verify_with_client_key(&challenge, &response));
+
+        // The client fields survive the JSON form.
+        let json: String = serde_json::to_string(&response).unwrap();
+        let parsed: IronShieldChallengeResponse = serde_json::from_str(&json).unwrap();
+        assert!(verify_with_client_key(&challenge, &parsed));
+    }
+
+    #[test]
+    fn test_verify_with_client_key_forged() {
+        let (challenge, response) = client_bound_response();
+
+        let mut forged = response.clone();
+        forged.client_signature = Some([0x42; 64]);
+        assert!(!verify_with_client_key(&challenge, &forged));
+
+        // A valid signature from a different key over another solution.
+        let mut swapped = response.clone();
+        swapped.solution += 1;
+        attach_client_signature(&mut swapped, &[0x08; 32]);
+        swapped.client_public_key = response.client_public_key;
+        assert!(!verify_with_client_key(&challenge, &swapped));
+
+        let mut unsigned = response.clone();
+        unsigned.client_signature = None;
+        assert!(!verify_with_client_key(&challenge, &unsigned));
+    }
+
+    #[test]
+    fn test_leading_zeros_param_accepts_solutions_of_both_schemes() {
+        let zeros: usize = 4;
+        let param: [u8; 32] = leading_zeros_to_param(zeros);
+
+        // A leading-zero solution's hash is also below the threshold.
+        let (nonce, hash) = crate::solve::find_solution_bytes("migration_challenge", zeros).unwrap();
+        assert!(verify_solution("migration_challenge", &nonce.to_string(), zeros));
+        assert!(hash < param);
+
+        // A threshold solution's hash also has the leading zeros.
+        let challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "cafe1234".to_string(),
+            chrono::Utc::now().timestamp_millis(),
+            "example.com".to_string(),
+            param,
+            [0x00; 32],
+            [0x00; 64],
+        );
+        let solution: i64 = crate::solve::find_solution_single_threaded(&challenge).unwrap().solution;
+        assert!(verify_ironshield_solution(&challenge, solution));
+        let hash: [u8; 32] = ironshield_hash(&challenge.random_nonce, solution).unwrap();
+        assert!(leading_zero_bits(&hash) as usize >= zeros * 4);
+    }
+}
@@ -1,6 +1,23 @@
-use crate::serde_utils::{serialize_signature, deserialize_signature, serialize_32_bytes, deserialize_32_bytes};
+use crate::serde_utils::{
+    canonical_json_object,
+    serialize_signature,
+    deserialize_signature,
+    serialize_32_bytes,
+    deserialize_32_bytes,
+    serialize_optional_32_bytes,
+    deserialize_optional_32_bytes,
+};
 use chrono::Utc;
+use rand_core::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// How long a challenge created by `IronShieldChallenge::new` stays
+/// valid, in milliseconds.
+pub const DEFAULT_CHALLENGE_TTL_MS: i64 = 30_000;
+
+/// Number of cells in `IronShieldChallenge::difficulty_bar`.
+const DIFFICULTY_BAR_WIDTH: usize = 10;
 
 /// IronShield Challenge structure for the proof-of-work algorithm
 /// 
@@ -12,6 +29,8 @@ use serde::{Deserialize, Serialize};
 /// * `website_id`:           The identifier of the website.
 /// * `public_key`:           Ed25519 public key for signature verification.
 /// * `challenge_signature`:  Ed25519 signature over the challenge data.
+/// * `resource_hash`:        Optional SHA-256 of the one resource the challenge unlocks (signed when set).
+/// * `difficulty`:           Optional difficulty `challenge_param` was derived from (signed when set).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IronShieldChallenge {
     pub random_nonce:        String,
@@ -34,10 +53,23 @@ pub struct IronShieldChallenge {
         deserialize_with = "deserialize_signature"
     )]
     pub challenge_signature: [u8; 64],
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_32_bytes",
+        deserialize_with = "deserialize_optional_32_bytes"
+    )]
+    pub resource_hash:       Option<[u8; 32]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty:          Option<u64>,
 }
 
 impl IronShieldChallenge {
-    /// Constructor for creating a new IronShieldChallenge instance.
+    /// Constructor for creating a new IronShieldChallenge instance valid
+    /// for `DEFAULT_CHALLENGE_TTL_MS`.
+    ///
+    /// See `IronShieldChallenge::builder` for a less error-prone way to
+    /// build a challenge from a difficulty.
     pub fn new(
         random_nonce:     String,
         created_time:     i64,
@@ -45,19 +77,98 @@ impl IronShieldChallenge {
         challenge_param:  [u8; 32],
         public_key:       [u8; 32],
         signature:        [u8; 64],
+    ) -> Self {
+        Self::new_with_ttl(
+            random_nonce,
+            created_time,
+            website_id,
+            challenge_param,
+            public_key,
+            signature,
+            DEFAULT_CHALLENGE_TTL_MS,
+        )
+    }
+
+    /// Constructor like `new`, valid for `ttl_ms` milliseconds after
+    /// `created_time` instead of `DEFAULT_CHALLENGE_TTL_MS`.
+    pub fn new_with_ttl(
+        random_nonce:     String,
+        created_time:     i64,
+        website_id:       String,
+        challenge_param:  [u8; 32],
+        public_key:       [u8; 32],
+        signature:        [u8; 64],
+        ttl_ms:           i64,
     ) -> Self {
         Self {
             random_nonce,
             created_time,
             website_id,
-            expiration_time: created_time + 30_000, // 30 seconds
+            expiration_time: created_time.saturating_add(ttl_ms),
             challenge_param,
             recommended_attempts: 0, // This will be set later
             public_key,
             challenge_signature: signature,
+            resource_hash: None,
+            difficulty: None,
         }
     }
 
+    /// Starts building a challenge with `IronShieldChallengeBuilder`.
+    pub fn builder() -> IronShieldChallengeBuilder {
+        IronShieldChallengeBuilder::default()
+    }
+
+    /// Checks that the challenge is structurally usable.
+    ///
+    /// The `website_id` is the audience the challenge is bound to, so an
+    /// empty (or whitespace-only) value would silently disable audience
+    /// checks and is rejected, as is a `difficulty` that disagrees with
+    /// `challenge_param`. This does not check the signature or expiry;
+    /// see `crypto::validate_challenge` for the full check.
+    ///
+    /// # Returns
+    /// * `Result<(), String>`: `Ok(())` if the challenge is well-formed,
+    ///   or a description of the first problem found.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.random_nonce.is_empty() {
+            return Err("Empty random_nonce".to_string());
+        }
+
+        if self.website_id.trim().is_empty() {
+            return Err("Empty website_id".to_string());
+        }
+
+        if !self.difficulty_matches_param() {
+            return Err("difficulty does not match challenge_param".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Checks the redundant `difficulty` field against `challenge_param`.
+    ///
+    /// A challenge carrying its difficulty must have exactly the param
+    /// `difficulty_to_challenge_param` derives from it, so a param altered
+    /// on its own (or a corrupted difficulty) is caught. Challenges without
+    /// the field always match.
+    ///
+    /// # Returns
+    /// * `bool`: `true` if `difficulty` is unset or agrees with `challenge_param`.
+    pub fn difficulty_matches_param(&self) -> bool {
+        self.difficulty.is_none_or(|difficulty: u64| {
+            difficulty > 0 && Self::difficulty_to_challenge_param(difficulty) == self.challenge_param
+        })
+    }
+
+    /// Returns how long this challenge is valid for, in milliseconds.
+    ///
+    /// # Returns
+    /// * `i64`: `expiration_time - created_time`.
+    pub fn ttl_ms(&self) -> i64 {
+        self.expiration_time - self.created_time
+    }
+
     /// Converts a difficulty value (expected number of attempts) to a challenge_param.
     ///
     /// The difficulty represents the expected number of hash attempts needed to find a valid nonce
@@ -136,14 +247,83 @@ impl IronShieldChallenge {
         result
     }
 
+    /// Converts a challenge_param back to a difficulty (expected number of attempts).
+    ///
+    /// This is the inverse of `difficulty_to_challenge_param`:
+    /// difficulty = 2^256 / challenge_param, rounded to the nearest integer
+    /// and computed with integer division on the param's leading 64 bits.
+    ///
+    /// # Arguments
+    /// * `challenge_param`: The challenge_param bytes in big-endian format.
+    ///
+    /// # Returns
+    /// * `u64`: The expected number of attempts, at least 1. An all-zero
+    ///   challenge_param has no solution and returns `u64::MAX`.
+    ///
+    /// # Examples
+    /// * challenge_param = [0xFF; 32] → difficulty = 1
+    /// * challenge_param = [0x80, 0x00, ...] → difficulty = 2
+    pub fn challenge_param_to_difficulty(challenge_param: &[u8; 32]) -> u64 {
+        let high: u128 = u128::from_be_bytes(challenge_param[..16].try_into().unwrap());
+
+        // Below 2^192 (which includes zero) the quotient exceeds u64.
+        if high >> 64 == 0 {
+            return u64::MAX;
+        }
+
+        // With the top bit at position `top` (>= 192), dividing by the
+        // param's leading 64 bits `divisor` scales 2^256 down to
+        // 2^(319 - top), which fits in u128. The dropped low bits change
+        // the quotient by under 2^-63 relative, i.e. not at all after
+        // rounding for any u64 result.
+        let top: u32 = 255 - high.leading_zeros();
+        let divisor: u128 = high >> (top - 191);
+        let numerator: u128 = 1u128 << (319 - top);
+        let difficulty: u128 = (numerator + divisor / 2) / divisor;
+        u64::try_from(difficulty).unwrap_or(u64::MAX).max(1)
+    }
+
+    /// Returns the difficulty a challenge minted for `requested` actually
+    /// carries.
+    ///
+    /// `difficulty_to_challenge_param` rounds to the nearest power of two,
+    /// so the effective difficulty can be up to ~41% above or ~29% below
+    /// the requested one. Comparing the two shows operators the rounding
+    /// error of a configured difficulty.
+    ///
+    /// # Arguments
+    /// * `requested`: The requested difficulty, must be at least 1.
+    ///
+    /// # Returns
+    /// * `u64`: `challenge_param_to_difficulty` of the param produced
+    ///   for `requested`.
+    ///
+    /// # Examples
+    /// * requested = 1,000 → effective = 1,024
+    /// * requested = 50,000 → effective = 65,536
+    pub fn effective_difficulty(requested: u64) -> u64 {
+        Self::challenge_param_to_difficulty(&Self::difficulty_to_challenge_param(requested))
+    }
+
     /// Check if the challenge has expired.
     pub fn is_expired(&self) -> bool {
-        Utc::now().timestamp_millis() > self.expiration_time
+        self.is_expired_at(Utc::now().timestamp_millis())
+    }
+
+    /// Checks like `is_expired`, at the given time in Unix milliseconds.
+    pub fn is_expired_at(&self, now_millis: i64) -> bool {
+        now_millis > self.expiration_time
     }
 
     /// Returns the remaining time until expiration in milliseconds.
     pub fn time_until_expiration(&self) -> i64 {
-        self.expiration_time - Utc::now().timestamp_millis()
+        self.time_until_expiration_at(Utc::now().timestamp_millis())
+    }
+
+    /// Returns like `time_until_expiration`, at the given time in Unix
+    /// milliseconds.
+    pub fn time_until_expiration_at(&self, now_millis: i64) -> i64 {
+        self.expiration_time - now_millis
     }
 
     /// Returns the recommended number of attempts to expect for a given difficulty.
@@ -173,6 +353,96 @@ impl IronShieldChallenge {
         self.recommended_attempts = Self::recommended_attempts(difficulty);
     }
 
+    /// Returns the human-readable difficulty band of this challenge,
+    /// derived from `challenge_param` via `challenge_param_to_difficulty`.
+    pub fn difficulty_class(&self) -> crate::DifficultyClass {
+        crate::DifficultyClass::from_difficulty(Self::challenge_param_to_difficulty(&self.challenge_param))
+    }
+
+    /// Renders the difficulty as a short gauge for log lines, e.g.
+    /// `[####------] Medium (~65k attempts)`.
+    ///
+    /// Each filled cell stands for one leading zero hex digit's worth of
+    /// work (16x the attempts, see `expected_attempts_to_leading_zeros`),
+    /// so the bar grows on the same log scale as the difficulty bands.
+    /// It is full from 10 digits (~1.1T attempts) up.
+    ///
+    /// # Returns
+    /// * `String`: The gauge, the `DifficultyClass` label, and the
+    ///   expected attempts rounded down to a metric prefix.
+    pub fn difficulty_bar(&self) -> String {
+        let difficulty: u64 = Self::challenge_param_to_difficulty(&self.challenge_param);
+        let filled: usize = crate::expected_attempts_to_leading_zeros(difficulty).min(DIFFICULTY_BAR_WIDTH);
+
+        format!(
+            "[{}{}] {} (~{} attempts)",
+            "#".repeat(filled),
+            "-".repeat(DIFFICULTY_BAR_WIDTH - filled),
+            crate::DifficultyClass::from_difficulty(difficulty),
+            compact_count(difficulty),
+        )
+    }
+
+    /// Hashes a resource identifier, e.g. a request path, for
+    /// `resource_hash`.
+    ///
+    /// # Returns
+    /// * `[u8; 32]`: The SHA-256 digest of `resource`.
+    pub fn resource_hash_for(resource: &str) -> [u8; 32] {
+        Sha256::digest(resource.as_bytes()).into()
+    }
+
+    /// Returns whether a solution to this challenge may unlock `resource`.
+    ///
+    /// Only meaningful once the signature is verified, since
+    /// `resource_hash` is covered by it.
+    ///
+    /// # Returns
+    /// * `bool`: `true` if the challenge is unbound or bound to
+    ///   `resource`.
+    pub fn is_bound_to(&self, resource: &str) -> bool {
+        self.resource_hash
+            .is_none_or(|resource_hash: [u8; 32]| resource_hash == Self::resource_hash_for(resource))
+    }
+
+    /// Computes a compact identifier for this challenge.
+    ///
+    /// The identifier is the SHA-256 hash of `concat_struct()`, so it
+    /// commits to every signed field plus the signature itself. It can be
+    /// stored in place of the full challenge for audit trails.
+    ///
+    /// # Returns
+    /// * `[u8; 32]`: The SHA-256 digest identifying this challenge.
+    pub fn challenge_id(&self) -> [u8; 32] {
+        Sha256::digest(self.concat_struct().as_bytes()).into()
+    }
+
+    /// Signs this challenge with an Ed25519 key.
+    ///
+    /// Sets `public_key` to the key's public half, then stores the
+    /// signature over every field but `challenge_signature` (the same
+    /// message as `sign_challenge_with_key`) in `challenge_signature`.
+    ///
+    /// # Arguments
+    /// * `signing_key`: The issuer's Ed25519 signing key.
+    pub fn sign(&mut self, signing_key: &ed25519_dalek::SigningKey) {
+        self.public_key = signing_key.verifying_key().to_bytes();
+        self.challenge_signature = crate::sign_challenge_with_key(self, &signing_key.to_bytes());
+    }
+
+    /// Checks `challenge_signature` against the embedded `public_key`.
+    ///
+    /// This proves the fields are unchanged since signing, but anyone can
+    /// sign with their own key. Servers must also check that `public_key`
+    /// is their trusted key, or verify with
+    /// `verify_challenge_signature_with_key` directly.
+    ///
+    /// # Returns
+    /// * `bool`: `true` if the signature is valid for `public_key`.
+    pub fn verify_signature(&self) -> bool {
+        crate::verify_challenge_signature_with_key(self, &self.public_key).is_ok()
+    }
+
     /// Concatenates the challenge data into a string.
     ///
     /// Concatenates:
@@ -182,8 +452,10 @@ impl IronShieldChallenge {
     /// - `website_id`       as a string.
     /// - `public_key`       as a lowercase hex string.
     /// - `challenge_params` as a lowercase hex string.
+    /// - `resource_hash`    as a lowercase hex string, only when set.
+    /// - `difficulty`       as u64, only when set (after an empty `resource_hash` if unset).
     pub fn concat_struct(&self) -> String {
-        format!(
+        let concat: String = format!(
             "{}|{}|{}|{}|{}|{}|{}",
             self.random_nonce,
             self.created_time,
@@ -193,7 +465,43 @@ impl IronShieldChallenge {
             hex::encode(self.challenge_param),
             hex::encode(self.public_key),
             hex::encode(self.challenge_signature)
-        )
+        );
+        append_optional_fields(concat, self.resource_hash, self.difficulty)
+    }
+
+    /// Serializes the signed challenge fields as canonical JSON.
+    ///
+    /// Covers the same fields as the Ed25519 signing message, so it can
+    /// be signed by systems that prefer JSON over the `|`-separated form.
+    /// Keys are emitted in this fixed (alphabetical) order with no
+    /// whitespace, and byte arrays as lowercase hex strings:
+    /// - `challenge_param`
+    /// - `created_time`
+    /// - `difficulty`, only when set
+    /// - `expiration_time`
+    /// - `public_key`
+    /// - `random_nonce`
+    /// - `resource_hash`, only when set
+    /// - `website_id`
+    ///
+    /// # Returns
+    /// * `String`: The canonical JSON, byte-identical for equal fields.
+    pub fn to_canonical_json(&self) -> String {
+        let mut fields: Vec<(&str, serde_json::Value)> = vec![
+            ("challenge_param", hex::encode(self.challenge_param).into()),
+            ("created_time",    self.created_time.into()),
+        ];
+        if let Some(difficulty) = self.difficulty {
+            fields.push(("difficulty", difficulty.into()));
+        }
+        fields.push(("expiration_time", self.expiration_time.into()));
+        fields.push(("public_key",      hex::encode(self.public_key).into()));
+        fields.push(("random_nonce",    self.random_nonce.as_str().into()));
+        if let Some(resource_hash) = self.resource_hash {
+            fields.push(("resource_hash", hex::encode(resource_hash).into()));
+        }
+        fields.push(("website_id", self.website_id.as_str().into()));
+        canonical_json_object(&fields)
     }
 
     /// Creates an `IronShieldChallenge` from a concatenated string.
@@ -202,6 +510,8 @@ impl IronShieldChallenge {
     /// `IronShieldChallenge::concat_struct`.
     /// Expects a string in the format:
     /// "random_nonce|created_time|expiration_time|website_id|challenge_params|public_key|challenge_signature"
+    /// optionally followed by "|resource_hash" and then "|difficulty", where
+    /// `resource_hash` may be empty when only `difficulty` is set.
     ///
     /// # Arguments
     ///
@@ -216,8 +526,8 @@ impl IronShieldChallenge {
     pub fn from_concat_struct(concat_str: &str) -> Result<Self, String> {
         let parts: Vec<&str> = concat_str.split('|').collect();
 
-        if parts.len() != 7 {
-            return Err(format!("Expected 7 parts, got {}", parts.len()));
+        if !(7..=9).contains(&parts.len()) {
+            return Err(format!("Expected 7 to 9 parts, got {}", parts.len()));
         }
 
         let random_nonce: String = parts[0].to_string();
@@ -247,6 +557,21 @@ impl IronShieldChallenge {
             .try_into()
             .map_err(|_| "Signature must be exactly 64 bytes")?;
 
+        let resource_hash: Option<[u8; 32]> = match parts.get(7) {
+            Some(part) if part.is_empty() && parts.len() == 9 => None,
+            Some(part) => {
+                let resource_hash_bytes: Vec<u8> = hex::decode(part)
+                    .map_err(|_| "Failed to decode resource_hash hex string")?;
+                Some(resource_hash_bytes.try_into().map_err(|_| "Resource hash must be exactly 32 bytes")?)
+            }
+            None => None,
+        };
+
+        let difficulty: Option<u64> = match parts.get(8) {
+            Some(part) => Some(part.parse::<u64>().map_err(|_| "Failed to parse difficulty as u64")?),
+            None => None,
+        };
+
         Ok(Self {
             random_nonce,
             created_time,
@@ -256,6 +581,8 @@ impl IronShieldChallenge {
             recommended_attempts: 0, // This will be set later
             public_key,
             challenge_signature,
+            resource_hash,
+            difficulty,
         })
     }
 
@@ -322,6 +649,165 @@ impl IronShieldChallenge {
     }
 }
 
+/// Builder for an unsigned `IronShieldChallenge`.
+///
+/// Unset fields default to a fresh random nonce, an empty `website_id`
+/// (which `validate` rejects), `MEDIUM_DIFFICULTY_MIN`, `DEFAULT_CHALLENGE_TTL_MS`
+/// and an all-zero public key. The built challenge carries its difficulty
+/// and a zero signature until signed with `IronShieldChallenge::sign`.
+///
+/// * `random_nonce`:    The nonce to use, or `None` for a fresh random one.
+/// * `website_id`:      The identifier of the website.
+/// * `difficulty`:      Expected number of attempts, `challenge_param` is derived from it.
+/// * `challenge_param`: A precomputed `challenge_param` for `difficulty`, if any.
+/// * `ttl_ms`:          How long the challenge stays valid, in milliseconds.
+/// * `public_key`:      Ed25519 public key for signature verification.
+/// * `resource_hash`:   The resource the challenge is bound to, if any.
+#[derive(Debug, Clone)]
+pub struct IronShieldChallengeBuilder {
+    random_nonce:    Option<String>,
+    website_id:      String,
+    difficulty:      u64,
+    challenge_param: Option<[u8; 32]>,
+    ttl_ms:          i64,
+    public_key:      [u8; 32],
+    resource_hash:   Option<[u8; 32]>,
+}
+
+impl Default for IronShieldChallengeBuilder {
+    fn default() -> Self {
+        Self {
+            random_nonce:    None,
+            website_id:      String::new(),
+            difficulty:      crate::MEDIUM_DIFFICULTY_MIN,
+            challenge_param: None,
+            ttl_ms:          DEFAULT_CHALLENGE_TTL_MS,
+            public_key:      [0u8; 32],
+            resource_hash:   None,
+        }
+    }
+}
+
+impl IronShieldChallengeBuilder {
+    /// Sets the challenge's `random_nonce` instead of a fresh random one.
+    pub fn random_nonce(mut self, random_nonce: impl Into<String>) -> Self {
+        self.random_nonce = Some(random_nonce.into());
+        self
+    }
+
+    /// Sets the identifier of the website the challenge is for.
+    pub fn website_id(mut self, website_id: impl Into<String>) -> Self {
+        self.website_id = website_id.into();
+        self
+    }
+
+    /// Sets the difficulty; values below 1 are raised to 1.
+    pub fn difficulty(mut self, difficulty: u64) -> Self {
+        self.difficulty = difficulty.max(1);
+        self
+    }
+
+    /// Sets the `challenge_param` for the difficulty, e.g. from a lookup
+    /// table, instead of converting the difficulty on build.
+    pub fn challenge_param(mut self, challenge_param: [u8; 32]) -> Self {
+        self.challenge_param = Some(challenge_param);
+        self
+    }
+
+    /// Sets how long the challenge stays valid, in milliseconds.
+    pub fn ttl_ms(mut self, ttl_ms: i64) -> Self {
+        self.ttl_ms = ttl_ms;
+        self
+    }
+
+    /// Sets the public key the challenge will be verified with.
+    pub fn public_key(mut self, public_key: [u8; 32]) -> Self {
+        self.public_key = public_key;
+        self
+    }
+
+    /// Binds the challenge to `resource`, see `IronShieldChallenge::is_bound_to`.
+    pub fn resource(mut self, resource: &str) -> Self {
+        self.resource_hash = Some(IronShieldChallenge::resource_hash_for(resource));
+        self
+    }
+
+    /// Builds the challenge, created now.
+    ///
+    /// # Returns
+    /// * `IronShieldChallenge`: The unsigned challenge.
+    pub fn build(self) -> IronShieldChallenge {
+        self.build_at(Utc::now().timestamp_millis())
+    }
+
+    /// Builds like `build`, created at the given time in Unix milliseconds
+    /// instead of the system clock.
+    ///
+    /// # Arguments
+    /// * `now_millis`: The challenge's `created_time`.
+    ///
+    /// # Returns
+    /// * `IronShieldChallenge`: The unsigned challenge.
+    pub fn build_at(self, now_millis: i64) -> IronShieldChallenge {
+        let random_nonce: String = self.random_nonce.unwrap_or_else(|| {
+            let mut nonce: [u8; 32] = [0u8; 32];
+            rand_core::OsRng.fill_bytes(&mut nonce);
+            hex::encode(nonce)
+        });
+
+        let mut challenge: IronShieldChallenge = IronShieldChallenge::new_with_ttl(
+            random_nonce,
+            now_millis,
+            self.website_id,
+            self.challenge_param
+                .unwrap_or_else(|| IronShieldChallenge::difficulty_to_challenge_param(self.difficulty)),
+            self.public_key,
+            [0u8; 64],
+            self.ttl_ms,
+        );
+        challenge.set_recommended_attempts(self.difficulty);
+        challenge.resource_hash = self.resource_hash;
+        challenge.difficulty = Some(self.difficulty);
+        challenge
+    }
+}
+
+/// Appends the optional trailing fields shared by `concat_struct` and
+/// the signing message.
+///
+/// Nothing is appended for unset fields, so challenges without them
+/// keep their original format. When only `difficulty` is set, an empty
+/// `resource_hash` segment keeps it in the ninth position.
+///
+/// # Arguments
+/// * `concat`:        The `|`-separated required fields.
+/// * `resource_hash`: The optional resource hash.
+/// * `difficulty`:    The optional redundant difficulty.
+///
+/// # Returns
+/// * `String`: `concat` with the set optional fields appended.
+pub(crate) fn append_optional_fields(concat: String, resource_hash: Option<[u8; 32]>, difficulty: Option<u64>) -> String {
+    let resource_hash_hex: String = resource_hash.map(hex::encode).unwrap_or_default();
+    match (resource_hash, difficulty) {
+        (_, Some(difficulty)) => format!("{}|{}|{}", concat, resource_hash_hex, difficulty),
+        (Some(_), None) => format!("{}|{}", concat, resource_hash_hex),
+        (None, None) => concat,
+    }
+}
+
+/// Formats a count with a metric prefix, rounding down, e.g. `65k`.
+fn compact_count(count: u64) -> String {
+    const PREFIXES: [&str; 7] = ["", "k", "M", "G", "T", "P", "E"];
+
+    let mut scaled: u64 = count;
+    let mut prefix: usize = 0;
+    while scaled >= 1_000 {
+        scaled /= 1_000;
+        prefix += 1;
+    }
+    format!("{}{}", scaled, PREFIXES[prefix])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -569,7 +1055,37 @@ mod tests {
         let invalid_format: String = URL_SAFE_NO_PAD.encode(b"not|enough|parts");
         let result: Result<IronShieldChallenge, String> = IronShieldChallenge::from_base64url_header(&invalid_format);
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Expected 7 parts"));
+        assert!(result.unwrap_err().contains("Expected 7 to 9 parts"));
+    }
+
+    #[test]
+    fn test_base64url_header_rejects_non_canonical_encoding() {
+        let challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1_000_000,
+            "test_website".to_string(),
+            [0x12; 32],
+            [0x34; 32],
+            [0x56; 64],
+        );
+        let canonical: String = challenge.to_base64url_header();
+        assert!(IronShieldChallenge::from_base64url_header(&canonical).is_ok());
+
+        // The encoded length leaves spare bits in the last symbol, so setting
+        // one gives another string for the same bytes.
+        assert_ne!(canonical.len() % 4, 0);
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+        let last: usize = ALPHABET.iter().position(|&c| c == *canonical.as_bytes().last().unwrap()).unwrap();
+        let mut non_canonical: String = canonical.clone();
+        non_canonical.pop();
+        non_canonical.push(ALPHABET[last | 1] as char);
+        assert_ne!(non_canonical, canonical);
+        assert!(IronShieldChallenge::from_base64url_header(&non_canonical).is_err());
+
+        // The canonical check itself, independent of the decoder.
+        assert!(crate::is_canonical_base64url("QQ", b"A"));
+        assert!(!crate::is_canonical_base64url("QR", b"A"));
+        assert!(!crate::is_canonical_base64url("QQ==", b"A"));
     }
 
     #[test]
@@ -650,4 +1166,395 @@ mod tests {
         assert_eq!(parsed.public_key, [0xffu8; 32]);
         assert_eq!(parsed.challenge_signature, [0xffu8; 64]);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_challenge_id_is_stable_and_distinct() {
+        let challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1000000,
+            "test_website".to_string(),
+            [0x12; 32],
+            [0x34; 32],
+            [0x56; 64],
+        );
+        let mut other: IronShieldChallenge = challenge.clone();
+        other.random_nonce = "cafebabe".to_string();
+
+        assert_eq!(challenge.challenge_id(), challenge.clone().challenge_id());
+        assert_ne!(challenge.challenge_id(), other.challenge_id());
+    }
+
+    #[test]
+    fn test_validate_rejects_empty_website_id() {
+        let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1000000,
+            "example.com".to_string(),
+            [0x12; 32],
+            [0x34; 32],
+            [0x56; 64],
+        );
+        assert_eq!(challenge.validate(), Ok(()));
+
+        challenge.website_id = String::new();
+        assert_eq!(challenge.validate(), Err("Empty website_id".to_string()));
+
+        challenge.website_id = "   ".to_string();
+        assert_eq!(challenge.validate(), Err("Empty website_id".to_string()));
+    }
+
+    #[test]
+    fn test_challenge_param_to_difficulty_roundtrip() {
+        assert_eq!(IronShieldChallenge::challenge_param_to_difficulty(&[0xFF; 32]), 1);
+        assert_eq!(IronShieldChallenge::challenge_param_to_difficulty(&[0x00; 32]), u64::MAX);
+
+        // Powers of two survive the round-trip exactly.
+        for difficulty in [2u64, 256, 1024, 1 << 20, 1 << 40] {
+            let param: [u8; 32] = IronShieldChallenge::difficulty_to_challenge_param(difficulty);
+            assert_eq!(IronShieldChallenge::challenge_param_to_difficulty(&param), difficulty);
+        }
+
+        // Other values round to the nearest power of two.
+        let param: [u8; 32] = IronShieldChallenge::difficulty_to_challenge_param(50_000);
+        assert_eq!(IronShieldChallenge::challenge_param_to_difficulty(&param), 65_536);
+
+        // Within that rounding, about 41%, any difficulty round-trips.
+        for difficulty in [1u64, 256, 65_536, 1_000_000] {
+            let param: [u8; 32] = IronShieldChallenge::difficulty_to_challenge_param(difficulty);
+            let ratio: f64 = IronShieldChallenge::challenge_param_to_difficulty(&param) as f64 / difficulty as f64;
+            assert!((0.70..=1.42).contains(&ratio), "difficulty {} came back {:.2}x", difficulty, ratio);
+        }
+    }
+
+    #[test]
+    fn test_challenge_param_to_difficulty_is_exact() {
+        // 3 * 2^200: 2^56 / 3 rounds down, beyond the reach of f64.
+        let mut param: [u8; 32] = [0x00; 32];
+        param[6] = 0x03;
+        assert_eq!(IronShieldChallenge::challenge_param_to_difficulty(&param), (1u64 << 56) / 3);
+
+        // 2^192 gives exactly 2^64, one past u64::MAX.
+        let mut param: [u8; 32] = [0x00; 32];
+        param[7] = 0x01;
+        assert_eq!(IronShieldChallenge::challenge_param_to_difficulty(&param), u64::MAX);
+        param[7] = 0x02;
+        assert_eq!(IronShieldChallenge::challenge_param_to_difficulty(&param), 1 << 63);
+        param[8] = 0x80;
+        assert_eq!(IronShieldChallenge::challenge_param_to_difficulty(&param), ((1u128 << 65) / 5) as u64);
+    }
+
+    #[test]
+    fn test_effective_difficulty_drift() {
+        // (requested, effective): each rounds to the nearest power of two
+        // in log space.
+        let cases: [(u64, u64); 7] = [
+            (3, 4),
+            (48, 64),
+            (1_000, 1_024),
+            (10_000, 8_192),
+            (50_000, 65_536),
+            (1_000_000, 1_048_576),
+            (3_000_000_000, 2_147_483_648),
+        ];
+        for (requested, effective) in cases {
+            assert_eq!(IronShieldChallenge::effective_difficulty(requested), effective, "requested {}", requested);
+
+            // The drift stays within one rounding step, 2^±0.5.
+            let drift: f64 = effective as f64 / requested as f64;
+            assert!((0.5f64.sqrt()..=2f64.sqrt()).contains(&drift), "requested {} drifted by {}", requested, drift);
+        }
+
+        // 10k rounds down: challenges are ~18% easier than requested.
+        let drift_percent: f64 = (IronShieldChallenge::effective_difficulty(10_000) as f64 / 10_000.0 - 1.0) * 100.0;
+        assert_eq!(drift_percent.round(), -18.0);
+
+        // Powers of two, and the difficulty-1 special case, are exact.
+        for requested in [1u64, 2, 4096, 1 << 40] {
+            assert_eq!(IronShieldChallenge::effective_difficulty(requested), requested);
+        }
+    }
+
+    #[test]
+    fn test_new_uses_default_ttl() {
+        let challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1_700_000_000_000,
+            "test_website".to_string(),
+            [0x12; 32],
+            [0x34; 32],
+            [0x56; 64],
+        );
+
+        assert_eq!(challenge.expiration_time, challenge.created_time + DEFAULT_CHALLENGE_TTL_MS);
+        assert_eq!(challenge.ttl_ms(), DEFAULT_CHALLENGE_TTL_MS);
+    }
+
+    #[test]
+    fn test_new_with_ttl() {
+        let created_time: i64 = Utc::now().timestamp_millis();
+        let challenge: IronShieldChallenge = IronShieldChallenge::new_with_ttl(
+            "deadbeef".to_string(),
+            created_time,
+            "test_website".to_string(),
+            [0x12; 32],
+            [0x34; 32],
+            [0x56; 64],
+            120_000,
+        );
+
+        assert_eq!(challenge.expiration_time, created_time + 120_000);
+        assert_eq!(challenge.ttl_ms(), 120_000);
+        assert!(!challenge.is_expired());
+        assert!(!challenge.is_expired_at(created_time + 119_999));
+        assert!(challenge.is_expired_at(created_time + 120_001));
+        assert!(challenge.time_until_expiration() > DEFAULT_CHALLENGE_TTL_MS);
+    }
+
+    #[test]
+    fn test_to_canonical_json_is_stable() {
+        let challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1_700_000_000_000,
+            "example.com/\"quoted\"".to_string(),
+            [0x12; 32],
+            [0x34; 32],
+            [0x56; 64],
+        );
+        let rebuilt: IronShieldChallenge = IronShieldChallenge::from_concat_struct(&challenge.concat_struct()).unwrap();
+        let reparsed: IronShieldChallenge = serde_json::from_str(&serde_json::to_string(&challenge).unwrap()).unwrap();
+
+        assert_eq!(challenge.to_canonical_json(), rebuilt.to_canonical_json());
+        assert_eq!(challenge.to_canonical_json(), reparsed.to_canonical_json());
+        assert_eq!(
+            challenge.to_canonical_json(),
+            format!(
+                "{{\"challenge_param\":\"{}\",\"created_time\":1700000000000,\"expiration_time\":1700000030000,\
+                 \"public_key\":\"{}\",\"random_nonce\":\"deadbeef\",\"website_id\":\"example.com/\\\"quoted\\\"\"}}",
+                "12".repeat(32),
+                "34".repeat(32),
+            )
+        );
+
+        // The signature is not part of the signed form.
+        let mut resigned: IronShieldChallenge = challenge.clone();
+        resigned.challenge_signature = [0x78; 64];
+        assert_eq!(challenge.to_canonical_json(), resigned.to_canonical_json());
+    }
+
+    #[test]
+    fn test_resource_binding_roundtrip() {
+        let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1_700_000_000_000,
+            "example.com".to_string(),
+            [0x12; 32],
+            [0x34; 32],
+            [0x56; 64],
+        );
+        assert!(challenge.is_bound_to("/a"));
+        assert_eq!(challenge.concat_struct().split('|').count(), 7);
+        assert!(!challenge.to_canonical_json().contains("resource_hash"));
+        assert!(!serde_json::to_string(&challenge).unwrap().contains("resource_hash"));
+
+        challenge.resource_hash = Some(IronShieldChallenge::resource_hash_for("/a"));
+        assert!(challenge.is_bound_to("/a"));
+        assert!(!challenge.is_bound_to("/b"));
+
+        let concat: String = challenge.concat_struct();
+        assert_eq!(concat.split('|').count(), 8);
+        assert_eq!(IronShieldChallenge::from_concat_struct(&concat).unwrap().resource_hash, challenge.resource_hash);
+
+        let reparsed: IronShieldChallenge = serde_json::from_str(&serde_json::to_string(&challenge).unwrap()).unwrap();
+        assert_eq!(reparsed.resource_hash, challenge.resource_hash);
+        assert!(challenge.to_canonical_json().contains(&format!(
+            "\"public_key\":\"{}\",\"random_nonce\":\"deadbeef\",\"resource_hash\":\"{}\"",
+            "34".repeat(32),
+            hex::encode(IronShieldChallenge::resource_hash_for("/a")),
+        )));
+    }
+
+    #[test]
+    fn test_difficulty_class_at_boundaries() {
+        use crate::{DifficultyClass, EXTREME_DIFFICULTY_MIN, HIGH_DIFFICULTY_MIN, MEDIUM_DIFFICULTY_MIN};
+
+        let class = |difficulty: u64| {
+            IronShieldChallenge::new(
+                "deadbeef".to_string(),
+                1_700_000_000_000,
+                "test_website".to_string(),
+                IronShieldChallenge::difficulty_to_challenge_param(difficulty),
+                [0x00; 32],
+                [0x00; 64],
+            )
+            .difficulty_class()
+        };
+
+        assert_eq!(class(1), DifficultyClass::Low);
+        assert_eq!(class(MEDIUM_DIFFICULTY_MIN / 2), DifficultyClass::Low);
+        assert_eq!(class(MEDIUM_DIFFICULTY_MIN), DifficultyClass::Medium);
+        assert_eq!(class(HIGH_DIFFICULTY_MIN / 2), DifficultyClass::Medium);
+        assert_eq!(class(HIGH_DIFFICULTY_MIN), DifficultyClass::High);
+        assert_eq!(class(EXTREME_DIFFICULTY_MIN / 2), DifficultyClass::High);
+        assert_eq!(class(EXTREME_DIFFICULTY_MIN), DifficultyClass::Extreme);
+    }
+
+    #[test]
+    fn test_expiry_at_fixed_time() {
+        let challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1_700_000_000_000,
+            "test_website".to_string(),
+            [0xFF; 32],
+            [0x00; 32],
+            [0x00; 64],
+        );
+        let expires: i64 = challenge.expiration_time;
+
+        assert!(!challenge.is_expired_at(challenge.created_time));
+        assert!(!challenge.is_expired_at(expires));
+        assert!(challenge.is_expired_at(expires + 1));
+        assert_eq!(challenge.time_until_expiration_at(challenge.created_time), DEFAULT_CHALLENGE_TTL_MS);
+        assert_eq!(challenge.time_until_expiration_at(expires + 500), -500);
+
+        // The real clock is long past this challenge.
+        assert!(challenge.is_expired());
+    }
+
+    #[test]
+    fn test_difficulty_bar() {
+        let bar = |difficulty: u64| {
+            IronShieldChallenge::new(
+                "deadbeef".to_string(),
+                1_700_000_000_000,
+                "test_website".to_string(),
+                IronShieldChallenge::difficulty_to_challenge_param(difficulty),
+                [0x00; 32],
+                [0x00; 64],
+            )
+            .difficulty_bar()
+        };
+
+        assert_eq!(bar(65_536), "[####------] Medium (~65k attempts)");
+        assert_eq!(bar(1), "[----------] Low (~1 attempts)");
+        assert_eq!(bar(1 << 24), "[######----] Extreme (~16M attempts)");
+        assert_eq!(bar(1 << 60), "[##########] Extreme (~1E attempts)");
+
+        // The gauge only grows with the difficulty.
+        let filled = |difficulty: u64| bar(difficulty).matches('#').count();
+        for bits in 1..63 {
+            assert!(filled(1 << bits) <= filled(1 << (bits + 1)));
+            assert_eq!(bar(1 << bits).find(']'), Some(DIFFICULTY_BAR_WIDTH + 1));
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify_signature() {
+        use rand_core::OsRng;
+
+        let signing_key: ed25519_dalek::SigningKey = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1_700_000_000_000,
+            "example.com".to_string(),
+            IronShieldChallenge::difficulty_to_challenge_param(1_000_000),
+            signing_key.verifying_key().to_bytes(),
+            [0x00; 64],
+        );
+        // Not with an all-zero key: it has small order, so an all-zero
+        // signature verifies under it for some messages.
+        assert!(!challenge.verify_signature());
+
+        challenge.sign(&signing_key);
+        assert_eq!(challenge.public_key, signing_key.verifying_key().to_bytes());
+        assert!(challenge.verify_signature());
+        assert!(crate::verify_challenge_signature_with_key(&challenge, &challenge.public_key).is_ok());
+
+        // A client lowering the difficulty invalidates the signature.
+        let mut tampered: IronShieldChallenge = challenge.clone();
+        tampered.challenge_param = [0xFF; 32];
+        assert!(!tampered.verify_signature());
+
+        // So does swapping in another public key.
+        let mut wrong_key: IronShieldChallenge = challenge.clone();
+        wrong_key.public_key = ed25519_dalek::SigningKey::generate(&mut OsRng).verifying_key().to_bytes();
+        assert!(!wrong_key.verify_signature());
+    }
+
+    #[test]
+    fn test_mismatched_difficulty_is_rejected() {
+        use rand_core::OsRng;
+
+        let signing_key: ed25519_dalek::SigningKey = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1_700_000_000_000,
+            "example.com".to_string(),
+            IronShieldChallenge::difficulty_to_challenge_param(1_000_000),
+            [0x00; 32],
+            [0x00; 64],
+        );
+        assert!(challenge.difficulty_matches_param());
+
+        challenge.difficulty = Some(1_000_000);
+        challenge.sign(&signing_key);
+        assert!(challenge.difficulty_matches_param() && challenge.validate().is_ok());
+
+        // Carried through every encoding, with an empty resource_hash slot.
+        let concat: String = challenge.concat_struct();
+        assert!(concat.ends_with("||1000000"));
+        assert_eq!(IronShieldChallenge::from_concat_struct(&concat).unwrap().difficulty, Some(1_000_000));
+        assert_eq!(IronShieldChallenge::from_concat_struct(&concat).unwrap().resource_hash, None);
+        let reparsed: IronShieldChallenge = serde_json::from_str(&serde_json::to_string(&challenge).unwrap()).unwrap();
+        assert_eq!(reparsed.difficulty, Some(1_000_000));
+        assert!(challenge.to_canonical_json().contains("\"difficulty\":1000000,"));
+
+        // The field is signed, so it cannot be swapped to match a new param.
+        let mut resigned_elsewhere: IronShieldChallenge = challenge.clone();
+        resigned_elsewhere.difficulty = Some(1 << 30);
+        assert!(!resigned_elsewhere.verify_signature());
+
+        // A param that diverges from the carried difficulty is rejected.
+        for difficulty in [500_000, 4_000_000, 0] {
+            let mut mismatched: IronShieldChallenge = challenge.clone();
+            mismatched.difficulty = Some(difficulty);
+            mismatched.sign(&signing_key);
+            assert!(mismatched.verify_signature());
+            assert!(!mismatched.difficulty_matches_param());
+            assert!(mismatched.validate().is_err());
+        }
+
+        challenge.resource_hash = Some([0x33; 32]);
+        let rebuilt: IronShieldChallenge = IronShieldChallenge::from_concat_struct(&challenge.concat_struct()).unwrap();
+        assert_eq!((rebuilt.resource_hash, rebuilt.difficulty), (Some([0x33; 32]), Some(1_000_000)));
+        assert!(IronShieldChallenge::from_concat_struct(&format!("{}|extra", challenge.concat_struct())).is_err());
+    }
+
+    #[test]
+    fn test_builder_defaults_and_setters() {
+        let challenge: IronShieldChallenge = IronShieldChallenge::builder()
+            .website_id("example.com")
+            .difficulty(1_000_000)
+            .ttl_ms(60_000)
+            .public_key([0x34; 32])
+            .build();
+
+        assert_eq!(challenge.expiration_time, challenge.created_time + 60_000);
+        assert_eq!(challenge.challenge_param, IronShieldChallenge::difficulty_to_challenge_param(1_000_000));
+        assert_eq!(challenge.difficulty, Some(1_000_000));
+        assert_eq!(challenge.recommended_attempts, IronShieldChallenge::recommended_attempts(1_000_000));
+        assert_eq!(challenge.public_key, [0x34; 32]);
+        assert_eq!(challenge.challenge_signature, [0u8; 64]);
+        assert_eq!(challenge.random_nonce.len(), 64);
+        assert!(challenge.validate().is_ok());
+
+        let defaulted: IronShieldChallenge = IronShieldChallenge::builder().random_nonce("deadbeef").build_at(1_700_000_000_000);
+        assert_eq!(defaulted.random_nonce, "deadbeef");
+        assert_eq!(defaulted.created_time, 1_700_000_000_000);
+        assert_eq!(defaulted.ttl_ms(), DEFAULT_CHALLENGE_TTL_MS);
+        assert_eq!(defaulted.difficulty, Some(crate::MEDIUM_DIFFICULTY_MIN));
+        assert!(defaulted.validate().is_err()); // No website_id.
+
+        // A zero difficulty is raised rather than panicking.
+        assert_eq!(IronShieldChallenge::builder().difficulty(0).build().challenge_param, [0xFF; 32]);
+    }
+}
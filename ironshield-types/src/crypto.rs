@@ -18,17 +18,23 @@
 //! - `verify_challenge_signature()` - Verify using environment public key
 //! - `verify_challenge_signature_with_key()` - Verify using provided key
 //! - `validate_challenge()` - Comprehensive validation (signature + expiration)
+//! - `validate_challenge_at()` - The same, checking expiry at a given time
+//!
+//! ### Token Signing
+//! - `create_signed_token()` - Issue a token signed with the given private key
+//! - `verify_token_signature_with_key()` - Verify a token using provided key
 //!
 //! ## Security Design
 //!
 //! ### Signature Coverage
 //! Signatures cover all challenge fields except the signature itself:
-//! - `random_nonce` 
+//! - `random_nonce` (length-prefixed)
 //! - `created_time`
 //! - `expiration_time`
-//! - `website_id`
+//! - `website_id` (length-prefixed)
 //! - `challenge_param` (hex-encoded)
 //! - `public_key` (hex-encoded)
+//! - `resource_hash` and `difficulty`, when set
 //!
 //! This prevents tampering with any challenge parameters while allowing verification.
 //!
@@ -79,8 +85,9 @@
 //! # }
 //! ```
 
-use ed25519_dalek::{Signature, Signer, Verifier, SigningKey, VerifyingKey, PUBLIC_KEY_LENGTH, SECRET_KEY_LENGTH};
-use crate::IronShieldChallenge;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use ed25519_dalek::{verify_batch, Signature, Signer, Verifier, SigningKey, VerifyingKey, PUBLIC_KEY_LENGTH, SECRET_KEY_LENGTH};
+use crate::{IronShieldChallenge, IronShieldChallengeResponse, IronShieldToken};
 use base64::{Engine, engine::general_purpose::STANDARD};
 use std::env;
 
@@ -97,6 +104,8 @@ pub enum CryptoError {
     VerificationFailed(String),
     /// Base64 decoding failed
     Base64DecodingFailed(String),
+    /// The token or challenge is past its expiration time
+    Expired(String),
 }
 
 impl std::fmt::Display for CryptoError {
@@ -107,6 +116,7 @@ impl std::fmt::Display for CryptoError {
             CryptoError::SigningFailed(msg) => write!(f, "Signing failed: {}", msg),
             CryptoError::VerificationFailed(msg) => write!(f, "Verification failed: {}", msg),
             CryptoError::Base64DecodingFailed(msg) => write!(f, "Base64 decoding failed: {}", msg),
+            CryptoError::Expired(msg) => write!(f, "Expired: {}", msg),
         }
     }
 }
@@ -182,7 +192,11 @@ pub fn load_public_key_from_env() -> Result<VerifyingKey, CryptoError> {
 /// Creates a message to be signed from challenge data (excluding the signature field)
 /// 
 /// This function creates a canonical representation of the challenge data for signing.
-/// It uses the same format as `concat_struct()` but excludes the signature field.
+/// It uses the same format as `concat_struct()` but excludes the signature field,
+/// except that the free-form `random_nonce` and `website_id` are prefixed
+/// with their byte length (`"len:value"`), so a `|` inside them cannot
+/// shift the optional fields. The resource hash and difficulty are
+/// appended only when set.
 /// 
 /// # Arguments
 /// * `challenge` - The challenge to create a signing message for
@@ -190,15 +204,18 @@ pub fn load_public_key_from_env() -> Result<VerifyingKey, CryptoError> {
 /// # Returns
 /// * `String` - Canonical string representation for signing
 fn create_signing_message(challenge: &IronShieldChallenge) -> String {
-    format!(
-        "{}|{}|{}|{}|{}|{}",
+    let message: String = format!(
+        "{}:{}|{}|{}|{}:{}|{}|{}",
+        challenge.random_nonce.len(),
         challenge.random_nonce,
         challenge.created_time,
         challenge.expiration_time,
+        challenge.website_id.len(),
         challenge.website_id,
         hex::encode(challenge.challenge_param),
         hex::encode(challenge.public_key)
-    )
+    );
+    crate::challenge::append_optional_fields(message, challenge.resource_hash, challenge.difficulty)
 }
 
 /// Signs a challenge using the private key from environment variables
@@ -232,10 +249,24 @@ fn create_signing_message(challenge: &IronShieldChallenge) -> String {
 pub fn sign_challenge(challenge: &IronShieldChallenge) -> Result<[u8; 64], CryptoError> {
     let signing_key: SigningKey = load_private_key_from_env()?;
     
+    Ok(sign_challenge_with_key(challenge, &signing_key.to_bytes()))
+}
+
+/// Signs a challenge with the given Ed25519 private key
+/// 
+/// Use this instead of `sign_challenge` when the key does not come from
+/// process environment variables (e.g. Cloudflare Worker bindings).
+/// 
+/// # Arguments
+/// * `challenge` - The challenge to sign (signature field is ignored)
+/// * `private_key_bytes` - The 32-byte Ed25519 private key
+/// 
+/// # Returns
+/// * `[u8; 64]` - The Ed25519 signature bytes
+pub fn sign_challenge_with_key(challenge: &IronShieldChallenge, private_key_bytes: &[u8; 32]) -> [u8; 64] {
+    let signing_key: SigningKey = SigningKey::from_bytes(private_key_bytes);
     let message: String = create_signing_message(challenge);
-    let signature: Signature = signing_key.sign(message.as_bytes());
-    
-    Ok(signature.to_bytes())
+    signing_key.sign(message.as_bytes()).to_bytes()
 }
 
 /// Verifies a challenge signature using the public key from environment variables
@@ -308,6 +339,201 @@ pub fn verify_challenge_signature_with_key(
     Ok(())
 }
 
+/// Verifies the signatures of many challenges against one public key
+/// 
+/// All signatures are first checked together with Ed25519 batch
+/// verification, which is much faster than checking them one by one.
+/// Only if the batch fails, e.g. because some challenges are forged or
+/// were signed under another key, is each signature checked individually
+/// to find the bad ones.
+/// 
+/// Batch verification only catches a small-order component in a
+/// signature's `R` for some of its random coefficients, so it can accept
+/// signatures that the individual check rejects. Such signatures are
+/// rejected up front and left out of the batch, keeping both paths in
+/// agreement.
+/// 
+/// # Arguments
+/// * `challenges` - The challenges with signatures to verify
+/// * `public_key_bytes` - The Ed25519 public key bytes to use for verification
+/// 
+/// # Returns
+/// * `Vec<bool>` - Whether each challenge's signature is valid, in order;
+///   all `false` if the public key is invalid
+pub fn verify_challenge_signatures_batch(
+    challenges: &[IronShieldChallenge],
+    public_key_bytes: &[u8; 32],
+) -> Vec<bool> {
+    let Ok(verifying_key) = VerifyingKey::from_bytes(public_key_bytes) else {
+        return vec![false; challenges.len()];
+    };
+
+    let messages: Vec<String> = challenges.iter().map(create_signing_message).collect();
+    let message_bytes: Vec<&[u8]> = messages.iter().map(|message: &String| message.as_bytes()).collect();
+    let signatures: Vec<Signature> = challenges
+        .iter()
+        .map(|challenge: &IronShieldChallenge| Signature::from_bytes(&challenge.challenge_signature))
+        .collect();
+    let torsion_free: Vec<bool> = signatures.iter().map(has_torsion_free_r).collect();
+
+    let (batch_messages, batch_signatures): (Vec<&[u8]>, Vec<Signature>) = message_bytes
+        .iter()
+        .zip(&signatures)
+        .zip(&torsion_free)
+        .filter(|(_, torsion_free)| **torsion_free)
+        .map(|((message, signature), _)| (*message, *signature))
+        .unzip();
+    if verify_batch(&batch_messages, &batch_signatures, &vec![verifying_key; batch_signatures.len()]).is_ok() {
+        return torsion_free;
+    }
+    message_bytes
+        .iter()
+        .zip(&signatures)
+        .zip(&torsion_free)
+        .map(|((message, signature), torsion_free)| *torsion_free && verifying_key.verify(message, signature).is_ok())
+        .collect()
+}
+
+/// Returns whether the `R` component of `signature` decodes to a curve
+/// point without a small-order component, see
+/// `verify_challenge_signatures_batch`.
+fn has_torsion_free_r(signature: &Signature) -> bool {
+    CompressedEdwardsY(*signature.r_bytes())
+        .decompress()
+        .is_some_and(|r: EdwardsPoint| r.is_torsion_free())
+}
+
+/// Signs a response with the client's device key and attaches the key
+/// and signature to it
+/// 
+/// # Arguments
+/// * `response` - The response to bind to the device
+/// * `private_key_bytes` - The client's 32-byte Ed25519 private key
+pub fn attach_client_signature(response: &mut IronShieldChallengeResponse, private_key_bytes: &[u8; 32]) {
+    let signing_key: SigningKey = SigningKey::from_bytes(private_key_bytes);
+    let message: String = response.client_signing_message();
+
+    response.client_public_key = Some(signing_key.verifying_key().to_bytes());
+    response.client_signature = Some(signing_key.sign(message.as_bytes()).to_bytes());
+}
+
+/// Verifies the client signature carried by a response
+/// 
+/// # Arguments
+/// * `response` - The response with `client_public_key` and `client_signature` set
+/// 
+/// # Returns
+/// * `Result<(), CryptoError>` - Ok(()) if valid, error if either field is
+///   missing or the signature does not verify
+pub fn verify_client_signature(response: &IronShieldChallengeResponse) -> Result<(), CryptoError> {
+    let (public_key_bytes, signature_bytes) = match (response.client_public_key, response.client_signature) {
+        (Some(public_key), Some(signature)) => (public_key, signature),
+        _ => return Err(CryptoError::VerificationFailed("Response carries no client signature".to_string())),
+    };
+
+    let verifying_key: VerifyingKey = VerifyingKey::from_bytes(&public_key_bytes)
+        .map_err(|e| CryptoError::InvalidKeyFormat(format!("Invalid client public key: {}", e)))?;
+    let signature: Signature = Signature::from_bytes(&signature_bytes);
+
+    verifying_key.verify(response.client_signing_message().as_bytes(), &signature)
+        .map_err(|e| CryptoError::VerificationFailed(format!("Client signature verification failed: {}", e)))
+}
+
+/// Issues an `IronShieldToken` signed with the given Ed25519 private key
+/// 
+/// The token's `public_key` is set to the key's verifying key and
+/// `authentication_signature` covers `authentication_message()`.
+/// 
+/// # Arguments
+/// * `challenge_signature` - Signature of the solved challenge
+/// * `valid_for` - Expiration time in Unix millis
+/// * `resource_hash` - The resource the token is bound to, or `None` for
+///   every resource
+/// * `private_key_bytes` - The 32-byte Ed25519 private key
+/// 
+/// # Returns
+/// * `IronShieldToken` - The signed token
+pub fn create_signed_token(
+    challenge_signature: [u8; 64],
+    valid_for: i64,
+    resource_hash: Option<[u8; 32]>,
+    private_key_bytes: &[u8; 32],
+) -> IronShieldToken {
+    let mut token: IronShieldToken = IronShieldToken::new(challenge_signature, valid_for, [0u8; 32], [0u8; 64]);
+    token.resource_hash = resource_hash;
+    token.sign(&SigningKey::from_bytes(private_key_bytes));
+    token
+}
+
+/// Verifies a token's Ed25519 `authentication_signature` using a provided public key
+/// 
+/// The key embedded in the token is not trusted; the caller supplies the
+/// issuer's key. Expiration is not checked.
+/// 
+/// # Arguments
+/// * `token` - The token to verify
+/// * `public_key_bytes` - The issuer's Ed25519 public key bytes
+/// 
+/// # Returns
+/// * `Result<(), CryptoError>` - Ok(()) if valid, error if verification fails
+pub fn verify_token_signature_with_key(
+    token: &IronShieldToken,
+    public_key_bytes: &[u8; 32],
+) -> Result<(), CryptoError> {
+    if token.public_key != *public_key_bytes {
+        return Err(CryptoError::VerificationFailed("Token was issued under a different key".to_string()));
+    }
+
+    let verifying_key: VerifyingKey = VerifyingKey::from_bytes(public_key_bytes)
+        .map_err(|e| CryptoError::InvalidKeyFormat(format!("Invalid public key: {}", e)))?;
+    let signature: Signature = Signature::from_bytes(&token.authentication_signature);
+
+    verifying_key.verify(token.authentication_message().as_bytes(), &signature)
+        .map_err(|e| CryptoError::VerificationFailed(format!("Token signature verification failed: {}", e)))
+}
+
+/// Decodes and fully verifies a base64url `IronShieldToken`
+/// 
+/// This is the single check a bypass credential needs:
+/// - The token decodes, in the compact binary form or the `concat_struct` form
+/// - Its `authentication_signature` verifies under `server_public_key`
+/// - It has not expired
+/// 
+/// # Arguments
+/// * `token_b64` - The base64url-encoded token
+/// * `server_public_key` - The issuer's Ed25519 public key bytes
+/// 
+/// # Returns
+/// * `Result<(), CryptoError>` - Ok(()) if valid; `Base64DecodingFailed`,
+///   `VerificationFailed` (or `InvalidKeyFormat`), or `Expired` for
+///   whichever check fails first
+pub fn verify_token(token_b64: &str, server_public_key: &[u8; 32]) -> Result<(), CryptoError> {
+    verify_token_at(token_b64, server_public_key, chrono::Utc::now().timestamp_millis())
+}
+
+/// Verifies like `verify_token`, checking expiry at the given time in
+/// Unix milliseconds instead of the system clock.
+/// 
+/// # Arguments
+/// * `token_b64` - The base64url-encoded token
+/// * `server_public_key` - The issuer's Ed25519 public key bytes
+/// * `now_millis` - The time to check expiry at
+/// 
+/// # Returns
+/// * `Result<(), CryptoError>` - Ok(()) if valid, error if invalid
+pub fn verify_token_at(token_b64: &str, server_public_key: &[u8; 32], now_millis: i64) -> Result<(), CryptoError> {
+    let token: IronShieldToken = IronShieldToken::from_base64url(token_b64)
+        .map_err(CryptoError::Base64DecodingFailed)?;
+
+    verify_token_signature_with_key(&token, server_public_key)?;
+
+    if token.is_expired_at(now_millis) {
+        return Err(CryptoError::Expired("Token has expired".to_string()));
+    }
+
+    Ok(())
+}
+
 /// Generates a new Ed25519 keypair for testing purposes
 /// 
 /// This function generates a fresh keypair and returns the keys in base64 format
@@ -387,6 +613,58 @@ pub fn create_signed_challenge(
     Ok(challenge)
 }
 
+/// Mints a complete signed challenge in one call
+/// 
+/// Draws a random 32-byte nonce from the OS RNG, stamps the challenge
+/// with the current time, derives `challenge_param` and
+/// `recommended_attempts` from `difficulty`, and signs it with
+/// `signing_key`, whose public half becomes `public_key`.
+/// 
+/// # Arguments
+/// * `difficulty` - Expected number of hashes to solve the challenge
+/// * `website_id` - Website identifier the challenge is issued for
+/// * `signing_key` - The issuer's Ed25519 signing key
+/// * `ttl_ms` - How long the challenge stays valid, in milliseconds
+/// 
+/// # Returns
+/// * `IronShieldChallenge` - The signed challenge, ready to send
+pub fn mint_challenge(
+    difficulty: u64,
+    website_id: &str,
+    signing_key: &SigningKey,
+    ttl_ms: i64,
+) -> IronShieldChallenge {
+    mint_challenge_from(
+        IronShieldChallenge::builder()
+            .website_id(website_id)
+            .difficulty(difficulty)
+            .ttl_ms(ttl_ms),
+        signing_key,
+        chrono::Utc::now().timestamp_millis(),
+    )
+}
+
+/// Mints like `mint_challenge` from a configured builder, for issuers
+/// that choose the nonce or bind a resource, created at the given time
+/// in Unix milliseconds instead of the system clock.
+/// 
+/// # Arguments
+/// * `builder` - The challenge's fields
+/// * `signing_key` - The issuer's Ed25519 signing key
+/// * `now_millis` - The challenge's `created_time`
+/// 
+/// # Returns
+/// * `IronShieldChallenge` - The signed challenge, ready to send
+pub fn mint_challenge_from(
+    builder: crate::IronShieldChallengeBuilder,
+    signing_key: &SigningKey,
+    now_millis: i64,
+) -> IronShieldChallenge {
+    let mut challenge: IronShieldChallenge = builder.build_at(now_millis);
+    challenge.sign(signing_key);
+    challenge
+}
+
 /// Verifies a challenge and checks if it's valid and not expired
 /// 
 /// This is a comprehensive validation function that checks:
@@ -400,22 +678,29 @@ pub fn create_signed_challenge(
 /// # Returns
 /// * `Result<(), CryptoError>` - Ok(()) if valid, error if invalid
 pub fn validate_challenge(challenge: &IronShieldChallenge) -> Result<(), CryptoError> {
+    validate_challenge_at(challenge, chrono::Utc::now().timestamp_millis())
+}
+
+/// Validates like `validate_challenge`, checking expiry at the given time
+/// in Unix milliseconds instead of the system clock.
+///
+/// # Arguments
+/// * `challenge`  - The challenge to validate
+/// * `now_millis` - The time to check expiry at
+///
+/// # Returns
+/// * `Result<(), CryptoError>` - Ok(()) if valid, error if invalid
+pub fn validate_challenge_at(challenge: &IronShieldChallenge, now_millis: i64) -> Result<(), CryptoError> {
     // Check signature first
     verify_challenge_signature(challenge)?;
     
     // Check expiration
-    if challenge.is_expired() {
+    if challenge.is_expired_at(now_millis) {
         return Err(CryptoError::VerificationFailed("Challenge has expired".to_string()));
     }
     
     // Basic format validation
-    if challenge.random_nonce.is_empty() {
-        return Err(CryptoError::VerificationFailed("Empty random_nonce".to_string()));
-    }
-    
-    if challenge.website_id.is_empty() {
-        return Err(CryptoError::VerificationFailed("Empty website_id".to_string()));
-    }
+    challenge.validate().map_err(CryptoError::VerificationFailed)?;
     
     Ok(())
 }
@@ -735,6 +1020,10 @@ mod tests {
         
         let result = validate_challenge(&expired_challenge);
         assert!(result.is_err());
+
+        // A fixed clock decides expiry without waiting.
+        validate_challenge_at(&challenge, challenge.expiration_time).unwrap();
+        assert!(validate_challenge_at(&challenge, challenge.expiration_time + 1).is_err());
     }
 
     #[test]
@@ -793,4 +1082,283 @@ mod tests {
         // Should have exactly 5 pipe separators (6 total fields, excluding signature)
         assert_eq!(message.matches('|').count(), 5);
     }
-} 
+
+    #[test]
+    fn test_sign_challenge_with_key() {
+        use rand_core::OsRng;
+
+        let signing_key: SigningKey = SigningKey::generate(&mut OsRng);
+        let public_key: [u8; 32] = signing_key.verifying_key().to_bytes();
+        let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1_700_000_000_000,
+            "example.com".to_string(),
+            [0x12; 32],
+            public_key,
+            [0x00; 64],
+        );
+
+        challenge.challenge_signature = sign_challenge_with_key(&challenge, &signing_key.to_bytes());
+        assert!(verify_challenge_signature_with_key(&challenge, &public_key).is_ok());
+
+        challenge.website_id = "evil.com".to_string();
+        assert!(verify_challenge_signature_with_key(&challenge, &public_key).is_err());
+    }
+
+    #[test]
+    fn test_mint_challenge() {
+        use rand_core::OsRng;
+
+        let signing_key: SigningKey = SigningKey::generate(&mut OsRng);
+        let challenge: IronShieldChallenge = mint_challenge(1 << 20, "example.com", &signing_key, 30_000);
+
+        assert!(challenge.verify_signature());
+        assert_eq!(challenge.public_key, signing_key.verifying_key().to_bytes());
+        assert!(verify_challenge_signature_with_key(&challenge, &signing_key.verifying_key().to_bytes()).is_ok());
+        assert_eq!(IronShieldChallenge::challenge_param_to_difficulty(&challenge.challenge_param), 1 << 20);
+        assert_eq!(challenge.recommended_attempts, IronShieldChallenge::recommended_attempts(1 << 20));
+        assert_eq!(challenge.difficulty, Some(1 << 20));
+        assert_eq!(challenge.ttl_ms(), 30_000);
+        assert_eq!(challenge.website_id, "example.com");
+        assert_eq!(challenge.random_nonce.len(), 64);
+        assert!(challenge.validate().is_ok() && !challenge.is_expired());
+
+        // Every call draws a fresh nonce.
+        assert_ne!(mint_challenge(1 << 20, "example.com", &signing_key, 30_000).random_nonce, challenge.random_nonce);
+    }
+
+    #[test]
+    fn test_mint_challenge_from_builder() {
+        use rand_core::OsRng;
+
+        let signing_key: SigningKey = SigningKey::generate(&mut OsRng);
+        let builder: crate::IronShieldChallengeBuilder = IronShieldChallenge::builder()
+            .random_nonce("deadbeef")
+            .website_id("example.com")
+            .difficulty(65_536)
+            .ttl_ms(30_000)
+            .resource("/a");
+        let challenge: IronShieldChallenge = mint_challenge_from(builder.clone(), &signing_key, 1_700_000_000_000);
+
+        assert!(challenge.verify_signature());
+        assert_eq!(challenge.random_nonce, "deadbeef");
+        assert_eq!(challenge.created_time, 1_700_000_000_000);
+        assert_eq!(challenge.expiration_time, 1_700_000_030_000);
+        assert!(challenge.is_bound_to("/a") && !challenge.is_bound_to("/b"));
+
+        // A precomputed param is used as given.
+        let param: [u8; 32] = IronShieldChallenge::difficulty_to_challenge_param(65_536);
+        let precomputed: IronShieldChallenge =
+            mint_challenge_from(builder.challenge_param(param), &signing_key, 1_700_000_000_000);
+        assert_eq!(precomputed.challenge_param, challenge.challenge_param);
+        assert_eq!(precomputed.difficulty, Some(65_536));
+    }
+
+    #[test]
+    fn test_verify_token_lifecycle() {
+        use rand_core::OsRng;
+
+        let signing_key: SigningKey = SigningKey::generate(&mut OsRng);
+        let public_key: [u8; 32] = signing_key.verifying_key().to_bytes();
+        let token: IronShieldToken = create_signed_token([0xAB; 64], 1_700_000_000_000, None, &signing_key.to_bytes());
+
+        // Valid, in either encoding.
+        assert!(verify_token_at(&token.to_base64url_bytes(), &public_key, 1_700_000_000_000).is_ok());
+        assert!(verify_token_at(&token.to_base64url_header(), &public_key, 1_699_000_000_000).is_ok());
+        let fresh: IronShieldToken = create_signed_token([0xAB; 64], i64::MAX, None, &signing_key.to_bytes());
+        assert!(verify_token(&fresh.to_base64url_bytes(), &public_key).is_ok());
+
+        // Expired.
+        assert!(matches!(
+            verify_token_at(&token.to_base64url_bytes(), &public_key, 1_700_000_000_001),
+            Err(CryptoError::Expired(_))
+        ));
+        assert!(matches!(verify_token(&token.to_base64url_bytes(), &public_key), Err(CryptoError::Expired(_))));
+
+        // Bad signature: tampered expiry, or signed by another key.
+        let mut extended: IronShieldToken = token.clone();
+        extended.valid_for = i64::MAX;
+        assert!(matches!(
+            verify_token_at(&extended.to_base64url_bytes(), &public_key, 0),
+            Err(CryptoError::VerificationFailed(_))
+        ));
+        let other_key: SigningKey = SigningKey::generate(&mut OsRng);
+        assert!(matches!(
+            verify_token_at(&token.to_base64url_bytes(), &other_key.verifying_key().to_bytes(), 0),
+            Err(CryptoError::VerificationFailed(_))
+        ));
+
+        // Undecodable.
+        assert!(matches!(verify_token_at("not a token", &public_key, 0), Err(CryptoError::Base64DecodingFailed(_))));
+    }
+
+    #[test]
+    fn test_batch_verification_matches_individual() {
+        use rand_core::OsRng;
+
+        let signing_key: SigningKey = SigningKey::generate(&mut OsRng);
+        let other_key: SigningKey = SigningKey::generate(&mut OsRng);
+        let public_key: [u8; 32] = signing_key.verifying_key().to_bytes();
+        let challenge = |nonce: &str, key: &SigningKey| {
+            let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+                nonce.to_string(),
+                1_700_000_000_000,
+                "example.com".to_string(),
+                [0x12; 32],
+                public_key,
+                [0x00; 64],
+            );
+            challenge.challenge_signature = sign_challenge_with_key(&challenge, &key.to_bytes());
+            challenge
+        };
+
+        let valid: Vec<IronShieldChallenge> = (0..4).map(|i| challenge(&format!("cafe{:04}", i), &signing_key)).collect();
+        assert_eq!(verify_challenge_signatures_batch(&valid, &public_key), vec![true; 4]);
+
+        let mut mixed: Vec<IronShieldChallenge> = valid.clone();
+        mixed[1].website_id = "evil.com".to_string();
+        mixed[2] = challenge("cafe0002", &other_key);
+        mixed[3].challenge_signature = [0xFF; 64];
+        mixed.push(challenge("cafe0004", &signing_key));
+        let individual: Vec<bool> = mixed
+            .iter()
+            .map(|challenge: &IronShieldChallenge| verify_challenge_signature_with_key(challenge, &public_key).is_ok())
+            .collect();
+        assert_eq!(individual, vec![true, false, false, false, true]);
+        assert_eq!(verify_challenge_signatures_batch(&mixed, &public_key), individual);
+
+        assert!(verify_challenge_signatures_batch(&[], &public_key).is_empty());
+    }
+
+    #[test]
+    fn test_batch_verification_rejects_torsioned_signatures() {
+        use curve25519_dalek::{constants::EIGHT_TORSION, Scalar};
+        use sha2::{Digest, Sha512};
+
+        // Signs by hand, so a small-order point can be added to `R`.
+        let secret: Scalar = Scalar::from_bytes_mod_order_wide(&[0x07; 64]);
+        let public_key: [u8; 32] = EdwardsPoint::mul_base(&secret).compress().to_bytes();
+        let challenge = |nonce: &str, torsion: EdwardsPoint| {
+            let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+                nonce.to_string(),
+                1_700_000_000_000,
+                "example.com".to_string(),
+                [0x12; 32],
+                public_key,
+                [0x00; 64],
+            );
+            let message: String = create_signing_message(&challenge);
+            let r: Scalar = Scalar::from_hash(Sha512::new().chain_update(nonce));
+            let big_r: [u8; 32] = (EdwardsPoint::mul_base(&r) + torsion).compress().to_bytes();
+            let h: Scalar = Scalar::from_hash(Sha512::new().chain_update(big_r).chain_update(public_key).chain_update(&message));
+            challenge.challenge_signature[..32].copy_from_slice(&big_r);
+            challenge.challenge_signature[32..].copy_from_slice((r + h * secret).as_bytes());
+            challenge
+        };
+
+        let mut batch: Vec<IronShieldChallenge> = (0..4).map(|i| challenge(&format!("cafe{:04}", i), EIGHT_TORSION[0])).collect();
+        assert_eq!(verify_challenge_signatures_batch(&batch, &public_key), vec![true; 4]);
+
+        // Adding the order-2 point to `R` fails the individual check, but
+        // `verify_batch` alone misses it for half of its coefficients, which
+        // are derived from the inputs. Find a nonce it misses it for.
+        let verifying_key: VerifyingKey = VerifyingKey::from_bytes(&public_key).unwrap();
+        let raw_batch_accepts = |batch: &[IronShieldChallenge]| {
+            let messages: Vec<String> = batch.iter().map(create_signing_message).collect();
+            let message_bytes: Vec<&[u8]> = messages.iter().map(|message: &String| message.as_bytes()).collect();
+            let signatures: Vec<Signature> =
+                batch.iter().map(|challenge| Signature::from_bytes(&challenge.challenge_signature)).collect();
+            verify_batch(&message_bytes, &signatures, &vec![verifying_key; batch.len()]).is_ok()
+        };
+        batch[2] = (0..64)
+            .map(|i| challenge(&format!("torsion{:04}", i), EIGHT_TORSION[4]))
+            .find(|torsioned| {
+                let mut candidate: Vec<IronShieldChallenge> = batch.clone();
+                candidate[2] = torsioned.clone();
+                raw_batch_accepts(&candidate)
+            })
+            .unwrap();
+
+        assert!(verify_challenge_signature_with_key(&batch[2], &public_key).is_err());
+        assert!(!has_torsion_free_r(&Signature::from_bytes(&batch[2].challenge_signature)));
+        assert_eq!(verify_challenge_signatures_batch(&batch, &public_key), vec![true, true, false, true]);
+        assert_eq!(verify_challenge_signatures_batch(&batch[2..3], &public_key), vec![false]);
+    }
+
+    #[test]
+    fn test_resource_hash_is_signed() {
+        use rand_core::OsRng;
+
+        let signing_key: SigningKey = SigningKey::generate(&mut OsRng);
+        let public_key: [u8; 32] = signing_key.verifying_key().to_bytes();
+        let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "deadbeef".to_string(),
+            1_700_000_000_000,
+            "example.com".to_string(),
+            [0x12; 32],
+            public_key,
+            [0x00; 64],
+        );
+        let unbound_message: String = create_signing_message(&challenge);
+
+        challenge.resource_hash = Some(IronShieldChallenge::resource_hash_for("/downloads/a.zip"));
+        assert_eq!(
+            create_signing_message(&challenge),
+            format!("{}|{}", unbound_message, hex::encode(challenge.resource_hash.unwrap()))
+        );
+        challenge.challenge_signature = sign_challenge_with_key(&challenge, &signing_key.to_bytes());
+        assert!(verify_challenge_signature_with_key(&challenge, &public_key).is_ok());
+        assert!(challenge.is_bound_to("/downloads/a.zip"));
+        assert!(!challenge.is_bound_to("/downloads/b.zip"));
+
+        // Rebinding or unbinding the challenge breaks the signature.
+        let mut rebound: IronShieldChallenge = challenge.clone();
+        rebound.resource_hash = Some(IronShieldChallenge::resource_hash_for("/downloads/b.zip"));
+        assert!(verify_challenge_signature_with_key(&rebound, &public_key).is_err());
+        rebound.resource_hash = None;
+        assert!(verify_challenge_signature_with_key(&rebound, &public_key).is_err());
+        assert!(rebound.is_bound_to("/downloads/b.zip"), "unbound challenges unlock any resource");
+    }
+
+    #[test]
+    fn test_website_id_cannot_shift_signed_fields() {
+        let bound: IronShieldChallenge = IronShieldChallenge {
+            resource_hash: Some([0x56; 32]),
+            ..IronShieldChallenge::new("deadbeef".to_string(), 1_000_000, "w".to_string(), [0x12; 32], [0x34; 32], [0x00; 64])
+        };
+        // Moving `challenge_param` into `website_id` shifts the bound
+        // challenge's fields one to the left.
+        let shifted: IronShieldChallenge = IronShieldChallenge {
+            expiration_time: bound.expiration_time,
+            ..IronShieldChallenge::new(
+                "deadbeef".to_string(),
+                1_000_000,
+                format!("w|{}", hex::encode([0x12; 32])),
+                [0x34; 32],
+                [0x56; 32],
+                [0x00; 64],
+            )
+        };
+
+        assert_ne!(create_signing_message(&bound), create_signing_message(&shifted));
+        assert!(create_signing_message(&shifted).contains(&format!("|66:w|{}|", hex::encode([0x12; 32]))));
+    }
+
+    #[test]
+    fn test_signed_token_verifies_only_with_issuer_key() {
+        use rand_core::OsRng;
+
+        let signing_key: SigningKey = SigningKey::generate(&mut OsRng);
+        let public_key: [u8; 32] = signing_key.verifying_key().to_bytes();
+        let token: IronShieldToken = create_signed_token([0x11; 64], 1_700_000_000_000, None, &signing_key.to_bytes());
+        assert_eq!(token.public_key, public_key);
+        assert!(verify_token_signature_with_key(&token, &public_key).is_ok());
+
+        let other_key: [u8; 32] = SigningKey::generate(&mut OsRng).verifying_key().to_bytes();
+        assert!(verify_token_signature_with_key(&token, &other_key).is_err());
+
+        let mut extended: IronShieldToken = token.clone();
+        extended.valid_for += 1;
+        assert!(verify_token_signature_with_key(&extended, &public_key).is_err());
+    }
+}
@@ -0,0 +1,518 @@
+//! Helpers for reasoning about challenge difficulty when choosing
+//! difficulty policy.
+//!
+//! Difficulty here has the same meaning as in
+//! `IronShieldChallenge::difficulty_to_challenge_param`: the expected
+//! number of hash attempts needed to find a valid nonce.
+
+use serde::{Deserialize, Serialize};
+
+use crate::IronShieldChallenge;
+
+/// Lowest difficulty in the `DifficultyClass::Medium` band.
+pub const MEDIUM_DIFFICULTY_MIN:  u64 = 1 << 14;
+/// Lowest difficulty in the `DifficultyClass::High` band.
+pub const HIGH_DIFFICULTY_MIN:    u64 = 1 << 18;
+/// Lowest difficulty in the `DifficultyClass::Extreme` band.
+pub const EXTREME_DIFFICULTY_MIN: u64 = 1 << 22;
+
+/// Human-readable band of a challenge difficulty, for logs and dashboards.
+///
+/// Band edges are powers of two because challenge params encode
+/// difficulties as powers of two (see `challenge_param_to_difficulty`).
+///
+/// * `Low`:     below `MEDIUM_DIFFICULTY_MIN` (16,384 attempts).
+/// * `Medium`:  below `HIGH_DIFFICULTY_MIN` (262,144 attempts).
+/// * `High`:    below `EXTREME_DIFFICULTY_MIN` (4,194,304 attempts).
+/// * `Extreme`: everything above.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DifficultyClass {
+    Low,
+    Medium,
+    High,
+    Extreme,
+}
+
+impl DifficultyClass {
+    /// Classifies a difficulty (expected number of attempts).
+    pub fn from_difficulty(difficulty: u64) -> Self {
+        match difficulty {
+            d if d >= EXTREME_DIFFICULTY_MIN => Self::Extreme,
+            d if d >= HIGH_DIFFICULTY_MIN    => Self::High,
+            d if d >= MEDIUM_DIFFICULTY_MIN  => Self::Medium,
+            _                                => Self::Low,
+        }
+    }
+
+    /// Returns the display label, e.g. `"Medium"`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Low     => "Low",
+            Self::Medium  => "Medium",
+            Self::High    => "High",
+            Self::Extreme => "Extreme",
+        }
+    }
+}
+
+impl std::fmt::Display for DifficultyClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// Reports the single-threaded difficulty equivalent to solving a
+/// `difficulty` challenge with `threads` parallel workers.
+///
+/// Parallel search divides the wall-clock time roughly by the number of
+/// threads, so an attacker with 32 cores experiences a difficulty of
+/// 1,000,000 like a single core experiences 31,250. Operators can use
+/// this to pick a difficulty that still imposes a meaningful wall-time
+/// on well-provisioned attackers.
+///
+/// # Arguments
+/// * `difficulty`: Expected number of attempts for the challenge.
+/// * `threads`:    Number of parallel workers; `0` is treated as `1`.
+///
+/// # Returns
+/// * `u64`: The effective wall-clock difficulty, at least 1.
+///
+/// # Examples
+/// * difficulty = 1,000,000, threads = 1  → 1,000,000
+/// * difficulty = 1,000,000, threads = 32 → 31,250
+pub fn effective_wall_difficulty(difficulty: u64, threads: usize) -> u64 {
+    let threads: u64 = (threads as u64).max(1);
+    (difficulty / threads).max(1)
+}
+
+/// Computes how many nonces a solver may try before giving up on a
+/// `difficulty` challenge.
+///
+/// The budget is `IronShieldChallenge::recommended_attempts(difficulty)`
+/// multiplied by `safety_factor`, saturating at every step so callers
+/// never have to guard the multiplication themselves.
+///
+/// # Arguments
+/// * `difficulty`:    Expected number of attempts for the challenge.
+/// * `safety_factor`: Multiple of the recommended attempts to allow.
+///
+/// # Returns
+/// * `u64`: The attempt budget, saturating at `u64::MAX`.
+///
+/// # Examples
+/// * difficulty = 1,000, safety_factor = 20 → 60,000
+pub fn attempt_budget(difficulty: u64, safety_factor: u64) -> u64 {
+    IronShieldChallenge::recommended_attempts(difficulty).saturating_mul(safety_factor)
+}
+
+/// Computes the largest difficulty a solver capped at `attempt_cap`
+/// attempts still solves with probability at least `success_prob`.
+///
+/// Each attempt succeeds independently with probability `1 / difficulty`,
+/// so the chance of solving within the cap is roughly
+/// `1 - e^(-attempt_cap / difficulty)`. Clients compare an issued
+/// challenge's difficulty against this before starting, and warn or ask
+/// for an easier challenge rather than grinding to failure.
+///
+/// # Arguments
+/// * `attempt_cap`:  Most nonces the solver will try, e.g. the 10M cap of
+///   the WASM solver.
+/// * `success_prob`: Required probability of finding a solution, in
+///   `(0, 1)`. Values at or below `0` allow any difficulty; values at or
+///   above `1` (and NaN) allow only difficulty 1.
+///
+/// # Returns
+/// * `u64`: The maximum solvable difficulty, non-decreasing in
+///   `attempt_cap` and non-increasing in `success_prob`. `0` when
+///   `attempt_cap` is `0`.
+///
+/// # Examples
+/// * attempt_cap = 10,000,000, success_prob = 0.99 → 2,171,472
+pub fn max_solvable_difficulty(attempt_cap: u64, success_prob: f64) -> u64 {
+    if attempt_cap == 0 {
+        return 0;
+    }
+    if success_prob <= 0.0 {
+        return u64::MAX;
+    }
+    if success_prob >= 1.0 || success_prob.is_nan() {
+        return 1;
+    }
+
+    // -ln(1 - p) attempts per unit of difficulty; float-to-int casts
+    // saturate, so tiny probabilities clamp to u64::MAX.
+    let attempts_per_difficulty: f64 = -(-success_prob).ln_1p();
+    ((attempt_cap as f64 / attempts_per_difficulty).floor() as u64).max(1)
+}
+
+/// Leading-zero requirement of a string challenge, with its unit explicit
+/// so clients and servers cannot read "difficulty 5" differently.
+///
+/// Serialized externally tagged, e.g. `{"hexDigits":5}` or `{"bits":20}`.
+///
+/// * `HexDigits`: Leading zero hex digits, as in `find_solution`.
+/// * `Bits`:      Leading zero bits, for finer-grained difficulty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Difficulty {
+    HexDigits(u8),
+    Bits(u16),
+}
+
+impl Difficulty {
+    /// Returns the number of leading zero bits a hash needs.
+    pub fn required_bits(&self) -> u32 {
+        match *self {
+            Self::HexDigits(digits) => digits as u32 * 4,
+            Self::Bits(bits)        => bits as u32,
+        }
+    }
+
+    /// Returns the expected number of attempts, `2^required_bits`,
+    /// saturating at `u64::MAX`.
+    pub fn expected_attempts(&self) -> u64 {
+        1u64.checked_shl(self.required_bits()).unwrap_or(u64::MAX)
+    }
+}
+
+/// Converts an expected number of attempts to the leading-zero
+/// requirement, in hex digits, closest to it.
+///
+/// This is the inverse of `Difficulty::HexDigits(_).expected_attempts()`
+/// for powers of 16. Other values round to the nearest digit count on a
+/// log scale, since one hex digit changes the expected attempts 16-fold.
+///
+/// # Arguments
+/// * `attempts`: Expected number of attempts.
+///
+/// # Returns
+/// * `usize`: The closest number of leading zero hex digits; `0` for
+///   `attempts` of `0` or `1`.
+///
+/// # Examples
+/// * attempts = 65,536 → 4
+/// * attempts = 100,000 → 4
+pub fn expected_attempts_to_leading_zeros(attempts: u64) -> usize {
+    match attempts {
+        0 => 0,
+        _ => ((attempts as f64).log2() / 4.0).round() as usize,
+    }
+}
+
+/// Converts a leading-zero requirement, in hex digits as in
+/// `find_solution`, to the equivalent `IronShieldChallenge` threshold.
+///
+/// The param is `2^(256 - 4 * zeros)`, so a hash is below it exactly when
+/// it has at least `4 * zeros` leading zero bits. A challenge issued with
+/// it accepts the same hashes as the leading-zero verifier, which lets one
+/// issuance serve both schemes while migrating between them.
+///
+/// # Arguments
+/// * `zeros`: Required leading zero hex digits.
+///
+/// # Returns
+/// * `[u8; 32]`: The challenge_param bytes in big-endian format;
+///   `[0xFF; 32]` for `0` and all zeros, which no hash is below, beyond
+///   64 digits.
+///
+/// # Examples
+/// * zeros = 4 → `[0x00, 0x01, 0x00, ...]`, equal to
+///   `difficulty_to_challenge_param(65_536)`
+pub fn leading_zeros_to_param(zeros: usize) -> [u8; 32] {
+    let mut param: [u8; 32] = [0u8; 32];
+    match zeros.saturating_mul(4) {
+        0 => param = [0xFF; 32],
+        bits @ 1..=256 => {
+            // The single set bit follows `bits - 1` zero bits.
+            let index: usize = bits - 1;
+            param[index / 8] = 0x80 >> (index % 8);
+        }
+        _ => {}
+    }
+    param
+}
+
+/// How a challenge expresses its difficulty.
+///
+/// * `LeadingZeros`: Leading zero hex digits of a string challenge.
+/// * `Threshold`:    Expected attempts of an `IronShieldChallenge`, as in
+///   `IronShieldChallenge::difficulty_to_challenge_param`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DifficultyScheme {
+    LeadingZeros,
+    Threshold,
+}
+
+/// Expresses a difficulty from one scheme in another, for migrating
+/// difficulty policy between schemes.
+///
+/// # Arguments
+/// * `difficulty`: The difficulty in the `from` scheme.
+/// * `from`:       The scheme `difficulty` is expressed in.
+/// * `to`:         The scheme to express it in.
+///
+/// # Returns
+/// * `u64`: The closest equivalent difficulty in the `to` scheme, see
+///   `Difficulty::expected_attempts` and
+///   `expected_attempts_to_leading_zeros`.
+///
+/// # Examples
+/// * 4, `LeadingZeros` → `Threshold` = 65,536
+pub fn equivalent_difficulty(difficulty: u64, from: DifficultyScheme, to: DifficultyScheme) -> u64 {
+    match (from, to) {
+        (DifficultyScheme::LeadingZeros, DifficultyScheme::Threshold) => {
+            u8::try_from(difficulty).map_or(u64::MAX, |digits: u8| Difficulty::HexDigits(digits).expected_attempts())
+        }
+        (DifficultyScheme::Threshold, DifficultyScheme::LeadingZeros) => {
+            expected_attempts_to_leading_zeros(difficulty) as u64
+        }
+        _ => difficulty,
+    }
+}
+
+/// How many solutions a submission carries, for capacity planning.
+///
+/// * `Single`: One nonce per challenge, as verified today.
+/// * `KOfN`:   `k` independent solutions, e.g. for batched or k-of-n
+///   challenge schemes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationScheme {
+    Single,
+    KOfN,
+}
+
+/// Number of SHA-256 hashes the server computes to verify a submission.
+///
+/// Verification recomputes one hash per solution, so its cost does not
+/// depend on the difficulty; only solving gets harder.
+///
+/// # Arguments
+/// * `scheme`:      The submission scheme.
+/// * `_difficulty`: The challenge difficulty. Accepted for symmetry with
+///   the solve-side helpers; it does not affect the cost.
+/// * `k`:           Solutions per submission under `VerificationScheme::KOfN`;
+///   ignored for `Single`.
+///
+/// # Returns
+/// * `u64`: The hash operations needed per submission.
+pub fn verification_cost(scheme: VerificationScheme, _difficulty: u64, k: u64) -> u64 {
+    match scheme {
+        VerificationScheme::Single => 1,
+        VerificationScheme::KOfN   => k,
+    }
+}
+
+/// Computes the challenge params for a schedule of difficulties, e.g. a
+/// difficulty ramp, so callers can cache them instead of converting on
+/// every issuance.
+///
+/// # Arguments
+/// * `difficulties`: Expected numbers of attempts, each greater than 0.
+///
+/// # Returns
+/// * `Vec<[u8; 32]>`: `IronShieldChallenge::difficulty_to_challenge_param`
+///   of each difficulty, in order.
+///
+/// # Panics
+/// * Panics if any difficulty is 0.
+pub fn precompute_params(difficulties: &[u64]) -> Vec<[u8; 32]> {
+    difficulties
+        .iter()
+        .map(|&difficulty: &u64| IronShieldChallenge::difficulty_to_challenge_param(difficulty))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_wall_difficulty_decreases_with_threads() {
+        let difficulty: u64 = 1_000_000;
+
+        assert_eq!(effective_wall_difficulty(difficulty, 1), difficulty);
+        assert_eq!(effective_wall_difficulty(difficulty, 32), 31_250);
+
+        let mut previous: u64 = u64::MAX;
+        for threads in [1, 2, 4, 8, 16, 32, 64] {
+            let effective: u64 = effective_wall_difficulty(difficulty, threads);
+            assert!(effective < previous, "{} threads should be faster than fewer", threads);
+            previous = effective;
+        }
+    }
+
+    #[test]
+    fn test_effective_wall_difficulty_edge_cases() {
+        assert_eq!(effective_wall_difficulty(1_000, 0), 1_000);
+        assert_eq!(effective_wall_difficulty(10, 64), 1);
+        assert_eq!(effective_wall_difficulty(u64::MAX, 1), u64::MAX);
+    }
+
+    #[test]
+    fn test_attempt_budget() {
+        assert_eq!(attempt_budget(1_000, 7), 21_000);
+        assert_eq!(attempt_budget(1_000, 0), 0);
+        assert_eq!(attempt_budget(0, 7), 0);
+    }
+
+    #[test]
+    fn test_attempt_budget_scales_with_difficulty() {
+        // Solvers derive the difficulty from the challenge param, so walk
+        // easy to hard through that round trip.
+        let difficulties: [u64; 5] = [1_000, 10_000, 100_000, 1_000_000, 100_000_000];
+
+        for safety_factor in [1, 7, 20] {
+            let budgets: Vec<u64> = difficulties
+                .iter()
+                .map(|&difficulty: &u64| {
+                    let param: [u8; 32] = IronShieldChallenge::difficulty_to_challenge_param(difficulty);
+                    attempt_budget(IronShieldChallenge::challenge_param_to_difficulty(&param), safety_factor)
+                })
+                .collect();
+            for pair in budgets.windows(2) {
+                assert!(pair[0] < pair[1], "budgets {:?} should grow with difficulty", budgets);
+            }
+        }
+    }
+
+    #[test]
+    fn test_attempt_budget_saturates() {
+        assert_eq!(attempt_budget(u64::MAX, 1), u64::MAX);
+        assert_eq!(attempt_budget(u64::MAX / 2, 2), u64::MAX);
+        assert_eq!(attempt_budget(1 << 40, 1 << 40), u64::MAX);
+        assert_eq!(attempt_budget(u64::MAX, u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn test_difficulty_class_from_difficulty() {
+        assert_eq!(DifficultyClass::from_difficulty(1), DifficultyClass::Low);
+        assert_eq!(DifficultyClass::from_difficulty(MEDIUM_DIFFICULTY_MIN - 1), DifficultyClass::Low);
+        assert_eq!(DifficultyClass::from_difficulty(MEDIUM_DIFFICULTY_MIN), DifficultyClass::Medium);
+        assert_eq!(DifficultyClass::from_difficulty(HIGH_DIFFICULTY_MIN), DifficultyClass::High);
+        assert_eq!(DifficultyClass::from_difficulty(EXTREME_DIFFICULTY_MIN), DifficultyClass::Extreme);
+        assert_eq!(DifficultyClass::from_difficulty(u64::MAX), DifficultyClass::Extreme);
+        assert_eq!(DifficultyClass::High.to_string(), "High");
+    }
+
+    #[test]
+    fn test_max_solvable_difficulty() {
+        assert_eq!(max_solvable_difficulty(10_000_000, 0.99), 2_171_472);
+        assert_eq!(max_solvable_difficulty(0, 0.5), 0);
+        assert_eq!(max_solvable_difficulty(1_000, 0.0), u64::MAX);
+        assert_eq!(max_solvable_difficulty(1_000, 1.0), 1);
+        assert_eq!(max_solvable_difficulty(1_000, f64::NAN), 1);
+
+        // About 63% of difficulty-d challenges are solved within d attempts.
+        assert_eq!(max_solvable_difficulty(1_000, 1.0 - (-1.0f64).exp()), 1_000);
+    }
+
+    #[test]
+    fn test_max_solvable_difficulty_is_monotonic() {
+        let caps: [u64; 6] = [1, 10, 1_000, 100_000, 10_000_000, u64::MAX];
+        let probs: [f64; 7] = [0.01, 0.1, 0.5, 0.9, 0.99, 0.999_999, 1.0];
+
+        for prob in probs {
+            for pair in caps.windows(2) {
+                assert!(max_solvable_difficulty(pair[0], prob) <= max_solvable_difficulty(pair[1], prob));
+            }
+        }
+        for cap in caps {
+            for pair in probs.windows(2) {
+                assert!(max_solvable_difficulty(cap, pair[0]) >= max_solvable_difficulty(cap, pair[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_difficulty_units() {
+        assert_eq!(Difficulty::HexDigits(5).required_bits(), 20);
+        assert_eq!(Difficulty::Bits(5).required_bits(), 5);
+        assert_eq!(Difficulty::HexDigits(4).expected_attempts(), 65_536);
+        assert_eq!(Difficulty::Bits(5).expected_attempts(), 32);
+        assert_eq!(Difficulty::Bits(64).expected_attempts(), u64::MAX);
+    }
+
+    #[test]
+    fn test_cross_scheme_equivalence() {
+        assert_eq!(equivalent_difficulty(0, DifficultyScheme::LeadingZeros, DifficultyScheme::Threshold), 1);
+        assert_eq!(equivalent_difficulty(16, DifficultyScheme::LeadingZeros, DifficultyScheme::Threshold), u64::MAX);
+        assert_eq!(equivalent_difficulty(u64::MAX, DifficultyScheme::LeadingZeros, DifficultyScheme::Threshold), u64::MAX);
+        assert_eq!(expected_attempts_to_leading_zeros(65_536), 4);
+        assert_eq!(expected_attempts_to_leading_zeros(100_000), 4);
+        assert_eq!(expected_attempts_to_leading_zeros(0), 0);
+
+        let threshold: u64 = equivalent_difficulty(4, DifficultyScheme::LeadingZeros, DifficultyScheme::Threshold);
+        assert_eq!(threshold, 65_536);
+        assert_eq!(threshold, Difficulty::HexDigits(4).expected_attempts());
+        assert_eq!(equivalent_difficulty(threshold, DifficultyScheme::Threshold, DifficultyScheme::LeadingZeros), 4);
+        assert_eq!(equivalent_difficulty(4, DifficultyScheme::LeadingZeros, DifficultyScheme::LeadingZeros), 4);
+
+        // The threshold encoding represents the equivalent exactly.
+        let param: [u8; 32] = IronShieldChallenge::difficulty_to_challenge_param(threshold);
+        assert_eq!(IronShieldChallenge::challenge_param_to_difficulty(&param), threshold);
+
+        for digits in 0..16u8 {
+            let attempts: u64 = Difficulty::HexDigits(digits).expected_attempts();
+            assert_eq!(expected_attempts_to_leading_zeros(attempts), digits as usize);
+        }
+    }
+
+    #[test]
+    fn test_leading_zeros_to_param() {
+        let param: [u8; 32] = leading_zeros_to_param(4);
+        let mut expected: [u8; 32] = [0u8; 32];
+        expected[1] = 0x01;
+        assert_eq!(param, expected);
+
+        // Hashes below it are exactly those with 16 leading zero bits.
+        let mut sixteen_zero_bits: [u8; 32] = [0xFF; 32];
+        sixteen_zero_bits[..2].copy_from_slice(&[0x00, 0x00]);
+        let mut fifteen_zero_bits: [u8; 32] = [0x00; 32];
+        fifteen_zero_bits[1] = 0x01;
+        assert!(sixteen_zero_bits < param);
+        assert!(fifteen_zero_bits >= param);
+
+        for digits in 1..16u8 {
+            let attempts: u64 = Difficulty::HexDigits(digits).expected_attempts();
+            assert_eq!(leading_zeros_to_param(digits as usize), IronShieldChallenge::difficulty_to_challenge_param(attempts));
+        }
+        assert_eq!(leading_zeros_to_param(0), [0xFF; 32]);
+        let mut one: [u8; 32] = [0u8; 32];
+        one[31] = 0x01;
+        assert_eq!(leading_zeros_to_param(64), one);
+        assert_eq!(leading_zeros_to_param(65), [0u8; 32]);
+        assert_eq!(leading_zeros_to_param(usize::MAX), [0u8; 32]);
+    }
+
+    #[test]
+    fn test_difficulty_wire_format_is_unambiguous() {
+        let hex: String = serde_json::to_string(&Difficulty::HexDigits(5)).unwrap();
+        let bits: String = serde_json::to_string(&Difficulty::Bits(5)).unwrap();
+        assert_eq!(hex, r#"{"hexDigits":5}"#);
+        assert_eq!(bits, r#"{"bits":5}"#);
+
+        assert_eq!(serde_json::from_str::<Difficulty>(&bits).unwrap(), Difficulty::Bits(5));
+        assert!(serde_json::from_str::<Difficulty>("5").is_err());
+    }
+
+    #[test]
+    fn test_verification_cost() {
+        for difficulty in [1u64, 16, 1 << 20, u64::MAX] {
+            assert_eq!(verification_cost(VerificationScheme::Single, difficulty, 5), 1);
+            assert_eq!(verification_cost(VerificationScheme::KOfN, difficulty, 5), 5);
+        }
+        assert_eq!(verification_cost(VerificationScheme::KOfN, 16, 1), 1);
+    }
+
+    #[test]
+    fn test_precompute_params() {
+        let difficulties: [u64; 4] = [1, 16, 4_096, 1 << 24];
+        let params: Vec<[u8; 32]> = precompute_params(&difficulties);
+
+        assert_eq!(params.len(), difficulties.len());
+        for (difficulty, param) in difficulties.iter().zip(&params) {
+            assert_eq!(*param, IronShieldChallenge::difficulty_to_challenge_param(*difficulty));
+        }
+        assert!(precompute_params(&[]).is_empty());
+    }
+}
@@ -8,12 +8,16 @@ mod challenge;
 mod response;
 mod token;
 mod crypto;
+mod difficulty;
 
 pub use serde_utils::*;
 pub use challenge::*;
 pub use response::*;
 pub use token::*;
 pub use crypto::*;
+pub use difficulty::*;
 
 // Re-export chrono for convenience
-pub use chrono; 
\ No newline at end of file
+pub use chrono;
+// Re-export the key type `mint_challenge` signs with
+pub use ed25519_dalek::SigningKey; 
\ No newline at end of file
@@ -1,10 +1,27 @@
-use crate::serde_utils::{serialize_signature, deserialize_signature};
+use crate::serde_utils::{
+    canonical_json_object,
+    serialize_signature,
+    deserialize_signature,
+    serialize_optional_32_bytes,
+    deserialize_optional_32_bytes,
+    serialize_optional_signature,
+    deserialize_optional_signature,
+};
 use serde::{Deserialize, Serialize};
 
 /// IronShield Challenge Response structure
 /// 
 /// * `challenge_signature`: The Ed25519 signature of the challenge (copied from challenge).
 /// * `solution`:            The nonce solution found by the proof-of-work algorithm.
+/// * `challenge_id`:        Optional `IronShieldChallenge::challenge_id()` of the
+///   solved challenge, for correlating responses in audit logs.
+/// * `client_public_key`:   Optional Ed25519 public key of the client's device
+///   key (e.g. a WebAuthn passkey), for device-bound responses.
+/// * `client_signature`:    Optional signature by `client_public_key` over
+///   `client_signing_message()`.
+///
+/// The client fields are carried in the JSON form only; `concat_struct`
+/// and the header encoding omit them.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IronShieldChallengeResponse {
     #[serde(
@@ -13,6 +30,27 @@ pub struct IronShieldChallengeResponse {
     )]
     pub challenge_signature: [u8; 64],
     pub solution:            i64,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_32_bytes",
+        deserialize_with = "deserialize_optional_32_bytes"
+    )]
+    pub challenge_id:        Option<[u8; 32]>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_32_bytes",
+        deserialize_with = "deserialize_optional_32_bytes"
+    )]
+    pub client_public_key:   Option<[u8; 32]>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_signature",
+        deserialize_with = "deserialize_optional_signature"
+    )]
+    pub client_signature:    Option<[u8; 64]>,
 }
 
 impl IronShieldChallengeResponse {
@@ -21,6 +59,21 @@ impl IronShieldChallengeResponse {
         Self {
             challenge_signature,
             solution,
+            challenge_id: None,
+            client_public_key: None,
+            client_signature: None,
+        }
+    }
+
+    /// Constructor for a response that carries the id of the solved
+    /// challenge, see `IronShieldChallenge::challenge_id()`.
+    pub fn new_with_challenge_id(challenge_signature: [u8; 64], solution: i64, challenge_id: [u8; 32]) -> Self {
+        Self {
+            challenge_signature,
+            solution,
+            challenge_id: Some(challenge_id),
+            client_public_key: None,
+            client_signature: None,
         }
     }
 
@@ -29,22 +82,59 @@ impl IronShieldChallengeResponse {
     /// Concatenates:
     /// - `challenge_signature` as a lowercase hex string.
     /// - `solution`:           as a string.
+    /// - `challenge_id`:       as a lowercase hex string, only when present.
     pub fn concat_struct(&self) -> String {
-        format!(
+        let concat: String = format!(
             "{}|{}",
             // Use of hex::encode to convert the signature to a hex string
             // "Encodes data as hex string using lowercase characters."
             // Requirement of `format!`.
             hex::encode(self.challenge_signature),
             self.solution
-        )
+        );
+
+        match self.challenge_id {
+            Some(challenge_id) => format!("{}|{}", concat, hex::encode(challenge_id)),
+            None => concat,
+        }
+    }
+
+    /// Builds the message a client device key signs to bind this
+    /// response to the device.
+    ///
+    /// Format: "hex_challenge_signature|solution", so the signature
+    /// commits to both the solved challenge and the solution.
+    pub fn client_signing_message(&self) -> String {
+        format!("{}|{}", hex::encode(self.challenge_signature), self.solution)
+    }
+
+    /// Serializes the response as canonical JSON.
+    ///
+    /// Keys are emitted in this fixed (alphabetical) order with no
+    /// whitespace, and byte arrays as lowercase hex strings:
+    /// - `challenge_id`, only when present
+    /// - `challenge_signature`
+    /// - `solution`
+    ///
+    /// # Returns
+    /// * `String`: The canonical JSON, byte-identical for equal fields.
+    pub fn to_canonical_json(&self) -> String {
+        let mut fields: Vec<(&str, serde_json::Value)> = Vec::with_capacity(3);
+        if let Some(challenge_id) = self.challenge_id {
+            fields.push(("challenge_id", hex::encode(challenge_id).into()));
+        }
+        fields.push(("challenge_signature", hex::encode(self.challenge_signature).into()));
+        fields.push(("solution",            self.solution.into()));
+
+        canonical_json_object(&fields)
     }
 
     /// Creates an `IronShieldChallengeResponse` from a concatenated string.
     ///
     /// This function reverses the operation of
     /// `IronShieldChallengeResponse::concat_struct`.
-    /// Expects a string in the format: "hex_signature|solution".
+    /// Expects a string in the format: "hex_signature|solution", optionally
+    /// followed by "|hex_challenge_id".
     ///
     /// # Arguments
     /// * `concat_string`: The concatenated string to parse, typically
@@ -57,8 +147,8 @@ impl IronShieldChallengeResponse {
     pub fn from_concat_struct(concat_string: &str) -> Result<Self, String> {
         let parts: Vec<&str> = concat_string.split('|').collect();
 
-        if parts.len() != 2 {
-            return Err(format!("Expected 2 parts, got {}", parts.len()));
+        if parts.len() != 2 && parts.len() != 3 {
+            return Err(format!("Expected 2 parts (3 with challenge_id), got {}", parts.len()));
         }
 
         let signature_bytes = hex::decode(parts[0])
@@ -69,9 +159,22 @@ impl IronShieldChallengeResponse {
         let solution = parts[1].parse::<i64>()
             .map_err(|_| "Failed to parse solution as i64")?;
 
+        let challenge_id: Option<[u8; 32]> = match parts.get(2) {
+            Some(id_hex) => {
+                let id_bytes = hex::decode(id_hex)
+                    .map_err(|_| "Failed to decode challenge_id hex string")?;
+                Some(id_bytes.try_into()
+                    .map_err(|_| "Challenge id must be exactly 32 bytes")?)
+            }
+            None => None,
+        };
+
         Ok(Self {
             challenge_signature,
             solution,
+            challenge_id,
+            client_public_key: None,
+            client_signature: None,
         })
     }
 
@@ -205,4 +308,61 @@ mod tests {
         assert_eq!(parsed.challenge_signature, [0xffu8; 64]);
         assert_eq!(parsed.solution, -1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_from_concat_struct_invalid_solution() {
+        let signature_hex: String = hex::encode([0xAB; 64]);
+        for solution in ["", "12abc", "1.5", "0x10", "9223372036854775808"] {
+            let result: Result<IronShieldChallengeResponse, String> =
+                IronShieldChallengeResponse::from_concat_struct(&format!("{}|{}", signature_hex, solution));
+            assert_eq!(result.unwrap_err(), "Failed to parse solution as i64", "solution {:?}", solution);
+        }
+
+        let max: String = format!("{}|{}", signature_hex, i64::MAX);
+        assert_eq!(IronShieldChallengeResponse::from_concat_struct(&max).unwrap().solution, i64::MAX);
+    }
+
+    #[test]
+    fn test_response_challenge_id_roundtrip() {
+        let response: IronShieldChallengeResponse = IronShieldChallengeResponse::new_with_challenge_id([0xAB; 64], 12345, [0xCD; 32]);
+
+        let decoded: IronShieldChallengeResponse = IronShieldChallengeResponse::from_concat_struct(&response.concat_struct()).unwrap();
+        assert_eq!(decoded.challenge_id, Some([0xCD; 32]));
+
+        let json: String = serde_json::to_string(&response).unwrap();
+        let decoded: IronShieldChallengeResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.challenge_id, Some([0xCD; 32]));
+    }
+
+    #[test]
+    fn test_response_without_challenge_id_is_unchanged() {
+        let response: IronShieldChallengeResponse = IronShieldChallengeResponse::new([0xAB; 64], 12345);
+        assert_eq!(response.concat_struct().split('|').count(), 2);
+
+        let json: String = serde_json::to_string(&response).unwrap();
+        assert!(!json.contains("challenge_id"));
+        let decoded: IronShieldChallengeResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.challenge_id, None);
+    }
+
+    #[test]
+    fn test_response_invalid_challenge_id() {
+        let concat: String = format!("{}|12345|{}", hex::encode([0xAB; 64]), hex::encode([0xCD; 16]));
+        let result: Result<IronShieldChallengeResponse, String> = IronShieldChallengeResponse::from_concat_struct(&concat);
+        assert!(result.unwrap_err().contains("exactly 32 bytes"));
+    }
+
+    #[test]
+    fn test_to_canonical_json() {
+        let response: IronShieldChallengeResponse = IronShieldChallengeResponse::new([0xAB; 64], -42);
+        assert_eq!(
+            response.to_canonical_json(),
+            format!("{{\"challenge_signature\":\"{}\",\"solution\":-42}}", "ab".repeat(64))
+        );
+
+        let with_id: IronShieldChallengeResponse = IronShieldChallengeResponse::new_with_challenge_id([0xAB; 64], 7, [0x01; 32]);
+        let rebuilt: IronShieldChallengeResponse = IronShieldChallengeResponse::from_concat_struct(&with_id.concat_struct()).unwrap();
+        assert_eq!(with_id.to_canonical_json(), rebuilt.to_canonical_json());
+        assert!(with_id.to_canonical_json().starts_with(&format!("{{\"challenge_id\":\"{}\",", "01".repeat(32))));
+    }
+}
@@ -51,6 +51,87 @@ where
     Ok(array)
 }
 
+/// Custom serialization for optional 32-byte arrays (challenge ids)
+pub fn serialize_optional_32_bytes<S>(bytes: &Option<[u8; 32]>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match bytes {
+        Some(bytes) => serializer.serialize_some(&bytes[..]),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Custom deserialization for optional 32-byte arrays (challenge ids)
+pub fn deserialize_optional_32_bytes<'de, D>(deserializer: D) -> Result<Option<[u8; 32]>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+    let bytes: Option<Vec<u8>> = Option::deserialize(deserializer)?;
+
+    match bytes {
+        None => Ok(None),
+        Some(bytes) => {
+            let array: [u8; 32] = bytes.try_into()
+                .map_err(|bytes: Vec<u8>| Error::custom(format!("Expected 32 bytes, got {}", bytes.len())))?;
+            Ok(Some(array))
+        }
+    }
+}
+
+/// Custom serialization for optional 64-byte arrays (client signatures)
+pub fn serialize_optional_signature<S>(signature: &Option<[u8; 64]>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match signature {
+        Some(signature) => serializer.serialize_some(&signature[..]),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Custom deserialization for optional 64-byte arrays (client signatures)
+pub fn deserialize_optional_signature<'de, D>(deserializer: D) -> Result<Option<[u8; 64]>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+    let bytes: Option<Vec<u8>> = Option::deserialize(deserializer)?;
+
+    match bytes {
+        None => Ok(None),
+        Some(bytes) => {
+            let array: [u8; 64] = bytes.try_into()
+                .map_err(|bytes: Vec<u8>| Error::custom(format!("Expected 64 bytes, got {}", bytes.len())))?;
+            Ok(Some(array))
+        }
+    }
+}
+
+/// Writes a JSON object with keys in exactly the given order and no
+/// whitespace, for `to_canonical_json` implementations.
+///
+/// `serde_json::Map` ordering depends on the `preserve_order` feature,
+/// which any crate in the build can enable, so canonical output is
+/// assembled by hand instead.
+///
+/// # Arguments
+/// * `fields`: `(key, value)` pairs, already in canonical (sorted) order.
+///
+/// # Returns
+/// * `String`: The compact JSON object.
+pub(crate) fn canonical_json_object(fields: &[(&str, serde_json::Value)]) -> String {
+    debug_assert!(fields.windows(2).all(|pair| pair[0].0 < pair[1].0), "canonical keys must be sorted");
+
+    let members: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}:{}", serde_json::Value::from(*key), value))
+        .collect();
+
+    format!("{{{}}}", members.join(","))
+}
+
 /// Encodes a concatenated string into a Base64 URL-safe 
 /// format without padding.
 /// 
@@ -80,6 +161,10 @@ pub fn concat_struct_base64url_encode(concat_string: &str) -> String {
 /// * `encoded_string`: The Base64 URL-safe encoded string 
 ///                     to decode.
 /// 
+/// Only the canonical encoding is accepted, see
+/// `is_canonical_base64url`, so every challenge has exactly one valid
+/// encoding.
+///
 /// # Returns
 /// * A Result containing the decoded string or an error 
 ///   if decoding fails.
@@ -87,11 +172,30 @@ pub fn concat_struct_base64url_encode(concat_string: &str) -> String {
 /// # Errors
 /// * Returns a `base64::DecodeError` if the input string 
 ///   is not valid Base64 URL-safe encoded.
+/// * Returns an error if the input is not the canonical encoding
+///   of the bytes it decodes to.
 pub fn concat_struct_base64url_decode(encoded_string: String) -> Result<String, String> {
     let decoded_bytes: Vec<u8> = base64::engine::general_purpose::URL_SAFE_NO_PAD
-        .decode(encoded_string)
+        .decode(&encoded_string)
         .map_err(|e: base64::DecodeError| format!("Base64 decode error: {}", e))?;
 
+    if !is_canonical_base64url(&encoded_string, &decoded_bytes) {
+        return Err("Non-canonical base64url encoding".to_string());
+    }
+
     String::from_utf8(decoded_bytes)
         .map_err(|e: std::string::FromUtf8Error| format!("UTF-8 conversion error: {}", e))
-} 
\ No newline at end of file
+}
+
+/// Returns whether `encoded` is the canonical unpadded base64url encoding
+/// of `decoded`, i.e. re-encoding `decoded` reproduces it exactly.
+///
+/// Rejects encodings with non-zero trailing bits, which decode to the same
+/// bytes as the canonical one under lenient decoders.
+///
+/// # Arguments
+/// * `encoded`: The received base64url string.
+/// * `decoded`: The bytes it decoded to.
+pub fn is_canonical_base64url(encoded: &str, decoded: &[u8]) -> bool {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(decoded) == encoded
+}
@@ -1,6 +1,53 @@
+use base64::Engine;
 use chrono::Utc;
-use crate::serde_utils::{serialize_signature, deserialize_signature};
+use crate::serde_utils::{
+    serialize_signature,
+    deserialize_signature,
+    serialize_optional_32_bytes,
+    deserialize_optional_32_bytes,
+};
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Length in bytes of an HMAC-SHA256 tag stored in
+/// `authentication_signature`.
+const HMAC_TAG_LEN: usize = 32;
+/// Length in bytes of a token in the binary form produced by
+/// `IronShieldToken::to_bytes`.
+pub const TOKEN_BYTES_LEN: usize = 64 + 8 + 32 + 64;
+/// Length in bytes of the binary form of a token with a `resource_hash`.
+pub const BOUND_TOKEN_BYTES_LEN: usize = TOKEN_BYTES_LEN + 32;
+
+/// How a token's `authentication_signature` is produced and checked.
+///
+/// * `Ed25519`: Asymmetric signature clients can verify with the public key.
+/// * `Hmac`:    HMAC-SHA256 with a server-only secret; cheaper, server-verified only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenAuthMode {
+    Ed25519,
+    Hmac,
+}
+
+impl TokenAuthMode {
+    /// Parses a configuration value (`"ed25519"` or `"hmac"`,
+    /// case-insensitive).
+    ///
+    /// # Arguments
+    /// * `value`: The raw configuration value.
+    ///
+    /// # Returns
+    /// * `Option<Self>`: The mode, or `None` if the value is not recognized.
+    pub fn from_config_value(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "ed25519" => Some(Self::Ed25519),
+            "hmac"    => Some(Self::Hmac),
+            _         => None,
+        }
+    }
+}
 
 /// IronShield Token structure
 /// 
@@ -10,7 +57,8 @@ use serde::{Deserialize, Serialize};
 ///                               to the central private key (32 bytes).
 /// * `authentication_signature`: The signature over (challenge_signature 
 ///                               || valid_for).
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// * `resource_hash`:            Optional SHA-256 of the one resource the token unlocks (signed when set).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct IronShieldToken {
     #[serde(
         serialize_with = "serialize_signature",
@@ -24,6 +72,13 @@ pub struct IronShieldToken {
         deserialize_with = "deserialize_signature"
     )]
     pub authentication_signature: [u8; 64],
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_32_bytes",
+        deserialize_with = "deserialize_optional_32_bytes"
+    )]
+    pub resource_hash:            Option<[u8; 32]>,
 }
 
 impl IronShieldToken {
@@ -38,12 +93,142 @@ impl IronShieldToken {
             valid_for,
             public_key,
             authentication_signature,
+            resource_hash: None,
+        }
+    }
+
+    /// Constructor for a token authenticated with HMAC-SHA256 instead of
+    /// an Ed25519 signature.
+    ///
+    /// The 32-byte tag is stored in the first half of
+    /// `authentication_signature`; the second half is zero.
+    ///
+    /// # Arguments
+    /// * `challenge_signature`: The Ed25519 signature of the challenge.
+    /// * `valid_for`:           Expiration time in Unix millis.
+    /// * `public_key`:          The Ed25519 public key of the issuer.
+    /// * `secret`:              The server-only HMAC key.
+    pub fn new_hmac(
+        challenge_signature: [u8; 64],
+        valid_for:           i64,
+        public_key:          [u8; 32],
+        secret:              &[u8],
+    ) -> Self {
+        let mut token: Self = Self::new(challenge_signature, valid_for, public_key, [0u8; 64]);
+        token.sign_hmac(secret);
+        token
+    }
+
+    /// Authenticates this token with HMAC-SHA256, as `new_hmac` does.
+    ///
+    /// Replaces `authentication_signature` with the tag over
+    /// `authentication_message()` followed by zero padding, so call it
+    /// after setting `resource_hash`.
+    ///
+    /// # Arguments
+    /// * `secret`: The server-only HMAC key.
+    pub fn sign_hmac(&mut self, secret: &[u8]) {
+        let tag: [u8; HMAC_TAG_LEN] = self.hmac_tag(secret);
+        self.authentication_signature = [0u8; 64];
+        self.authentication_signature[..HMAC_TAG_LEN].copy_from_slice(&tag);
+    }
+
+    /// Verifies an HMAC-authenticated token created by `new_hmac`.
+    ///
+    /// # Arguments
+    /// * `secret`: The server-only HMAC key.
+    ///
+    /// # Returns
+    /// * `bool`: `true` if the tag matches and the padding is zero.
+    pub fn verify_hmac(&self, secret: &[u8]) -> bool {
+        let (tag, padding) = self.authentication_signature.split_at(HMAC_TAG_LEN);
+        if padding.iter().any(|&byte| byte != 0) {
+            return false;
+        }
+
+        let mut mac: HmacSha256 = HmacSha256::new_from_slice(secret)
+            .expect("HMAC accepts keys of any length");
+        mac.update(self.authentication_message().as_bytes());
+        // `verify_slice` compares in constant time.
+        mac.verify_slice(tag).is_ok()
+    }
+
+    /// Signs this token with the issuer's Ed25519 key.
+    ///
+    /// Sets `public_key` to the key's public half, then stores the
+    /// signature over `authentication_message()` in
+    /// `authentication_signature`.
+    ///
+    /// # Arguments
+    /// * `signing_key`: The issuer's Ed25519 signing key.
+    pub fn sign(&mut self, signing_key: &ed25519_dalek::SigningKey) {
+        use ed25519_dalek::Signer;
+
+        self.public_key = signing_key.verifying_key().to_bytes();
+        self.authentication_signature = signing_key.sign(self.authentication_message().as_bytes()).to_bytes();
+    }
+
+    /// Checks `authentication_signature` against the issuer's key.
+    ///
+    /// Only the signature is checked; use `crate::verify_token` to also
+    /// check expiry.
+    ///
+    /// # Arguments
+    /// * `server_public_key`: The issuer's Ed25519 public key, not the
+    ///   (untrusted) one embedded in the token.
+    ///
+    /// # Returns
+    /// * `bool`: `true` if the token was signed by `server_public_key`
+    ///   and is unchanged since.
+    pub fn verify(&self, server_public_key: &[u8; 32]) -> bool {
+        crate::verify_token_signature_with_key(self, server_public_key).is_ok()
+    }
+
+    /// Returns the message covered by `authentication_signature`:
+    /// "challenge_signature|valid_for|public_key" with hex-encoded arrays,
+    /// followed by "|resource_hash" when set. No field can contain `|`, so
+    /// the optional field cannot be forged by shifting the others.
+    pub fn authentication_message(&self) -> String {
+        let message: String = format!(
+            "{}|{}|{}",
+            hex::encode(self.challenge_signature),
+            self.valid_for,
+            hex::encode(self.public_key)
+        );
+        match self.resource_hash {
+            Some(resource_hash) => format!("{}|{}", message, hex::encode(resource_hash)),
+            None => message,
         }
     }
 
+    /// Returns whether this token may unlock `resource`.
+    ///
+    /// Only meaningful once the signature is verified, since
+    /// `resource_hash` is covered by it.
+    ///
+    /// # Returns
+    /// * `bool`: `true` if the token is unbound or bound to `resource`.
+    pub fn is_bound_to(&self, resource: &str) -> bool {
+        self.resource_hash
+            .is_none_or(|resource_hash: [u8; 32]| resource_hash == crate::IronShieldChallenge::resource_hash_for(resource))
+    }
+
+    /// Computes the HMAC-SHA256 tag over `authentication_message()`.
+    fn hmac_tag(&self, secret: &[u8]) -> [u8; HMAC_TAG_LEN] {
+        let mut mac: HmacSha256 = HmacSha256::new_from_slice(secret)
+            .expect("HMAC accepts keys of any length");
+        mac.update(self.authentication_message().as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+
     /// Check if the token has expired.
     pub fn is_expired(&self) -> bool {
-        Utc::now().timestamp_millis() > self.valid_for
+        self.is_expired_at(Utc::now().timestamp_millis())
+    }
+
+    /// Checks like `is_expired`, at the given time in Unix milliseconds.
+    pub fn is_expired_at(&self, now_millis: i64) -> bool {
+        now_millis > self.valid_for
     }
 
     /// Concatenates the token data into a string.
@@ -53,8 +238,9 @@ impl IronShieldToken {
     /// - `valid_for`:                as a string.
     /// - `public_key`:               as a lowercase hex string.
     /// - `authentication_signature`: as a lowercase hex string.
+    /// - `resource_hash`:            as a lowercase hex string, only when set.
     pub fn concat_struct(&self) -> String {
-        format!(
+        let concat: String = format!(
             "{}|{}|{}|{}",
             // Use of hex::encode to convert the arrays to hex strings
             // "Encodes data as hex string using lowercase characters."
@@ -63,14 +249,19 @@ impl IronShieldToken {
             self.valid_for,
             hex::encode(self.public_key),
             hex::encode(self.authentication_signature)
-        )
+        );
+        match self.resource_hash {
+            Some(resource_hash) => format!("{}|{}", concat, hex::encode(resource_hash)),
+            None => concat,
+        }
     }
     
     /// Creates an `IronShieldToken` from a concatenated string.
     ///
     /// This function reverses the operation of `IronShieldToken::concat_struct`.
     /// Expects a string in the format:
-    /// "challenge_signature|valid_for|public_key|authentication_signature"
+    /// "challenge_signature|valid_for|public_key|authentication_signature",
+    /// optionally followed by "|resource_hash".
     ///
     /// # Arguments
     ///
@@ -85,8 +276,8 @@ impl IronShieldToken {
     pub fn from_concat_struct(concat_str: &str) -> Result<Self, String> {
         let parts: Vec<&str> = concat_str.split('|').collect();
         
-        if parts.len() != 4 {
-            return Err(format!("Expected 4 parts, got {}", parts.len()));
+        if !(4..=5).contains(&parts.len()) {
+            return Err(format!("Expected 4 or 5 parts, got {}", parts.len()));
         }
 
         let challenge_signature_bytes = hex::decode(parts[0])
@@ -107,13 +298,127 @@ impl IronShieldToken {
         let authentication_signature: [u8; 64] = authentication_signature_bytes.try_into()
             .map_err(|_| "Authentication signature must be exactly 64 bytes")?;
 
+        let resource_hash: Option<[u8; 32]> = match parts.get(4) {
+            Some(part) => {
+                let resource_hash_bytes: Vec<u8> = hex::decode(part)
+                    .map_err(|_| "Failed to decode resource_hash hex string")?;
+                Some(resource_hash_bytes.try_into().map_err(|_| "Resource hash must be exactly 32 bytes")?)
+            }
+            None => None,
+        };
+
         Ok(Self {
             challenge_signature,
             valid_for,
             public_key,
             authentication_signature,
+            resource_hash,
         })
     }
+
+    /// Encodes the token as a base64url string for HTTP headers and
+    /// cookies.
+    ///
+    /// # Returns
+    /// * `String`: The base64url-encoded `concat_struct()`.
+    pub fn to_base64url_header(&self) -> String {
+        crate::serde_utils::concat_struct_base64url_encode(&self.concat_struct())
+    }
+
+    /// Decodes a token produced by `to_base64url_header`.
+    ///
+    /// # Arguments
+    /// * `encoded_header`: The base64url-encoded token.
+    ///
+    /// # Returns
+    /// * `Result<Self, String>`: The decoded token or an error message.
+    pub fn from_base64url_header(encoded_header: &str) -> Result<Self, String> {
+        let concat_str: String = crate::serde_utils::concat_struct_base64url_decode(encoded_header.to_string())?;
+        Self::from_concat_struct(&concat_str)
+    }
+
+    /// Encodes the token in its binary form.
+    ///
+    /// Layout, `TOKEN_BYTES_LEN` bytes, or `BOUND_TOKEN_BYTES_LEN` with a
+    /// `resource_hash`:
+    /// - `challenge_signature`:      64 bytes.
+    /// - `valid_for`:                8 bytes, big-endian.
+    /// - `public_key`:               32 bytes.
+    /// - `authentication_signature`: 64 bytes.
+    /// - `resource_hash`:            32 bytes, only when set.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(BOUND_TOKEN_BYTES_LEN);
+        bytes.extend_from_slice(&self.challenge_signature);
+        bytes.extend_from_slice(&self.valid_for.to_be_bytes());
+        bytes.extend_from_slice(&self.public_key);
+        bytes.extend_from_slice(&self.authentication_signature);
+        if let Some(resource_hash) = self.resource_hash {
+            bytes.extend_from_slice(&resource_hash);
+        }
+        bytes
+    }
+
+    /// Decodes a token produced by `to_bytes`.
+    ///
+    /// # Arguments
+    /// * `bytes`: Exactly `TOKEN_BYTES_LEN` or `BOUND_TOKEN_BYTES_LEN` bytes.
+    ///
+    /// # Returns
+    /// * `Result<Self, String>`: The decoded token or an error message.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() != TOKEN_BYTES_LEN && bytes.len() != BOUND_TOKEN_BYTES_LEN {
+            return Err(format!(
+                "Expected {} or {} bytes, got {}",
+                TOKEN_BYTES_LEN,
+                BOUND_TOKEN_BYTES_LEN,
+                bytes.len()
+            ));
+        }
+
+        let mut token: Self = Self::new([0u8; 64], 0, [0u8; 32], [0u8; 64]);
+        token.challenge_signature.copy_from_slice(&bytes[..64]);
+        token.valid_for = i64::from_be_bytes(bytes[64..72].try_into().expect("slice is 8 bytes"));
+        token.public_key.copy_from_slice(&bytes[72..104]);
+        token.authentication_signature.copy_from_slice(&bytes[104..TOKEN_BYTES_LEN]);
+        token.resource_hash = bytes[TOKEN_BYTES_LEN..].try_into().ok();
+        Ok(token)
+    }
+
+    /// Encodes the token as base64url over `to_bytes()`, half the length
+    /// of `to_base64url_header()`, for cookies.
+    ///
+    /// # Returns
+    /// * `String`: The unpadded base64url encoding, 224 characters, or
+    ///   267 with a `resource_hash`.
+    pub fn to_base64url_bytes(&self) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(self.to_bytes())
+    }
+
+    /// Decodes a token produced by either `to_base64url_bytes` or
+    /// `to_base64url_header`.
+    ///
+    /// # Arguments
+    /// * `encoded`: The base64url-encoded token, in either form.
+    ///
+    /// # Returns
+    /// * `Result<Self, String>`: The decoded token or an error message.
+    pub fn from_base64url(encoded: &str) -> Result<Self, String> {
+        Self::from_base64url_bytes(encoded).or_else(|_| Self::from_base64url_header(encoded))
+    }
+
+    /// Decodes a token produced by `to_base64url_bytes`.
+    ///
+    /// # Arguments
+    /// * `encoded`: The base64url-encoded binary token.
+    ///
+    /// # Returns
+    /// * `Result<Self, String>`: The decoded token or an error message.
+    pub fn from_base64url_bytes(encoded: &str) -> Result<Self, String> {
+        let bytes: Vec<u8> = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|e: base64::DecodeError| format!("Base64 decode error: {}", e))?;
+        Self::from_bytes(&bytes)
+    }
 }
 
 #[cfg(test)]
@@ -164,6 +469,16 @@ mod tests {
         assert_eq!(parsed.authentication_signature, [0xffu8; 64]);
     }
 
+    #[test]
+    fn test_expiry_at_fixed_time() {
+        let token = IronShieldToken::new([0xAB; 64], 1_700_000_000_000, [0xCD; 32], [0xEF; 64]);
+
+        assert!(!token.is_expired_at(1_699_999_999_999));
+        assert!(!token.is_expired_at(1_700_000_000_000));
+        assert!(token.is_expired_at(1_700_000_000_001));
+        assert!(token.is_expired());
+    }
+
     #[test]
     fn test_concat_struct_roundtrip() {
         // Create a token with known values.
@@ -179,17 +494,79 @@ mod tests {
         let parsed_token = IronShieldToken::from_concat_struct(&concat_str).unwrap();
 
         // Verify all fields are preserved.
-        assert_eq!(original_token.challenge_signature, parsed_token.challenge_signature);
-        assert_eq!(original_token.valid_for, parsed_token.valid_for);
-        assert_eq!(original_token.public_key, parsed_token.public_key);
-        assert_eq!(original_token.authentication_signature, parsed_token.authentication_signature);
+        assert_eq!(parsed_token, original_token);
+
+        let negative: IronShieldToken = IronShieldToken::new([0x01; 64], -1, [0x02; 32], [0x03; 64]);
+        assert_eq!(IronShieldToken::from_concat_struct(&negative.concat_struct()).unwrap(), negative);
+    }
+
+    #[test]
+    fn test_base64url_header_roundtrip() {
+        let token: IronShieldToken = IronShieldToken::new([0xAB; 64], 1700000000000, [0xCD; 32], [0xEF; 64]);
+        let parsed: IronShieldToken = IronShieldToken::from_base64url_header(&token.to_base64url_header()).unwrap();
+
+        assert_eq!(parsed.concat_struct(), token.concat_struct());
+        assert!(IronShieldToken::from_base64url_header("not a token").is_err());
+    }
+
+    #[test]
+    fn test_bytes_roundtrip() {
+        let token: IronShieldToken = IronShieldToken::new([0xAB; 64], -1_700_000_000_000, [0xCD; 32], [0xEF; 64]);
+        let bytes: Vec<u8> = token.to_bytes();
+        assert_eq!(bytes.len(), 168);
+        assert_eq!(IronShieldToken::from_bytes(&bytes).unwrap().concat_struct(), token.concat_struct());
+
+        let encoded: String = token.to_base64url_bytes();
+        assert_eq!(encoded.len(), 224);
+        assert_eq!(IronShieldToken::from_base64url_bytes(&encoded).unwrap().concat_struct(), token.concat_struct());
+
+        assert_eq!(IronShieldToken::from_bytes(&bytes[1..]).unwrap_err(), "Expected 168 or 200 bytes, got 167");
+        assert!(IronShieldToken::from_base64url_bytes("not a token").is_err());
+        assert!(IronShieldToken::from_base64url_bytes(&token.to_base64url_header()).is_err());
+    }
+
+    #[test]
+    fn test_resource_hash_roundtrip() {
+        let mut token: IronShieldToken = IronShieldToken::new([0xAB; 64], 1_700_000_000_000, [0xCD; 32], [0xEF; 64]);
+        let unbound_message: String = token.authentication_message();
+        assert!(token.is_bound_to("/anything"));
+
+        token.resource_hash = Some(crate::IronShieldChallenge::resource_hash_for("/reports/a.pdf"));
+        assert_eq!(
+            token.authentication_message(),
+            format!("{}|{}", unbound_message, hex::encode(token.resource_hash.unwrap()))
+        );
+        assert!(token.is_bound_to("/reports/a.pdf"));
+        assert!(!token.is_bound_to("/reports/b.pdf"));
+
+        let bytes: Vec<u8> = token.to_bytes();
+        assert_eq!(bytes.len(), BOUND_TOKEN_BYTES_LEN);
+        assert_eq!(IronShieldToken::from_bytes(&bytes).unwrap(), token);
+        assert_eq!(token.to_base64url_bytes().len(), 267);
+        assert_eq!(IronShieldToken::from_base64url_bytes(&token.to_base64url_bytes()).unwrap(), token);
+        assert_eq!(IronShieldToken::from_concat_struct(&token.concat_struct()).unwrap(), token);
+        assert_eq!(IronShieldToken::from_base64url_header(&token.to_base64url_header()).unwrap(), token);
+
+        let json: String = serde_json::to_string(&token).unwrap();
+        assert_eq!(serde_json::from_str::<IronShieldToken>(&json).unwrap(), token);
+        token.resource_hash = None;
+        assert!(!serde_json::to_string(&token).unwrap().contains("resource_hash"));
+    }
+
+    #[test]
+    fn test_binary_form_is_half_the_concat_form() {
+        let token: IronShieldToken = IronShieldToken::new([0xAB; 64], 1_700_000_000_000, [0xCD; 32], [0xEF; 64]);
+        let compact: usize = token.to_base64url_bytes().len();
+        let concat: usize = token.to_base64url_header().len();
+
+        assert_eq!(compact * 2, concat, "{} is not half of {}", compact, concat);
     }
 
     #[test]
     fn test_empty_string_parsing() {
         let result = IronShieldToken::from_concat_struct("");
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Expected 4 parts, got 1"));
+        assert!(result.unwrap_err().contains("Expected 4 or 5 parts, got 1"));
     }
 
 
@@ -198,11 +575,11 @@ mod tests {
         // Test with the wrong number of parts.
         let result = IronShieldToken::from_concat_struct("only|two|parts");
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Expected 4 parts, got 3"));
+        assert!(result.unwrap_err().contains("Expected 4 or 5 parts, got 3"));
 
-        let result = IronShieldToken::from_concat_struct("too|many|parts|here|extra");
+        let result = IronShieldToken::from_concat_struct("too|many|parts|here|and|extra");
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Expected 4 parts, got 5"));
+        assert!(result.unwrap_err().contains("Expected 4 or 5 parts, got 6"));
 
         // Test with invalid hex for challenge_signature.
         let valid_32_hex = "0".repeat(64);
@@ -245,4 +622,80 @@ mod tests {
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("Public key must be exactly 32 bytes"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_hmac_token_verifies_with_correct_key() {
+        let token: IronShieldToken = IronShieldToken::new_hmac([0x11; 64], 1_700_000_000_000, [0x22; 32], b"server-secret");
+
+        assert!(token.verify_hmac(b"server-secret"));
+        assert_eq!(token.authentication_signature[32..], [0u8; 32]);
+
+        // The tag survives a concat round-trip.
+        let parsed: IronShieldToken = IronShieldToken::from_concat_struct(&token.concat_struct()).unwrap();
+        assert!(parsed.verify_hmac(b"server-secret"));
+    }
+
+    #[test]
+    fn test_hmac_token_fails_with_wrong_key_or_tampering() {
+        let token: IronShieldToken = IronShieldToken::new_hmac([0x11; 64], 1_700_000_000_000, [0x22; 32], b"server-secret");
+        assert!(!token.verify_hmac(b"other-secret"));
+
+        let mut extended: IronShieldToken = token.clone();
+        extended.valid_for += 1;
+        assert!(!extended.verify_hmac(b"server-secret"));
+
+        let mut padded: IronShieldToken = token.clone();
+        padded.authentication_signature[63] = 1;
+        assert!(!padded.verify_hmac(b"server-secret"));
+
+        // A resource binding set after construction is only covered once re-signed.
+        let mut bound: IronShieldToken = token.clone();
+        bound.resource_hash = Some(crate::IronShieldChallenge::resource_hash_for("/reports/a.pdf"));
+        assert!(!bound.verify_hmac(b"server-secret"));
+        bound.sign_hmac(b"server-secret");
+        assert!(bound.verify_hmac(b"server-secret"));
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        use rand_core::OsRng;
+
+        let signing_key: ed25519_dalek::SigningKey = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let server_public_key: [u8; 32] = signing_key.verifying_key().to_bytes();
+        let mut token: IronShieldToken = IronShieldToken::new([0xAB; 64], 1_700_000_000_000, [0x00; 32], [0x00; 64]);
+        assert!(!token.verify(&server_public_key));
+
+        // Valid.
+        token.sign(&signing_key);
+        assert_eq!(token.public_key, server_public_key);
+        assert!(token.verify(&server_public_key));
+        assert_eq!(token, crate::create_signed_token([0xAB; 64], 1_700_000_000_000, None, &signing_key.to_bytes()));
+
+        // Expired: the signature still holds, expiry is checked separately.
+        assert!(token.is_expired());
+        assert!(crate::verify_token(&token.to_base64url_bytes(), &server_public_key).is_err());
+
+        // Tampered.
+        for tamper in [
+            |token: &mut IronShieldToken| token.valid_for = i64::MAX,
+            |token: &mut IronShieldToken| token.challenge_signature[0] ^= 1,
+            |token: &mut IronShieldToken| token.authentication_signature[0] ^= 1,
+        ] {
+            let mut tampered: IronShieldToken = token.clone();
+            tamper(&mut tampered);
+            assert!(!tampered.verify(&server_public_key));
+        }
+
+        // Self-signed with another key.
+        let mut forged: IronShieldToken = token.clone();
+        forged.sign(&ed25519_dalek::SigningKey::generate(&mut OsRng));
+        assert!(!forged.verify(&server_public_key));
+    }
+
+    #[test]
+    fn test_token_auth_mode_from_config_value() {
+        assert_eq!(TokenAuthMode::from_config_value("HMAC"), Some(TokenAuthMode::Hmac));
+        assert_eq!(TokenAuthMode::from_config_value(" ed25519 "), Some(TokenAuthMode::Ed25519));
+        assert_eq!(TokenAuthMode::from_config_value("rsa"), None);
+    }
+}
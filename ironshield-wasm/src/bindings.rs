@@ -0,0 +1,390 @@
+//! `#[wasm_bindgen]` exports, built with the `bindings` feature.
+
+use ironshield_core;
+use wasm_bindgen::prelude::*;
+use serde_json;
+
+use crate::solve::{
+    create_verbose_solution_result, matches_test_vector, solve_and_verify_pow, solve_ironshield, solve_pow, solve_signed,
+};
+
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+use crate::solve::{validate_thread_count, MAX_THREADS};
+
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+use crate::solve::{create_ironshield_solution_result, create_solution_result};
+
+/// Support for threading in WASM
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+use wasm_bindgen_rayon::init_thread_pool;
+
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+use wasm_bindgen_futures::JsFuture;
+
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Size of the shared rayon thread pool, or `0` before it has been
+/// initialized. The pool can only be created once per WASM instance.
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+static THREAD_POOL_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// Solves proof-of-work challenges using single-threaded computation.
+/// 
+/// # Arguments
+/// * `challenge` - Base string to append nonce to for hashing.
+/// * `difficulty` - Number of leading zeros required in hash.
+/// 
+/// # Returns
+/// JavaScript object with nonce and hash, or error message.
+#[wasm_bindgen]
+pub fn solve_pow_challenge(challenge: &str, difficulty: usize) -> Result<JsValue, JsValue> {
+    // Enable better error messages in browser console
+    console_error_panic_hook::set_once();
+
+    // Find valid nonce using brute force search
+    let solution_result = solve_pow(challenge, difficulty)
+        .map_err(|e: String| JsValue::from_str(&e))?;
+
+    // Convert Rust struct to JavaScript object
+    serde_wasm_bindgen::to_value(&solution_result)
+        .map_err(|err| JsValue::from_str(&format!("Error serializing result: {:?}", err)))
+}
+
+/// Solves proof-of-work challenges using single-threaded computation,
+/// with control over how much detail is returned.
+/// 
+/// # Arguments
+/// * `challenge` - Base string to append nonce to for hashing.
+/// * `difficulty` - Number of leading zeros required in hash.
+/// * `verbose` - Include hash, hash prefix, attempts, and timing.
+/// 
+/// # Returns
+/// JavaScript object `{nonce}` or `{nonce, hash, hashPrefix, attempts, durationMs}`,
+/// or error message.
+#[wasm_bindgen]
+pub fn solve_pow_challenge_verbose(challenge: &str, difficulty: usize, verbose: bool) -> Result<JsValue, JsValue> {
+    // Enable better error messages in browser console
+    console_error_panic_hook::set_once();
+
+    let started_ms: f64 = js_sys::Date::now();
+    let (nonce, hash) = ironshield_core::find_solution(challenge, difficulty)
+        .map_err(|e| JsValue::from_str(&format!("Error solving challenge: {}", e)))?;
+    let duration_ms: f64 = js_sys::Date::now() - started_ms;
+
+    let solution_result = create_verbose_solution_result(nonce, hash, duration_ms, verbose);
+
+    // Convert Rust struct to JavaScript object
+    serde_wasm_bindgen::to_value(&solution_result)
+        .map_err(|err| JsValue::from_str(&format!("Error serializing result: {:?}", err)))
+}
+
+/// Initializes WebAssembly thread pool for parallel proof-of-work
+/// 
+/// # Arguments
+/// * `num_threads` - Number of worker threads to spawn, `1..=MAX_THREADS`
+/// 
+/// # Note
+/// Only available when compiled with a "parallel" feature flag.
+/// The pool is created once; later calls are no-ops and keep the
+/// original pool size. An out-of-range `num_threads` is an error.
+#[wasm_bindgen]
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+pub async fn init_threads(num_threads: usize) -> Result<(), JsValue> {
+    ensure_thread_pool(num_threads).await.map(|_| ())
+}
+
+/// Creates the shared thread pool unless it already exists.
+/// 
+/// # Arguments
+/// * `num_threads` - Number of worker threads to spawn on first use.
+/// 
+/// # Returns
+/// The size of the thread pool in use, or an error if `num_threads` is
+/// outside `1..=MAX_THREADS` or creation failed.
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+async fn ensure_thread_pool(num_threads: usize) -> Result<usize, JsValue> {
+    let num_threads: usize = validate_thread_count(num_threads)
+        .map_err(|e: String| JsValue::from_str(&e))?;
+
+    // Claim initialization before awaiting so concurrent callers
+    // cannot start a second pool.
+    if let Err(existing) = THREAD_POOL_SIZE.compare_exchange(0, num_threads, Ordering::SeqCst, Ordering::SeqCst) {
+        return Ok(existing);
+    }
+
+    // Create a shared memory thread pool for parallel processing
+    let promise = init_thread_pool(num_threads);
+    match JsFuture::from(promise).await {
+        Ok(_) => Ok(num_threads),
+        Err(e) => {
+            // Allow a later call to retry.
+            THREAD_POOL_SIZE.store(0, Ordering::SeqCst);
+            Err(e)
+        }
+    }
+}
+
+/// Returns the number of threads recommended for parallel solving.
+/// 
+/// # Returns
+/// `navigator.hardwareConcurrency` when the browser exposes it,
+/// otherwise `1`.
+#[wasm_bindgen]
+pub fn recommended_threads() -> usize {
+    let global: JsValue = js_sys::global().into();
+    js_sys::Reflect::get(&global, &JsValue::from_str("navigator"))
+        .ok()
+        .filter(|navigator| navigator.is_object())
+        .and_then(|navigator| js_sys::Reflect::get(&navigator, &JsValue::from_str("hardwareConcurrency")).ok())
+        .and_then(|cores| cores.as_f64())
+        .filter(|cores| *cores >= 1.0)
+        .map(|cores| cores as usize)
+        .unwrap_or(1)
+}
+
+/// Solves proof-of-work challenges using multithreaded parallel computation
+/// 
+/// # Arguments
+/// * `challenge` - Base string to append nonce to for hashing
+/// * `difficulty` - Number of leading zeros required in hash
+/// * `num_threads` - Number of parallel workers to use, `1..=MAX_THREADS`
+/// 
+/// # Returns
+/// JavaScript object with nonce and hash, or error message
+/// 
+/// # Note
+/// Requires thread pool initialization via `init_threads()` first. If a
+/// worker thread dies mid-solve, the search finishes sequentially on the
+/// calling thread instead of hanging.
+#[wasm_bindgen]
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+pub fn solve_pow_challenge_parallel(
+    challenge: &str,
+    difficulty: usize,
+    num_threads: usize,
+) -> Result<JsValue, JsValue> {
+    // Enable better error messages in browser console
+    console_error_panic_hook::set_once();
+
+    let num_threads: usize = validate_thread_count(num_threads)
+        .map_err(|e: String| JsValue::from_str(&e))?;
+
+    // Distribute nonce search across multiple threads
+    let (nonce, hash) = ironshield_core::find_solution_parallel_with_fallback(
+        challenge,
+        difficulty,
+        num_threads,
+        ironshield_core::DEFAULT_STALL_BUDGET,
+    )
+        .map_err(|e| JsValue::from_str(&format!("Error solving challenge in parallel: {}", e)))?;
+
+    // Package result for JavaScript consumption
+    let solution_result = create_solution_result(nonce, hash);
+
+    // Convert Rust struct to JavaScript object
+    serde_wasm_bindgen::to_value(&solution_result)
+        .map_err(|err| JsValue::from_str(&format!("Error serializing parallel result: {:?}", err)))
+}
+
+/// Solves proof-of-work challenges on every available core.
+/// 
+/// Initializes the thread pool with `recommended_threads()`, at most
+/// `MAX_THREADS`, on first use and solves with the pool's actual size, so callers never have to keep
+/// `init_threads()` and `solve_pow_challenge_parallel()` in sync.
+/// 
+/// # Arguments
+/// * `challenge` - Base string to append nonce to for hashing
+/// * `difficulty` - Number of leading zeros required in hash
+/// 
+/// # Returns
+/// JavaScript object with nonce and hash, or error message
+#[wasm_bindgen]
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+pub async fn solve_pow_challenge_all_cores(challenge: String, difficulty: usize) -> Result<JsValue, JsValue> {
+    // Enable better error messages in browser console
+    console_error_panic_hook::set_once();
+
+    let num_threads: usize = ensure_thread_pool(recommended_threads().min(MAX_THREADS)).await?;
+    solve_pow_challenge_parallel(&challenge, difficulty, num_threads)
+}
+
+/// Checks if parallel processing is available in the current build.
+/// 
+/// # Returns
+/// `true` if compiled with a "parallel" feature, `false` otherwise.
+#[wasm_bindgen]
+pub fn are_threads_supported() -> bool {
+    #[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+    return true;
+
+    #[cfg(not(all(feature = "parallel", not(feature = "no-parallel"))))]
+    return false;
+}
+
+/// Verifies a proof-of-work solution without recomputing.
+/// 
+/// # Arguments
+/// * `challenge` - Original challenge string.
+/// * `nonce_value` - Proposed solution nonce as string.
+/// * `difficulty` - Required number of leading zeros.
+/// 
+/// # Returns
+/// `true` if a solution is valid, `false` otherwise.
+#[wasm_bindgen]
+pub fn verify_pow_solution(challenge: &str, nonce_value: &str, difficulty: usize) -> bool {
+    ironshield_core::verify_solution(challenge, nonce_value, difficulty)
+}
+
+/// Solves a proof-of-work challenge and verifies the solution in one call.
+/// 
+/// Meant for client-only demos and smoke tests that have no server to
+/// verify against.
+/// 
+/// # Arguments
+/// * `challenge` - Base string to append nonce to for hashing.
+/// * `difficulty` - Number of leading zeros required in hash.
+/// 
+/// # Returns
+/// JavaScript object `{solution, verified}`, where `solution` has the shape
+/// returned by `solve_pow_challenge`, or error message.
+#[wasm_bindgen]
+pub fn solve_and_verify(challenge: &str, difficulty: usize) -> Result<JsValue, JsValue> {
+    console_error_panic_hook::set_once();
+
+    let result = solve_and_verify_pow(challenge, difficulty)
+        .map_err(|e: String| JsValue::from_str(&e))?;
+
+    serde_wasm_bindgen::to_value(&result)
+        .map_err(|err| JsValue::from_str(&format!("Error serializing result: {:?}", err)))
+}
+
+/// Checks that this module's solver and verifier match the server.
+/// 
+/// Solves and verifies the built-in test vector entirely in-process and
+/// compares the result with the expected nonce and hash. A failing
+/// self-test indicates a build or version mismatch, so front-ends should
+/// not rely on the module.
+/// 
+/// # Returns
+/// `true` if the round-trip matches the test vector, `false` otherwise.
+#[wasm_bindgen]
+pub fn self_test() -> bool {
+    matches_test_vector()
+}
+
+/// Outputs debug message to browser console.
+/// 
+/// # Arguments
+/// * `s` - Message string to log.
+/// 
+/// # Note
+/// Useful for debugging WASM execution from JavaScript.
+#[wasm_bindgen]
+pub fn console_log(s: &str) {
+    web_sys::console::log_1(&JsValue::from_str(s));
+}
+
+/// Solves IronShield proof-of-work challenges using single-threaded computation.
+/// 
+/// # Arguments
+/// * `challenge_json` - JSON string containing the IronShieldChallenge
+/// 
+/// # Returns
+/// JavaScript object with solution nonce and challenge signature, or error message.
+#[wasm_bindgen]
+pub fn solve_ironshield_challenge(challenge_json: &str) -> Result<JsValue, JsValue> {
+    // Enable better error messages in browser console
+    console_error_panic_hook::set_once();
+
+    // Find valid nonce using single-threaded algorithm
+    let solution_result = solve_ironshield(challenge_json)
+        .map_err(|e: String| JsValue::from_str(&e))?;
+
+    // Convert Rust struct to JavaScript object
+    serde_wasm_bindgen::to_value(&solution_result)
+        .map_err(|err| JsValue::from_str(&format!("Error serializing IronShield result: {:?}", err)))
+}
+
+/// Solves a signed IronShield challenge as sent in the `X-IronShield-Challenge`
+/// header.
+/// 
+/// The difficulty is read from the challenge's signed `challenge_param`
+/// rather than passed in, so the page cannot lower it, and the attempt
+/// budget follows from it as in `ironshield_core::find_solution_single_threaded`.
+/// 
+/// # Arguments
+/// * `challenge_b64` - The challenge as produced by `IronShieldChallenge::to_base64url_header`.
+/// 
+/// # Returns
+/// JavaScript object with solution nonce and challenge signature, or error message.
+#[wasm_bindgen]
+pub fn solve_signed_challenge(challenge_b64: &str) -> Result<JsValue, JsValue> {
+    console_error_panic_hook::set_once();
+
+    let solution_result = solve_signed(challenge_b64)
+        .map_err(|e: String| JsValue::from_str(&e))?;
+
+    serde_wasm_bindgen::to_value(&solution_result)
+        .map_err(|err| JsValue::from_str(&format!("Error serializing signed challenge result: {:?}", err)))
+}
+
+/// Solves IronShield proof-of-work challenges using optimized multi-threaded computation.
+/// 
+/// This function provides the fastest possible PoW solving by distributing the work
+/// across all available CPU cores with optimal load balancing and early termination.
+/// 
+/// # Arguments
+/// * `challenge_json` - JSON string containing the IronShieldChallenge
+/// 
+/// # Returns
+/// JavaScript object with solution nonce and challenge signature, or error message.
+/// 
+/// # Performance
+/// - **Multi-core scaling**: Near-linear performance improvement with CPU core count
+/// - **WASM optimization**: Fully compatible with SharedArrayBuffer and Web Workers
+/// - **Early termination**: Stops all threads immediately when solution is found
+/// - **Memory efficient**: Minimal overhead compared to single-threaded version
+/// 
+/// # Note
+/// Requires thread pool initialization via `init_threads()` first when using parallel features
+#[wasm_bindgen]
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+pub fn solve_ironshield_challenge_multi_threaded(challenge_json: &str) -> Result<JsValue, JsValue> {
+    // Enable better error messages in browser console
+    console_error_panic_hook::set_once();
+
+    // Parse the challenge from JSON
+    let challenge: ironshield_core::IronShieldChallenge = serde_json::from_str(challenge_json)
+        .map_err(|e| JsValue::from_str(&format!("Error parsing challenge JSON: {}", e)))?;
+
+    // Find valid nonce using optimized multi-threaded algorithm
+    let response = ironshield_core::find_solution_multi_threaded(&challenge)
+        .map_err(|e| JsValue::from_str(&format!("Error solving IronShield challenge with multi-threading: {}", e)))?;
+
+    // Package result for JavaScript consumption
+    let solution_result = create_ironshield_solution_result(response);
+
+    // Convert Rust struct to JavaScript object
+    serde_wasm_bindgen::to_value(&solution_result)
+        .map_err(|err| JsValue::from_str(&format!("Error serializing multi-threaded IronShield result: {:?}", err)))
+}
+
+/// Verifies an IronShield proof-of-work solution without recomputing.
+/// 
+/// # Arguments
+/// * `challenge_json` - JSON string containing the original IronShieldChallenge.
+/// * `solution_nonce` - Proposed solution nonce as i64.
+/// 
+/// # Returns
+/// `true` if the solution is valid, `false` otherwise.
+#[wasm_bindgen]
+pub fn verify_ironshield_solution(challenge_json: &str, solution_nonce: i64) -> Result<bool, JsValue> {
+    // Parse the challenge from JSON
+    let challenge: ironshield_core::IronShieldChallenge = serde_json::from_str(challenge_json)
+        .map_err(|e| JsValue::from_str(&format!("Error parsing challenge JSON for verification: {}", e)))?;
+
+    // Verify the solution
+    let is_valid = ironshield_core::verify_ironshield_solution(&challenge, solution_nonce);
+    Ok(is_valid)
+}
\ No newline at end of file
@@ -0,0 +1,279 @@
+//! Solving logic behind the JavaScript bindings.
+//!
+//! Nothing here depends on `wasm-bindgen`, so it builds and is tested on
+//! the host target. The bindings only convert these results to
+//! JavaScript values and errors.
+
+use ironshield_core::{IronShieldChallenge, IronShieldChallengeResponse};
+
+/// Length of the SHA-256 hex digest returned by the core solver.
+const HASH_HEX_LEN:    usize = 64;
+/// Leading hash characters returned for quick visual verification.
+const HASH_PREFIX_LEN: usize = 10;
+/// Most worker threads the parallel bindings accept. Larger pools only add
+/// memory pressure in the browser and can hang pool initialization.
+pub const MAX_THREADS: usize = 32;
+
+/// JavaScript-compatible solution result containing proof-of-work data
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SolutionResult {
+    /// String representation to avoid JavaScript BigInt precision issues.
+    pub nonce_str: String,
+    /// Original numeric value for compatibility.
+    pub nonce: u64,
+    /// Complete SHA-256 hash as a hex string.
+    pub hash: String,
+    /// First `HASH_PREFIX_LEN` characters for quick visual verification.
+    pub hash_prefix: String,
+}
+
+/// JavaScript-compatible solution result whose debug fields can be
+/// omitted to keep payloads small.
+///
+/// Serializes as `{nonce}` when minimal, or
+/// `{nonce, hash, hashPrefix, attempts, durationMs}` when verbose.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerboseSolutionResult {
+    /// String representation to avoid JavaScript BigInt precision issues.
+    pub nonce: String,
+    /// Complete SHA-256 hash as a hex string.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// First `HASH_PREFIX_LEN` characters for quick visual verification.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash_prefix: Option<String>,
+    /// Number of nonces hashed, including the winning one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<u64>,
+    /// Wall-clock solve time in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<f64>,
+}
+
+/// JavaScript-compatible solution result for IronShield challenges
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct IronShieldSolutionResult {
+    /// String representation of the solution nonce to avoid JavaScript BigInt precision issues.
+    pub solution_str: String,
+    /// Original numeric value for compatibility.
+    pub solution: i64,
+    /// Challenge signature preserved from the original challenge.
+    pub challenge_signature_hex: String,
+}
+
+/// JavaScript-compatible result of solving a challenge and verifying the
+/// solution in the same call.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct VerifiedSolutionResult {
+    /// The solution found by the solver.
+    pub solution: SolutionResult,
+    /// Whether the verifier accepts `solution`.
+    pub verified: bool,
+}
+
+/// Returns the first `HASH_PREFIX_LEN` characters of a solution hash.
+///
+/// The core solver always returns a full SHA-256 hex digest; a different
+/// length means the hash algorithm changed and the prefix needs revisiting.
+fn hash_prefix(hash: &str) -> String {
+    debug_assert!(
+        hash.len() == HASH_HEX_LEN && hash.bytes().all(|b: u8| b.is_ascii_hexdigit()),
+        "expected a {}-character hex digest, got {:?}",
+        HASH_HEX_LEN,
+        hash
+    );
+    hash[..HASH_PREFIX_LEN].to_string()
+}
+
+/// Creates a standardized solution result from core library output.
+pub fn create_solution_result(nonce: u64, hash: String) -> SolutionResult {
+    SolutionResult {
+        nonce_str: nonce.to_string(),
+        nonce,
+        hash: hash.clone(),
+        hash_prefix: hash_prefix(&hash),
+    }
+}
+
+/// Creates a verbose or minimal solution result from sequential solver output.
+pub fn create_verbose_solution_result(nonce: u64, hash: String, duration_ms: f64, verbose: bool) -> VerboseSolutionResult {
+    if !verbose {
+        return VerboseSolutionResult {
+            nonce: nonce.to_string(),
+            hash: None,
+            hash_prefix: None,
+            attempts: None,
+            duration_ms: None,
+        };
+    }
+
+    VerboseSolutionResult {
+        nonce: nonce.to_string(),
+        hash_prefix: Some(hash_prefix(&hash)),
+        hash: Some(hash),
+        // The sequential solver tries nonces from zero upward.
+        attempts: Some(nonce + 1),
+        duration_ms: Some(duration_ms),
+    }
+}
+
+/// Creates a standardized IronShield solution result from core library output.
+pub fn create_ironshield_solution_result(response: IronShieldChallengeResponse) -> IronShieldSolutionResult {
+    IronShieldSolutionResult {
+        solution_str: response.solution.to_string(),
+        solution: response.solution,
+        challenge_signature_hex: hex::encode(response.challenge_signature),
+    }
+}
+
+/// Solves a leading-zero challenge on the calling thread.
+///
+/// # Arguments
+/// * `challenge` - Base string to append nonce to for hashing.
+/// * `difficulty` - Number of leading zeros required in hash.
+///
+/// # Returns
+/// The nonce and hash, or an error message.
+pub fn solve_pow(challenge: &str, difficulty: usize) -> Result<SolutionResult, String> {
+    let (nonce, hash) = ironshield_core::find_solution(challenge, difficulty)
+        .map_err(|e| format!("Error solving challenge: {}", e))?;
+    Ok(create_solution_result(nonce, hash))
+}
+
+/// Solves a leading-zero challenge and verifies the solution, for
+/// self-contained flows with no server to check it.
+///
+/// # Arguments
+/// * `challenge` - Base string to append nonce to for hashing.
+/// * `difficulty` - Number of leading zeros required in hash.
+///
+/// # Returns
+/// The solution and whether it verified, or an error message if solving failed.
+pub fn solve_and_verify_pow(challenge: &str, difficulty: usize) -> Result<VerifiedSolutionResult, String> {
+    let solution: SolutionResult = solve_pow(challenge, difficulty)?;
+    let verified: bool = ironshield_core::verify_solution(challenge, &solution.nonce_str, difficulty);
+    Ok(VerifiedSolutionResult { solution, verified })
+}
+
+/// Solves an IronShield challenge given as JSON on the calling thread.
+///
+/// # Arguments
+/// * `challenge_json` - JSON string containing the IronShieldChallenge
+///
+/// # Returns
+/// The solution nonce and challenge signature, or an error message.
+pub fn solve_ironshield(challenge_json: &str) -> Result<IronShieldSolutionResult, String> {
+    let challenge: IronShieldChallenge = serde_json::from_str(challenge_json)
+        .map_err(|e| format!("Error parsing challenge JSON: {}", e))?;
+
+    let response = ironshield_core::find_solution_single_threaded(&challenge)
+        .map_err(|e| format!("Error solving IronShield challenge: {}", e))?;
+    Ok(create_ironshield_solution_result(response))
+}
+
+/// Solves a signed IronShield challenge as sent in the
+/// `X-IronShield-Challenge` header, on the calling thread.
+///
+/// # Arguments
+/// * `challenge_b64` - The challenge as produced by `IronShieldChallenge::to_base64url_header`.
+///
+/// # Returns
+/// The solution nonce and challenge signature, or an error message.
+pub fn solve_signed(challenge_b64: &str) -> Result<IronShieldSolutionResult, String> {
+    let challenge: IronShieldChallenge = IronShieldChallenge::from_base64url_header(challenge_b64)
+        .map_err(|e| format!("Error decoding signed challenge: {}", e))?;
+    challenge.validate()
+        .map_err(|e| format!("Invalid signed challenge: {}", e))?;
+
+    let response = ironshield_core::find_solution_single_threaded(&challenge)
+        .map_err(|e| format!("Error solving signed challenge: {}", e))?;
+    Ok(create_ironshield_solution_result(response))
+}
+
+/// Checks a requested thread count against `1..=MAX_THREADS`.
+///
+/// # Arguments
+/// * `num_threads` - Number of worker threads requested by the caller.
+///
+/// # Returns
+/// The thread count, or an error message if it is out of range.
+pub fn validate_thread_count(num_threads: usize) -> Result<usize, String> {
+    match num_threads {
+        1..=MAX_THREADS => Ok(num_threads),
+        _ => Err(format!("num_threads must be between 1 and {}, got {}", MAX_THREADS, num_threads)),
+    }
+}
+
+/// Solves and verifies the built-in test vector and compares the result
+/// with the expected nonce and hash.
+///
+/// # Returns
+/// `true` if the round-trip matches the test vector, `false` otherwise.
+pub fn matches_test_vector() -> bool {
+    let expected: (u64, String) = (
+        ironshield_core::TEST_VECTOR_NONCE,
+        ironshield_core::TEST_VECTOR_HASH.to_string(),
+    );
+
+    ironshield_core::find_solution(ironshield_core::TEST_VECTOR_CHALLENGE, ironshield_core::TEST_VECTOR_DIFFICULTY)
+        .is_ok_and(|solution: (u64, String)| solution == expected)
+        && ironshield_core::verify_solution(
+            ironshield_core::TEST_VECTOR_CHALLENGE,
+            &ironshield_core::TEST_VECTOR_NONCE.to_string(),
+            ironshield_core::TEST_VECTOR_DIFFICULTY,
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solves_natively() {
+        assert!(matches_test_vector());
+
+        let solution: SolutionResult = solve_pow("native_test_challenge", 2).unwrap();
+        assert_eq!(solution.nonce_str, solution.nonce.to_string());
+        assert!(solution.hash.starts_with("00"));
+        assert!(solution.hash.starts_with(&solution.hash_prefix));
+        assert!(ironshield_core::verify_solution("native_test_challenge", &solution.nonce_str, 2));
+
+        let checked: VerifiedSolutionResult = solve_and_verify_pow("native_test_challenge", 2).unwrap();
+        assert!(checked.verified);
+        assert_eq!(checked.solution, solution);
+
+        let minimal: VerboseSolutionResult = create_verbose_solution_result(solution.nonce, solution.hash, 1.5, false);
+        assert_eq!(serde_json::to_string(&minimal).unwrap(), format!("{{\"nonce\":\"{}\"}}", solution.nonce));
+    }
+
+    #[test]
+    fn test_solves_signed_challenge_natively() {
+        let challenge: IronShieldChallenge = IronShieldChallenge::new(
+            "cafe1234".to_string(),
+            chrono::Utc::now().timestamp_millis(),
+            "example.com".to_string(),
+            IronShieldChallenge::difficulty_to_challenge_param(256),
+            [0x00; 32],
+            [0x00; 64],
+        );
+
+        let solved: IronShieldSolutionResult = solve_signed(&challenge.to_base64url_header()).unwrap();
+        assert!(ironshield_core::verify_ironshield_solution(&challenge, solved.solution));
+        assert_eq!(solved.challenge_signature_hex, hex::encode(challenge.challenge_signature));
+        assert_eq!(solve_ironshield(&serde_json::to_string(&challenge).unwrap()).unwrap(), solved);
+
+        assert!(solve_signed("not a challenge").unwrap_err().starts_with("Error decoding signed challenge"));
+        assert!(solve_ironshield("{}").unwrap_err().starts_with("Error parsing challenge JSON"));
+    }
+
+    #[test]
+    fn test_validate_thread_count() {
+        for num_threads in [1, 2, 8, MAX_THREADS] {
+            assert_eq!(validate_thread_count(num_threads), Ok(num_threads));
+        }
+        for num_threads in [0, MAX_THREADS + 1, usize::MAX] {
+            assert!(validate_thread_count(num_threads).unwrap_err().starts_with("num_threads must be between 1 and 32"));
+        }
+    }
+}
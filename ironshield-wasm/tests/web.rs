@@ -0,0 +1,144 @@
+//! Browser tests for the WASM bindings. Run with
+//! `wasm-pack test --headless --chrome`. The parallel tests need a
+//! cross-origin isolated context so `SharedArrayBuffer` (and therefore
+//! threads) is available.
+#![cfg(all(target_arch = "wasm32", feature = "bindings"))]
+
+use ironshield_types::IronShieldChallenge;
+use ironshield_wasm::{
+    recommended_threads, self_test, solve_and_verify, solve_pow_challenge, solve_pow_challenge_verbose, solve_signed_challenge,
+    verify_ironshield_solution, verify_pow_solution,
+};
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+use ironshield_wasm::{init_threads, solve::MAX_THREADS, solve_pow_challenge_all_cores, solve_pow_challenge_parallel};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn object_keys(value: &JsValue) -> Vec<String> {
+    js_sys::Object::keys(&js_sys::Object::from(value.clone()))
+        .iter()
+        .map(|key| key.as_string().unwrap())
+        .collect()
+}
+
+#[wasm_bindgen_test]
+fn test_solve_pow_challenge_verbose_shapes() {
+    let challenge: &str = "verbose_test_challenge";
+
+    let minimal: JsValue = solve_pow_challenge_verbose(challenge, 2, false).unwrap();
+    assert_eq!(object_keys(&minimal), vec!["nonce"]);
+
+    let verbose: JsValue = solve_pow_challenge_verbose(challenge, 2, true).unwrap();
+    assert_eq!(
+        object_keys(&verbose),
+        vec!["nonce", "hash", "hashPrefix", "attempts", "durationMs"]
+    );
+
+    // Both shapes report the same solution.
+    let nonce = |value: &JsValue| js_sys::Reflect::get(value, &JsValue::from_str("nonce")).unwrap().as_string().unwrap();
+    assert_eq!(nonce(&minimal), nonce(&verbose));
+    assert!(verify_pow_solution(challenge, &nonce(&minimal), 2));
+}
+
+#[wasm_bindgen_test]
+fn test_solve_and_verify_verifies_own_solution() {
+    let challenge: &str = "solve_and_verify_test_challenge";
+    let result: JsValue = solve_and_verify(challenge, 2).unwrap();
+    assert_eq!(object_keys(&result), vec!["solution", "verified"]);
+    assert_eq!(js_sys::Reflect::get(&result, &JsValue::from_str("verified")).unwrap().as_bool(), Some(true));
+
+    let solution: JsValue = js_sys::Reflect::get(&result, &JsValue::from_str("solution")).unwrap();
+    let nonce_str: String = js_sys::Reflect::get(&solution, &JsValue::from_str("nonce_str")).unwrap().as_string().unwrap();
+    assert!(verify_pow_solution(challenge, &nonce_str, 2));
+
+    assert!(solve_and_verify("", 1).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_hash_prefix_is_ten_chars_of_hash() {
+    let field = |value: &JsValue, name: &str| {
+        js_sys::Reflect::get(value, &JsValue::from_str(name)).unwrap().as_string().unwrap()
+    };
+
+    let result: JsValue = solve_pow_challenge("hash_prefix_test_challenge", 1).unwrap();
+    let hash: String = field(&result, "hash");
+    assert_eq!(hash.len(), 64);
+    assert_eq!(field(&result, "hash_prefix"), hash[..10]);
+
+    let verbose: JsValue = solve_pow_challenge_verbose("hash_prefix_test_challenge", 1, true).unwrap();
+    assert_eq!(field(&verbose, "hashPrefix"), field(&verbose, "hash")[..10]);
+}
+
+#[wasm_bindgen_test]
+fn test_solve_signed_challenge_uses_embedded_difficulty() {
+    let private_key: [u8; 32] = [7; 32];
+    let mut challenge: IronShieldChallenge = IronShieldChallenge::new(
+        "cafe1234".to_string(),
+        js_sys::Date::now() as i64,
+        "example.com".to_string(),
+        IronShieldChallenge::difficulty_to_challenge_param(64),
+        [0; 32],
+        [0; 64],
+    );
+    challenge.challenge_signature = ironshield_types::sign_challenge_with_key(&challenge, &private_key);
+
+    let result: JsValue = solve_signed_challenge(&challenge.to_base64url_header()).unwrap();
+    let solution: i64 = js_sys::Reflect::get(&result, &JsValue::from_str("solution_str"))
+        .unwrap()
+        .as_string()
+        .unwrap()
+        .parse()
+        .unwrap();
+    let challenge_json: String = serde_json::to_string(&challenge).unwrap();
+    assert!(verify_ironshield_solution(&challenge_json, solution).unwrap());
+
+    assert!(solve_signed_challenge("not a challenge").is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_empty_challenge_rejected() {
+    assert!(solve_pow_challenge("", 1).is_err());
+    assert!(solve_pow_challenge_verbose("", 1, true).is_err());
+    assert!(!verify_pow_solution("", "0", 0));
+}
+
+#[wasm_bindgen_test]
+fn test_self_test_passes() {
+    assert!(self_test());
+}
+
+#[wasm_bindgen_test]
+fn test_recommended_threads_is_positive() {
+    assert!(recommended_threads() >= 1);
+}
+
+#[wasm_bindgen_test]
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+async fn test_solve_pow_challenge_all_cores() {
+    let challenge: &str = "all_cores_test_challenge";
+    let difficulty: usize = 2;
+
+    // Calling twice must not attempt to re-create the thread pool.
+    for _ in 0..2 {
+        let result: JsValue = solve_pow_challenge_all_cores(challenge.to_string(), difficulty)
+            .await
+            .expect("solving should succeed");
+        let nonce_str: String = js_sys::Reflect::get(&result, &JsValue::from_str("nonce_str"))
+            .unwrap()
+            .as_string()
+            .unwrap();
+
+        assert!(verify_pow_solution(challenge, &nonce_str, difficulty));
+    }
+}
+
+#[wasm_bindgen_test]
+#[cfg(all(feature = "parallel", not(feature = "no-parallel")))]
+async fn test_out_of_range_thread_counts_rejected() {
+    for num_threads in [0, MAX_THREADS + 1, usize::MAX] {
+        assert!(init_threads(num_threads).await.is_err(), "init_threads({}) should fail", num_threads);
+        assert!(solve_pow_challenge_parallel("thread_count_challenge", 1, num_threads).is_err());
+    }
+}